@@ -3,11 +3,28 @@
 extern crate xmodem;
 extern crate pi;
 
+use std::alloc::{GlobalAlloc, Layout};
 use std::fmt::Write;
 use std::io;
+use std::ptr;
+use std::time::Duration;
 
 pub mod lang_items;
 
+/// The bootloader never allocates, so every allocation fails.
+struct NoAllocator;
+
+unsafe impl GlobalAlloc for NoAllocator {
+    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+        ptr::null_mut()
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) { }
+}
+
+#[global_allocator]
+static ALLOCATOR: NoAllocator = NoAllocator;
+
 /// Start address of the binary to load and of the bootloader.
 const BINARY_START_ADDR: usize = 0x80000;
 const BOOTLOADER_START_ADDR: usize = 0x4000000;
@@ -33,20 +50,18 @@ fn jump_to(addr: *mut u8) -> ! {
 }
 
 pub fn boot() -> ! {
-    let mut console = pi::uart::MiniUart::new();
+    let mut uart = pi::uart::MiniUart::new();
+    uart.set_read_timeout(Duration::from_millis(750));
 
     loop {
         let output = unsafe { std::slice::from_raw_parts_mut(BINARY_START, MAX_BINARY_SIZE) };
-        let mut uart = pi::uart::MiniUart::new();
-        uart.set_read_timeout(750);
-
-        match xmodem::Xmodem::receive(uart, output) {
+        match xmodem::Xmodem::receive(&mut uart, output) {
             Ok(_) => {
-                write!(&mut console, "load complete").unwrap();
+                write!(&mut uart, "load complete").unwrap();
                 jump_to(BINARY_START)
             },
             Err(e) => if e.kind() != io::ErrorKind::TimedOut {
-                write!(&mut console, "failed receive: {:?}\n", e).unwrap();
+                write!(&mut uart, "failed receive: {:?}\n", e).unwrap();
             }
         }
     }
@@ -0,0 +1,215 @@
+use std::str;
+
+use entry::{Entry, Kind};
+use header::{self, Header, HEADER_SIZE, TRAILER};
+
+/// Error type for archive parsing failures. Each variant holds the offset of
+/// the header of the entry at fault.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The archive ends inside the entry's header, name or data.
+    Truncated(usize),
+    /// The entry doesn't start with a `newc` magic number.
+    BadMagic(usize),
+    /// The named header field isn't eight hexadecimal digits or has an
+    /// invalid value.
+    BadField(usize, &'static str),
+    /// The entry's name isn't a NUL terminated UTF-8 relative path without
+    /// `.` or `..` components.
+    BadName(usize),
+    /// The entry's data doesn't match the checksum in its header.
+    BadChecksum(usize),
+    /// The entry has the same path as an earlier entry.
+    Duplicate(usize),
+    /// A path containing the entry belongs to an entry that isn't a
+    /// directory.
+    NotInDirectory(usize),
+    /// The archive ends without a trailer entry.
+    MissingTrailer,
+}
+
+/// An entry with the header fields needed to validate the archive.
+struct Parsed<'a> {
+    entry: Entry<'a>,
+    offset: usize,
+    /// The device and inode numbers identifying hard links to one file.
+    inode: (u32, u32, u32),
+    links: u32,
+}
+
+/// Strips leading `/` and `./` from `name`. Returns `None` if the rest isn't
+/// a relative path of non-empty components other than `.` and `..`, and `""`
+/// for the root directory.
+fn normalize(name: &str) -> Option<&str> {
+    let mut path = name;
+    loop {
+        if path.starts_with("./") {
+            path = &path[2..];
+        } else if path.starts_with('/') {
+            path = &path[1..];
+        } else {
+            break;
+        }
+    }
+
+    if path == "." || path.is_empty() {
+        return Some("");
+    }
+
+    match path.split('/').any(|c| c.is_empty() || c == "." || c == "..") {
+        true => None,
+        false => Some(path)
+    }
+}
+
+/// Returns `data[start..start + len]`, or `None` if it's out of bounds.
+fn slice(data: &[u8], start: usize, len: usize) -> Option<&[u8]> {
+    match start.checked_add(len) {
+        Some(end) if end <= data.len() => Some(&data[start..end]),
+        _ => None
+    }
+}
+
+/// Parses the entries of `data` up to its trailer. The root directory's
+/// entry, if any, is skipped.
+fn parse_entries<'a>(data: &'a [u8]) -> Result<Vec<Parsed<'a>>, Error> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    loop {
+        if offset == data.len() {
+            return Err(Error::MissingTrailer);
+        }
+
+        let header = Header::parse(data, offset)?;
+        let name_start = offset + HEADER_SIZE;
+        let name = slice(data, name_start, header.namesize as usize).ok_or(Error::Truncated(offset))?;
+        let name = match name.split_last() {
+            Some((&0, name)) if !name.contains(&0) => name,
+            _ => return Err(Error::BadName(offset))
+        };
+
+        let data_start = header::align(name_start + name.len() + 1).ok_or(Error::Truncated(offset))?;
+        let contents = slice(data, data_start, header.filesize as usize).ok_or(Error::Truncated(offset))?;
+        if let Some(check) = header.check {
+            let sum = contents.iter().fold(0u32, |sum, &byte| sum.wrapping_add(byte as u32));
+            if sum != check {
+                return Err(Error::BadChecksum(offset));
+            }
+        }
+
+        let name = str::from_utf8(name).map_err(|_| Error::BadName(offset))?;
+        if name == TRAILER {
+            return Ok(entries);
+        }
+
+        let entry_offset = offset;
+        offset = header::align(data_start + contents.len()).ok_or(Error::Truncated(offset))?;
+
+        let path = normalize(name).ok_or(Error::BadName(entry_offset))?;
+        let entry = Entry { path, mode: header.mode, mtime: header.mtime, data: contents };
+        if entry.is_dir() && !contents.is_empty() {
+            return Err(Error::BadField(entry_offset, "filesize"));
+        } else if path.is_empty() {
+            match entry.is_dir() {
+                true => continue,
+                false => return Err(Error::BadName(entry_offset))
+            }
+        }
+
+        entries.push(Parsed {
+            entry,
+            offset: entry_offset,
+            inode: (header.devmajor, header.devminor, header.ino),
+            links: header.nlink,
+        });
+    }
+}
+
+/// A validated archive.
+#[derive(Debug)]
+pub struct Archive<'a> {
+    /// Every entry, including implied directories, sorted by path.
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Archive<'a> {
+    /// Parses and validates the archive in `data`. Bytes past the trailer,
+    /// such as padding to a block size, are ignored.
+    ///
+    /// Directories that contain archived entries but aren't archived
+    /// themselves are added as read-only directories with no modification
+    /// time. The data of a hard linked file, which `newc` stores with only
+    /// one of the links, is shared by all of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` describing the first problem found if `data` isn't
+    /// a well-formed archive.
+    pub fn parse(data: &'a [u8]) -> Result<Archive<'a>, Error> {
+        let mut entries = parse_entries(data)?;
+
+        for i in 0..entries.len() {
+            if entries[i].links < 2 || !entries[i].entry.is_file() || !entries[i].entry.data.is_empty() {
+                continue;
+            }
+
+            let inode = entries[i].inode;
+            let linked = entries.iter()
+                .find(|other| other.inode == inode && other.entry.is_file() && !other.entry.data.is_empty())
+                .map(|other| other.entry.data);
+            if let Some(data) = linked {
+                entries[i].entry.data = data;
+            }
+        }
+
+        entries.sort_by(|a, b| a.entry.path.cmp(b.entry.path));
+        for pair in entries.windows(2) {
+            if pair[0].entry.path == pair[1].entry.path {
+                return Err(Error::Duplicate(pair[1].offset));
+            }
+        }
+
+        let mut implied = Vec::new();
+        for parsed in entries.iter() {
+            let path: &'a str = parsed.entry.path;
+            for (i, _) in path.match_indices('/') {
+                let ancestor = &path[..i];
+                match entries.binary_search_by(|other| other.entry.path.cmp(ancestor)) {
+                    Ok(j) if entries[j].entry.kind() != Kind::Dir => {
+                        return Err(Error::NotInDirectory(parsed.offset));
+                    }
+                    Ok(_) => {}
+                    Err(_) => implied.push(ancestor)
+                }
+            }
+        }
+
+        implied.sort();
+        implied.dedup();
+
+        let mut entries: Vec<Entry<'a>> = entries.into_iter().map(|parsed| parsed.entry).collect();
+        entries.extend(implied.into_iter().map(Entry::implied_dir));
+        entries.sort_by(|a, b| a.path.cmp(b.path));
+        Ok(Archive { entries })
+    }
+
+    /// Every entry in the archive, sorted by path. The root directory isn't
+    /// included.
+    pub fn entries(&self) -> &[Entry<'a>] {
+        &self.entries
+    }
+
+    /// Returns the entry at `path`, written the way `Entry::path()` returns
+    /// it, if there is one.
+    pub fn get(&self, path: &str) -> Option<&Entry<'a>> {
+        self.entries.binary_search_by(|entry| entry.path.cmp(path))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+
+    /// Returns the entries directly inside the directory at `dir`, or inside
+    /// the root if `dir` is `""`.
+    pub fn children<'s>(&'s self, dir: &'s str) -> impl Iterator<Item = &'s Entry<'a>> + 's {
+        self.entries.iter().filter(move |entry| entry.parent() == dir)
+    }
+}
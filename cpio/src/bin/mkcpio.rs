@@ -0,0 +1,91 @@
+//! `mkcpio <dir> <archive>`: packs the contents of `dir` into a `newc`
+//! archive, such as the kernel's initramfs.
+//!
+//! Entries are written in sorted order with paths relative to `dir`, so the
+//! same tree always produces the same archive. Ownership isn't recorded;
+//! every entry belongs to root.
+
+extern crate cpio;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::UNIX_EPOCH;
+
+use cpio::Builder;
+
+#[cfg(unix)]
+fn permissions(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn permissions(metadata: &fs::Metadata) -> u32 {
+    match (metadata.is_dir(), metadata.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
+
+fn mtime(metadata: &fs::Metadata) -> u32 {
+    metadata.modified().ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs() as u32)
+}
+
+/// Adds the contents of `dir` to `builder`, naming them relative to `prefix`.
+fn add_dir(builder: &mut Builder, dir: &Path, prefix: &str) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().into_string()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file name isn't UTF-8"))?;
+        let path = match prefix {
+            "" => name,
+            _ => format!("{}/{}", prefix, name)
+        };
+
+        let metadata = fs::symlink_metadata(entry.path())?;
+        let kind = metadata.file_type();
+        if kind.is_dir() {
+            builder.dir(&path, permissions(&metadata), mtime(&metadata));
+            add_dir(builder, &entry.path(), &path)?;
+        } else if kind.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            let target = target.to_str()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "link target isn't UTF-8"))?;
+            builder.symlink(&path, target, mtime(&metadata));
+        } else if kind.is_file() {
+            let data = fs::read(entry.path())?;
+            builder.file(&path, permissions(&metadata), mtime(&metadata), &data);
+        } else {
+            eprintln!("mkcpio: skipping {}: not a file, directory or link", entry.path().display());
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: mkcpio <dir> <archive>");
+        process::exit(2);
+    }
+
+    let mut builder = Builder::new();
+    if let Err(e) = add_dir(&mut builder, Path::new(&args[1]), "") {
+        eprintln!("mkcpio: {}: {}", args[1], e);
+        process::exit(1);
+    }
+
+    if let Err(e) = fs::write(&args[2], builder.finish()) {
+        eprintln!("mkcpio: {}: {}", args[2], e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,82 @@
+use header::{self, MAGIC, TRAILER, TYPE_DIR, TYPE_FILE, TYPE_SYMLINK};
+
+/// The size of the largest entry `newc` can store.
+const MAX_SIZE: u64 = 0xFFFF_FFFF;
+
+/// Writes a `newc` archive in memory.
+///
+/// Entries are written in the order they're added. Paths are stored as
+/// given, so they should be relative and use `/` as the separator.
+#[derive(Debug, Default)]
+pub struct Builder {
+    data: Vec<u8>,
+    /// The inode number of the last entry added.
+    inode: u32,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// Adds a directory with the permission bits `permissions` and the
+    /// modification time `mtime`, in seconds since the Unix epoch.
+    pub fn dir(&mut self, path: &str, permissions: u32, mtime: u32) -> &mut Builder {
+        self.entry(path, TYPE_DIR | permissions, 2, mtime, &[])
+    }
+
+    /// Adds a file holding `data`.
+    pub fn file(&mut self, path: &str, permissions: u32, mtime: u32, data: &[u8]) -> &mut Builder {
+        self.entry(path, TYPE_FILE | permissions, 1, mtime, data)
+    }
+
+    /// Adds a symbolic link to `target`.
+    pub fn symlink(&mut self, path: &str, target: &str, mtime: u32) -> &mut Builder {
+        self.entry(path, TYPE_SYMLINK | 0o777, 1, mtime, target.as_bytes())
+    }
+
+    /// Adds an entry with the full mode `mode`, type bits included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is 4GiB or larger, which `newc` can't store.
+    pub fn entry(&mut self, path: &str, mode: u32, nlink: u32, mtime: u32, data: &[u8]) -> &mut Builder {
+        assert!(data.len() as u64 <= MAX_SIZE, "entry data is too large");
+        self.inode += 1;
+        let inode = self.inode;
+        self.write(inode, mode, nlink, mtime, path, data);
+        self
+    }
+
+    /// Writes the trailer and returns the archive.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.write(0, 0, 1, 0, TRAILER, &[]);
+        self.data
+    }
+
+    fn write(&mut self, ino: u32, mode: u32, nlink: u32, mtime: u32, name: &str, data: &[u8]) {
+        let fields = [
+            ino, mode, 0, 0, nlink, mtime, data.len() as u32,
+            0, 0, 0, 0, name.len() as u32 + 1, 0,
+        ];
+
+        self.data.extend_from_slice(MAGIC);
+        for &field in fields.iter() {
+            for shift in (0..8).rev() {
+                self.data.push(b"0123456789ABCDEF"[(field >> (shift * 4)) as usize & 0xF]);
+            }
+        }
+
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        self.pad();
+        self.data.extend_from_slice(data);
+        self.pad();
+    }
+
+    /// Pads the archive to the alignment of headers and data.
+    fn pad(&mut self) {
+        let len = header::align(self.data.len()).expect("archive is too large");
+        self.data.resize(len, 0);
+    }
+}
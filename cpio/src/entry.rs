@@ -0,0 +1,84 @@
+use header::{TYPE_DIR, TYPE_FILE, TYPE_MASK, TYPE_SYMLINK};
+
+/// The type of an archive entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+    /// A device node, FIFO or socket.
+    Other,
+}
+
+/// A file, directory or other object stored in an archive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Entry<'a> {
+    pub(crate) path: &'a str,
+    pub(crate) mode: u32,
+    pub(crate) mtime: u32,
+    pub(crate) data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Returns the entry for a directory that isn't in an archive itself but
+    /// contains entries that are.
+    pub(crate) fn implied_dir(path: &'a str) -> Entry<'a> {
+        Entry { path, mode: TYPE_DIR | 0o555, mtime: 0, data: &[] }
+    }
+
+    /// The entry's path relative to the archive's root, such as `etc/motd`.
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// The last component of the entry's path.
+    pub fn name(&self) -> &'a str {
+        match self.path.rfind('/') {
+            Some(i) => &self.path[i + 1..],
+            None => self.path
+        }
+    }
+
+    /// The path of the directory containing the entry, or `""` if it's at
+    /// the archive's root.
+    pub fn parent(&self) -> &'a str {
+        match self.path.rfind('/') {
+            Some(i) => &self.path[..i],
+            None => ""
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self.mode & TYPE_MASK {
+            TYPE_FILE => Kind::File,
+            TYPE_DIR => Kind::Dir,
+            TYPE_SYMLINK => Kind::Symlink,
+            _ => Kind::Other,
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind() == Kind::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind() == Kind::Dir
+    }
+
+    /// The permission bits of the entry's mode, such as `0o644`.
+    pub fn permissions(&self) -> u32 {
+        self.mode & 0o7777
+    }
+
+    /// The time of the last modification in seconds since the Unix epoch, or
+    /// `0` if it isn't known.
+    pub fn mtime(&self) -> u32 {
+        self.mtime
+    }
+
+    /// The contents of a file, or the target of a symbolic link. Directories
+    /// have no data.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
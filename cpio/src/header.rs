@@ -0,0 +1,92 @@
+use archive::Error;
+
+/// The magic number of a `newc` header.
+pub const MAGIC: &[u8; 6] = b"070701";
+
+/// The magic number of a `newc` header whose `check` field holds the sum of
+/// the entry's data bytes.
+pub const CRC_MAGIC: &[u8; 6] = b"070702";
+
+/// The size of a header in bytes: the magic number then thirteen fields of
+/// eight hexadecimal digits.
+pub const HEADER_SIZE: usize = 110;
+
+/// The name of the entry that ends an archive.
+pub const TRAILER: &str = "TRAILER!!!";
+
+/// The names of the header fields, in order.
+pub const FIELDS: [&str; 13] = [
+    "ino", "mode", "uid", "gid", "nlink", "mtime", "filesize",
+    "devmajor", "devminor", "rdevmajor", "rdevminor", "namesize", "check",
+];
+
+/// The bits of `mode` holding the file type, and the types.
+pub const TYPE_MASK: u32 = 0o170000;
+pub const TYPE_FILE: u32 = 0o100000;
+pub const TYPE_DIR: u32 = 0o040000;
+pub const TYPE_SYMLINK: u32 = 0o120000;
+
+/// The fields of a header the parser uses.
+#[derive(Debug, Copy, Clone)]
+pub struct Header {
+    pub ino: u32,
+    pub mode: u32,
+    pub nlink: u32,
+    pub mtime: u32,
+    pub filesize: u32,
+    pub devmajor: u32,
+    pub devminor: u32,
+    pub namesize: u32,
+    /// The checksum of the data, if the header has the CRC magic number.
+    pub check: Option<u32>,
+}
+
+/// Parses `digits` as a hexadecimal number. Unlike `u32::from_str_radix()`
+/// this rejects signs.
+fn hex(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |value, &digit| {
+        (digit as char).to_digit(16).map(|digit| value << 4 | digit)
+    })
+}
+
+impl Header {
+    /// Parses the header at `offset` in `data`.
+    pub fn parse(data: &[u8], offset: usize) -> Result<Header, Error> {
+        let bytes = match offset.checked_add(HEADER_SIZE) {
+            Some(end) if end <= data.len() => &data[offset..end],
+            _ => return Err(Error::Truncated(offset))
+        };
+
+        let crc = match &bytes[..6] {
+            magic if magic == MAGIC => false,
+            magic if magic == CRC_MAGIC => true,
+            _ => return Err(Error::BadMagic(offset))
+        };
+
+        let mut fields = [0; 13];
+        for (i, field) in fields.iter_mut().enumerate() {
+            let start = 6 + i * 8;
+            *field = hex(&bytes[start..start + 8]).ok_or(Error::BadField(offset, FIELDS[i]))?;
+        }
+
+        Ok(Header {
+            ino: fields[0],
+            mode: fields[1],
+            nlink: fields[4],
+            mtime: fields[5],
+            filesize: fields[6],
+            devmajor: fields[7],
+            devminor: fields[8],
+            namesize: fields[11],
+            check: match crc {
+                true => Some(fields[12]),
+                false => None
+            },
+        })
+    }
+}
+
+/// Rounds `n` up to a multiple of four, the alignment of headers and data.
+pub fn align(n: usize) -> Option<usize> {
+    n.checked_add(3).map(|n| n & !3)
+}
@@ -0,0 +1,17 @@
+//! Reading and writing `newc` format cpio archives, such as the kernel's
+//! initramfs.
+//!
+//! `Archive::parse()` validates a whole archive up front and then serves its
+//! entries by path without copying their names or contents. Malformed
+//! archives are rejected with an `Error` naming the offending entry's offset
+//! instead of being partially read. `Builder` writes archives in memory; the
+//! `mkcpio` binary uses it to pack a directory tree at build time.
+
+mod archive;
+mod builder;
+mod entry;
+mod header;
+
+pub use archive::{Archive, Error};
+pub use builder::Builder;
+pub use entry::{Entry, Kind};
@@ -0,0 +1,250 @@
+extern crate cpio;
+
+use cpio::{Archive, Builder, Error, Kind};
+
+/// The modification time given to test entries: 2018-03-14 15:09:26 UTC.
+const MTIME: u32 = 1_521_040_166;
+
+fn sample() -> Vec<u8> {
+    let mut builder = Builder::new();
+    builder.dir(".", 0o755, MTIME)
+        .dir("etc", 0o755, MTIME)
+        .file("etc/motd", 0o644, MTIME, b"hello\n")
+        .file("etc/empty", 0o600, MTIME, b"")
+        .symlink("etc/link", "motd", MTIME)
+        .file("README", 0o444, MTIME, b"odd length")
+        .dir("bin", 0o755, MTIME);
+    builder.finish()
+}
+
+/// Sets header field `field` of the entry at `offset` to `value`.
+fn set_field(data: &mut [u8], offset: usize, field: usize, value: &[u8; 8]) {
+    let start = offset + 6 + field * 8;
+    data[start..start + 8].copy_from_slice(value);
+}
+
+/// Returns the offset of the header of the entry named `name`.
+fn offset_of(data: &[u8], name: &str) -> usize {
+    let mut name = name.as_bytes().to_vec();
+    name.push(0);
+    data.windows(name.len()).position(|w| w == &name[..]).unwrap() - 110
+}
+
+#[test]
+fn parses_built_archive() {
+    let data = sample();
+    let archive = Archive::parse(&data).expect("parse");
+    let paths: Vec<&str> = archive.entries().iter().map(|e| e.path()).collect();
+    assert_eq!(paths, ["README", "bin", "etc", "etc/empty", "etc/link", "etc/motd"]);
+
+    let motd = archive.get("etc/motd").unwrap();
+    assert_eq!(motd.kind(), Kind::File);
+    assert_eq!(motd.name(), "motd");
+    assert_eq!(motd.parent(), "etc");
+    assert_eq!(motd.data(), b"hello\n");
+    assert_eq!(motd.permissions(), 0o644);
+    assert_eq!(motd.mtime(), MTIME);
+
+    let link = archive.get("etc/link").unwrap();
+    assert_eq!(link.kind(), Kind::Symlink);
+    assert_eq!(link.data(), b"motd");
+
+    assert_eq!(archive.get("README").unwrap().data(), b"odd length");
+    assert_eq!(archive.get("etc/empty").unwrap().data(), b"");
+    assert!(archive.get("etc").unwrap().is_dir());
+    assert!(archive.get("").is_none());
+    assert!(archive.get("etc/missing").is_none());
+}
+
+#[test]
+fn lists_children() {
+    let data = sample();
+    let archive = Archive::parse(&data).unwrap();
+    let names = |dir: &str| archive.children(dir).map(|e| e.name()).collect::<Vec<_>>();
+    assert_eq!(names(""), ["README", "bin", "etc"]);
+    assert_eq!(names("etc"), ["empty", "link", "motd"]);
+    assert!(names("bin").is_empty());
+    assert!(names("README").is_empty());
+}
+
+#[test]
+fn normalizes_paths() {
+    let mut builder = Builder::new();
+    builder.dir("./", 0o755, 0).dir("/", 0o755, 0).file("./a", 0o644, 0, b"a").file("/b", 0o644, 0, b"b");
+    let data = builder.finish();
+    let archive = Archive::parse(&data).unwrap();
+    let paths: Vec<&str> = archive.entries().iter().map(|e| e.path()).collect();
+    assert_eq!(paths, ["a", "b"]);
+}
+
+#[test]
+fn implies_missing_directories() {
+    let mut builder = Builder::new();
+    builder.file("a/b/c", 0o644, MTIME, b"c").file("a/d", 0o644, MTIME, b"d").dir("x", 0o700, MTIME);
+    let data = builder.finish();
+    let archive = Archive::parse(&data).unwrap();
+    let paths: Vec<&str> = archive.entries().iter().map(|e| e.path()).collect();
+    assert_eq!(paths, ["a", "a/b", "a/b/c", "a/d", "x"]);
+
+    let a = archive.get("a/b").unwrap();
+    assert!(a.is_dir());
+    assert_eq!(a.permissions(), 0o555);
+    assert_eq!(a.mtime(), 0);
+    assert_eq!(archive.get("x").unwrap().permissions(), 0o700);
+}
+
+#[test]
+fn shares_hard_linked_data() {
+    let mut builder = Builder::new();
+    builder.entry("first", 0o100644, 2, MTIME, b"").entry("second", 0o100644, 2, MTIME, b"shared");
+    let mut data = builder.finish();
+
+    // Give both links the second entry's inode number.
+    let (first, second) = (offset_of(&data, "first"), offset_of(&data, "second"));
+    let mut ino = [0; 8];
+    ino.copy_from_slice(&data[second + 6..second + 14]);
+    set_field(&mut data, first, 0, &ino);
+
+    let archive = Archive::parse(&data).unwrap();
+    assert_eq!(archive.get("first").unwrap().data(), b"shared");
+    assert_eq!(archive.get("second").unwrap().data(), b"shared");
+}
+
+#[test]
+fn ignores_data_after_trailer() {
+    let mut data = sample();
+    let len = data.len();
+    data.resize(len + 512, 0);
+    data.extend_from_slice(b"garbage");
+    assert_eq!(Archive::parse(&data).unwrap().entries().len(), 6);
+}
+
+#[test]
+fn verifies_checksums() {
+    let mut builder = Builder::new();
+    builder.file("f", 0o644, 0, b"abc");
+    let mut data = builder.finish();
+    data[5] = b'2';
+    set_field(&mut data, 0, 12, b"00000126");
+    assert!(Archive::parse(&data).is_ok());
+
+    set_field(&mut data, 0, 12, b"00000127");
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::BadChecksum(0));
+}
+
+#[test]
+fn rejects_missing_trailer() {
+    assert_eq!(Archive::parse(&[]).unwrap_err(), Error::MissingTrailer);
+
+    let mut builder = Builder::new();
+    builder.file("f", 0o644, 0, b"data");
+    let mut data = builder.finish();
+    let trailer = offset_of(&data, "TRAILER!!!");
+    data.truncate(trailer);
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::MissingTrailer);
+}
+
+#[test]
+fn rejects_every_truncation() {
+    let data = sample();
+    for len in 1..data.len() {
+        assert!(Archive::parse(&data[..len]).is_err(), "accepted {} bytes", len);
+    }
+}
+
+#[test]
+fn rejects_bad_headers() {
+    let good = sample();
+    let motd = offset_of(&good, "etc/motd");
+
+    let mut data = good.clone();
+    data[motd] = b'1';
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::BadMagic(motd));
+
+    for &(field, value) in [(1, b"0000g1A4"), (6, b"+0000006"), (11, b"0000 009")].iter() {
+        let mut data = good.clone();
+        set_field(&mut data, motd, field, value);
+        match Archive::parse(&data).unwrap_err() {
+            Error::BadField(offset, _) => assert_eq!(offset, motd),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    let mut data = good.clone();
+    set_field(&mut data, motd, 6, b"FFFFFFFF");
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::Truncated(motd));
+
+    let mut data = good.clone();
+    set_field(&mut data, motd, 11, b"FFFFFFFF");
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::Truncated(motd));
+}
+
+#[test]
+fn rejects_bad_names() {
+    let good = sample();
+    let motd = offset_of(&good, "etc/motd");
+
+    // The name's terminating NUL is replaced, then an inner byte is.
+    for &(index, byte) in [(8, b'x'), (3, 0), (3, 0xFF)].iter() {
+        let mut data = good.clone();
+        data[motd + 110 + index] = byte;
+        assert_eq!(Archive::parse(&data).unwrap_err(), Error::BadName(motd), "byte {}", index);
+    }
+
+    let mut data = good.clone();
+    set_field(&mut data, motd, 11, b"00000000");
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::BadName(motd));
+
+    for &path in ["a/../b", "a//b", "a/./b", "..", "a/"].iter() {
+        let mut builder = Builder::new();
+        builder.file(path, 0o644, 0, b"");
+        assert_eq!(Archive::parse(&builder.finish()).unwrap_err(), Error::BadName(0), "{}", path);
+    }
+
+    let mut builder = Builder::new();
+    builder.file(".", 0o644, 0, b"");
+    assert_eq!(Archive::parse(&builder.finish()).unwrap_err(), Error::BadName(0));
+}
+
+#[test]
+fn rejects_inconsistent_trees() {
+    let mut builder = Builder::new();
+    builder.file("a", 0o644, 0, b"1").file("b", 0o644, 0, b"2").dir("a", 0o755, 0);
+    let data = builder.finish();
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::Duplicate(offset_of(&data, "b") + 116));
+
+    let mut builder = Builder::new();
+    builder.file("a", 0o644, 0, b"1").file("a/b", 0o644, 0, b"2");
+    let data = builder.finish();
+    assert_eq!(Archive::parse(&data).unwrap_err(), Error::NotInDirectory(offset_of(&data, "a/b")));
+
+    let mut builder = Builder::new();
+    builder.entry("d", 0o040755, 2, 0, b"data");
+    assert_eq!(Archive::parse(&builder.finish()).unwrap_err(), Error::BadField(0, "filesize"));
+}
+
+#[test]
+fn survives_corruption() {
+    let good = sample();
+    let mut state = 0x2545_F491_u32;
+    let mut random = move || {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        state >> 8
+    };
+
+    for _ in 0..20_000 {
+        let mut data = good.clone();
+        for _ in 0..1 + random() % 4 {
+            let i = random() as usize % data.len();
+            data[i] = random() as u8;
+        }
+
+        // Any result is fine as long as parsing doesn't panic and accepted
+        // entries stay inside the archive.
+        if let Ok(archive) = Archive::parse(&data) {
+            for entry in archive.entries() {
+                assert!(entry.data().len() <= data.len());
+            }
+        }
+    }
+}
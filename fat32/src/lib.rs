@@ -0,0 +1,17 @@
+//! A FAT32 filesystem driver over a `storage::BlockDevice`.
+//!
+//! `VFat::from()` mounts a volume, either from the first FAT32 partition of
+//! an MBR partitioned device or from a device formatted without a partition
+//! table. Files and directories are then opened, created and removed by path,
+//! and files are read and written through the `io::Read`, `io::Write` and
+//! `io::Seek` traits.
+
+extern crate storage;
+extern crate lock;
+
+mod util;
+mod shared;
+pub mod vfat;
+
+pub use shared::{Shared, SharedGuard};
+pub use vfat::{VFat, Error, BiosParameterBlock, Cluster, Status, Dir, DirIter, Entry, File, Metadata, Attributes, Timestamp};
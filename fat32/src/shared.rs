@@ -0,0 +1,39 @@
+use std::fmt;
+use std::sync::Arc;
+
+use lock::{Mutex, MutexGuard};
+
+/// A reference-counted, lock-protected value shared between a filesystem and
+/// the files and directories opened from it.
+///
+/// `lock()` spins until no other guard is alive, so a `Shared` can be used
+/// from several threads. Locking it twice from the same thread deadlocks.
+pub struct Shared<T>(Arc<Mutex<T>>);
+
+/// A guard giving access to the value in a `Shared`. The lock is released
+/// when the guard is dropped.
+pub type SharedGuard<'a, T> = MutexGuard<'a, T>;
+
+impl<T> Shared<T> {
+    /// Wraps `value` in a new `Shared`.
+    pub fn new(value: T) -> Shared<T> {
+        Shared(Arc::new(Mutex::new(value)))
+    }
+
+    /// Locks the value, spinning until it is available.
+    pub fn lock(&self) -> SharedGuard<'_, T> {
+        self.0.lock()
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Shared<T> {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Shared { .. }")
+    }
+}
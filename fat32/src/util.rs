@@ -0,0 +1,25 @@
+use std::io;
+
+/// Reads a little-endian `u16` from `bytes` at `offset`.
+pub fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// Reads a little-endian `u32` from `bytes` at `offset`.
+pub fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// Divides `a` by `b`, rounding up.
+pub fn div_ceil(a: u64, b: u64) -> u64 {
+    match a % b {
+        0 => a / b,
+        _ => a / b + 1
+    }
+}
+
+/// Returns an error of kind `Other` for a failure with no better kind.
+#[allow(clippy::io_other_error)]
+pub fn other_error(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, reason)
+}
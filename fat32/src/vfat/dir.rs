@@ -0,0 +1,480 @@
+use std::io;
+
+use storage::BlockDevice;
+
+use shared::Shared;
+use util::{read_u16, read_u32, div_ceil, other_error};
+use vfat::name::{self, LongName, ShortName, DELETED_MARKER, SHORT_NAME_SIZE};
+use vfat::{VFat, Entry, File, Metadata, Attributes, Cluster};
+use vfat::volume::corrupt;
+
+/// The size of a directory entry in bytes.
+pub const DIR_ENTRY_SIZE: usize = 32;
+
+/// The most entries a directory can hold.
+const MAX_DIR_ENTRIES: usize = 65536;
+
+/// The first name byte of the entry marking the end of a directory.
+const END_MARKER: u8 = 0x00;
+
+/// Where a file or directory's entries are stored in its parent directory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Location {
+    /// The parent directory's first cluster.
+    pub dir: Cluster,
+    /// The offset of the first entry, which is a long name entry if the
+    /// name has any.
+    pub start: u64,
+    /// The number of entries, including the short name entry.
+    pub slots: u64,
+}
+
+impl Location {
+    /// The offset of the short name entry.
+    pub fn short_entry(&self) -> u64 {
+        self.start + (self.slots - 1) * DIR_ENTRY_SIZE as u64
+    }
+}
+
+/// Returns a short name entry with zeroed timestamps.
+fn raw_entry(name: &[u8; SHORT_NAME_SIZE], case: u8, attributes: u8, cluster: Option<Cluster>, size: u32) -> [u8; DIR_ENTRY_SIZE] {
+    let cluster = cluster.map_or(0, |cluster| cluster.number());
+    let mut entry = [0; DIR_ENTRY_SIZE];
+    entry[..SHORT_NAME_SIZE].copy_from_slice(name);
+    entry[11] = attributes;
+    entry[12] = case;
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// A directory on a FAT32 volume.
+#[derive(Debug)]
+pub struct Dir<T: BlockDevice> {
+    fs: Shared<VFat<T>>,
+    name: String,
+    metadata: Metadata,
+    start: Cluster,
+    /// `None` for the root directory.
+    location: Option<Location>,
+}
+
+impl<T: BlockDevice> Dir<T> {
+    pub(crate) fn new(fs: Shared<VFat<T>>, name: String, metadata: Metadata, start: Cluster, location: Option<Location>) -> Dir<T> {
+        Dir { fs, name, metadata, start, location }
+    }
+
+    /// The directory's name. The root directory's name is empty.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The directory's attributes and timestamps.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The directory's first cluster.
+    pub fn cluster(&self) -> Cluster {
+        self.start
+    }
+
+    pub(crate) fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    /// Returns an iterator over the directory's entries, including `.` and
+    /// `..` for directories other than the root. Deleted entries and the
+    /// volume label are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reading the directory's clusters.
+    pub fn entries(&self) -> io::Result<DirIter<T>> {
+        let mut fs = self.fs.lock();
+        let mut data = Vec::new();
+        fs.read_chain(self.start, &mut data)?;
+        Ok(DirIter { fs: self.fs.clone(), root: fs.root_cluster(), dir: self.start, data, offset: 0 })
+    }
+
+    /// Returns the entry named `name`, compared case-insensitively, or
+    /// `None` if there is none.
+    pub fn find(&self, name: &str) -> io::Result<Option<Entry<T>>> {
+        Ok(self.entries()?.find(|entry| entry.name().eq_ignore_ascii_case(name)))
+    }
+
+    /// Creates an empty file named `name` in this directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AlreadyExists` error if the name is taken,
+    /// `InvalidInput` if it isn't a valid name, and any error updating the
+    /// directory.
+    pub fn create_file(&self, name: &str) -> io::Result<File<T>> {
+        name::validate(name)?;
+
+        let mut fs = self.fs.lock();
+        let entry = raw_entry(&[b' '; SHORT_NAME_SIZE], 0, Attributes::ARCHIVE, None, 0);
+        let (location, metadata) = self.add_entry(&mut fs, name, entry)?;
+        Ok(File::new(self.fs.clone(), name.to_string(), metadata, None, 0, location))
+    }
+
+    /// Creates an empty directory named `name` in this directory.
+    ///
+    /// The new directory's cluster is initialized before its entry is
+    /// added, so an interrupted call at worst leaves an unused cluster
+    /// allocated.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `create_file()`, and an error if the volume is
+    /// full.
+    pub fn create_dir(&self, name: &str) -> io::Result<Dir<T>> {
+        name::validate(name)?;
+
+        let mut fs = self.fs.lock();
+        let cluster = fs.alloc_cluster(None, true)?;
+        let parent = match self.start == fs.root_cluster() {
+            true => None,
+            false => Some(self.start)
+        };
+
+        match self.add_dir_entry(&mut fs, name, cluster, parent) {
+            Ok((location, metadata)) => {
+                Ok(Dir::new(self.fs.clone(), name.to_string(), metadata, cluster, Some(location)))
+            }
+            Err(error) => {
+                // The cluster isn't referenced yet; a failure here only
+                // leaks it.
+                let _ = fs.free_chain(cluster);
+                Err(error)
+            }
+        }
+    }
+
+    /// Removes the file or empty directory named `name` from this
+    /// directory.
+    ///
+    /// The entry is marked deleted before its clusters are freed, so an
+    /// interrupted call at worst leaves them allocated.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NotFound` error if there is no such entry, `InvalidInput`
+    /// for `.` and `..`, `Other` if the directory isn't empty, and any
+    /// error updating the volume.
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        if name == "." || name == ".." {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't remove `.` or `..`"));
+        }
+
+        let (location, start) = match self.find(name)? {
+            Some(Entry::File(file)) => (file.location(), file.cluster()),
+            Some(Entry::Dir(dir)) => {
+                if dir.entries()?.any(|entry| entry.name() != "." && entry.name() != "..") {
+                    return Err(other_error("directory not empty"));
+                }
+
+                (dir.location().expect("named directory without an entry"), Some(dir.cluster()))
+            }
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+        };
+
+        let mut fs = self.fs.lock();
+        mark_deleted(&mut fs, location)?;
+        match start {
+            Some(start) => fs.free_chain(start),
+            None => Ok(())
+        }
+    }
+
+    /// Moves the file or directory named `name` in this directory to
+    /// `new_name` in `to`, which must be a directory on the same volume.
+    /// Its attributes, timestamps and clusters are kept.
+    ///
+    /// The old entry is marked deleted before the new one is added, so an
+    /// interrupted call at worst leaves the clusters allocated but
+    /// unreferenced. If adding the new entry fails, the old one is restored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NotFound` error if there is no such entry,
+    /// `AlreadyExists` if `new_name` is taken in `to`, `InvalidInput` for
+    /// the root, `.` and `..`, invalid names and moving a directory into itself, and
+    /// any error updating the volume.
+    pub(crate) fn rename(&self, name: &str, to: &Dir<T>, new_name: &str) -> io::Result<()> {
+        if name.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't rename the root directory"));
+        } else if name == "." || name == ".." {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't rename `.` or `..`"));
+        }
+
+        name::validate(new_name)?;
+        let (location, moved_dir) = match self.find(name)? {
+            Some(Entry::File(file)) => (file.location(), None),
+            Some(Entry::Dir(dir)) => {
+                (dir.location().expect("named directory without an entry"), Some(dir.cluster()))
+            }
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+        };
+
+        // Renaming to a name differing only in case finds the entry itself.
+        let existing = match to.find(new_name)? {
+            Some(Entry::File(file)) => Some(file.location()),
+            Some(Entry::Dir(dir)) => dir.location(),
+            None => None
+        };
+
+        if existing.is_some() && existing != Some(location) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+        }
+
+        if let Some(cluster) = moved_dir {
+            if to.is_within(cluster)? {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't move a directory into itself"));
+            }
+        }
+
+        let mut fs = self.fs.lock();
+        let mut old = vec![0; location.slots as usize * DIR_ENTRY_SIZE];
+        for (i, slot) in old.chunks_mut(DIR_ENTRY_SIZE).enumerate() {
+            fs.read_chain_at(location.dir, location.start + (i * DIR_ENTRY_SIZE) as u64, slot)?;
+        }
+
+        let mut short_entry = [0; DIR_ENTRY_SIZE];
+        short_entry.copy_from_slice(&old[old.len() - DIR_ENTRY_SIZE..]);
+
+        let added = match mark_deleted(&mut fs, location) {
+            Ok(()) => to.add_entry(&mut fs, new_name, short_entry),
+            Err(error) => Err(error)
+        };
+
+        if let Err(error) = added {
+            // If restoring fails too, the clusters are only leaked.
+            for (i, slot) in old.chunks(DIR_ENTRY_SIZE).enumerate() {
+                let _ = fs.write_chain_at(location.dir, location.start + (i * DIR_ENTRY_SIZE) as u64, slot);
+            }
+
+            return Err(error);
+        }
+
+        // A moved directory's `..` entry must point at its new parent.
+        match moved_dir {
+            Some(cluster) if to.start != self.start => {
+                let parent = match to.start == fs.root_cluster() {
+                    true => 0,
+                    false => to.start.number()
+                };
+
+                let mut dot_dot = [0; DIR_ENTRY_SIZE];
+                fs.read_chain_at(cluster, DIR_ENTRY_SIZE as u64, &mut dot_dot)?;
+                dot_dot[20..22].copy_from_slice(&((parent >> 16) as u16).to_le_bytes());
+                dot_dot[26..28].copy_from_slice(&(parent as u16).to_le_bytes());
+                fs.write_chain_at(cluster, DIR_ENTRY_SIZE as u64, &dot_dot)
+            }
+            _ => Ok(())
+        }
+    }
+
+    /// Returns `true` if this directory is the directory starting at
+    /// `cluster` or lies below it.
+    fn is_within(&self, cluster: Cluster) -> io::Result<bool> {
+        let (root, cluster_count) = {
+            let fs = self.fs.lock();
+            (fs.root_cluster(), fs.cluster_count())
+        };
+
+        // No directory is nested deeper than there are clusters.
+        let mut current = self.start;
+        for _ in 0..cluster_count {
+            if current == cluster {
+                return Ok(true);
+            } else if current == root {
+                return Ok(false);
+            }
+
+            let dir = Dir::new(self.fs.clone(), String::new(), Metadata::default(), current, None);
+            current = match dir.find("..")? {
+                Some(Entry::Dir(parent)) => parent.cluster(),
+                _ => return Err(corrupt("directory without `..` entry"))
+            };
+        }
+
+        Err(corrupt("directory loop"))
+    }
+
+    /// Writes the `.` and `..` entries of the new directory at `cluster`,
+    /// whose parent is `parent` (`None` for the root), and adds its entry.
+    fn add_dir_entry(&self, fs: &mut VFat<T>, name: &str, cluster: Cluster, parent: Option<Cluster>) -> io::Result<(Location, Metadata)> {
+        let dot = raw_entry(b".          ", 0, Attributes::DIRECTORY, Some(cluster), 0);
+        let dot_dot = raw_entry(b"..         ", 0, Attributes::DIRECTORY, parent, 0);
+        fs.write_chain_at(cluster, 0, &dot)?;
+        fs.write_chain_at(cluster, DIR_ENTRY_SIZE as u64, &dot_dot)?;
+        let entry = raw_entry(&[b' '; SHORT_NAME_SIZE], 0, Attributes::DIRECTORY, Some(cluster), 0);
+        self.add_entry(fs, name, entry)
+    }
+
+    /// Adds the entries for a file or directory named `name` to this
+    /// directory, reusing free entries or growing the directory. The short
+    /// name entry is a copy of `template` with the name replaced.
+    fn add_entry(&self, fs: &mut VFat<T>, name: &str, mut template: [u8; DIR_ENTRY_SIZE]) -> io::Result<(Location, Metadata)> {
+        let mut data = Vec::new();
+        fs.read_chain(self.start, &mut data)?;
+
+        let existing = DirIter { fs: self.fs.clone(), root: fs.root_cluster(), dir: self.start, data: data.clone(), offset: 0 };
+        let mut taken = Vec::new();
+        for entry in existing {
+            if entry.name().eq_ignore_ascii_case(name) {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+            }
+        }
+
+        for slot in data.chunks(DIR_ENTRY_SIZE) {
+            let attributes = Attributes(slot[11]);
+            if slot[0] != END_MARKER && slot[0] != DELETED_MARKER && !attributes.is_lfn() {
+                let mut short = [0; SHORT_NAME_SIZE];
+                short.copy_from_slice(&slot[..SHORT_NAME_SIZE]);
+                taken.push(short);
+            }
+        }
+
+        let short = ShortName::generate(name, &taken);
+        let mut entries = match short.needs_lfn {
+            true => short.lfn_entries(name),
+            false => vec![]
+        };
+        template[..SHORT_NAME_SIZE].copy_from_slice(&short.name);
+        template[12] = short.case;
+        entries.push(template);
+
+        // Find a run of free entries, which is every entry from the end
+        // marker on.
+        let total = data.len() / DIR_ENTRY_SIZE;
+        let (mut run_start, mut run_len, mut ended) = (total, 0, false);
+        for (i, slot) in data.chunks(DIR_ENTRY_SIZE).enumerate() {
+            ended = ended || slot[0] == END_MARKER;
+            if !ended && slot[0] != DELETED_MARKER {
+                run_len = 0;
+                continue;
+            }
+
+            if run_len == 0 {
+                run_start = i;
+            }
+
+            run_len += 1;
+            if run_len == entries.len() {
+                break;
+            }
+        }
+
+        if run_len == 0 {
+            run_start = total;
+        }
+
+        if run_start + entries.len() > MAX_DIR_ENTRIES {
+            return Err(other_error("directory is full"));
+        }
+
+        if run_len < entries.len() {
+            let bytes_per_cluster = fs.bytes_per_cluster();
+            let missing = (entries.len() - run_len) * DIR_ENTRY_SIZE;
+            let mut last = fs.last_cluster(self.start)?;
+            for _ in 0..div_ceil(missing as u64, bytes_per_cluster as u64) {
+                last = fs.alloc_cluster(Some(last), true)?;
+            }
+        }
+
+        // Long name entries go first, so an interrupted call leaves at worst
+        // orphaned long name entries, which readers skip.
+        for (i, entry) in entries.iter().enumerate() {
+            fs.write_chain_at(self.start, ((run_start + i) * DIR_ENTRY_SIZE) as u64, entry)?;
+        }
+
+        let location = Location { dir: self.start, start: (run_start * DIR_ENTRY_SIZE) as u64, slots: entries.len() as u64 };
+        Ok((location, Metadata::parse(&entries[entries.len() - 1])))
+    }
+}
+
+/// Marks the entries at `location` deleted.
+fn mark_deleted<T: BlockDevice>(fs: &mut VFat<T>, location: Location) -> io::Result<()> {
+    for slot in 0..location.slots {
+        let offset = location.start + slot * DIR_ENTRY_SIZE as u64;
+        fs.write_chain_at(location.dir, offset, &[DELETED_MARKER])?;
+    }
+
+    Ok(())
+}
+
+/// An iterator over the entries of a `Dir`.
+pub struct DirIter<T: BlockDevice> {
+    fs: Shared<VFat<T>>,
+    root: Cluster,
+    dir: Cluster,
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl<T: BlockDevice> DirIter<T> {
+    /// Builds the entry for the short name entry `raw`, named `name`.
+    fn entry(&self, name: String, raw: &[u8], location: Location) -> Entry<T> {
+        let metadata = Metadata::parse(raw);
+        let cluster = (read_u16(raw, 20) as u32) << 16 | read_u16(raw, 26) as u32;
+
+        match metadata.attributes.directory() {
+            true => {
+                // A `..` entry pointing at the root directory stores cluster 0.
+                let start = match cluster {
+                    0 => self.root,
+                    _ => Cluster::new(cluster)
+                };
+
+                Entry::Dir(Dir::new(self.fs.clone(), name, metadata, start, Some(location)))
+            }
+            false => {
+                let start = match cluster {
+                    0 => None,
+                    _ => Some(Cluster::new(cluster))
+                };
+
+                Entry::File(File::new(self.fs.clone(), name, metadata, start, read_u32(raw, 28), location))
+            }
+        }
+    }
+}
+
+impl<T: BlockDevice> Iterator for DirIter<T> {
+    type Item = Entry<T>;
+
+    fn next(&mut self) -> Option<Entry<T>> {
+        let mut long_name: Option<LongName> = None;
+        while self.offset + DIR_ENTRY_SIZE <= self.data.len() {
+            let start = self.offset;
+            self.offset += DIR_ENTRY_SIZE;
+
+            let raw = &self.data[start..start + DIR_ENTRY_SIZE];
+            let attributes = Attributes(raw[11]);
+            match raw[0] {
+                END_MARKER => {
+                    self.offset = self.data.len();
+                    return None;
+                }
+                DELETED_MARKER => long_name = None,
+                _ if attributes.is_lfn() => long_name = LongName::push(long_name.take(), raw, start as u64),
+                _ if attributes.volume_id() => long_name = None,
+                _ => {
+                    let long = long_name.take()
+                        .and_then(|long| long.finish(raw).map(|name| (name, long.start)));
+
+                    let (name, first) = long.unwrap_or_else(|| (name::short_name(raw), start as u64));
+                    let slots = (start as u64 - first) / DIR_ENTRY_SIZE as u64 + 1;
+                    let location = Location { dir: self.dir, start: first, slots };
+                    return Some(self.entry(name, raw, location));
+                }
+            }
+        }
+
+        None
+    }
+}
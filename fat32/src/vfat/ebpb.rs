@@ -0,0 +1,180 @@
+use util::{read_u16, read_u32};
+use vfat::Error;
+
+/// The size of the boot sector fields in bytes, including the signature.
+pub const BOOT_SECTOR_SIZE: usize = 512;
+
+/// The last two bytes of a valid boot sector.
+const SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// Extended boot signatures: `0x29` means the volume ID, label and type
+/// fields are present, `0x28` that only the volume ID is.
+const EXTENDED_SIGNATURES: [u8; 2] = [0x28, 0x29];
+
+/// The BIOS parameter block and FAT32 extended BIOS parameter block found
+/// in the first sector of a FAT32 volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BiosParameterBlock {
+    /// The OEM identifier, usually the name of the formatting tool.
+    pub oem_id: [u8; 8],
+    /// The size of a logical sector in bytes.
+    pub bytes_per_sector: u16,
+    /// The number of logical sectors in a cluster.
+    pub sectors_per_cluster: u8,
+    /// The number of sectors before the first FAT, including this one.
+    pub reserved_sectors: u16,
+    /// The number of copies of the FAT.
+    pub num_fats: u8,
+    /// The media descriptor byte.
+    pub media: u8,
+    /// The total number of sectors in the volume.
+    pub total_sectors: u32,
+    /// The number of sectors in one copy of the FAT.
+    pub sectors_per_fat: u32,
+    /// FAT mirroring flags. Bit 7 set means only the FAT numbered by bits
+    /// 0-3 is active.
+    pub ext_flags: u16,
+    /// The first cluster of the root directory.
+    pub root_cluster: u32,
+    /// The sector holding the FSInfo structure.
+    pub fsinfo_sector: u16,
+    /// The sector holding the backup copy of the boot sector.
+    pub backup_boot_sector: u16,
+    /// The volume serial number.
+    pub volume_id: u32,
+    /// The volume label, padded with spaces, if the EBPB has one.
+    pub volume_label: Option<[u8; 11]>,
+}
+
+impl BiosParameterBlock {
+    /// Parses the boot sector in `sector`.
+    ///
+    /// A volume is accepted as FAT32 when its FAT16 fields (fixed root
+    /// directory size and 16-bit FAT size) are zero, regardless of its
+    /// cluster count.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BadSignature` if the boot sector or extended boot signature
+    /// is invalid, `NotFat32` if the volume isn't FAT32, and
+    /// `BadGeometry(field)` if a size or count field is unusable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sector` is shorter than `BOOT_SECTOR_SIZE`.
+    pub fn parse(sector: &[u8]) -> Result<BiosParameterBlock, Error> {
+        if sector[BOOT_SECTOR_SIZE - 2..BOOT_SECTOR_SIZE] != SIGNATURE {
+            return Err(Error::BadSignature);
+        }
+
+        if read_u16(sector, 17) != 0 || read_u16(sector, 22) != 0 {
+            return Err(Error::NotFat32);
+        }
+
+        let signature = sector[66];
+        if !EXTENDED_SIGNATURES.contains(&signature) {
+            return Err(Error::BadSignature);
+        }
+
+        let mut oem_id = [0; 8];
+        oem_id.copy_from_slice(&sector[3..11]);
+
+        let volume_label = match signature {
+            0x29 => {
+                let mut label = [0; 11];
+                label.copy_from_slice(&sector[71..82]);
+                Some(label)
+            }
+            _ => None
+        };
+
+        let total_sectors = match read_u16(sector, 19) {
+            0 => read_u32(sector, 32),
+            sectors => sectors as u32
+        };
+
+        let bpb = BiosParameterBlock {
+            oem_id,
+            bytes_per_sector: read_u16(sector, 11),
+            sectors_per_cluster: sector[13],
+            reserved_sectors: read_u16(sector, 14),
+            num_fats: sector[16],
+            media: sector[21],
+            total_sectors,
+            sectors_per_fat: read_u32(sector, 36),
+            ext_flags: read_u16(sector, 40),
+            root_cluster: read_u32(sector, 44),
+            fsinfo_sector: read_u16(sector, 48),
+            backup_boot_sector: read_u16(sector, 50),
+            volume_id: read_u32(sector, 67),
+            volume_label,
+        };
+
+        bpb.validate()?;
+        Ok(bpb)
+    }
+
+    /// Checks that the volume's geometry is usable.
+    fn validate(&self) -> Result<(), Error> {
+        match self.bytes_per_sector {
+            512..=4096 if self.bytes_per_sector.is_power_of_two() => { }
+            _ => return Err(Error::BadGeometry("bytes per sector"))
+        }
+
+        if self.sectors_per_cluster == 0 || !self.sectors_per_cluster.is_power_of_two() {
+            return Err(Error::BadGeometry("sectors per cluster"));
+        }
+
+        if self.reserved_sectors == 0 {
+            return Err(Error::BadGeometry("reserved sectors"));
+        }
+
+        if self.num_fats == 0 {
+            return Err(Error::BadGeometry("number of FATs"));
+        }
+
+        if self.sectors_per_fat == 0 {
+            return Err(Error::BadGeometry("sectors per FAT"));
+        }
+
+        if self.data_start_sector() >= self.total_sectors as u64 {
+            return Err(Error::BadGeometry("total sectors"));
+        }
+
+        let fat_entries = self.sectors_per_fat as u64 * self.bytes_per_sector as u64 / 4;
+        if fat_entries < self.cluster_count() as u64 + 2 {
+            return Err(Error::BadGeometry("sectors per FAT"));
+        }
+
+        if self.root_cluster < 2 || self.root_cluster - 2 >= self.cluster_count() {
+            return Err(Error::BadGeometry("root cluster"));
+        }
+
+        Ok(())
+    }
+
+    /// The first sector of FAT number `n`.
+    pub fn fat_start_sector(&self, n: u8) -> u64 {
+        self.reserved_sectors as u64 + n as u64 * self.sectors_per_fat as u64
+    }
+
+    /// The first sector of the data region, which starts with cluster 2.
+    pub fn data_start_sector(&self) -> u64 {
+        self.fat_start_sector(self.num_fats)
+    }
+
+    /// The number of clusters in the data region.
+    pub fn cluster_count(&self) -> u32 {
+        let data_sectors = self.total_sectors as u64 - self.data_start_sector();
+        (data_sectors / self.sectors_per_cluster as u64) as u32
+    }
+
+    /// The FAT used for lookups: the one selected by `ext_flags` when
+    /// mirroring is disabled, else the first.
+    pub fn active_fat(&self) -> u8 {
+        match self.ext_flags & 0x80 {
+            0 => 0,
+            _ => (self.ext_flags & 0x0F) as u8
+        }
+    }
+}
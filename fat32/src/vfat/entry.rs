@@ -0,0 +1,64 @@
+use storage::BlockDevice;
+
+use vfat::{Dir, File, Metadata};
+
+/// A file or directory.
+#[derive(Debug)]
+pub enum Entry<T: BlockDevice> {
+    File(File<T>),
+    Dir(Dir<T>),
+}
+
+impl<T: BlockDevice> Entry<T> {
+    /// The entry's name.
+    pub fn name(&self) -> &str {
+        match *self {
+            Entry::File(ref file) => file.name(),
+            Entry::Dir(ref dir) => dir.name(),
+        }
+    }
+
+    /// The entry's attributes and timestamps.
+    pub fn metadata(&self) -> &Metadata {
+        match *self {
+            Entry::File(ref file) => file.metadata(),
+            Entry::Dir(ref dir) => dir.metadata(),
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.as_file().is_some()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.as_dir().is_some()
+    }
+
+    pub fn as_file(&self) -> Option<&File<T>> {
+        match *self {
+            Entry::File(ref file) => Some(file),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    pub fn as_dir(&self) -> Option<&Dir<T>> {
+        match *self {
+            Entry::Dir(ref dir) => Some(dir),
+            Entry::File(_) => None,
+        }
+    }
+
+    pub fn into_file(self) -> Option<File<T>> {
+        match self {
+            Entry::File(file) => Some(file),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    pub fn into_dir(self) -> Option<Dir<T>> {
+        match self {
+            Entry::Dir(dir) => Some(dir),
+            Entry::File(_) => None,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+use util::read_u32;
+
+/// The size of a FAT32 FAT entry in bytes.
+pub const FAT_ENTRY_SIZE: usize = 4;
+
+/// Only the low 28 bits of a FAT32 entry are used.
+pub const ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+/// The end of chain marker written for new chains.
+pub const EOC: u32 = 0x0FFF_FFFF;
+
+/// A cluster number. Data clusters are numbered from 2.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cluster(u32);
+
+impl Cluster {
+    /// The cluster numbered `n`.
+    pub fn new(n: u32) -> Cluster {
+        Cluster(n & ENTRY_MASK)
+    }
+
+    /// The cluster's number.
+    pub fn number(&self) -> u32 {
+        self.0
+    }
+
+    /// The cluster's index in the data region, or `None` if the number is
+    /// below 2 and so doesn't name a data cluster.
+    pub fn index(&self) -> Option<u32> {
+        self.0.checked_sub(2)
+    }
+}
+
+impl fmt::Debug for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cluster({:#x})", self.0)
+    }
+}
+
+/// The status of a cluster as recorded in its FAT entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The cluster is unused.
+    Free,
+    /// The cluster is reserved.
+    Reserved,
+    /// The cluster is in use and its chain continues with `.0`.
+    Data(Cluster),
+    /// The cluster is marked bad.
+    Bad,
+    /// The cluster is the last in its chain. `.0` is the raw marker.
+    Eoc(u32),
+}
+
+impl Status {
+    /// Decodes the FAT entry `entry`.
+    pub fn from_entry(entry: u32) -> Status {
+        match entry & ENTRY_MASK {
+            0 => Status::Free,
+            1 => Status::Reserved,
+            next @ 2..=0x0FFF_FFEF => Status::Data(Cluster::new(next)),
+            0x0FFF_FFF0..=0x0FFF_FFF6 => Status::Reserved,
+            0x0FFF_FFF7 => Status::Bad,
+            marker => Status::Eoc(marker)
+        }
+    }
+
+    /// Encodes the status as the low 28 bits of a FAT entry.
+    pub fn to_entry(&self) -> u32 {
+        match *self {
+            Status::Free => 0,
+            Status::Reserved => 0x0FFF_FFF0,
+            Status::Data(next) => next.number(),
+            Status::Bad => 0x0FFF_FFF7,
+            Status::Eoc(marker) => marker & ENTRY_MASK,
+        }
+    }
+
+    /// Reads the FAT entry for `cluster` from `sector`, the FAT sector
+    /// holding it.
+    pub fn read(sector: &[u8], cluster: Cluster) -> Status {
+        let offset = (cluster.number() as usize * FAT_ENTRY_SIZE) % sector.len();
+        Status::from_entry(read_u32(sector, offset))
+    }
+}
@@ -0,0 +1,311 @@
+use std::cmp;
+use std::io::{self, SeekFrom};
+
+use storage::BlockDevice;
+
+use shared::Shared;
+use util::div_ceil;
+use vfat::dir::Location;
+use vfat::volume::corrupt;
+use vfat::{VFat, Metadata, Cluster};
+
+/// The largest file FAT32 can store: sizes are 32-bit.
+const MAX_SIZE: u64 = 0xFFFF_FFFF;
+
+/// A file on a FAT32 volume, read and written through `io::Read`,
+/// `io::Write` and `io::Seek`.
+///
+/// Writes go straight to the volume, and the file's directory entry is
+/// updated after the data it describes is written. Two `File`s for the same
+/// file don't see each other's size changes.
+#[derive(Debug)]
+pub struct File<T: BlockDevice> {
+    fs: Shared<VFat<T>>,
+    name: String,
+    metadata: Metadata,
+    /// The first cluster, `None` for an empty file.
+    start: Option<Cluster>,
+    size: u32,
+    position: u64,
+    /// The most recently used cluster and its index in the chain, so that
+    /// sequential reads don't walk the chain from the start.
+    current: Option<(u64, Cluster)>,
+    location: Location,
+}
+
+impl<T: BlockDevice> File<T> {
+    pub(crate) fn new(fs: Shared<VFat<T>>, name: String, metadata: Metadata, start: Option<Cluster>, size: u32, location: Location) -> File<T> {
+        File { fs, name, metadata, start, size, position: 0, current: None, location }
+    }
+
+    /// The file's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The file's attributes and timestamps.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The file's size in bytes.
+    pub fn size(&self) -> u64 {
+        self.size as u64
+    }
+
+    /// The file's first cluster, `None` if the file is empty.
+    pub fn cluster(&self) -> Option<Cluster> {
+        self.start
+    }
+
+    pub(crate) fn location(&self) -> Location {
+        self.location
+    }
+
+    /// Returns cluster number `index` of the file's chain. If `allocate` is
+    /// set, the chain is extended as needed to reach it.
+    fn cluster_at(&mut self, fs: &mut VFat<T>, index: u64, allocate: bool) -> io::Result<Cluster> {
+        let (mut i, mut cluster) = match (self.current, self.start) {
+            (Some((i, cluster)), _) if i <= index => (i, cluster),
+            (_, Some(start)) => (0, start),
+            (_, None) if allocate => {
+                let start = fs.alloc_cluster(None, false)?;
+                self.start = Some(start);
+                (0, start)
+            }
+            (_, None) => return Err(corrupt("file has data but no clusters"))
+        };
+
+        while i < index {
+            cluster = match (fs.next_cluster(cluster)?, allocate) {
+                (Some(next), _) => next,
+                (None, true) => fs.alloc_cluster(Some(cluster), false)?,
+                (None, false) => return Err(corrupt("cluster chain shorter than file"))
+            };
+            i += 1;
+        }
+
+        self.current = Some((index, cluster));
+        Ok(cluster)
+    }
+
+    /// Writes `buf` at `position`, allocating clusters as needed, without
+    /// updating the size.
+    fn write_at(&mut self, fs: &mut VFat<T>, mut position: u64, buf: &[u8]) -> io::Result<()> {
+        let bytes_per_cluster = fs.bytes_per_cluster() as u64;
+        let mut written = 0;
+        while written < buf.len() {
+            let cluster = self.cluster_at(fs, position / bytes_per_cluster, true)?;
+            let offset = (position % bytes_per_cluster) as usize;
+            let n = fs.write_cluster(cluster, offset, &buf[written..])?;
+            written += n;
+            position += n as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Records the file's first cluster and size in its directory entry.
+    fn update_entry(&self, fs: &mut VFat<T>) -> io::Result<()> {
+        let mut entry = [0; 32];
+        let offset = self.location.short_entry();
+        fs.read_chain_at(self.location.dir, offset, &mut entry)?;
+
+        let cluster = self.start.map_or(0, |cluster| cluster.number());
+        entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+        entry[28..32].copy_from_slice(&self.size.to_le_bytes());
+        fs.write_chain_at(self.location.dir, offset, &entry)
+    }
+
+    /// Writes zeroes from the end of the file up to `end`.
+    fn zero_fill(&mut self, fs: &mut VFat<T>, end: u64) -> io::Result<()> {
+        let zeroes = vec![0; fs.bytes_per_cluster()];
+        let mut position = self.size as u64;
+        while position < end {
+            let len = cmp::min(zeroes.len() as u64, end - position) as usize;
+            self.write_at(fs, position, &zeroes[..len])?;
+            position += len as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Truncates the file to `len` bytes, which is at most its size, and
+    /// frees the clusters past the new end. The new size is recorded
+    /// before any cluster is freed.
+    fn shrink(&mut self, fs: &mut VFat<T>, len: u64) -> io::Result<()> {
+        let bytes_per_cluster = fs.bytes_per_cluster() as u64;
+        let keep = div_ceil(len, bytes_per_cluster);
+        let (last, old_start) = match (keep, self.start) {
+            (0, start) => (None, start),
+            (_, _) => (Some(self.cluster_at(fs, keep - 1, false)?), None)
+        };
+
+        self.current = None;
+        if last.is_none() {
+            self.start = None;
+        }
+
+        if let Err(error) = self.record_size(fs, len as u32) {
+            self.start = self.start.or(old_start);
+            return Err(error);
+        }
+
+        match (last, old_start) {
+            (Some(last), _) => fs.truncate_chain(last),
+            (None, Some(start)) => fs.free_chain(start),
+            (None, None) => Ok(())
+        }
+    }
+
+    /// Truncates or extends the file to `len` bytes. Extended files are
+    /// filled with zeroes. The position is left unchanged.
+    ///
+    /// When shrinking, the new size is recorded before clusters are freed;
+    /// when growing, the zeroes are written before it is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if `len` is larger than FAT32
+    /// allows, and any error updating the volume.
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        if len > MAX_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "file too large"));
+        }
+
+        let fs = self.fs.clone();
+        let mut fs = fs.lock();
+        match len > self.size as u64 {
+            true => self.or_trim(&mut fs, |file, fs| {
+                file.zero_fill(fs, len)?;
+                file.record_size(fs, len as u32)
+            }),
+            false => self.shrink(&mut fs, len)
+        }
+    }
+
+    /// Runs `update`, which may extend the file's chain. If it fails, the
+    /// clusters past the recorded size are freed again.
+    fn or_trim<F>(&mut self, fs: &mut VFat<T>, update: F) -> io::Result<()>
+        where F: FnOnce(&mut File<T>, &mut VFat<T>) -> io::Result<()>
+    {
+        let result = update(self, fs);
+        if result.is_err() {
+            let size = self.size as u64;
+            let _ = self.shrink(fs, size);
+        }
+
+        result
+    }
+
+    /// Records `size` as the file's size in memory and in its entry.
+    fn record_size(&mut self, fs: &mut VFat<T>, size: u32) -> io::Result<()> {
+        let old = self.size;
+        self.size = size;
+        let result = self.update_entry(fs);
+        if result.is_err() {
+            self.size = old;
+        }
+
+        result
+    }
+}
+
+impl<T: BlockDevice> io::Read for File<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = (self.size as u64).saturating_sub(self.position);
+        let len = cmp::min(remaining, buf.len() as u64) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let fs = self.fs.clone();
+        let mut fs = fs.lock();
+        let bytes_per_cluster = fs.bytes_per_cluster() as u64;
+
+        let mut read = 0;
+        while read < len {
+            let cluster = self.cluster_at(&mut fs, self.position / bytes_per_cluster, false)?;
+            let offset = (self.position % bytes_per_cluster) as usize;
+            let n = fs.read_cluster(cluster, offset, &mut buf[read..len])?;
+            read += n;
+            self.position += n as u64;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<T: BlockDevice> io::Write for File<T> {
+    /// Writes `buf` at the current position. Writing past the end of the
+    /// file first fills the gap with zeroes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if the file would grow larger than
+    /// FAT32 allows, and any error updating the volume. After an error the
+    /// recorded size is unchanged.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let len = cmp::min(buf.len() as u64, MAX_SIZE.saturating_sub(self.position)) as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "file too large"));
+        }
+
+        let fs = self.fs.clone();
+        let mut fs = fs.lock();
+        let position = self.position;
+        self.or_trim(&mut fs, |file, fs| {
+            file.zero_fill(fs, position)?;
+            file.write_at(fs, position, &buf[..len])?;
+
+            let end = position + len as u64;
+            match end > file.size as u64 {
+                true => file.record_size(fs, end as u32),
+                false => Ok(())
+            }
+        })?;
+
+        self.position += len as u64;
+        Ok(len)
+    }
+
+    /// Writes are unbuffered, so there is nothing to flush.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: BlockDevice> io::Seek for File<T> {
+    /// Seeks to `pos`. Seeking past the end of the file is allowed; reads
+    /// there return no data and writes there extend the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if the new position would be
+    /// negative.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(offset) => (self.size as u64, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        let position = match offset >= 0 {
+            true => base.checked_add(offset as u64),
+            false => base.checked_sub(offset.wrapping_neg() as u64),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))
+        }
+    }
+}
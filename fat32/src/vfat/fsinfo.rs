@@ -0,0 +1,43 @@
+use util::read_u32;
+
+/// Signatures at the start, middle and end of the FSInfo sector.
+const LEAD_SIGNATURE: u32 = 0x4161_5252;
+const STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+
+/// Stored in either field when its value isn't known.
+const UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// The free cluster hints kept in the FSInfo sector. They speed up
+/// allocation and free space queries but may be stale or missing, so both
+/// are only used after a sanity check.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FsInfo {
+    /// The number of free clusters.
+    pub free_count: Option<u32>,
+    /// The cluster to start searching for a free cluster at.
+    pub next_free: Option<u32>,
+}
+
+impl FsInfo {
+    /// Parses the FSInfo sector `sector` of a volume with `cluster_count`
+    /// clusters. Returns `None` if its signatures are invalid.
+    pub fn parse(sector: &[u8], cluster_count: u32) -> Option<FsInfo> {
+        if read_u32(sector, 0) != LEAD_SIGNATURE || read_u32(sector, 484) != STRUCT_SIGNATURE
+            || read_u32(sector, 508) != TRAIL_SIGNATURE {
+            return None;
+        }
+
+        let free_count = Some(read_u32(sector, 488)).filter(|&count| count <= cluster_count);
+        let next_free = Some(read_u32(sector, 492))
+            .filter(|&next| next >= 2 && next - 2 < cluster_count);
+
+        Some(FsInfo { free_count, next_free })
+    }
+
+    /// Writes the hints into `sector`, an FSInfo sector.
+    pub fn write(&self, sector: &mut [u8]) {
+        sector[488..492].copy_from_slice(&self.free_count.unwrap_or(UNKNOWN).to_le_bytes());
+        sector[492..496].copy_from_slice(&self.next_free.unwrap_or(UNKNOWN).to_le_bytes());
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+use util::read_u16;
+
+/// The attribute byte of a directory entry.
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub struct Attributes(pub u8);
+
+impl Attributes {
+    pub const READ_ONLY: u8 = 0x01;
+    pub const HIDDEN: u8 = 0x02;
+    pub const SYSTEM: u8 = 0x04;
+    pub const VOLUME_ID: u8 = 0x08;
+    pub const DIRECTORY: u8 = 0x10;
+    pub const ARCHIVE: u8 = 0x20;
+    /// The combination marking a long file name entry.
+    pub const LFN: u8 = 0x0F;
+
+    fn has(&self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.has(Attributes::READ_ONLY)
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.has(Attributes::HIDDEN)
+    }
+
+    pub fn system(&self) -> bool {
+        self.has(Attributes::SYSTEM)
+    }
+
+    pub fn volume_id(&self) -> bool {
+        self.has(Attributes::VOLUME_ID)
+    }
+
+    pub fn directory(&self) -> bool {
+        self.has(Attributes::DIRECTORY)
+    }
+
+    pub fn archive(&self) -> bool {
+        self.has(Attributes::ARCHIVE)
+    }
+
+    /// Returns `true` if these are the attributes of a long file name entry.
+    pub fn is_lfn(&self) -> bool {
+        self.0 & 0x3F == Attributes::LFN
+    }
+}
+
+impl fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (self.directory(), 'd'), (self.read_only(), 'r'), (self.hidden(), 'h'),
+            (self.system(), 's'), (self.archive(), 'a'), (self.volume_id(), 'v'),
+        ];
+
+        for &(set, c) in flags.iter() {
+            write!(f, "{}", if set { c } else { '-' })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A FAT date and time, with two-second resolution and no time zone.
+///
+/// A zero date means the timestamp wasn't recorded.
+#[derive(Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    /// Bits 9-15 are years since 1980, 5-8 the month, and 0-4 the day.
+    pub date: u16,
+    /// Bits 11-15 are hours, 5-10 minutes, and 0-4 seconds divided by two.
+    pub time: u16,
+}
+
+impl Timestamp {
+    /// Returns `true` if the timestamp was recorded.
+    pub fn is_set(&self) -> bool {
+        self.date != 0
+    }
+
+    /// The calendar year, such as `2018`.
+    pub fn year(&self) -> usize {
+        1980 + (self.date >> 9) as usize
+    }
+
+    /// The month, `1` to `12`.
+    pub fn month(&self) -> u8 {
+        ((self.date >> 5) & 0x0F) as u8
+    }
+
+    /// The day of the month, `1` to `31`.
+    pub fn day(&self) -> u8 {
+        (self.date & 0x1F) as u8
+    }
+
+    /// The hour, `0` to `23`.
+    pub fn hour(&self) -> u8 {
+        (self.time >> 11) as u8
+    }
+
+    /// The minute, `0` to `59`.
+    pub fn minute(&self) -> u8 {
+        ((self.time >> 5) & 0x3F) as u8
+    }
+
+    /// The second, `0` to `58`.
+    pub fn second(&self) -> u8 {
+        ((self.time & 0x1F) * 2) as u8
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year(), self.month(),
+            self.day(), self.hour(), self.minute(), self.second())
+    }
+}
+
+impl fmt::Debug for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timestamp({})", self)
+    }
+}
+
+/// The attributes and timestamps of a file or directory.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub attributes: Attributes,
+    pub created: Timestamp,
+    /// Only the date of the last access is recorded.
+    pub accessed: Timestamp,
+    pub modified: Timestamp,
+}
+
+impl Metadata {
+    /// Parses the metadata fields of the 32-byte directory entry `entry`.
+    pub fn parse(entry: &[u8]) -> Metadata {
+        Metadata {
+            attributes: Attributes(entry[11]),
+            created: Timestamp { date: read_u16(entry, 16), time: read_u16(entry, 14) },
+            accessed: Timestamp { date: read_u16(entry, 18), time: 0 },
+            modified: Timestamp { date: read_u16(entry, 24), time: read_u16(entry, 22) },
+        }
+    }
+}
@@ -0,0 +1,17 @@
+mod ebpb;
+mod fat;
+mod fsinfo;
+mod name;
+mod volume;
+mod dir;
+mod entry;
+mod file;
+mod metadata;
+
+pub use self::ebpb::BiosParameterBlock;
+pub use self::fat::{Cluster, Status};
+pub use self::volume::{VFat, Error};
+pub use self::dir::{Dir, DirIter};
+pub use self::entry::Entry;
+pub use self::file::File;
+pub use self::metadata::{Metadata, Attributes, Timestamp};
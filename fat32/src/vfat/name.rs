@@ -0,0 +1,271 @@
+use std::char::decode_utf16;
+use std::io;
+
+use util::{read_u16, div_ceil};
+
+/// The first name byte of a deleted entry.
+pub const DELETED_MARKER: u8 = 0xE5;
+
+/// Stands in for a leading `0xE5` byte in a short name, which would
+/// otherwise mark the entry as deleted.
+const KANJI_ESCAPE: u8 = 0x05;
+
+/// Flags in the NT reserved byte asking for the base name and extension
+/// of a short name to be shown in lower case.
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXT: u8 = 0x10;
+
+/// The size of a short name in bytes: an 8-byte base and 3-byte extension.
+pub const SHORT_NAME_SIZE: usize = 11;
+
+/// The number of UTF-16 code units in a long file name entry.
+const LFN_CHARS: usize = 13;
+
+/// The offsets of the UTF-16 code units in a long file name entry.
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// The longest name, in UTF-16 code units.
+const MAX_NAME_LEN: usize = 255;
+
+/// The most long file name entries a name can take.
+const MAX_LFN_ENTRIES: u8 = 20;
+
+/// Set in the sequence number of the last (first stored) long name entry.
+const LFN_LAST: u8 = 0x40;
+
+/// Characters that can't appear in any name.
+const INVALID_CHARS: &str = "\"*/:<>?\\|";
+
+/// Characters other than ASCII letters and digits allowed in a short name.
+const SHORT_NAME_CHARS: &[u8] = b"!#$%&'()-@^_`{}~";
+
+/// The checksum of an 11-byte short name, stored in each of its long file
+/// name entries.
+pub fn short_name_checksum(name: &[u8]) -> u8 {
+    name[..SHORT_NAME_SIZE].iter().fold(0u8, |sum, &byte| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(byte)
+    })
+}
+
+/// Returns the displayed form of the 11-byte short name in `entry`.
+pub fn short_name(entry: &[u8]) -> String {
+    let decode = |bytes: &[u8], lowercase: bool| -> String {
+        let end = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        bytes[..end].iter().map(|&b| match lowercase {
+            true => (b as char).to_ascii_lowercase(),
+            false => b as char
+        }).collect()
+    };
+
+    let mut base = [0; 8];
+    base.copy_from_slice(&entry[..8]);
+    if base[0] == KANJI_ESCAPE {
+        base[0] = DELETED_MARKER;
+    }
+
+    let mut name = decode(&base, entry[12] & LOWERCASE_BASE != 0);
+    let ext = decode(&entry[8..11], entry[12] & LOWERCASE_EXT != 0);
+    if !ext.is_empty() {
+        name.push('.');
+        name.push_str(&ext);
+    }
+
+    name
+}
+
+/// A long file name being assembled from its entries, which are stored in
+/// reverse order before the short name entry they belong to.
+pub struct LongName {
+    chars: Vec<u16>,
+    checksum: u8,
+    /// The sequence number of the entry read last.
+    next: u8,
+    /// The offset of the name's first entry in its directory.
+    pub start: u64,
+}
+
+impl LongName {
+    /// Records the long name entry `entry`, found at `offset` in its
+    /// directory. Returns `None` if it doesn't continue the name in
+    /// `current`, dropping the partial name.
+    pub fn push(current: Option<LongName>, entry: &[u8], offset: u64) -> Option<LongName> {
+        let sequence = entry[0] & !LFN_LAST;
+        let checksum = entry[13];
+        if sequence == 0 || sequence > MAX_LFN_ENTRIES {
+            return None;
+        }
+
+        let mut name = match (entry[0] & LFN_LAST != 0, current) {
+            (true, _) => LongName {
+                chars: vec![0xFFFF; sequence as usize * LFN_CHARS],
+                checksum,
+                next: sequence,
+                start: offset,
+            },
+            (false, Some(name)) => match name.next == sequence + 1 && name.checksum == checksum {
+                true => name,
+                false => return None
+            },
+            (false, None) => return None
+        };
+
+        let start = (sequence as usize - 1) * LFN_CHARS;
+        for (i, &offset) in LFN_OFFSETS.iter().enumerate() {
+            name.chars[start + i] = read_u16(entry, offset);
+        }
+
+        name.next = sequence;
+        Some(name)
+    }
+
+    /// Returns the name if it is complete and belongs to the short name
+    /// entry `entry`.
+    pub fn finish(&self, entry: &[u8]) -> Option<String> {
+        if self.next != 1 || self.checksum != short_name_checksum(entry) {
+            return None;
+        }
+
+        let end = self.chars.iter().position(|&c| c == 0x0000 || c == 0xFFFF)
+            .unwrap_or(self.chars.len());
+        let name: String = decode_utf16(self.chars[..end].iter().cloned())
+            .map(|c| c.unwrap_or(::std::char::REPLACEMENT_CHARACTER))
+            .collect();
+
+        match name.is_empty() {
+            true => None,
+            false => Some(name)
+        }
+    }
+}
+
+/// Returns an `InvalidInput` error if `name` can't be used as a file or
+/// directory name.
+pub fn validate(name: &str) -> io::Result<()> {
+    let invalid = name.is_empty() || name == "." || name == ".."
+        || name.ends_with('.') || name.ends_with(' ')
+        || name.encode_utf16().count() > MAX_NAME_LEN
+        || name.chars().any(|c| c < ' ' || c == '\u{7F}' || INVALID_CHARS.contains(c));
+
+    match invalid {
+        true => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid file name")),
+        false => Ok(())
+    }
+}
+
+/// The short name entry fields chosen for a new name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ShortName {
+    /// The 11-byte short name.
+    pub name: [u8; SHORT_NAME_SIZE],
+    /// The NT lower case flags.
+    pub case: u8,
+    /// Whether long name entries are needed to store the name.
+    pub needs_lfn: bool,
+}
+
+/// Returns the short name part of `name` for an alias: upper cased, with
+/// characters not allowed in short names replaced by `_` and spaces and
+/// periods dropped, at most `max` bytes long.
+fn alias_part(name: &str, max: usize) -> Vec<u8> {
+    name.chars()
+        .filter(|&c| c != ' ' && c != '.')
+        .map(|c| match c.is_ascii() && (c.is_ascii_alphanumeric() || SHORT_NAME_CHARS.contains(&(c as u8))) {
+            true => c.to_ascii_uppercase() as u8,
+            false => b'_'
+        })
+        .take(max)
+        .collect()
+}
+
+impl ShortName {
+    /// Chooses the short name for `name`, which must be valid, avoiding the
+    /// short names in `taken`.
+    ///
+    /// Names that fit 8.3 and use one case per part are stored as short
+    /// names alone, using the NT lower case flags. Others get a numbered
+    /// alias such as `LONGFI~1.TXT` and long name entries.
+    pub fn generate(name: &str, taken: &[[u8; SHORT_NAME_SIZE]]) -> ShortName {
+        let is_short_char = |b: u8| b.is_ascii_alphanumeric() || SHORT_NAME_CHARS.contains(&b);
+        let one_case = |s: &str| s == s.to_ascii_uppercase() || s == s.to_ascii_lowercase();
+        let lower = |s: &str| s.bytes().any(|b| b.is_ascii_lowercase());
+
+        let (base, ext) = match name.rfind('.') {
+            Some(i) => (&name[..i], &name[i + 1..]),
+            None => (name, "")
+        };
+
+        let fits = !base.is_empty() && base.len() <= 8 && ext.len() <= 3
+            && base.bytes().chain(ext.bytes()).all(&is_short_char)
+            && one_case(base) && one_case(ext);
+
+        let mut short = [b' '; SHORT_NAME_SIZE];
+        if fits {
+            short[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+            short[8..8 + ext.len()].copy_from_slice(ext.to_ascii_uppercase().as_bytes());
+            if !taken.contains(&short) {
+                let case = match (lower(base), lower(ext)) {
+                    (true, true) => LOWERCASE_BASE | LOWERCASE_EXT,
+                    (true, false) => LOWERCASE_BASE,
+                    (false, true) => LOWERCASE_EXT,
+                    (false, false) => 0,
+                };
+
+                return ShortName { name: short, case, needs_lfn: false };
+            }
+        }
+
+        // Leading periods don't start an extension.
+        let trimmed = name.trim_start_matches('.');
+        let (base, ext) = match trimmed.rfind('.') {
+            Some(i) => (&trimmed[..i], &trimmed[i + 1..]),
+            None => (trimmed, "")
+        };
+
+        let mut basis = alias_part(base, 6);
+        if basis.is_empty() {
+            basis.push(b'_');
+        }
+
+        let ext = alias_part(ext, 3);
+        let mut n = 1;
+        loop {
+            let tail = format!("~{}", n);
+            let len = basis.len().min(8 - tail.len());
+
+            let mut short = [b' '; SHORT_NAME_SIZE];
+            short[..len].copy_from_slice(&basis[..len]);
+            short[len..len + tail.len()].copy_from_slice(tail.as_bytes());
+            short[8..8 + ext.len()].copy_from_slice(&ext);
+            if !taken.contains(&short) {
+                return ShortName { name: short, case: 0, needs_lfn: true };
+            }
+
+            n += 1;
+        }
+    }
+
+    /// Returns the long file name entries storing `name` for this short
+    /// name, in the order they are stored on disk.
+    pub fn lfn_entries(&self, name: &str) -> Vec<[u8; 32]> {
+        let mut chars: Vec<u16> = name.encode_utf16().collect();
+        let count = div_ceil(chars.len() as u64, LFN_CHARS as u64) as usize;
+        if chars.len() < count * LFN_CHARS {
+            chars.push(0x0000);
+            chars.resize(count * LFN_CHARS, 0xFFFF);
+        }
+
+        let checksum = short_name_checksum(&self.name);
+        (1..=count).rev().map(|sequence| {
+            let mut entry = [0; 32];
+            entry[0] = sequence as u8 | if sequence == count { LFN_LAST } else { 0 };
+            entry[11] = 0x0F;
+            entry[13] = checksum;
+            for (i, &offset) in LFN_OFFSETS.iter().enumerate() {
+                let c = chars[(sequence - 1) * LFN_CHARS + i];
+                entry[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+            }
+
+            entry
+        }).collect()
+    }
+}
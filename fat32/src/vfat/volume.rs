@@ -0,0 +1,675 @@
+use std::cmp;
+use std::io;
+
+use storage::{BlockDevice, MasterBootRecord};
+use storage::mbr;
+
+use shared::Shared;
+use util::{read_u16, read_u32, other_error};
+use vfat::ebpb::{BiosParameterBlock, BOOT_SECTOR_SIZE};
+use vfat::fat::{Cluster, Status, FAT_ENTRY_SIZE, ENTRY_MASK, EOC};
+use vfat::fsinfo::FsInfo;
+use vfat::{Dir, Entry, File, Metadata, Attributes};
+
+/// Error type for mounting failures.
+#[derive(Debug)]
+pub enum Error {
+    /// There was an I/O error while reading the volume.
+    Io(io::Error),
+    /// The partition table was invalid.
+    Mbr(mbr::Error),
+    /// The device is partitioned but has no FAT32 partition.
+    NoFat32Partition,
+    /// The boot sector or extended boot signature was invalid.
+    BadSignature,
+    /// The volume is FAT12 or FAT16.
+    NotFat32,
+    /// The named boot sector field is unusable.
+    BadGeometry(&'static str),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<mbr::Error> for Error {
+    fn from(error: mbr::Error) -> Error {
+        Error::Mbr(error)
+    }
+}
+
+/// Returns an `InvalidData` error for a corrupted volume.
+pub(crate) fn corrupt(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// A mounted FAT32 volume.
+///
+/// Logical sectors may be larger than the device's sectors, in which case
+/// each logical sector is read as several consecutive device sectors.
+///
+/// Updates are ordered so that an interrupted operation can leave clusters
+/// allocated but unused, but never links a cluster into two chains or
+/// points an entry at unwritten clusters of another file.
+#[derive(Debug)]
+pub struct VFat<T: BlockDevice> {
+    device: T,
+    /// The volume's first device sector.
+    start: u64,
+    /// The number of device sectors in a logical sector.
+    factor: u64,
+    bytes_per_sector: usize,
+    sectors_per_cluster: u64,
+    fat_start_sector: u64,
+    /// The first sector of every FAT kept up to date: all of them, unless
+    /// mirroring is disabled.
+    mirrors: Vec<u64>,
+    data_start_sector: u64,
+    cluster_count: u32,
+    root_cluster: Cluster,
+    /// The FSInfo sector, if the volume has a valid one.
+    fsinfo_sector: Option<u64>,
+    fsinfo: FsInfo,
+    bpb: BiosParameterBlock,
+}
+
+/// Returns `true` if `sector` looks like a FAT boot sector rather than an
+/// MBR: it starts with an x86 jump and declares a plausible sector size.
+fn is_boot_sector(sector: &[u8]) -> bool {
+    let jump = sector[0] == 0xEB && sector[2] == 0x90 || sector[0] == 0xE9;
+    let bytes_per_sector = read_u16(sector, 11);
+    jump && bytes_per_sector >= 512 && bytes_per_sector.is_power_of_two()
+}
+
+impl<T: BlockDevice> VFat<T> {
+    /// Mounts the FAT32 volume on `device`.
+    ///
+    /// If sector 0 of `device` is a FAT boot sector, the whole device is
+    /// the volume. Otherwise sector 0 must be an MBR and the first FAT32
+    /// partition in it is mounted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Io` if reading the device fails, `Mbr` if the partition table
+    /// is invalid, `NoFat32Partition` if it has no FAT32 partition, and the
+    /// errors of `BiosParameterBlock::parse()` if the volume's boot sector
+    /// is invalid.
+    pub fn from(mut device: T) -> Result<Shared<VFat<T>>, Error> {
+        let device_sector_size = device.sector_size();
+        if device_sector_size < BOOT_SECTOR_SIZE as u64 {
+            return Err(Error::Mbr(mbr::Error::SectorTooSmall(device_sector_size)));
+        }
+
+        let mut sector = vec![0; device_sector_size as usize];
+        device.read_sector(0, &mut sector)?;
+
+        let start = match is_boot_sector(&sector) {
+            true => 0,
+            false => {
+                let mbr = MasterBootRecord::parse(&sector, device.sector_count())?;
+                let start = match mbr.first_fat32() {
+                    Some(entry) => entry.start,
+                    None => return Err(Error::NoFat32Partition)
+                };
+
+                device.read_sector(start, &mut sector)?;
+                start
+            }
+        };
+
+        let bpb = BiosParameterBlock::parse(&sector)?;
+        let bytes_per_sector = bpb.bytes_per_sector as u64;
+        let factor = bytes_per_sector / device_sector_size;
+        if factor == 0 || factor * device_sector_size != bytes_per_sector {
+            return Err(Error::BadGeometry("bytes per sector"));
+        }
+
+        if start + bpb.total_sectors as u64 * factor > device.sector_count() {
+            return Err(Error::BadGeometry("total sectors"));
+        }
+
+        let mirrors = match bpb.ext_flags & 0x80 {
+            0 => (0..bpb.num_fats).map(|n| bpb.fat_start_sector(n)).collect(),
+            _ => vec![bpb.fat_start_sector(bpb.active_fat())]
+        };
+
+        let mut vfat = VFat {
+            device,
+            start,
+            factor,
+            bytes_per_sector: bytes_per_sector as usize,
+            sectors_per_cluster: bpb.sectors_per_cluster as u64,
+            fat_start_sector: bpb.fat_start_sector(bpb.active_fat()),
+            mirrors,
+            data_start_sector: bpb.data_start_sector(),
+            cluster_count: bpb.cluster_count(),
+            root_cluster: Cluster::new(bpb.root_cluster),
+            fsinfo_sector: None,
+            fsinfo: FsInfo::default(),
+            bpb,
+        };
+
+        let fsinfo_sector = vfat.bpb.fsinfo_sector as u64;
+        if fsinfo_sector != 0 && fsinfo_sector < vfat.bpb.reserved_sectors as u64 {
+            let mut buf = vec![0; vfat.bytes_per_sector];
+            vfat.read_sector(fsinfo_sector, &mut buf)?;
+            if let Some(fsinfo) = FsInfo::parse(&buf, vfat.cluster_count) {
+                vfat.fsinfo_sector = Some(fsinfo_sector);
+                vfat.fsinfo = fsinfo;
+            }
+        }
+
+        Ok(Shared::new(vfat))
+    }
+
+    /// The volume's boot sector fields.
+    pub fn bpb(&self) -> &BiosParameterBlock {
+        &self.bpb
+    }
+
+    /// The size of a logical sector in bytes.
+    pub fn bytes_per_sector(&self) -> usize {
+        self.bytes_per_sector
+    }
+
+    /// The size of a cluster in bytes.
+    pub fn bytes_per_cluster(&self) -> usize {
+        self.bytes_per_sector * self.sectors_per_cluster as usize
+    }
+
+    /// The number of data clusters on the volume.
+    pub fn cluster_count(&self) -> u32 {
+        self.cluster_count
+    }
+
+    /// The first cluster of the root directory.
+    pub fn root_cluster(&self) -> Cluster {
+        self.root_cluster
+    }
+
+    /// The number of free clusters recorded in the FSInfo sector, if known.
+    pub fn free_clusters(&self) -> Option<u32> {
+        self.fsinfo.free_count
+    }
+
+    /// Returns the number of free clusters, counting them in the FAT if the
+    /// FSInfo sector doesn't record it. The count is then kept up to date as
+    /// clusters are allocated and freed.
+    pub fn count_free_clusters(&mut self) -> io::Result<u32> {
+        if let Some(free) = self.fsinfo.free_count {
+            return Ok(free);
+        }
+
+        let per_sector = (self.bytes_per_sector / FAT_ENTRY_SIZE) as u64;
+        let mut buf = vec![0; self.bytes_per_sector];
+        let mut loaded = None;
+        let mut free = 0;
+        for i in 0..self.cluster_count {
+            let cluster = Cluster::new(2 + i);
+            let sector = cluster.number() as u64 / per_sector;
+            if loaded != Some(sector) {
+                let fat_start_sector = self.fat_start_sector;
+                self.read_sector(fat_start_sector + sector, &mut buf)?;
+                loaded = Some(sector);
+            }
+
+            if Status::read(&buf, cluster) == Status::Free {
+                free += 1;
+            }
+        }
+
+        self.fsinfo.free_count = Some(free);
+        Ok(free)
+    }
+
+    /// The device the volume is mounted from.
+    pub fn device(&self) -> &T {
+        &self.device
+    }
+
+    /// The device the volume is mounted from. Writing to it directly can
+    /// corrupt the volume.
+    pub fn device_mut(&mut self) -> &mut T {
+        &mut self.device
+    }
+
+    /// Reads logical sector `n` of the volume into `buf`, which must be
+    /// exactly one logical sector long.
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<()> {
+        let device_sector_size = buf.len() / self.factor as usize;
+        let first = self.start + n * self.factor;
+        for (i, chunk) in buf.chunks_mut(device_sector_size).enumerate() {
+            if self.device.read_sector(first + i as u64, chunk)? < chunk.len() {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "short sector read"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes `buf`, which must be exactly one logical sector long, to
+    /// logical sector `n` of the volume.
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<()> {
+        let device_sector_size = buf.len() / self.factor as usize;
+        let first = self.start + n * self.factor;
+        for (i, chunk) in buf.chunks(device_sector_size).enumerate() {
+            if self.device.write_sector(first + i as u64, chunk)? < chunk.len() {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "short sector write"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `cluster`'s logical sector, or an error if it isn't a data
+    /// cluster of this volume.
+    fn cluster_sector(&self, cluster: Cluster) -> io::Result<u64> {
+        match cluster.index() {
+            Some(index) if index < self.cluster_count => {
+                Ok(self.data_start_sector + index as u64 * self.sectors_per_cluster)
+            }
+            _ => Err(corrupt("cluster number out of range"))
+        }
+    }
+
+    /// Returns the status of `cluster` from the active FAT.
+    pub fn fat_entry(&mut self, cluster: Cluster) -> io::Result<Status> {
+        self.cluster_sector(cluster)?;
+
+        let offset = cluster.number() as u64 * FAT_ENTRY_SIZE as u64;
+        let sector = self.fat_start_sector + offset / self.bytes_per_sector as u64;
+        let mut buf = vec![0; self.bytes_per_sector];
+        self.read_sector(sector, &mut buf)?;
+        Ok(Status::read(&buf, cluster))
+    }
+
+    /// Returns the cluster after `cluster` in its chain, or `None` if
+    /// `cluster` is the last one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidData` error if the chain runs into a free,
+    /// reserved or bad cluster.
+    pub fn next_cluster(&mut self, cluster: Cluster) -> io::Result<Option<Cluster>> {
+        match self.fat_entry(cluster)? {
+            Status::Data(next) => {
+                self.cluster_sector(next)?;
+                Ok(Some(next))
+            }
+            Status::Eoc(_) => Ok(None),
+            Status::Free => Err(corrupt("cluster chain runs into a free cluster")),
+            Status::Reserved => Err(corrupt("cluster chain runs into a reserved cluster")),
+            Status::Bad => Err(corrupt("cluster chain runs into a bad cluster")),
+        }
+    }
+
+    /// Sets the FAT entry for `cluster` to `status` in every mirrored FAT,
+    /// keeping the entry's reserved high bits.
+    fn set_fat_entry(&mut self, cluster: Cluster, status: Status) -> io::Result<()> {
+        self.cluster_sector(cluster)?;
+
+        let offset = cluster.number() as u64 * FAT_ENTRY_SIZE as u64;
+        let within = (offset % self.bytes_per_sector as u64) as usize;
+        let mut buf = vec![0; self.bytes_per_sector];
+        for i in 0..self.mirrors.len() {
+            let sector = self.mirrors[i] + offset / self.bytes_per_sector as u64;
+            self.read_sector(sector, &mut buf)?;
+            let entry = read_u32(&buf, within) & !ENTRY_MASK | status.to_entry();
+            buf[within..within + FAT_ENTRY_SIZE].copy_from_slice(&entry.to_le_bytes());
+            self.write_sector(sector, &buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the free cluster hints back to the FSInfo sector, if any.
+    fn write_fsinfo(&mut self) -> io::Result<()> {
+        if let Some(sector) = self.fsinfo_sector {
+            let mut buf = vec![0; self.bytes_per_sector];
+            self.read_sector(sector, &mut buf)?;
+            self.fsinfo.write(&mut buf);
+            self.write_sector(sector, &buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a free cluster, searching from the FSInfo hint, or `None` if
+    /// the volume is full.
+    fn find_free(&mut self) -> io::Result<Option<Cluster>> {
+        let per_sector = (self.bytes_per_sector / FAT_ENTRY_SIZE) as u64;
+        let first = self.fsinfo.next_free.unwrap_or(2) - 2;
+
+        let mut buf = vec![0; self.bytes_per_sector];
+        let mut loaded = None;
+        for i in 0..self.cluster_count {
+            let cluster = Cluster::new(2 + (first + i) % self.cluster_count);
+            let sector = cluster.number() as u64 / per_sector;
+            if loaded != Some(sector) {
+                let fat_start_sector = self.fat_start_sector;
+                self.read_sector(fat_start_sector + sector, &mut buf)?;
+                loaded = Some(sector);
+            }
+
+            if Status::read(&buf, cluster) == Status::Free {
+                return Ok(Some(cluster));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Allocates a cluster, zeroing it if `zero` is set, and appends it to
+    /// the chain ending with `prev`, if any.
+    ///
+    /// The new cluster is marked as the end of a chain before `prev` is
+    /// linked to it, so an interrupted call at worst leaks it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Other` error if the volume is full.
+    pub(crate) fn alloc_cluster(&mut self, prev: Option<Cluster>, zero: bool) -> io::Result<Cluster> {
+        let cluster = match self.find_free()? {
+            Some(cluster) => cluster,
+            None => return Err(other_error("no space left on volume"))
+        };
+
+        if zero {
+            let bytes_per_cluster = self.bytes_per_cluster();
+            self.write_cluster(cluster, 0, &vec![0; bytes_per_cluster])?;
+        }
+
+        self.set_fat_entry(cluster, Status::Eoc(EOC))?;
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, Status::Data(cluster))?;
+        }
+
+        let next = 2 + (cluster.number() - 1) % self.cluster_count;
+        self.fsinfo.free_count = self.fsinfo.free_count.map(|count| count.saturating_sub(1));
+        self.fsinfo.next_free = Some(next);
+        self.write_fsinfo()?;
+        Ok(cluster)
+    }
+
+    /// Frees every cluster in the chain starting at `start`.
+    pub(crate) fn free_chain(&mut self, start: Cluster) -> io::Result<()> {
+        let mut cluster = Some(start);
+        let mut count = 0;
+        while let Some(current) = cluster {
+            count += 1;
+            if count > self.cluster_count {
+                return Err(corrupt("cluster chain loops"));
+            }
+
+            cluster = self.next_cluster(current)?;
+            self.set_fat_entry(current, Status::Free)?;
+        }
+
+        let cluster_count = self.cluster_count;
+        self.fsinfo.free_count = self.fsinfo.free_count
+            .map(|free| free.saturating_add(count).min(cluster_count));
+        self.write_fsinfo()
+    }
+
+    /// Makes `last` the end of its chain, freeing the clusters after it.
+    pub(crate) fn truncate_chain(&mut self, last: Cluster) -> io::Result<()> {
+        let rest = self.next_cluster(last)?;
+        self.set_fat_entry(last, Status::Eoc(EOC))?;
+        match rest {
+            Some(rest) => self.free_chain(rest),
+            None => Ok(())
+        }
+    }
+
+    /// Returns the last cluster of the chain starting at `start`.
+    pub(crate) fn last_cluster(&mut self, start: Cluster) -> io::Result<Cluster> {
+        let mut cluster = start;
+        let mut count = 0;
+        while let Some(next) = self.next_cluster(cluster)? {
+            count += 1;
+            if count > self.cluster_count {
+                return Err(corrupt("cluster chain loops"));
+            }
+
+            cluster = next;
+        }
+
+        Ok(cluster)
+    }
+
+    /// Returns cluster number `index` of the chain starting at `start`.
+    fn chain_cluster(&mut self, start: Cluster, index: u64) -> io::Result<Cluster> {
+        let mut cluster = start;
+        for _ in 0..index {
+            cluster = match self.next_cluster(cluster)? {
+                Some(next) => next,
+                None => return Err(corrupt("offset past the end of a cluster chain"))
+            };
+        }
+
+        Ok(cluster)
+    }
+
+    /// Reads `buf.len()` bytes at `offset` in the chain starting at
+    /// `start`. The bytes must not span clusters.
+    pub(crate) fn read_chain_at(&mut self, start: Cluster, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let bytes_per_cluster = self.bytes_per_cluster() as u64;
+        let cluster = self.chain_cluster(start, offset / bytes_per_cluster)?;
+        self.read_cluster(cluster, (offset % bytes_per_cluster) as usize, buf)?;
+        Ok(())
+    }
+
+    /// Writes `buf` at `offset` in the chain starting at `start`. The bytes
+    /// must not span clusters.
+    pub(crate) fn write_chain_at(&mut self, start: Cluster, offset: u64, buf: &[u8]) -> io::Result<()> {
+        let bytes_per_cluster = self.bytes_per_cluster() as u64;
+        let cluster = self.chain_cluster(start, offset / bytes_per_cluster)?;
+        self.write_cluster(cluster, (offset % bytes_per_cluster) as usize, buf)?;
+        Ok(())
+    }
+
+    /// Writes `buf` to `cluster`, starting `offset` bytes into it. Returns
+    /// the number of bytes written, which is less than `buf.len()` if the
+    /// end of the cluster is reached.
+    pub(crate) fn write_cluster(&mut self, cluster: Cluster, offset: usize, buf: &[u8]) -> io::Result<usize> {
+        let first = self.cluster_sector(cluster)?;
+        let bytes_per_sector = self.bytes_per_sector;
+        let end = cmp::min(self.bytes_per_cluster(), offset + buf.len());
+
+        let mut sector_buf = vec![0; bytes_per_sector];
+        let mut position = offset;
+        while position < end {
+            let sector = first + (position / bytes_per_sector) as u64;
+            let start = position % bytes_per_sector;
+            let len = cmp::min(bytes_per_sector - start, end - position);
+            let data = &buf[position - offset..position - offset + len];
+
+            if len == bytes_per_sector {
+                self.write_sector(sector, data)?;
+            } else {
+                self.read_sector(sector, &mut sector_buf)?;
+                sector_buf[start..start + len].copy_from_slice(data);
+                self.write_sector(sector, &sector_buf)?;
+            }
+
+            position += len;
+        }
+
+        Ok(end.saturating_sub(offset))
+    }
+
+    /// Reads from `cluster`, starting `offset` bytes into it, into `buf`.
+    /// Returns the number of bytes read, which is less than `buf.len()` if
+    /// the end of the cluster is reached.
+    pub fn read_cluster(&mut self, cluster: Cluster, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
+        let first = self.cluster_sector(cluster)?;
+        let bytes_per_sector = self.bytes_per_sector;
+        let end = cmp::min(self.bytes_per_cluster(), offset + buf.len());
+
+        let mut sector_buf = vec![0; bytes_per_sector];
+        let mut position = offset;
+        while position < end {
+            let sector = first + (position / bytes_per_sector) as u64;
+            let start = position % bytes_per_sector;
+            let len = cmp::min(bytes_per_sector - start, end - position);
+            let out = &mut buf[position - offset..position - offset + len];
+
+            if len == bytes_per_sector {
+                self.read_sector(sector, out)?;
+            } else {
+                self.read_sector(sector, &mut sector_buf)?;
+                out.copy_from_slice(&sector_buf[start..start + len]);
+            }
+
+            position += len;
+        }
+
+        Ok(end.saturating_sub(offset))
+    }
+
+    /// Appends the contents of every cluster in the chain starting at
+    /// `start` to `buf`. Returns the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidData` error if the chain is broken or loops.
+    pub fn read_chain(&mut self, start: Cluster, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let bytes_per_cluster = self.bytes_per_cluster();
+        let initial = buf.len();
+
+        let mut cluster = Some(start);
+        let mut count = 0;
+        while let Some(current) = cluster {
+            count += 1;
+            if count > self.cluster_count {
+                return Err(corrupt("cluster chain loops"));
+            }
+
+            let position = buf.len();
+            buf.resize(position + bytes_per_cluster, 0);
+            self.read_cluster(current, 0, &mut buf[position..])?;
+            cluster = self.next_cluster(current)?;
+        }
+
+        Ok(buf.len() - initial)
+    }
+}
+
+impl<T: BlockDevice> Shared<VFat<T>> {
+    /// Returns the root directory.
+    pub fn root(&self) -> Dir<T> {
+        let root_cluster = self.lock().root_cluster();
+        let metadata = Metadata { attributes: Attributes(Attributes::DIRECTORY), ..Metadata::default() };
+        Dir::new(self.clone(), String::new(), metadata, root_cluster, None)
+    }
+
+    /// Opens the file or directory at `path`.
+    ///
+    /// Paths are relative to the root directory whether or not they start
+    /// with `/`. Names are compared case-insensitively, `.` components are
+    /// ignored and `..` goes to the parent directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NotFound` error if a component doesn't exist,
+    /// `InvalidInput` if a component other than the last is a file, and any
+    /// error reading the directories along the way.
+    pub fn open(&self, path: &str) -> io::Result<Entry<T>> {
+        let mut entry = Entry::Dir(self.root());
+        for name in path.split('/').filter(|name| !name.is_empty() && *name != ".") {
+            let dir = match entry {
+                Entry::Dir(dir) => dir,
+                Entry::File(_) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"));
+                }
+            };
+
+            entry = match (name, dir.find(name)?) {
+                (_, Some(found)) => found,
+                ("..", None) => Entry::Dir(dir),
+                (_, None) => return Err(io::Error::new(io::ErrorKind::NotFound, "no such file or directory")),
+            };
+        }
+
+        Ok(entry)
+    }
+
+    /// Returns the directory containing `path` and the last component of
+    /// `path`.
+    fn parent<'a>(&self, path: &'a str) -> io::Result<(Dir<T>, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = match path.rfind('/') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path)
+        };
+
+        match self.open(parent)? {
+            Entry::Dir(dir) => Ok((dir, name)),
+            Entry::File(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+        }
+    }
+
+    /// Opens the file at `path` for writing, truncating it if it exists and
+    /// creating it if it doesn't.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if `path` names a directory, and the
+    /// errors of `open()` for the parent directory and of
+    /// `Dir::create_file()`.
+    pub fn create(&self, path: &str) -> io::Result<File<T>> {
+        let (dir, name) = self.parent(path)?;
+        match dir.find(name)? {
+            Some(Entry::File(mut file)) => {
+                file.set_len(0)?;
+                Ok(file)
+            }
+            Some(Entry::Dir(_)) => Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory")),
+            None => dir.create_file(name)
+        }
+    }
+
+    /// Creates the directory at `path`. Its parent must exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()` for the parent directory and of
+    /// `Dir::create_dir()`.
+    pub fn create_dir(&self, path: &str) -> io::Result<Dir<T>> {
+        let (dir, name) = self.parent(path)?;
+        dir.create_dir(name)
+    }
+
+    /// Moves the file or directory at `from` to `to`. The parent of `to`
+    /// must exist and `to` must not, unless it only differs from `from` in
+    /// case.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()` for both parent directories, and a
+    /// `NotFound` error if `from` doesn't exist, `AlreadyExists` if `to`
+    /// does, and `InvalidInput` if `to` isn't a valid name or is inside the
+    /// directory being moved.
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let (from_dir, from_name) = self.parent(from)?;
+        let (to_dir, to_name) = self.parent(to)?;
+        from_dir.rename(from_name, &to_dir, to_name)
+    }
+
+    /// Removes the file or empty directory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()` for the parent directory and of
+    /// `Dir::remove()`.
+    pub fn remove(&self, path: &str) -> io::Result<()> {
+        let (dir, name) = self.parent(path)?;
+        if name.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't remove the root directory"));
+        }
+
+        dir.remove(name)
+    }
+}
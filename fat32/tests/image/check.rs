@@ -0,0 +1,301 @@
+//! An independent consistency checker for FAT32 images, modelled on
+//! `fsck.fat -n`. Problems that `fsck.fat` repairs without losing data,
+//! like lost clusters, are warnings; anything that loses or shares data is
+//! an error. If `fsck.fat` is installed, it is run as well, and anything it
+//! reports is a warning.
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Report {
+    /// Panics unless the image has no errors and no warnings.
+    pub fn assert_clean(&self) {
+        assert!(self.errors.is_empty() && self.warnings.is_empty(), "image not clean: {:#?}", self);
+    }
+
+    /// Panics if the image has errors.
+    pub fn assert_no_errors(&self) {
+        assert!(self.errors.is_empty(), "image has errors: {:#?}", self);
+    }
+}
+
+struct Volume<'a> {
+    data: &'a [u8],
+    bytes_per_cluster: usize,
+    data_start: usize,
+    cluster_count: u32,
+    fat: Vec<u32>,
+    used: HashSet<u32>,
+    report: Report,
+}
+
+impl<'a> Volume<'a> {
+    fn chain(&mut self, start: u32, what: &str) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut cluster = start;
+        loop {
+            if cluster < 2 || cluster - 2 >= self.cluster_count {
+                self.report.errors.push(format!("{}: cluster {:#x} out of range", what, cluster));
+                break;
+            }
+
+            if !self.used.insert(cluster) {
+                self.report.errors.push(format!("{}: cluster {:#x} cross-linked or looping", what, cluster));
+                break;
+            }
+
+            chain.push(cluster);
+            match self.fat[cluster as usize] & 0x0FFF_FFFF {
+                next @ 2..=0x0FFF_FFEF => cluster = next,
+                0x0FFF_FFF8..=0x0FFF_FFFF => break,
+                other => {
+                    self.report.errors.push(format!("{}: chain runs into FAT entry {:#x}", what, other));
+                    break;
+                }
+            }
+        }
+
+        chain
+    }
+
+    fn read(&self, chain: &[u32]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &cluster in chain {
+            let offset = self.data_start + (cluster as usize - 2) * self.bytes_per_cluster;
+            out.extend_from_slice(&self.data[offset..offset + self.bytes_per_cluster]);
+        }
+        out
+    }
+
+    fn checksum(short: &[u8]) -> u8 {
+        short[..11].iter().fold(0u8, |sum, &b| (sum >> 1).wrapping_add(sum << 7).wrapping_add(b))
+    }
+
+    fn dir(&mut self, path: &str, start: u32, parent: u32, is_root: bool) {
+        let chain = self.chain(start, path);
+        let data = self.read(&chain);
+
+        let mut short_names = HashSet::new();
+        let mut long_names = HashSet::new();
+        let mut lfn: Option<(u8, u8, String)> = None;
+        let mut subdirs = Vec::new();
+
+        for (i, entry) in data.chunks(32).enumerate() {
+            if entry[0] == 0x00 {
+                break;
+            }
+
+            if entry[0] == 0xE5 {
+                if lfn.take().is_some() {
+                    self.report.warnings.push(format!("{}: orphaned long name entries", path));
+                }
+                continue;
+            }
+
+            if entry[11] & 0x3F == 0x0F {
+                let sequence = entry[0] & 0x3F;
+                let chars: Vec<u16> = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30].iter()
+                    .map(|&o| u16_at(entry, o)).take_while(|&c| c != 0 && c != 0xFFFF).collect();
+                let part = String::from_utf16_lossy(&chars);
+                lfn = match (entry[0] & 0x40 != 0, lfn.take()) {
+                    (true, previous) => {
+                        if previous.is_some() {
+                            self.report.warnings.push(format!("{}: orphaned long name entries", path));
+                        }
+                        Some((sequence, entry[13], part))
+                    }
+                    (false, Some((next, checksum, name))) if next == sequence + 1 && checksum == entry[13] => {
+                        Some((sequence, checksum, part + &name))
+                    }
+                    (false, _) => {
+                        self.report.warnings.push(format!("{}: orphaned long name entries", path));
+                        None
+                    }
+                };
+                continue;
+            }
+
+            if entry[11] & 0x08 != 0 {
+                if !is_root {
+                    self.report.errors.push(format!("{}: volume label outside the root", path));
+                }
+                continue;
+            }
+
+            let short: Vec<u8> = entry[..11].to_vec();
+            let short_display = String::from_utf8_lossy(&short).to_string();
+            let name = match lfn.take() {
+                Some((1, checksum, name)) if checksum == Volume::checksum(entry) => name,
+                Some(_) => {
+                    self.report.warnings.push(format!("{}: orphaned long name entries before {}", path, short_display));
+                    short_display.trim().to_string()
+                }
+                None => short_display.trim().to_string(),
+            };
+
+            let cluster = (u16_at(entry, 20) as u32) << 16 | u16_at(entry, 26) as u32;
+            let size = u32_at(entry, 28);
+            let is_dir = entry[11] & 0x10 != 0;
+
+            if !is_root && i < 2 {
+                let (expected_name, expected_cluster) = match i {
+                    0 => (b".          ", start),
+                    _ => (b"..         ", parent),
+                };
+                if short[..] != expected_name[..] || cluster != expected_cluster || !is_dir {
+                    self.report.errors.push(format!("{}: bad dot entry {}", path, short_display));
+                }
+                continue;
+            }
+
+            if short.iter().any(|b| b.is_ascii_lowercase()) || short[0] == b' ' {
+                self.report.errors.push(format!("{}: invalid short name {:?}", path, short_display));
+            }
+
+            if !short_names.insert(short.clone()) {
+                self.report.errors.push(format!("{}: duplicate short name {:?}", path, short_display));
+            }
+
+            if !long_names.insert(name.to_lowercase()) {
+                self.report.errors.push(format!("{}: duplicate name {:?}", path, name));
+            }
+
+            let child = format!("{}/{}", path, name);
+            if is_dir {
+                if cluster == 0 {
+                    self.report.errors.push(format!("{}: directory without a cluster", child));
+                } else {
+                    subdirs.push((child, cluster));
+                }
+                continue;
+            }
+
+            match cluster {
+                0 if size != 0 => self.report.errors.push(format!("{}: {} bytes but no clusters", child, size)),
+                0 => (),
+                _ => {
+                    let chain = self.chain(cluster, &child);
+                    let needed = super::div_ceil(size as usize, self.bytes_per_cluster);
+                    if chain.len() < needed {
+                        self.report.errors.push(format!("{}: chain too short for {} bytes", child, size));
+                    } else if chain.len() > needed {
+                        self.report.warnings.push(format!("{}: chain longer than {} bytes", child, size));
+                    }
+                }
+            }
+        }
+
+        if lfn.is_some() {
+            self.report.warnings.push(format!("{}: orphaned long name entries", path));
+        }
+
+        let own = if is_root { 0 } else { start };
+        for (child, cluster) in subdirs {
+            self.dir(&child, cluster, own, false);
+        }
+    }
+}
+
+/// Checks the FAT32 volume in `data`, which may be partitioned.
+pub fn check(data: &[u8]) -> Report {
+    let volume_start = match data[0] {
+        0xEB | 0xE9 => 0,
+        _ => u32_at(data, 446 + 8) as usize * 512,
+    };
+
+    let boot = &data[volume_start..];
+    let bps = u16_at(boot, 11) as usize;
+    let spc = boot[13] as usize;
+    let reserved = u16_at(boot, 14) as usize;
+    let num_fats = boot[16] as usize;
+    let total = u32_at(boot, 32) as usize;
+    let sectors_per_fat = u32_at(boot, 36) as usize;
+    let mirrored = u16_at(boot, 40) & 0x80 == 0;
+    let root = u32_at(boot, 44);
+    let fsinfo = u16_at(boot, 48) as usize;
+
+    let data_start = volume_start + (reserved + num_fats * sectors_per_fat) * bps;
+    let cluster_count = ((total - reserved - num_fats * sectors_per_fat) / spc) as u32;
+    let fat_at = |n: usize| -> Vec<u32> {
+        let offset = volume_start + (reserved + n * sectors_per_fat) * bps;
+        (0..cluster_count as usize + 2).map(|i| u32_at(data, offset + i * 4)).collect()
+    };
+
+    let mut volume = Volume {
+        data,
+        bytes_per_cluster: bps * spc,
+        data_start,
+        cluster_count,
+        fat: fat_at(0),
+        used: HashSet::new(),
+        report: Report::default(),
+    };
+
+    if mirrored {
+        for n in 1..num_fats {
+            if fat_at(n) != volume.fat {
+                volume.report.warnings.push(format!("FAT {} differs from FAT 0", n));
+            }
+        }
+    }
+
+    volume.dir("", root, 0, true);
+
+    let allocated: Vec<u32> = (2..cluster_count + 2)
+        .filter(|&c| volume.fat[c as usize] & 0x0FFF_FFFF != 0)
+        .collect();
+    let lost = allocated.iter().filter(|c| !volume.used.contains(c)).count();
+    if lost > 0 {
+        volume.report.warnings.push(format!("{} lost clusters", lost));
+    }
+
+    let free = cluster_count - allocated.len() as u32;
+    let fsinfo = volume_start + fsinfo * bps;
+    let recorded = u32_at(data, fsinfo + 488);
+    if recorded != 0xFFFF_FFFF && recorded != free {
+        volume.report.warnings.push(format!("FSInfo free count {} but {} clusters are free", recorded, free));
+    }
+
+    let mut report = volume.report;
+    if let Some(message) = fsck(&data[volume_start..volume_start + total * bps]) {
+        report.warnings.push(message);
+    }
+
+    report
+}
+
+/// Runs `fsck.fat -n` on `volume` if it is installed. Returns its output if
+/// it reports a problem.
+fn fsck(volume: &[u8]) -> Option<String> {
+    if Command::new("fsck.fat").arg("--help").output().is_err() {
+        return None;
+    }
+
+    let path = env::temp_dir().join(format!("fat32-check-{}-{:p}.img", ::std::process::id(), volume));
+    fs::write(&path, volume).ok()?;
+    let output = Command::new("fsck.fat").arg("-n").arg(&path).output();
+    let _ = fs::remove_file(&path);
+
+    match output {
+        Ok(ref output) if !output.status.success() => {
+            Some(format!("fsck.fat: {}", String::from_utf8_lossy(&output.stdout)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,454 @@
+//! Builds FAT32 disk images in memory, laid out the way `mkfs.fat -F 32`
+//! lays them out, so the driver can be tested without host tools.
+
+#![allow(dead_code)]
+
+use std::io::Cursor;
+
+pub mod check;
+
+/// The date and time stamped on every entry: 2018-03-14 15:09:26, created
+/// two seconds earlier and last accessed a day later.
+pub const DATE: u16 = (38 << 9) | (3 << 5) | 14;
+pub const TIME: u16 = (15 << 11) | (9 << 5) | 13;
+pub const CREATED_TIME: u16 = TIME - 1;
+pub const ACCESSED_DATE: u16 = DATE + 1;
+
+/// The first sector of the partition in partitioned images.
+pub const PARTITION_START: usize = 2048;
+
+const RESERVED_SECTORS: usize = 32;
+const NUM_FATS: usize = 2;
+const ROOT_CLUSTER: u32 = 2;
+const FSINFO_SECTOR: usize = 1;
+const BACKUP_BOOT_SECTOR: usize = 6;
+pub const LABEL: &[u8; 11] = b"TESTVOLUME ";
+
+enum Kind {
+    File(Vec<u8>, u8),
+    Dir(Vec<usize>, u8),
+    Deleted,
+}
+
+struct Node {
+    name: String,
+    kind: Kind,
+    parent: usize,
+    clusters: Vec<u32>,
+}
+
+/// A disk image under construction.
+pub struct Builder {
+    bytes_per_sector: usize,
+    sectors_per_cluster: usize,
+    total_sectors: usize,
+    partitioned: bool,
+    fragmented: bool,
+    nodes: Vec<Node>,
+}
+
+/// A built image and the positions of its structures.
+pub struct Image {
+    pub data: Vec<u8>,
+    /// Byte offset of the volume's boot sector.
+    pub volume: usize,
+    pub bytes_per_sector: usize,
+    pub sectors_per_fat: usize,
+}
+
+impl Builder {
+    /// A partitioned image with a 4MiB volume, 512-byte sectors and one
+    /// sector per cluster.
+    pub fn new() -> Builder {
+        Builder {
+            bytes_per_sector: 512,
+            sectors_per_cluster: 1,
+            total_sectors: 8192,
+            partitioned: true,
+            fragmented: false,
+            nodes: vec![Node { name: String::new(), kind: Kind::Dir(vec![], 0), parent: 0, clusters: vec![] }],
+        }
+    }
+
+    pub fn bytes_per_sector(mut self, n: usize) -> Builder {
+        self.total_sectors = self.total_sectors * self.bytes_per_sector / n;
+        self.bytes_per_sector = n;
+        self
+    }
+
+    pub fn sectors_per_cluster(mut self, n: usize) -> Builder {
+        self.sectors_per_cluster = n;
+        self
+    }
+
+    /// Leaves out the MBR, putting the boot sector at sector 0.
+    pub fn unpartitioned(mut self) -> Builder {
+        self.partitioned = false;
+        self
+    }
+
+    /// Leaves a free cluster after every allocated one, so no chain is
+    /// contiguous.
+    pub fn fragmented(mut self) -> Builder {
+        self.fragmented = true;
+        self
+    }
+
+    /// Returns the directory node at `path`, creating missing directories.
+    fn dir(&mut self, path: &[&str]) -> usize {
+        let mut current = 0;
+        for name in path {
+            let children = match self.nodes[current].kind {
+                Kind::Dir(ref children, _) => children.clone(),
+                _ => panic!("{} is not a directory", name),
+            };
+
+            current = match children.into_iter().find(|&i| self.nodes[i].name == *name) {
+                Some(i) => i,
+                None => self.add(current, name, Kind::Dir(vec![], 0)),
+            };
+        }
+
+        current
+    }
+
+    fn add(&mut self, parent: usize, name: &str, kind: Kind) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node { name: name.to_string(), kind, parent, clusters: vec![] });
+        match self.nodes[parent].kind {
+            Kind::Dir(ref mut children, _) => children.push(index),
+            _ => unreachable!(),
+        }
+
+        index
+    }
+
+    fn insert(mut self, path: &str, kind: Kind) -> Builder {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let (name, dirs) = components.split_last().expect("empty path");
+        let parent = self.dir(dirs);
+        self.add(parent, name, kind);
+        self
+    }
+
+    pub fn file(self, path: &str, data: &[u8]) -> Builder {
+        self.insert(path, Kind::File(data.to_vec(), 0x20))
+    }
+
+    pub fn file_with_attributes(self, path: &str, data: &[u8], attributes: u8) -> Builder {
+        self.insert(path, Kind::File(data.to_vec(), attributes))
+    }
+
+    pub fn mkdir(mut self, path: &str) -> Builder {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        self.dir(&components);
+        self
+    }
+
+    pub fn hidden_dir(self, path: &str) -> Builder {
+        self.insert(path, Kind::Dir(vec![], 0x02))
+    }
+
+    /// Adds a deleted entry, with its long name entries, to a directory.
+    pub fn deleted(self, path: &str) -> Builder {
+        self.insert(path, Kind::Deleted)
+    }
+
+    pub fn build(mut self) -> Image {
+        let bps = self.bytes_per_sector;
+        let spc = self.sectors_per_cluster;
+        let bytes_per_cluster = bps * spc;
+
+        let mut sectors_per_fat = 1;
+        loop {
+            let clusters = (self.total_sectors - RESERVED_SECTORS - NUM_FATS * sectors_per_fat) / spc;
+            let needed = div_ceil((clusters + 2) * 4, bps);
+            if needed <= sectors_per_fat {
+                break;
+            }
+            sectors_per_fat = needed;
+        }
+
+        let data_start = RESERVED_SECTORS + NUM_FATS * sectors_per_fat;
+        let cluster_count = (self.total_sectors - data_start) / spc;
+
+        // Serialize directories first to know their sizes, then allocate.
+        let mut fat = vec![0u32; cluster_count + 2];
+        fat[0] = 0x0FFF_FFF8;
+        fat[1] = 0x0FFF_FFFF;
+
+        let mut next = ROOT_CLUSTER;
+        for i in 0..self.nodes.len() {
+            let size = match self.nodes[i].kind {
+                Kind::File(ref data, _) => data.len(),
+                Kind::Dir(..) => self.dir_entries(i, true).len(),
+                Kind::Deleted => 0,
+            };
+
+            let count = div_ceil(size, bytes_per_cluster);
+            let count = match self.nodes[i].kind {
+                Kind::Dir(..) => count.max(1),
+                _ => count,
+            };
+
+            for _ in 0..count {
+                assert!(((next - 2) as usize) < cluster_count, "image is full");
+                if let Some(&last) = self.nodes[i].clusters.last() {
+                    fat[last as usize] = next;
+                }
+
+                fat[next as usize] = 0x0FFF_FFFF;
+                self.nodes[i].clusters.push(next);
+                next += if self.fragmented { 2 } else { 1 };
+            }
+        }
+
+        let mut volume = vec![0u8; self.total_sectors * bps];
+        for i in 0..self.nodes.len() {
+            let contents = match self.nodes[i].kind {
+                Kind::File(ref data, _) => data.clone(),
+                Kind::Dir(..) => self.dir_entries(i, false),
+                Kind::Deleted => continue,
+            };
+
+            for (chunk, &cluster) in contents.chunks(bytes_per_cluster).zip(self.nodes[i].clusters.iter()) {
+                let offset = (data_start + (cluster as usize - 2) * spc) * bps;
+                volume[offset..offset + chunk.len()].copy_from_slice(chunk);
+            }
+        }
+
+        for n in 0..NUM_FATS {
+            let offset = (RESERVED_SECTORS + n * sectors_per_fat) * bps;
+            for (i, entry) in fat.iter().enumerate() {
+                volume[offset + i * 4..offset + i * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+            }
+        }
+
+        let boot = self.boot_sector(sectors_per_fat);
+        volume[..512].copy_from_slice(&boot);
+        volume[BACKUP_BOOT_SECTOR * bps..BACKUP_BOOT_SECTOR * bps + 512].copy_from_slice(&boot);
+
+        let used = self.nodes.iter().map(|n| n.clusters.len()).sum::<usize>() as u32;
+        let fsinfo = FSINFO_SECTOR * bps;
+        volume[fsinfo..fsinfo + 4].copy_from_slice(b"RRaA");
+        volume[fsinfo + 484..fsinfo + 488].copy_from_slice(b"rrAa");
+        volume[fsinfo + 488..fsinfo + 492].copy_from_slice(&(cluster_count as u32 - used).to_le_bytes());
+        volume[fsinfo + 492..fsinfo + 496].copy_from_slice(&next.to_le_bytes());
+        volume[fsinfo + 510] = 0x55;
+        volume[fsinfo + 511] = 0xAA;
+
+        if !self.partitioned {
+            return Image { data: volume, volume: 0, bytes_per_sector: bps, sectors_per_fat };
+        }
+
+        let start = PARTITION_START * 512;
+        let mut data = vec![0u8; start];
+        data[440..444].copy_from_slice(&0xDEAD_BEEFu32.to_le_bytes());
+        let entry = &mut data[446..462];
+        entry[4] = 0x0C;
+        entry[8..12].copy_from_slice(&(PARTITION_START as u32).to_le_bytes());
+        entry[12..16].copy_from_slice(&((self.total_sectors * bps / 512) as u32).to_le_bytes());
+        data[510] = 0x55;
+        data[511] = 0xAA;
+        data.extend(volume);
+
+        Image { data, volume: start, bytes_per_sector: bps, sectors_per_fat }
+    }
+
+    fn boot_sector(&self, sectors_per_fat: usize) -> [u8; 512] {
+        let mut s = [0u8; 512];
+        s[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        s[3..11].copy_from_slice(b"mkfs.fat");
+        s[11..13].copy_from_slice(&(self.bytes_per_sector as u16).to_le_bytes());
+        s[13] = self.sectors_per_cluster as u8;
+        s[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        s[16] = NUM_FATS as u8;
+        s[21] = 0xF8;
+        s[24..26].copy_from_slice(&32u16.to_le_bytes());
+        s[26..28].copy_from_slice(&64u16.to_le_bytes());
+        let hidden = if self.partitioned { PARTITION_START as u32 } else { 0 };
+        s[28..32].copy_from_slice(&hidden.to_le_bytes());
+        s[32..36].copy_from_slice(&(self.total_sectors as u32).to_le_bytes());
+        s[36..40].copy_from_slice(&(sectors_per_fat as u32).to_le_bytes());
+        s[44..48].copy_from_slice(&ROOT_CLUSTER.to_le_bytes());
+        s[48..50].copy_from_slice(&(FSINFO_SECTOR as u16).to_le_bytes());
+        s[50..52].copy_from_slice(&(BACKUP_BOOT_SECTOR as u16).to_le_bytes());
+        s[64] = 0x80;
+        s[66] = 0x29;
+        s[67..71].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        s[71..82].copy_from_slice(LABEL);
+        s[82..90].copy_from_slice(b"FAT32   ");
+        s[510] = 0x55;
+        s[511] = 0xAA;
+        s
+    }
+
+    fn first_cluster(&self, node: usize, sizing: bool) -> u32 {
+        match sizing || node == 0 {
+            true => 0,
+            false => self.nodes[node].clusters.first().cloned().unwrap_or(0),
+        }
+    }
+
+    /// Serializes directory `dir`. Cluster numbers are left at zero when
+    /// `sizing`, before clusters are allocated.
+    fn dir_entries(&self, dir: usize, sizing: bool) -> Vec<u8> {
+        let children = match self.nodes[dir].kind {
+            Kind::Dir(ref children, _) => children,
+            _ => unreachable!(),
+        };
+
+        let mut out = Vec::new();
+        if dir == 0 {
+            out.extend_from_slice(&raw_entry(LABEL, 0, 0x08, 0, 0));
+        } else {
+            let own = self.first_cluster(dir, sizing);
+            let parent = self.first_cluster(self.nodes[dir].parent, sizing);
+            out.extend_from_slice(&raw_entry(b".          ", 0, 0x10, own, 0));
+            out.extend_from_slice(&raw_entry(b"..         ", 0, 0x10, parent, 0));
+        }
+
+        for (n, &child) in children.iter().enumerate() {
+            let node = &self.nodes[child];
+            let (attributes, size) = match node.kind {
+                Kind::File(ref data, attributes) => (attributes, data.len() as u32),
+                Kind::Dir(_, attributes) => (attributes | 0x10, 0),
+                Kind::Deleted => (0x20, 0),
+            };
+
+            let (short, case, long) = short_name(&node.name, n + 1);
+            let mut entries = Vec::new();
+            if long {
+                entries.extend(lfn_entries(&node.name, &short));
+            }
+
+            entries.extend_from_slice(&raw_entry(&short, case, attributes, self.first_cluster(child, sizing), size));
+            if let Kind::Deleted = node.kind {
+                for entry in entries.chunks_mut(32) {
+                    entry[0] = 0xE5;
+                }
+            }
+
+            out.extend(entries);
+        }
+
+        out
+    }
+}
+
+pub fn div_ceil(a: usize, b: usize) -> usize {
+    match a % b {
+        0 => a / b,
+        _ => a / b + 1,
+    }
+}
+
+/// Returns the 11-byte short name for `name`, the NT lower case flags, and
+/// whether a long name is needed. `n` numbers the `~n` alias.
+fn short_name(name: &str, n: usize) -> ([u8; 11], u8, bool) {
+    let valid = |s: &str| s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    let one_case = |s: &str| s == s.to_ascii_uppercase() || s == s.to_ascii_lowercase();
+
+    let (base, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i + 1..]),
+        _ => (name, ""),
+    };
+
+    let mut short = [b' '; 11];
+    let fits = !base.is_empty() && base.len() <= 8 && ext.len() <= 3
+        && valid(base) && valid(ext) && one_case(base) && one_case(ext);
+
+    if fits {
+        short[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+        short[8..8 + ext.len()].copy_from_slice(ext.to_ascii_uppercase().as_bytes());
+        let mut case = 0;
+        if base.bytes().any(|b| b.is_ascii_lowercase()) {
+            case |= 0x08;
+        }
+        if ext.bytes().any(|b| b.is_ascii_lowercase()) {
+            case |= 0x10;
+        }
+        return (short, case, false);
+    }
+
+    let clean = |s: &str, max: usize| -> Vec<u8> {
+        s.bytes().filter(|b| b.is_ascii_alphanumeric()).map(|b| b.to_ascii_uppercase()).take(max).collect()
+    };
+
+    let tail = format!("~{}", n);
+    let mut alias = clean(base, 8 - tail.len());
+    if alias.is_empty() {
+        alias.push(b'_');
+    }
+    alias.extend(tail.bytes());
+    short[..alias.len()].copy_from_slice(&alias);
+    let ext = clean(ext, 3);
+    short[8..8 + ext.len()].copy_from_slice(&ext);
+    (short, 0, true)
+}
+
+fn checksum(short: &[u8; 11]) -> u8 {
+    short.iter().fold(0u8, |sum, &b| (sum >> 1).wrapping_add(sum << 7).wrapping_add(b))
+}
+
+fn lfn_entries(name: &str, short: &[u8; 11]) -> Vec<u8> {
+    let mut chars: Vec<u16> = name.encode_utf16().collect();
+    let padded = div_ceil(chars.len(), 13) * 13;
+    if padded > chars.len() {
+        chars.push(0);
+        chars.resize(padded, 0xFFFF);
+    }
+
+    const OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+    let count = chars.len() / 13;
+    let mut out = Vec::new();
+    for sequence in (1..=count).rev() {
+        let mut entry = [0u8; 32];
+        entry[0] = sequence as u8 | if sequence == count { 0x40 } else { 0 };
+        entry[11] = 0x0F;
+        entry[13] = checksum(short);
+        for (i, &offset) in OFFSETS.iter().enumerate() {
+            let c = chars[(sequence - 1) * 13 + i];
+            entry[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+        }
+        out.extend_from_slice(&entry);
+    }
+
+    out
+}
+
+fn raw_entry(short: &[u8; 11], case: u8, attributes: u8, cluster: u32, size: u32) -> [u8; 32] {
+    let mut e = [0u8; 32];
+    e[..11].copy_from_slice(short);
+    e[11] = attributes;
+    e[12] = case;
+    e[14..16].copy_from_slice(&CREATED_TIME.to_le_bytes());
+    e[16..18].copy_from_slice(&DATE.to_le_bytes());
+    e[18..20].copy_from_slice(&ACCESSED_DATE.to_le_bytes());
+    e[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    e[22..24].copy_from_slice(&TIME.to_le_bytes());
+    e[24..26].copy_from_slice(&DATE.to_le_bytes());
+    e[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    e[28..32].copy_from_slice(&size.to_le_bytes());
+    e
+}
+
+impl Image {
+    /// Sets FAT entry `cluster` in every FAT.
+    pub fn set_fat(&mut self, cluster: u32, value: u32) {
+        for n in 0..NUM_FATS {
+            let offset = self.volume + (RESERVED_SECTORS + n * self.sectors_per_fat) * self.bytes_per_sector
+                + cluster as usize * 4;
+            self.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    pub fn device(self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.data)
+    }
+}
+
+/// Returns `len` bytes of a repeating, position-dependent pattern.
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8 ^ (i / 251) as u8).collect()
+}
@@ -0,0 +1,111 @@
+extern crate fat32;
+extern crate storage;
+
+mod image;
+
+use std::io::Cursor;
+
+use fat32::{VFat, Error};
+use image::Builder;
+
+#[test]
+fn mounts_first_fat32_partition() {
+    let fs = VFat::from(Builder::new().build().device()).expect("mount");
+    let fs = fs.lock();
+    let bpb = fs.bpb();
+    assert_eq!(bpb.bytes_per_sector, 512);
+    assert_eq!(bpb.sectors_per_cluster, 1);
+    assert_eq!(bpb.num_fats, 2);
+    assert_eq!(bpb.root_cluster, 2);
+    assert_eq!(bpb.volume_id, 0x1234_5678);
+    assert_eq!(bpb.volume_label, Some(*image::LABEL));
+    assert_eq!(fs.bytes_per_cluster(), 512);
+    assert!(fs.cluster_count() > 8000);
+}
+
+#[test]
+fn mounts_unpartitioned_volume() {
+    let image = Builder::new().unpartitioned().file("/a.txt", b"a").build();
+    let fs = VFat::from(image.device()).expect("mount");
+    assert!(fs.open("/a.txt").is_ok());
+}
+
+#[test]
+fn mounts_large_logical_sectors() {
+    let data = image::pattern(10_000);
+    let image = Builder::new().bytes_per_sector(2048).sectors_per_cluster(2).file("/big", &data).build();
+    let fs = VFat::from(image.device()).expect("mount");
+    assert_eq!(fs.lock().bytes_per_cluster(), 4096);
+
+    let mut file = fs.open("/big").unwrap().into_file().unwrap();
+    let mut read = Vec::new();
+    ::std::io::Read::read_to_end(&mut file, &mut read).unwrap();
+    assert_eq!(read, data);
+}
+
+#[test]
+fn rejects_bad_boot_signature() {
+    let mut image = Builder::new().build();
+    image.data[image.volume + 511] = 0;
+    match VFat::from(image.device()) {
+        Err(Error::BadSignature) => (),
+        other => panic!("expected BadSignature, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn rejects_fat16() {
+    let mut image = Builder::new().build();
+    // A 16-bit FAT size marks a FAT12/16 volume.
+    image.data[image.volume + 22] = 0x20;
+    match VFat::from(image.device()) {
+        Err(Error::NotFat32) => (),
+        other => panic!("expected NotFat32, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn rejects_bad_geometry() {
+    let mut image = Builder::new().build();
+    image.data[image.volume + 13] = 3;
+    match VFat::from(image.device()) {
+        Err(Error::BadGeometry("sectors per cluster")) => (),
+        other => panic!("expected BadGeometry, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn rejects_volume_past_end_of_device() {
+    let mut image = Builder::new().build();
+    let len = image.data.len();
+    image.data.truncate(len - 4096);
+    // Shrink the partition so the MBR stays valid but the volume doesn't fit.
+    let sectors = (len - 4096) as u32 / 512 - image::PARTITION_START as u32;
+    image.data[458..462].copy_from_slice(&sectors.to_le_bytes());
+    match VFat::from(image.device()) {
+        Err(Error::BadGeometry("total sectors")) => (),
+        other => panic!("expected BadGeometry, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn requires_fat32_partition() {
+    let mut image = Builder::new().build();
+    image.data[446 + 4] = 0x83;
+    match VFat::from(image.device()) {
+        Err(Error::NoFat32Partition) => (),
+        other => panic!("expected NoFat32Partition, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn reports_bad_mbr() {
+    let mut data = vec![0u8; 1 << 20];
+    data[510] = 0x55;
+    data[511] = 0xAA;
+    data[446] = 0x12;
+    match VFat::from(Cursor::new(data)) {
+        Err(Error::Mbr(storage::mbr::Error::UnknownBootIndicator(0))) => (),
+        other => panic!("expected an MBR error, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,206 @@
+extern crate fat32;
+extern crate storage;
+
+mod image;
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use fat32::{VFat, Entry, Shared};
+use image::{Builder, pattern};
+
+fn names<T: storage::BlockDevice>(dir: &fat32::Dir<T>) -> Vec<String> {
+    dir.entries().unwrap().map(|e| e.name().to_string()).collect()
+}
+
+fn mount(builder: Builder) -> Shared<VFat<io::Cursor<Vec<u8>>>> {
+    VFat::from(builder.build().device()).expect("mount")
+}
+
+fn read_all<T: Read>(mut reader: T) -> Vec<u8> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn lists_root_directory() {
+    let fs = mount(Builder::new()
+        .file("/README", b"hello")
+        .file("/lower.txt", b"")
+        .file("/Mixed.Txt", b"")
+        .mkdir("/docs")
+        .deleted("/gone.txt")
+        .file("/A rather long file name.markdown", b"#"));
+
+    assert_eq!(names(&fs.root()), vec![
+        "README", "lower.txt", "Mixed.Txt", "docs", "A rather long file name.markdown",
+    ]);
+}
+
+#[test]
+fn lists_subdirectories_with_dot_entries() {
+    let fs = mount(Builder::new().file("/a/b/c.txt", b"c").mkdir("/a/empty"));
+    let a = fs.open("/a").unwrap().into_dir().unwrap();
+    assert_eq!(names(&a), vec![".", "..", "b", "empty"]);
+
+    let parent = fs.open("/a/b/..").unwrap();
+    assert_eq!(parent.as_dir().unwrap().cluster(), a.cluster());
+
+    let root = fs.open("/a/..").unwrap().into_dir().unwrap();
+    assert_eq!(root.cluster(), fs.root().cluster());
+    assert_eq!(fs.open("/../a/./b/c.txt").unwrap().name(), "c.txt");
+}
+
+#[test]
+fn long_names_span_several_entries_and_clusters() {
+    // Each name takes several long name entries; enough of them that the
+    // directory spans many clusters.
+    let mut builder = Builder::new();
+    let mut expected = Vec::new();
+    for i in 0..40 {
+        let name = format!("a file with a fairly long name, number {} \u{e9}\u{4e2d}.data", i);
+        builder = builder.file(&format!("/many/{}", name), name.as_bytes());
+        expected.push(name);
+    }
+
+    let fs = mount(builder);
+    let dir = fs.open("/many").unwrap().into_dir().unwrap();
+    let listed: Vec<String> = names(&dir).into_iter().skip(2).collect();
+    assert_eq!(listed, expected);
+
+    let file = fs.open(&format!("/many/{}", expected[17])).unwrap();
+    assert_eq!(read_all(file.into_file().unwrap()), expected[17].as_bytes());
+}
+
+#[test]
+fn open_is_case_insensitive() {
+    let fs = mount(Builder::new().file("/Dir/Some File.TXT", b"x"));
+    let entry = fs.open("dir/some file.txt").unwrap();
+    assert_eq!(entry.name(), "Some File.TXT");
+    assert!(entry.is_file());
+}
+
+#[test]
+fn open_errors() {
+    let fs = mount(Builder::new().file("/file", b"x").deleted("/gone"));
+    assert_eq!(fs.open("/missing").err().unwrap().kind(), io::ErrorKind::NotFound);
+    assert_eq!(fs.open("/gone").err().unwrap().kind(), io::ErrorKind::NotFound);
+    assert_eq!(fs.open("/file/x").err().unwrap().kind(), io::ErrorKind::InvalidInput);
+    assert!(fs.open("/").unwrap().is_dir());
+}
+
+#[test]
+fn reads_attributes_and_timestamps() {
+    let fs = mount(Builder::new()
+        .file_with_attributes("/ro", b"", 0x01 | 0x20)
+        .hidden_dir("/secret"));
+
+    let ro = fs.open("/ro").unwrap();
+    let metadata = ro.metadata();
+    assert!(metadata.attributes.read_only());
+    assert!(metadata.attributes.archive());
+    assert!(!metadata.attributes.directory());
+    assert_eq!(metadata.modified.to_string(), "2018-03-14 15:09:26");
+    assert_eq!(metadata.created.to_string(), "2018-03-14 15:09:24");
+    assert_eq!((metadata.accessed.year(), metadata.accessed.month(), metadata.accessed.day()), (2018, 3, 15));
+
+    let secret = fs.open("/secret").unwrap();
+    assert!(secret.metadata().attributes.hidden());
+    assert!(secret.metadata().attributes.directory());
+}
+
+#[test]
+fn reads_files_across_clusters() {
+    for &(bps, spc) in [(512, 1), (512, 8), (4096, 1)].iter() {
+        let data = pattern(100_000);
+        let fs = mount(Builder::new().bytes_per_sector(bps).sectors_per_cluster(spc)
+            .file("/empty", b"")
+            .file("/small", b"tiny")
+            .file("/large", &data));
+
+        let empty = fs.open("/empty").unwrap().into_file().unwrap();
+        assert_eq!(empty.size(), 0);
+        assert!(empty.cluster().is_none());
+        assert_eq!(read_all(empty), b"");
+
+        assert_eq!(read_all(fs.open("/small").unwrap().into_file().unwrap()), b"tiny");
+        assert_eq!(read_all(fs.open("/large").unwrap().into_file().unwrap()), data);
+    }
+}
+
+#[test]
+fn reads_fragmented_files() {
+    let data = pattern(20_000);
+    let fs = mount(Builder::new().fragmented().file("/a", &data).file("/b", b"b"));
+    let mut file = fs.open("/a").unwrap().into_file().unwrap();
+
+    let mut buf = [0; 777];
+    let mut read = Vec::new();
+    loop {
+        match file.read(&mut buf).unwrap() {
+            0 => break,
+            n => read.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    assert_eq!(read, data);
+}
+
+#[test]
+fn seeks() {
+    let data = pattern(5_000);
+    let fs = mount(Builder::new().file("/f", &data));
+    let mut file = fs.open("/f").unwrap().into_file().unwrap();
+    let mut buf = [0; 100];
+
+    assert_eq!(file.seek(SeekFrom::Start(3_000)).unwrap(), 3_000);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[3_000..3_100]);
+
+    // Backwards, into an earlier cluster.
+    assert_eq!(file.seek(SeekFrom::Current(-2_600)).unwrap(), 500);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[500..600]);
+
+    assert_eq!(file.seek(SeekFrom::End(-10)).unwrap(), 4_990);
+    assert_eq!(file.read(&mut buf).unwrap(), 10);
+    assert_eq!(&buf[..10], &data[4_990..]);
+
+    assert_eq!(file.seek(SeekFrom::End(100)).unwrap(), 5_100);
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+
+    assert_eq!(file.seek(SeekFrom::Current(-6_000)).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(file.seek(SeekFrom::Start(0)).unwrap(), 0);
+    assert_eq!(read_all(file), data);
+}
+
+#[test]
+fn detects_broken_chains() {
+    let data = pattern(3_000);
+    let mut image = Builder::new().file("/f", &data).build();
+    let cluster = {
+        let fs = VFat::from(io::Cursor::new(image.data.clone())).unwrap();
+        let file = fs.open("/f").unwrap().into_file().unwrap();
+        file.cluster().unwrap().number()
+    };
+
+    image.set_fat(cluster, 0);
+    let fs = VFat::from(image.device()).unwrap();
+    let mut file = fs.open("/f").unwrap().into_file().unwrap();
+    let mut read = Vec::new();
+    assert_eq!(file.read_to_end(&mut read).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn detects_directory_loops() {
+    let mut image = Builder::new().mkdir("/d").build();
+    // The root directory is cluster 2; point it at itself.
+    image.set_fat(2, 2);
+    let fs = VFat::from(image.device()).unwrap();
+    assert_eq!(fs.root().entries().err().unwrap().kind(), io::ErrorKind::InvalidData);
+    match fs.open("/d") {
+        Err(ref e) if e.kind() == io::ErrorKind::InvalidData => (),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(Entry::File(_)) | Ok(Entry::Dir(_)) => panic!("opened through a looping directory"),
+    }
+}
@@ -0,0 +1,451 @@
+extern crate fat32;
+extern crate storage;
+
+mod image;
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use fat32::{VFat, Shared};
+use image::check::check;
+use image::{Builder, pattern};
+use storage::{BlockDevice, CachedDevice};
+
+type Fs<'a> = Shared<VFat<Cursor<&'a mut [u8]>>>;
+
+fn mount(data: &mut [u8]) -> Fs<'_> {
+    VFat::from(Cursor::new(data)).expect("mount")
+}
+
+fn read_file<T: BlockDevice>(fs: &Shared<VFat<T>>, path: &str) -> Vec<u8> {
+    let mut file = fs.open(path).unwrap().into_file().expect("not a file");
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    data
+}
+
+fn names<T: BlockDevice>(fs: &Shared<VFat<T>>, path: &str) -> Vec<String> {
+    let dir = fs.open(path).unwrap().into_dir().expect("not a directory");
+    dir.entries().unwrap().map(|e| e.name().to_string())
+        .filter(|name| name != "." && name != "..")
+        .collect()
+}
+
+fn free_clusters(data: &mut [u8]) -> u32 {
+    mount(data).lock().free_clusters().expect("free count unknown")
+}
+
+#[test]
+fn checker_accepts_builder_images_and_finds_cross_links() {
+    let mut image = Builder::new().file("/a/b/Long Name.txt", &pattern(3000)).file("/d", b"d").build();
+    check(&image.data).assert_clean();
+
+    // Point the first file's chain into the second one's.
+    let (first, second) = {
+        let fs = VFat::from(Cursor::new(&mut image.data[..])).unwrap();
+        let first = fs.open("/a/b/Long Name.txt").unwrap().into_file().unwrap().cluster().unwrap();
+        let second = fs.open("/d").unwrap().into_file().unwrap().cluster().unwrap();
+        (first.number(), second.number())
+    };
+
+    image.set_fat(first, second);
+    assert!(!check(&image.data).errors.is_empty());
+}
+
+#[test]
+fn counts_free_clusters_without_fsinfo_hint() {
+    let mut data = Builder::new().unpartitioned().file("/a", &pattern(3000)).mkdir("/d").build().data;
+    let free = free_clusters(&mut data);
+
+    // Mark the FSInfo free count as unknown.
+    data[512 + 488..512 + 492].copy_from_slice(&[0xFF; 4]);
+    let fs = mount(&mut data);
+    assert_eq!(fs.lock().free_clusters(), None);
+    assert_eq!(fs.lock().count_free_clusters().unwrap(), free);
+
+    fs.create("/b").unwrap().write_all(&pattern(1000)).unwrap();
+    assert_eq!(fs.lock().free_clusters(), Some(free - 2));
+    assert_eq!(fs.lock().count_free_clusters().unwrap(), free - 2);
+}
+
+#[test]
+fn creates_files_with_short_and_long_names() {
+    let mut data = Builder::new().build().data;
+    let contents = pattern(4_321);
+    {
+        let fs = mount(&mut data);
+        for name in ["/NOTES.TXT", "/lower.txt", "/Mixed Case Name.markdown", "/.hidden", "/\u{e9}t\u{e9}.txt"].iter() {
+            let mut file = fs.create(name).unwrap();
+            file.write_all(name.as_bytes()).unwrap();
+        }
+
+        fs.create("/big.bin").unwrap().write_all(&contents).unwrap();
+        fs.create("/empty").unwrap();
+    }
+
+    check(&data).assert_clean();
+    let fs = mount(&mut data);
+    assert_eq!(names(&fs, "/"), vec![
+        "NOTES.TXT", "lower.txt", "Mixed Case Name.markdown", ".hidden", "\u{e9}t\u{e9}.txt", "big.bin", "empty",
+    ]);
+    assert_eq!(read_file(&fs, "/mixed case name.MARKDOWN"), b"/Mixed Case Name.markdown");
+    assert_eq!(read_file(&fs, "/big.bin"), contents);
+    assert_eq!(read_file(&fs, "/empty"), b"");
+}
+
+#[test]
+fn aliases_are_unique() {
+    let mut data = Builder::new().build().data;
+    {
+        let fs = mount(&mut data);
+        for i in 0..12 {
+            fs.create(&format!("/a long file name {}.text", i)).unwrap();
+        }
+    }
+
+    // Duplicate short names are reported by the checker.
+    check(&data).assert_clean();
+    assert_eq!(names(&mount(&mut data), "/").len(), 12);
+}
+
+#[test]
+fn rejects_bad_names_and_duplicates() {
+    let mut data = Builder::new().file("/exists.txt", b"x").mkdir("/dir").build().data;
+    let fs = mount(&mut data);
+    for name in ["/a:b", "/trailing.", "/", "/dir/..", "/with\u{1}control"].iter() {
+        assert!(fs.create(name).is_err(), "{:?}", name);
+    }
+
+    let long = "x".repeat(256);
+    assert_eq!(fs.create(&format!("/{}", long)).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(fs.create_dir("/EXISTS.TXT").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+    assert_eq!(fs.create("/Dir").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(fs.create("/missing/file").unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn appends_and_overwrites() {
+    let original = pattern(1_500);
+    let mut data = Builder::new().file("/log.txt", &original).build().data;
+    let free = free_clusters(&mut data);
+    {
+        let fs = mount(&mut data);
+        let mut file = fs.open("/log.txt").unwrap().into_file().unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b"appended").unwrap();
+
+        // Overwrite in the middle, across a sector boundary.
+        file.seek(SeekFrom::Start(510)).unwrap();
+        file.write_all(b"XXXX").unwrap();
+    }
+
+    check(&data).assert_clean();
+    let mut expected = original.clone();
+    expected[510..514].copy_from_slice(b"XXXX");
+    expected.extend_from_slice(b"appended");
+    assert_eq!(read_file(&mount(&mut data), "/log.txt"), expected);
+
+    {
+        // `create` truncates an existing file.
+        let fs = mount(&mut data);
+        fs.create("/LOG.TXT").unwrap().write_all(b"new").unwrap();
+    }
+
+    check(&data).assert_clean();
+    assert_eq!(read_file(&mount(&mut data), "/log.txt"), b"new");
+    assert_eq!(free_clusters(&mut data), free + 2);
+}
+
+#[test]
+fn truncates_and_extends() {
+    let original = pattern(5_000);
+    let mut data = Builder::new().file("/f", &original).build().data;
+    let free = free_clusters(&mut data);
+    {
+        let fs = mount(&mut data);
+        let mut file = fs.open("/f").unwrap().into_file().unwrap();
+        file.set_len(1_000).unwrap();
+    }
+
+    check(&data).assert_clean();
+    assert_eq!(read_file(&mount(&mut data), "/f"), &original[..1_000]);
+    assert_eq!(free_clusters(&mut data), free + 8);
+
+    {
+        let fs = mount(&mut data);
+        let mut file = fs.open("/f").unwrap().into_file().unwrap();
+        file.set_len(3_000).unwrap();
+        // Writing past the end fills the gap with zeroes.
+        file.seek(SeekFrom::Start(4_000)).unwrap();
+        file.write_all(b"end").unwrap();
+    }
+
+    check(&data).assert_clean();
+    let mut expected = original[..1_000].to_vec();
+    expected.resize(4_000, 0);
+    expected.extend_from_slice(b"end");
+    assert_eq!(read_file(&mount(&mut data), "/f"), expected);
+
+    {
+        let fs = mount(&mut data);
+        fs.open("/f").unwrap().into_file().unwrap().set_len(0).unwrap();
+    }
+
+    check(&data).assert_clean();
+    let fs = mount(&mut data);
+    let file = fs.open("/f").unwrap().into_file().unwrap();
+    assert_eq!((file.size(), file.cluster()), (0, None));
+    assert_eq!(fs.lock().free_clusters(), Some(free + 10));
+}
+
+#[test]
+fn creates_and_removes_directories() {
+    let mut data = Builder::new().build().data;
+    let free = free_clusters(&mut data);
+    {
+        let fs = mount(&mut data);
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/a/Nested Directory").unwrap();
+        fs.create("/a/Nested Directory/file.txt").unwrap().write_all(b"hi").unwrap();
+    }
+
+    check(&data).assert_clean();
+    {
+        let fs = mount(&mut data);
+        assert_eq!(names(&fs, "/a"), vec!["Nested Directory"]);
+        assert_eq!(read_file(&fs, "/a/nested directory/../Nested Directory/file.txt"), b"hi");
+
+        let err = fs.remove("/a/Nested Directory").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(fs.remove("/").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(fs.remove("/a/..").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(fs.remove("/nope").unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        fs.remove("/a/Nested Directory/FILE.TXT").unwrap();
+        fs.remove("/a/Nested Directory").unwrap();
+        fs.remove("/a").unwrap();
+    }
+
+    check(&data).assert_clean();
+    assert!(names(&mount(&mut data), "/").is_empty());
+    assert_eq!(free_clusters(&mut data), free);
+}
+
+#[test]
+fn grows_directories_and_reuses_deleted_entries() {
+    let mut data = Builder::new().sectors_per_cluster(1).build().data;
+    let expected: Vec<String> = (0..100).map(|i| format!("entry with a long name number {}", i)).collect();
+    {
+        let fs = mount(&mut data);
+        fs.create_dir("/d").unwrap();
+        for name in expected.iter() {
+            fs.create(&format!("/d/{}", name)).unwrap().write_all(name.as_bytes()).unwrap();
+        }
+    }
+
+    check(&data).assert_clean();
+    let free = free_clusters(&mut data);
+    {
+        let fs = mount(&mut data);
+        assert_eq!(names(&fs, "/d"), expected);
+        fs.remove(&format!("/d/{}", expected[3])).unwrap();
+        fs.create(&format!("/d/{}", "replacement")).unwrap();
+    }
+
+    check(&data).assert_clean();
+    let fs = mount(&mut data);
+    let listed = names(&fs, "/d");
+    assert_eq!(listed[3], "replacement");
+    assert_eq!(listed.len(), 100);
+    // The removed file's cluster was freed and the directory didn't grow.
+    assert_eq!(fs.lock().free_clusters(), Some(free + 1));
+}
+
+#[test]
+fn renames_files_and_directories() {
+    let mut data = Builder::new()
+        .file("/a.txt", &pattern(1_000))
+        .file("/dir/nested/deep.bin", &pattern(3_000))
+        .mkdir("/other")
+        .build()
+        .data;
+    {
+        let fs = mount(&mut data);
+        fs.rename("/a.txt", "/A Longer Name.txt").unwrap();
+        fs.rename("/A Longer Name.txt", "/other/B.TXT").unwrap();
+        fs.rename("/other/B.TXT", "/other/b.txt").unwrap();
+        fs.rename("/dir/nested", "/other/Moved Directory").unwrap();
+        fs.rename("/dir", "/other/Moved Directory/dir").unwrap();
+    }
+
+    check(&data).assert_clean();
+    let fs = mount(&mut data);
+    assert_eq!(names(&fs, "/"), vec!["other"]);
+    assert_eq!(names(&fs, "/other"), vec!["b.txt", "Moved Directory"]);
+    assert_eq!(read_file(&fs, "/other/b.txt"), pattern(1_000));
+    assert_eq!(read_file(&fs, "/other/Moved Directory/deep.bin"), pattern(3_000));
+    assert_eq!(read_file(&fs, "/other/moved directory/dir/../deep.bin"), pattern(3_000));
+    assert_eq!(names(&fs, "/other/Moved Directory/dir"), Vec::<String>::new());
+}
+
+#[test]
+fn rejects_bad_renames() {
+    let mut data = Builder::new().file("/f", b"f").file("/g", b"g").mkdir("/d/e").build().data;
+    let fs = mount(&mut data);
+    let kind = |from: &str, to: &str| fs.rename(from, to).unwrap_err().kind();
+    assert_eq!(kind("/missing", "/x"), io::ErrorKind::NotFound);
+    assert_eq!(kind("/f", "/missing/x"), io::ErrorKind::NotFound);
+    assert_eq!(kind("/f", "/G"), io::ErrorKind::AlreadyExists);
+    assert_eq!(kind("/f", "/d"), io::ErrorKind::AlreadyExists);
+    assert_eq!(kind("/f", "/bad:name"), io::ErrorKind::InvalidInput);
+    assert_eq!(kind("/d", "/d/e/d"), io::ErrorKind::InvalidInput);
+    assert_eq!(kind("/d", "/d/d"), io::ErrorKind::InvalidInput);
+    assert_eq!(kind("/", "/x"), io::ErrorKind::InvalidInput);
+    assert_eq!(kind("/d/..", "/x"), io::ErrorKind::InvalidInput);
+    drop(fs);
+
+    check(&data).assert_clean();
+    assert_eq!(read_file(&mount(&mut data), "/f"), b"f");
+}
+
+#[test]
+fn works_on_fragmented_volumes_with_large_clusters() {
+    let mut data = Builder::new().fragmented().bytes_per_sector(1024).sectors_per_cluster(4)
+        .file("/a", &pattern(9_000)).file("/b", &pattern(20_000)).build().data;
+    {
+        let fs = mount(&mut data);
+        let mut file = fs.open("/a").unwrap().into_file().unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(&pattern(30_000)).unwrap();
+        fs.remove("/b").unwrap();
+    }
+
+    check(&data).assert_clean();
+    let mut expected = pattern(9_000);
+    expected.extend(pattern(30_000));
+    assert_eq!(read_file(&mount(&mut data), "/a"), expected);
+}
+
+#[test]
+fn writes_through_a_sector_cache() {
+    let image = Builder::new().file("/other", &pattern(5000)).build().data;
+    let mut data = image.clone();
+    {
+        let fs = VFat::from(CachedDevice::new(Cursor::new(&mut data[..]), 16)).unwrap();
+        fs.create_dir("/dir").unwrap();
+        fs.create("/dir/Cached File.bin").unwrap().write_all(&pattern(40_000)).unwrap();
+        fs.remove("/other").unwrap();
+        assert_eq!(read_file(&fs, "/dir/Cached File.bin"), pattern(40_000));
+
+        let mut vfat = fs.lock();
+        let stats = vfat.device().stats();
+        assert!(stats.hits > 0 && stats.evictions > 0);
+        vfat.device_mut().flush().unwrap();
+        assert_eq!(vfat.device().dirty(), 0);
+    }
+
+    check(&data).assert_clean();
+    assert_eq!(read_file(&mount(&mut data), "/dir/Cached File.bin"), pattern(40_000));
+    assert!(mount(&mut data).open("/other").is_err());
+}
+
+#[test]
+fn fails_cleanly_when_full() {
+    let mut data = Builder::new().file("/keep", b"keep").build().data;
+    {
+        let fs = mount(&mut data);
+        let mut file = fs.create("/huge").unwrap();
+        let chunk = pattern(64 * 1024);
+        let error = loop {
+            if let Err(error) = file.write_all(&chunk) {
+                break error;
+            }
+        };
+
+        // The failed write gave back the clusters it had allocated.
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        let free = fs.lock().free_clusters().unwrap();
+        assert!(free > 0 && free < 64 * 1024 / 512);
+    }
+
+    check(&data).assert_clean();
+    let fs = mount(&mut data);
+    assert_eq!(read_file(&fs, "/keep"), b"keep");
+    assert_eq!(read_file(&fs, "/huge").len() % (64 * 1024), 0);
+}
+
+/// A device that fails every write after the first `budget`.
+struct Failing<'a> {
+    device: Cursor<&'a mut [u8]>,
+    budget: usize,
+}
+
+impl<'a> BlockDevice for Failing<'a> {
+    fn sector_count(&self) -> u64 {
+        self.device.sector_count()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.device.read_sector(n, buf)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        match self.budget {
+            0 => Err(io::Error::new(io::ErrorKind::TimedOut, "power lost")),
+            _ => {
+                self.budget -= 1;
+                self.device.write_sector(n, buf)
+            }
+        }
+    }
+}
+
+/// Runs `operation` on copies of `image`, cutting it off after every
+/// possible number of sector writes, and checks that the volume stays free
+/// of errors and `/other` stays intact.
+fn survives_interruption<F>(image: Vec<u8>, operation: F)
+    where F: Fn(&Shared<VFat<Failing>>) -> io::Result<()>
+{
+    let other = read_file(&mount(&mut image.clone()), "/other");
+    for budget in 0.. {
+        let mut data = image.clone();
+        let result = {
+            let fs = VFat::from(Failing { device: Cursor::new(&mut data[..]), budget }).unwrap();
+            operation(&fs)
+        };
+
+        check(&data).assert_no_errors();
+        assert_eq!(read_file(&mount(&mut data), "/other"), other);
+        if result.is_ok() {
+            check(&data).assert_clean();
+            return;
+        }
+
+        assert!(budget < 10_000, "operation never finished");
+    }
+}
+
+#[test]
+fn survives_interrupted_writes() {
+    let image = || Builder::new()
+        .file("/other", &pattern(3_000))
+        .file("/victim", &pattern(2_000))
+        .mkdir("/dir")
+        .build()
+        .data;
+
+    survives_interruption(image(), |fs| {
+        let mut file = fs.open("/victim")?.into_file().unwrap();
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&pattern(5_000))
+    });
+
+    survives_interruption(image(), |fs| {
+        fs.create("/dir/A New File With A Long Name.txt")?.write_all(&pattern(1_000))
+    });
+
+    survives_interruption(image(), |fs| fs.create_dir("/dir/Another Directory").map(|_| ()));
+    survives_interruption(image(), |fs| fs.open("/victim")?.into_file().unwrap().set_len(100));
+    survives_interruption(image(), |fs| fs.remove("/victim"));
+    survives_interruption(image(), |fs| fs.create("/victim").map(|_| ()));
+    survives_interruption(image(), |fs| fs.rename("/victim", "/dir/A Renamed Victim.bin"));
+}
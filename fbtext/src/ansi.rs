@@ -0,0 +1,111 @@
+/// The maximum number of parameters kept for a control sequence. Additional
+/// parameters are ignored.
+const MAX_PARAMS: usize = 8;
+
+/// The numeric parameters of a control sequence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Params {
+    values: [u16; MAX_PARAMS],
+    len: usize,
+}
+
+impl Params {
+    /// Returns the parameters as a slice.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.values[..self.len]
+    }
+}
+
+/// An action produced by feeding a byte to a `Parser`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Nothing to do: the byte is part of an unfinished escape sequence.
+    None,
+    /// Print the byte.
+    Print(u8),
+    /// Execute the C0 control character.
+    Execute(u8),
+    /// Apply the SGR (select graphic rendition) parameters. An empty slice
+    /// means "reset".
+    Sgr(Params),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// A parser for the subset of ANSI escape sequences understood by the
+/// framebuffer console. CSI sequences other than SGR (`ESC [ ... m`) are
+/// consumed and ignored, as are other escape sequences.
+#[derive(Debug)]
+pub struct Parser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    count: usize,
+}
+
+impl Parser {
+    /// Returns a new parser in the ground state.
+    pub const fn new() -> Parser {
+        Parser { state: State::Ground, params: [0; MAX_PARAMS], count: 0 }
+    }
+
+    /// Feeds `byte` to the parser and returns the resulting action.
+    pub fn advance(&mut self, byte: u8) -> Action {
+        match self.state {
+            State::Ground => match byte {
+                0x1B => {
+                    self.state = State::Escape;
+                    Action::None
+                }
+                0x00..=0x1F | 0x7F => Action::Execute(byte),
+                _ => Action::Print(byte)
+            },
+            State::Escape => match byte {
+                b'[' => {
+                    self.state = State::Csi;
+                    self.params = [0; MAX_PARAMS];
+                    self.count = 0;
+                    Action::None
+                }
+                _ => {
+                    self.state = State::Ground;
+                    Action::None
+                }
+            },
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.count == 0 {
+                        self.count = 1;
+                    }
+
+                    if self.count <= MAX_PARAMS {
+                        let param = &mut self.params[self.count - 1];
+                        *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    }
+                    Action::None
+                }
+                b';' => {
+                    // An empty parameter before a `;` is a zero.
+                    self.count = self.count.max(1) + 1;
+                    Action::None
+                }
+                0x40..=0x7E => {
+                    self.state = State::Ground;
+                    match byte {
+                        b'm' => Action::Sgr(Params {
+                            values: self.params,
+                            len: self.count.min(MAX_PARAMS)
+                        }),
+                        _ => Action::None
+                    }
+                }
+                // Intermediate and private parameter bytes are ignored.
+                _ => Action::None
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! A text console rendered into a framebuffer.
+//!
+//! `TextConsole` only draws into a `PixelBuffer`, which wraps a plain slice of
+//! pixels. The same code renders to the kernel's HDMI framebuffer and to
+//! in-memory buffers in host tests.
+
+mod ansi;
+mod font;
+
+use std::fmt;
+
+use ansi::{Action, Parser};
+
+pub use font::{GLYPH_WIDTH, GLYPH_HEIGHT};
+
+/// The number of columns between tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// The 16 ANSI colors as `0x00RRGGBB`: the 8 normal colors followed by their
+/// bright variants.
+pub const PALETTE: [u32; 16] = [
+    0x000000, 0xAA0000, 0x00AA00, 0xAA5500, 0x0000AA, 0xAA00AA, 0x00AAAA, 0xAAAAAA,
+    0x555555, 0xFF5555, 0x55FF55, 0xFFFF55, 0x5555FF, 0xFF55FF, 0x55FFFF, 0xFFFFFF,
+];
+
+/// The default foreground color's palette index.
+const DEFAULT_FG: usize = 7;
+
+/// The default background color's palette index.
+const DEFAULT_BG: usize = 0;
+
+/// A mutable view of 32-bit pixels, `0x00RRGGBB` unless `swap_red_blue` is
+/// set, laid out in rows of `stride` pixels.
+pub struct PixelBuffer<'a> {
+    pixels: &'a mut [u32],
+    width: usize,
+    height: usize,
+    stride: usize,
+    swap_red_blue: bool,
+}
+
+impl<'a> PixelBuffer<'a> {
+    /// Wraps `pixels` as a `width` by `height` buffer with rows `stride` pixels
+    /// apart.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` is too small for the given geometry.
+    pub fn new(pixels: &'a mut [u32], width: usize, height: usize, stride: usize) -> PixelBuffer<'a> {
+        assert!(stride >= width && pixels.len() >= stride * height,
+                "PixelBuffer::new(): buffer too small for {}x{}", width, height);
+
+        PixelBuffer { pixels, width, height, stride, swap_red_blue: false }
+    }
+
+    /// Stores colors as `0x00BBGGRR` instead of `0x00RRGGBB`.
+    pub fn set_swap_red_blue(&mut self, swap: bool) {
+        self.swap_red_blue = swap;
+    }
+
+    /// The width of the buffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the buffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The distance between the starts of rows in pixels.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the raw pixels, `stride` pixels per row.
+    pub fn pixels(&self) -> &[u32] {
+        &*self.pixels
+    }
+
+    /// Returns the raw pixels mutably, `stride` pixels per row.
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut *self.pixels
+    }
+
+    /// Converts `0x00RRGGBB` into the buffer's pixel format.
+    fn convert(&self, rgb: u32) -> u32 {
+        match self.swap_red_blue {
+            false => rgb,
+            true => (rgb & 0x00FF00) | (rgb >> 16) & 0xFF | (rgb & 0xFF) << 16
+        }
+    }
+
+    /// Fills the `w` by `h` rectangle at (`x`, `y`) with `rgb`. The rectangle
+    /// is clipped to the buffer.
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, rgb: u32) {
+        let color = self.convert(rgb);
+        let x_end = (x + w).min(self.width);
+        for row in y..(y + h).min(self.height) {
+            let start = row * self.stride;
+            for pixel in &mut self.pixels[start + x.min(x_end)..start + x_end] {
+                *pixel = color;
+            }
+        }
+    }
+
+    /// Moves every row up by `rows` pixels and fills the uncovered rows at the
+    /// bottom with `rgb`.
+    pub fn scroll_up(&mut self, rows: usize, rgb: u32) {
+        let rows = rows.min(self.height);
+        if rows == 0 {
+            return;
+        }
+
+        for row in 0..self.height - rows {
+            let (dst, src) = self.pixels.split_at_mut((row + rows) * self.stride);
+            let dst = &mut dst[row * self.stride..][..self.width];
+            dst.copy_from_slice(&src[..self.width]);
+        }
+
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, height - rows, width, rows, rgb);
+    }
+}
+
+/// Text attributes set through SGR escape sequences.
+#[derive(Debug, Copy, Clone)]
+struct Attributes {
+    fg: usize,
+    bg: usize,
+    bold: bool,
+}
+
+impl Attributes {
+    const DEFAULT: Attributes = Attributes { fg: DEFAULT_FG, bg: DEFAULT_BG, bold: false };
+
+    /// Applies the SGR parameters `params` to `self`.
+    fn apply(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Attributes::DEFAULT;
+        }
+
+        for &param in params {
+            match param {
+                0 => *self = Attributes::DEFAULT,
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.fg = (param - 30) as usize,
+                39 => self.fg = DEFAULT_FG,
+                40..=47 => self.bg = (param - 40) as usize,
+                49 => self.bg = DEFAULT_BG,
+                90..=97 => self.fg = (param - 90) as usize + 8,
+                100..=107 => self.bg = (param - 100) as usize + 8,
+                _ => { }
+            }
+        }
+    }
+
+    /// Returns the foreground and background colors as `0x00RRGGBB`. Bold
+    /// text uses the bright variant of a normal foreground color.
+    fn colors(&self) -> (u32, u32) {
+        let fg = match self.bold && self.fg < 8 {
+            true => self.fg + 8,
+            false => self.fg
+        };
+
+        (PALETTE[fg], PALETTE[self.bg])
+    }
+}
+
+/// A text console that renders characters into a `PixelBuffer`.
+///
+/// Lines wrap at the right edge and the console scrolls when the cursor moves
+/// past the last row. `\n`, `\r`, `\t` and backspace are interpreted, as are
+/// SGR color and bold escape sequences. UTF-8 sequences are drawn as a single
+/// placeholder glyph.
+pub struct TextConsole<'a> {
+    buffer: PixelBuffer<'a>,
+    scale: usize,
+    columns: usize,
+    rows: usize,
+    column: usize,
+    row: usize,
+    attributes: Attributes,
+    parser: Parser,
+}
+
+impl<'a> TextConsole<'a> {
+    /// Returns a console drawing into `buffer` with glyphs scaled by `scale`.
+    /// The buffer is cleared to the default background color.
+    pub fn new(buffer: PixelBuffer<'a>, scale: usize) -> TextConsole<'a> {
+        let scale = scale.max(1);
+        let mut console = TextConsole {
+            columns: buffer.width() / (GLYPH_WIDTH * scale),
+            rows: buffer.height() / (GLYPH_HEIGHT * scale),
+            buffer,
+            scale,
+            column: 0,
+            row: 0,
+            attributes: Attributes::DEFAULT,
+            parser: Parser::new(),
+        };
+
+        console.clear();
+        console
+    }
+
+    /// The number of text columns.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The number of text rows.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the (column, row) of the cursor.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.column, self.row)
+    }
+
+    /// Returns the underlying pixel buffer.
+    pub fn buffer(&self) -> &PixelBuffer<'a> {
+        &self.buffer
+    }
+
+    /// Returns the underlying pixel buffer mutably. Drawing into it doesn't
+    /// move the cursor.
+    pub fn buffer_mut(&mut self) -> &mut PixelBuffer<'a> {
+        &mut self.buffer
+    }
+
+    /// Clears the screen and moves the cursor to the top-left corner.
+    pub fn clear(&mut self) {
+        let (_, bg) = self.attributes.colors();
+        let (width, height) = (self.buffer.width(), self.buffer.height());
+        self.buffer.fill_rect(0, 0, width, height, bg);
+        self.column = 0;
+        self.row = 0;
+    }
+
+    /// Writes the bytes in `bytes`, interpreting control characters and
+    /// escape sequences.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_byte(byte);
+        }
+    }
+
+    /// Writes the byte `byte`, interpreting control characters and escape
+    /// sequences.
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.columns == 0 || self.rows == 0 {
+            return;
+        }
+
+        match self.parser.advance(byte) {
+            Action::None => { }
+            Action::Sgr(params) => self.attributes.apply(params.as_slice()),
+            Action::Execute(b'\n') => self.newline(),
+            Action::Execute(b'\r') => self.column = 0,
+            Action::Execute(b'\t') => {
+                for _ in 0..TAB_WIDTH - self.column % TAB_WIDTH {
+                    self.put(b' ');
+                }
+            }
+            Action::Execute(0x08) => self.column = self.column.saturating_sub(1),
+            Action::Execute(_) => { }
+            // UTF-8 continuation bytes belong to an already drawn glyph.
+            Action::Print(0x80..=0xBF) => { }
+            Action::Print(byte) => self.put(byte),
+        }
+    }
+
+    /// Draws `byte` at the cursor and advances it, wrapping as needed.
+    fn put(&mut self, byte: u8) {
+        if self.column >= self.columns {
+            self.newline();
+        }
+
+        let (fg, bg) = self.attributes.colors();
+        let (x, y) = (self.column * GLYPH_WIDTH * self.scale, self.row * GLYPH_HEIGHT * self.scale);
+        for (dy, bits) in font::glyph(byte).iter().enumerate() {
+            for dx in 0..GLYPH_WIDTH {
+                let color = if bits & (1 << dx) != 0 { fg } else { bg };
+                self.buffer.fill_rect(x + dx * self.scale, y + dy * self.scale,
+                                      self.scale, self.scale, color);
+            }
+        }
+
+        self.column += 1;
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling if needed.
+    fn newline(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+
+        let (_, bg) = self.attributes.colors();
+        self.buffer.scroll_up(GLYPH_HEIGHT * self.scale, bg);
+    }
+}
+
+impl<'a> fmt::Write for TextConsole<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+extern crate fbtext;
+
+use fbtext::{PixelBuffer, TextConsole, GLYPH_WIDTH, GLYPH_HEIGHT};
+
+const BLACK: u32 = 0x000000;
+const RED: u32 = 0xAA0000;
+const BLUE: u32 = 0x0000AA;
+const GRAY: u32 = 0xAAAAAA;
+const BRIGHT_GREEN: u32 = 0x55FF55;
+const BRIGHT_YELLOW: u32 = 0xFFFF55;
+
+const GLYPH_A: [u8; 8] = [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00];
+const GLYPH_B: [u8; 8] = [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00];
+const GLYPH_C: [u8; 8] = [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00];
+const GLYPH_1: [u8; 8] = [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00];
+const GLYPH_2: [u8; 8] = [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00];
+const GLYPH_3: [u8; 8] = [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00];
+const BLANK: [u8; 8] = [0; 8];
+
+/// A pixel value the console never draws, marking untouched pixels.
+const UNTOUCHED: u32 = 0xDEAD_BEEF;
+
+/// Returns the pixels of a `columns` by `rows` character screen at scale 1.
+fn screen(columns: usize, rows: usize) -> Vec<u32> {
+    vec![UNTOUCHED; columns * GLYPH_WIDTH * rows * GLYPH_HEIGHT]
+}
+
+/// Returns a console drawing into `pixels`, `columns` characters wide.
+fn console<'a>(pixels: &'a mut [u32], columns: usize) -> TextConsole<'a> {
+    let width = columns * GLYPH_WIDTH;
+    let height = pixels.len() / width;
+    TextConsole::new(PixelBuffer::new(pixels, width, height, width), 1)
+}
+
+/// Converts `art`, rows of `#` for `fg` and `.` for `bg`, to pixels.
+fn image(art: &[&str], fg: u32, bg: u32) -> Vec<u32> {
+    art.iter()
+        .flat_map(|row| row.chars())
+        .map(|c| if c == '#' { fg } else { bg })
+        .collect()
+}
+
+/// Asserts that the character cell at (`column`, `row`) shows `glyph` drawn
+/// with `fg` on `bg`, scaled by `scale`.
+fn assert_cell(buffer: &PixelBuffer, column: usize, row: usize, scale: usize,
+               glyph: [u8; 8], fg: u32, bg: u32) {
+    let (x0, y0) = (column * GLYPH_WIDTH * scale, row * GLYPH_HEIGHT * scale);
+    for y in 0..GLYPH_HEIGHT * scale {
+        for x in 0..GLYPH_WIDTH * scale {
+            let set = glyph[y / scale] & (1 << (x / scale)) != 0;
+            let expected = if set { fg } else { bg };
+            let pixel = buffer.pixels()[(y0 + y) * buffer.stride() + x0 + x];
+            assert_eq!(pixel, expected, "cell ({}, {}), pixel ({}, {})", column, row, x, y);
+        }
+    }
+}
+
+#[test]
+fn renders_golden_image() {
+    let mut pixels = screen(2, 1);
+    console(&mut pixels, 2).write_bytes(b"Hi");
+
+    let expected = image(&[
+        "##..##....##....",
+        "##..##..........",
+        "##..##...###....",
+        "######....##....",
+        "##..##....##....",
+        "##..##....##....",
+        "##..##...####...",
+        "................",
+    ], GRAY, BLACK);
+    assert_eq!(pixels, expected);
+}
+
+#[test]
+fn clears_to_background() {
+    let mut pixels = screen(3, 2);
+    let console = console(&mut pixels, 3);
+    assert_eq!(console.columns(), 3);
+    assert_eq!(console.rows(), 2);
+    assert_eq!(console.cursor(), (0, 0));
+    assert!(console.buffer().pixels().iter().all(|&pixel| pixel == BLACK));
+}
+
+#[test]
+fn places_glyphs_at_cursor() {
+    let mut pixels = screen(3, 2);
+    let mut console = console(&mut pixels, 3);
+    console.write_bytes(b"AB\nC");
+
+    assert_eq!(console.cursor(), (1, 1));
+    let buffer = console.buffer();
+    assert_cell(buffer, 0, 0, 1, GLYPH_A, GRAY, BLACK);
+    assert_cell(buffer, 1, 0, 1, GLYPH_B, GRAY, BLACK);
+    assert_cell(buffer, 2, 0, 1, BLANK, GRAY, BLACK);
+    assert_cell(buffer, 0, 1, 1, GLYPH_C, GRAY, BLACK);
+    assert_cell(buffer, 1, 1, 1, BLANK, GRAY, BLACK);
+}
+
+#[test]
+fn scales_glyphs_and_leaves_row_padding() {
+    // Two columns at scale 2, in rows padded to 40 pixels.
+    let (width, height, stride) = (32, 16, 40);
+    let mut pixels = vec![UNTOUCHED; stride * height];
+    {
+        let buffer = PixelBuffer::new(&mut pixels, width, height, stride);
+        let mut console = TextConsole::new(buffer, 2);
+        assert_eq!((console.columns(), console.rows()), (2, 1));
+
+        console.write_bytes(b" A");
+        assert_cell(console.buffer(), 0, 0, 2, BLANK, GRAY, BLACK);
+        assert_cell(console.buffer(), 1, 0, 2, GLYPH_A, GRAY, BLACK);
+    }
+
+    for row in pixels.chunks(stride) {
+        assert!(row[width..].iter().all(|&pixel| pixel == UNTOUCHED));
+    }
+}
+
+#[test]
+fn carriage_return_tab_and_backspace_move_cursor() {
+    let mut pixels = screen(10, 2);
+    let mut console = console(&mut pixels, 10);
+
+    console.write_bytes(b"AB\rC");
+    assert_eq!(console.cursor(), (1, 0));
+    assert_cell(console.buffer(), 0, 0, 1, GLYPH_C, GRAY, BLACK);
+    assert_cell(console.buffer(), 1, 0, 1, GLYPH_B, GRAY, BLACK);
+
+    console.write_bytes(b"\t");
+    assert_eq!(console.cursor(), (8, 0));
+    assert_cell(console.buffer(), 1, 0, 1, BLANK, GRAY, BLACK);
+
+    console.write_bytes(b"\x08\x08A");
+    assert_eq!(console.cursor(), (7, 0));
+    assert_cell(console.buffer(), 6, 0, 1, GLYPH_A, GRAY, BLACK);
+}
+
+#[test]
+fn wraps_at_right_edge() {
+    let mut pixels = screen(2, 2);
+    let mut console = console(&mut pixels, 2);
+    console.write_bytes(b"AB");
+    // The cursor stays past the last column until the next character.
+    assert_eq!(console.cursor(), (2, 0));
+
+    console.write_bytes(b"C");
+    assert_eq!(console.cursor(), (1, 1));
+    assert_cell(console.buffer(), 0, 0, 1, GLYPH_A, GRAY, BLACK);
+    assert_cell(console.buffer(), 1, 0, 1, GLYPH_B, GRAY, BLACK);
+    assert_cell(console.buffer(), 0, 1, 1, GLYPH_C, GRAY, BLACK);
+}
+
+#[test]
+fn scrolls_past_last_row() {
+    let mut pixels = screen(2, 2);
+    let mut console = console(&mut pixels, 2);
+    console.write_bytes(b"1\n2\n3");
+
+    assert_eq!(console.cursor(), (1, 1));
+    assert_cell(console.buffer(), 0, 0, 1, GLYPH_2, GRAY, BLACK);
+    assert_cell(console.buffer(), 0, 1, 1, GLYPH_3, GRAY, BLACK);
+
+    // A wrap on the last row scrolls too, clearing the new row.
+    console.write_bytes(b"AB");
+    assert_cell(console.buffer(), 0, 0, 1, GLYPH_3, GRAY, BLACK);
+    assert_cell(console.buffer(), 1, 0, 1, GLYPH_A, GRAY, BLACK);
+    assert_cell(console.buffer(), 0, 1, 1, GLYPH_B, GRAY, BLACK);
+    assert_cell(console.buffer(), 1, 1, 1, BLANK, GRAY, BLACK);
+}
+
+#[test]
+fn scrolled_rows_take_current_background() {
+    let mut pixels = screen(1, 2);
+    let mut console = console(&mut pixels, 1);
+    console.write_bytes(b"1\n\x1b[44m\n");
+
+    assert_cell(console.buffer(), 0, 0, 1, BLANK, GRAY, BLACK);
+    assert_cell(console.buffer(), 0, 1, 1, BLANK, GRAY, BLUE);
+}
+
+#[test]
+fn applies_sgr_colors() {
+    let mut pixels = screen(6, 1);
+    let mut console = console(&mut pixels, 6);
+    console.write_bytes(b"\x1b[31mA\x1b[0mB\x1b[1;32;44mC\x1b[22;39;49m1\x1b[93m2\x1b[m3");
+
+    assert_eq!(console.cursor(), (6, 0));
+    let buffer = console.buffer();
+    assert_cell(buffer, 0, 0, 1, GLYPH_A, RED, BLACK);
+    assert_cell(buffer, 1, 0, 1, GLYPH_B, GRAY, BLACK);
+    // Bold picks the bright variant of a normal color.
+    assert_cell(buffer, 2, 0, 1, GLYPH_C, BRIGHT_GREEN, BLUE);
+    assert_cell(buffer, 3, 0, 1, GLYPH_1, GRAY, BLACK);
+    assert_cell(buffer, 4, 0, 1, GLYPH_2, BRIGHT_YELLOW, BLACK);
+    assert_cell(buffer, 5, 0, 1, GLYPH_3, GRAY, BLACK);
+}
+
+#[test]
+fn ignores_other_escape_sequences() {
+    let mut pixels = screen(3, 1);
+    let mut console = console(&mut pixels, 3);
+    console.write_bytes(b"\x1b[2JA\x1b[?25lB\x1b7");
+
+    assert_eq!(console.cursor(), (2, 0));
+    assert_cell(console.buffer(), 0, 0, 1, GLYPH_A, GRAY, BLACK);
+    assert_cell(console.buffer(), 1, 0, 1, GLYPH_B, GRAY, BLACK);
+}
+
+#[test]
+fn swaps_red_and_blue() {
+    let mut pixels = screen(2, 1);
+    let mut buffer = PixelBuffer::new(&mut pixels, 16, 8, 16);
+    buffer.set_swap_red_blue(true);
+    buffer.fill_rect(0, 0, 1, 1, 0x123456);
+    assert_eq!(buffer.pixels()[0], 0x563412);
+
+    let mut console = TextConsole::new(buffer, 1);
+    console.write_bytes(b"\x1b[31;44mA\x1b[0m ");
+    assert_cell(console.buffer(), 0, 0, 1, GLYPH_A, BLUE, RED);
+    assert_cell(console.buffer(), 1, 0, 1, BLANK, GRAY, BLACK);
+}
+
+#[test]
+fn fill_rect_clips_to_buffer() {
+    let mut pixels = vec![0; 4 * 3];
+    {
+        let mut buffer = PixelBuffer::new(&mut pixels, 4, 3, 4);
+        buffer.fill_rect(2, 1, 10, 10, 1);
+        buffer.fill_rect(5, 0, 1, 1, 2);
+    }
+
+    assert_eq!(pixels, [
+        0, 0, 0, 0,
+        0, 0, 1, 1,
+        0, 0, 1, 1,
+    ]);
+}
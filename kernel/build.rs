@@ -1,4 +1,38 @@
+use std::env;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the abbreviated hash of the checked out commit, or `unknown` if
+/// the source isn't in a git repository.
+fn git_hash() -> String {
+    Command::new("git").args(&["rev-parse", "--short=12", "HEAD"]).output().ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Returns the build time in seconds since the Unix epoch. `SOURCE_DATE_EPOCH`
+/// overrides it for reproducible builds.
+fn build_time() -> u64 {
+    env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+        })
+}
+
 pub fn main() {
     println!("cargo:rerun-if-changed=ext/layout.ld");
     println!("cargo:rerun-if-changed=ext/init.S");
+
+    // Rerun whenever the kernel or the checked out commit changes, so that
+    // the version reported in `/proc/version` stays current.
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    println!("cargo:rustc-env=KERNEL_GIT_HASH={}", git_hash());
+    println!("cargo:rustc-env=KERNEL_BUILD_TIME={}", build_time());
 }
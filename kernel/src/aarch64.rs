@@ -0,0 +1,31 @@
+/// Returns the current exception level.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn current_el() -> u8 {
+    let el_reg: u64;
+    asm!("mrs $0, CurrentEL" : "=r"(el_reg));
+    ((el_reg & 0b1100) >> 2) as u8
+}
+
+/// Unmasks IRQ exceptions on the current core.
+#[inline(always)]
+pub fn enable_irq() {
+    unsafe { asm!("msr daifclr, #2" :::: "volatile"); }
+}
+
+/// Masks IRQ exceptions on the current core.
+#[inline(always)]
+pub fn disable_irq() {
+    unsafe { asm!("msr daifset, #2" :::: "volatile"); }
+}
+
+/// Returns the value of the main ID register, `MIDR_EL1`, which identifies
+/// the core's implementer, part number and revision.
+#[inline(always)]
+pub fn midr() -> u64 {
+    let midr: u64;
+    unsafe { asm!("mrs $0, midr_el1" : "=r"(midr)); }
+    midr
+}
@@ -0,0 +1,142 @@
+use std::alloc::Layout;
+use std::ptr;
+
+use allocator::linked_list::LinkedList;
+use allocator::util::align_up;
+use allocator::Stats;
+
+/// The log2 of the smallest block size. Blocks must have room for a free list
+/// link.
+const MIN_BLOCK_SHIFT: usize = 3;
+
+/// The number of bins. The largest block is `1 << (MIN_BLOCK_SHIFT +
+/// NUM_BINS - 1)` bytes: 1GiB.
+const NUM_BINS: usize = 28;
+
+/// A power-of-two size class allocator.
+///
+/// Every request is rounded up to a power of two no smaller than its
+/// alignment, and served from the free list ("bin") for that size. Empty bins
+/// are refilled by carving a new block, aligned to its own size, from the
+/// unused memory at the end of the heap. Freed blocks go back to their bin
+/// and are never coalesced.
+pub struct Allocator {
+    bins: [LinkedList; NUM_BINS],
+    start: usize,
+    current: usize,
+    end: usize,
+    /// The bytes in blocks handed out and not yet freed.
+    allocated: usize,
+    /// The number of blocks handed out and not yet freed.
+    allocations: usize,
+}
+
+impl Allocator {
+    /// Creates a new bin allocator that will allocate memory from the region
+    /// starting at address `start` and ending at address `end`.
+    pub fn new(start: usize, end: usize) -> Allocator {
+        let start = align_up(start, 1 << MIN_BLOCK_SHIFT);
+        Allocator {
+            bins: [LinkedList::new(); NUM_BINS],
+            start: start,
+            current: start,
+            end: end,
+            allocated: 0,
+            allocations: 0,
+        }
+    }
+
+    /// Returns the heap's current usage.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            total: self.end.saturating_sub(self.start),
+            allocated: self.allocated,
+            allocations: self.allocations,
+            untouched: self.end.saturating_sub(self.current),
+        }
+    }
+
+    /// Returns the bin serving `layout`, or `None` if it is too large.
+    fn bin_for(layout: &Layout) -> Option<usize> {
+        let size = layout.size()
+            .max(layout.align())
+            .max(1 << MIN_BLOCK_SHIFT)
+            .checked_next_power_of_two()?;
+
+        match size.trailing_zeros() as usize - MIN_BLOCK_SHIFT {
+            bin if bin < NUM_BINS => Some(bin),
+            _ => None
+        }
+    }
+
+    /// The size of the blocks in `bin`.
+    fn block_size(bin: usize) -> usize {
+        1 << (bin + MIN_BLOCK_SHIFT)
+    }
+
+    /// Allocates memory. Returns a pointer meeting the size and alignment
+    /// properties of `layout.size()` and `layout.align()`.
+    ///
+    /// If this method returns a null pointer, the request could not be
+    /// satisfied: the heap is exhausted or the request is larger than the
+    /// largest block.
+    pub fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let bin = match Allocator::bin_for(&layout) {
+            Some(bin) => bin,
+            None => return ptr::null_mut()
+        };
+
+        let size = Allocator::block_size(bin);
+        if let Some(block) = self.bins[bin].pop() {
+            self.allocated += size;
+            self.allocations += 1;
+            return block as *mut u8;
+        }
+
+        let start = match self.current.checked_add(size - 1) {
+            Some(_) => align_up(self.current, size),
+            None => return ptr::null_mut()
+        };
+
+        match start.checked_add(size) {
+            Some(end) if end <= self.end => {
+                let current = self.current;
+                self.release(current, start);
+                self.current = end;
+                self.allocated += size;
+                self.allocations += 1;
+                start as *mut u8
+            }
+            _ => ptr::null_mut()
+        }
+    }
+
+    /// Deallocates the memory referenced by `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by a previous call to `alloc()` with the
+    /// same `layout`, and must not be used after this call.
+    pub unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        let bin = Allocator::bin_for(&layout).expect("dealloc(): layout never allocated");
+        self.bins[bin].push(ptr as *mut usize);
+        self.allocated -= Allocator::block_size(bin);
+        self.allocations -= 1;
+    }
+
+    /// Puts the unused memory between `start` and `end`, skipped to align a
+    /// new block, into the bins as blocks aligned to their own size.
+    fn release(&mut self, mut start: usize, end: usize) {
+        let largest = Allocator::block_size(NUM_BINS - 1);
+        while start < end {
+            let mut size = (1 << start.trailing_zeros()).min(largest);
+            while start + size > end {
+                size >>= 1;
+            }
+
+            let bin = size.trailing_zeros() as usize - MIN_BLOCK_SHIFT;
+            unsafe { self.bins[bin].push(start as *mut usize) };
+            start += size;
+        }
+    }
+}
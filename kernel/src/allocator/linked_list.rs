@@ -0,0 +1,48 @@
+use std::ptr;
+
+/// An intrusive linked list of addresses.
+///
+/// A `LinkedList` stores the address of the next item at the address of each
+/// item, so it needs no memory of its own. Every pushed address must be
+/// aligned to and have room for a `usize`, and must not be used for anything
+/// else while it is in the list.
+#[derive(Copy, Clone)]
+pub struct LinkedList {
+    head: *mut usize,
+}
+
+unsafe impl Send for LinkedList {}
+
+impl LinkedList {
+    /// Returns a new, empty linked list.
+    pub const fn new() -> LinkedList {
+        LinkedList { head: ptr::null_mut() }
+    }
+
+    /// Returns `true` if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Pushes the address `item` to the front of the list.
+    ///
+    /// # Safety
+    ///
+    /// `item` must be a valid, writable and `usize` aligned address that is
+    /// not otherwise in use.
+    pub unsafe fn push(&mut self, item: *mut usize) {
+        *item = self.head as usize;
+        self.head = item;
+    }
+
+    /// Removes and returns the first address in the list, if any.
+    pub fn pop(&mut self) -> Option<*mut usize> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let item = self.head;
+        self.head = unsafe { *item as *mut usize };
+        Some(item)
+    }
+}
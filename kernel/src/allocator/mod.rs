@@ -0,0 +1,91 @@
+mod bin;
+mod linked_list;
+mod util;
+
+use std::alloc::{GlobalAlloc, Layout};
+
+use pi::mailbox::{self, GetArmMemory};
+
+use mutex::Mutex;
+
+/// A snapshot of the heap's usage.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// The size of the heap in bytes.
+    pub total: usize,
+    /// The bytes in blocks that are allocated, including the rounding of
+    /// requests up to their block size.
+    pub allocated: usize,
+    /// The number of blocks that are allocated.
+    pub allocations: usize,
+    /// The bytes at the end of the heap that have never been carved into
+    /// blocks.
+    pub untouched: usize,
+}
+
+impl Stats {
+    /// The bytes available for new allocations, in freed blocks and untouched
+    /// memory.
+    pub fn free(&self) -> usize {
+        self.total - self.allocated
+    }
+}
+
+/// Thread-safe (locking) wrapper around the kernel's memory allocator.
+pub struct Allocator(Mutex<Option<bin::Allocator>>);
+
+impl Allocator {
+    /// Returns an uninitialized `Allocator`.
+    ///
+    /// The allocator must be initialized by calling `initialize()` before the
+    /// first memory allocation. Failure to do will result in panics.
+    pub const fn uninitialized() -> Self {
+        Allocator(Mutex::new(None))
+    }
+
+    /// Initializes the memory allocator with the memory between the end of
+    /// the kernel binary and the end of the ARM's share of memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system's memory map could not be retrieved.
+    pub fn initialize(&self) {
+        let (start, end) = memory_map().expect("failed to find memory map");
+        *self.0.lock() = Some(bin::Allocator::new(start, end));
+    }
+
+    /// Returns the heap's current usage, or `None` if the allocator is
+    /// uninitialized.
+    pub fn stats(&self) -> Option<Stats> {
+        self.0.lock().as_ref().map(|allocator| allocator.stats())
+    }
+}
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0.lock().as_mut().expect("allocator uninitialized").alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().as_mut().expect("allocator uninitialized").dealloc(ptr, layout);
+    }
+}
+
+extern "C" {
+    static _end: u8;
+}
+
+/// Returns the (start address, end address) of the available memory on this
+/// system if it can be determined. If it cannot, `None` is returned.
+///
+/// This function is expected to return `Some` under all normal circumstances.
+pub fn memory_map() -> Option<(usize, usize)> {
+    let binary_end = unsafe { &_end as *const u8 as usize };
+    let memory = mailbox::property(&GetArmMemory).ok()?;
+    let end = memory.base as usize + memory.size as usize;
+    if binary_end >= end {
+        return None;
+    }
+
+    Some((binary_end, end))
+}
@@ -4,6 +4,8 @@ use std::fmt;
 use pi::uart::MiniUart;
 
 use mutex::Mutex;
+use fbcon;
+use thread::WaitQueue;
 
 /// A global singleton allowing read/write access to the console.
 pub struct Console {
@@ -31,14 +33,29 @@ impl Console {
         self.inner.as_mut().unwrap()
     }
 
-    /// Reads a byte from the UART device, blocking until a byte is available.
-    pub fn read_byte(&mut self) -> u8 {
-        self.inner().read_byte()
+    /// Reads a byte from the UART device without blocking. Returns `None` if
+    /// no byte is available.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        self.inner().try_read()
     }
 
-    /// Writes the byte `byte` to the UART device.
+    /// Writes the byte `byte` to the UART device and the framebuffer console.
     pub fn write_byte(&mut self, byte: u8) {
-        self.inner().write_byte(byte)
+        self.write_bytes(&[byte])
+    }
+
+    /// Writes `bytes` to the UART device and the framebuffer console.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        fbcon::mirror(bytes);
+        for &byte in bytes {
+            self.inner().write_byte(byte)
+        }
+    }
+
+    /// Switches the UART device to interrupt-driven I/O. The `Aux` interrupt
+    /// must already be routed to `pi::uart::handle_irq()`.
+    pub fn enable_interrupts(&mut self) {
+        self.inner().enable_interrupts()
     }
 }
 
@@ -50,6 +67,7 @@ impl io::Read for Console {
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        fbcon::mirror(buf);
         self.inner().write(buf)
     }
 
@@ -60,6 +78,7 @@ impl io::Write for Console {
 
 impl fmt::Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
+        fbcon::mirror(s.as_bytes());
         self.inner().write_str(s)
     }
 }
@@ -67,6 +86,54 @@ impl fmt::Write for Console {
 /// Global `Console` singleton.
 pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
 
+/// Threads waiting in `read_byte()` for input.
+static READERS: WaitQueue = WaitQueue::new();
+
+/// Reads a byte from the console, blocking the running thread until one is
+/// available. The console isn't locked while waiting, so other threads can
+/// write to it.
+///
+/// Waiting threads are woken by `wake_readers()`, so the console must have
+/// been switched to interrupt-driven I/O with `Console::enable_interrupts()`.
+pub fn read_byte() -> u8 {
+    let mut byte = None;
+    READERS.wait_until(|| {
+        byte = CONSOLE.lock().try_read_byte();
+        byte.is_some()
+    });
+
+    byte.unwrap()
+}
+
+/// Reads the bytes available from the console into `buf`, blocking the
+/// running thread until there's at least one, and returns how many were
+/// read. Returns `0` right away if `buf` is empty. See `read_byte()`.
+pub fn read(buf: &mut [u8]) -> usize {
+    if buf.is_empty() {
+        return 0;
+    }
+
+    buf[0] = read_byte();
+    let mut read = 1;
+    let mut console = CONSOLE.lock();
+    while read < buf.len() {
+        match console.try_read_byte() {
+            Some(byte) => buf[read] = byte,
+            None => break
+        }
+
+        read += 1;
+    }
+
+    read
+}
+
+/// Wakes the threads waiting in `read_byte()` and `read()`. Called by the `Aux` IRQ
+/// handler once received bytes are buffered.
+pub fn wake_readers() {
+    READERS.wake_all();
+}
+
 /// Internal function called by the `kprint[ln]!` macros.
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
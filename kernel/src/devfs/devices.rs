@@ -0,0 +1,298 @@
+//! The kernel's built-in devices.
+
+use std::cmp;
+use std::io::{self, Read, Write};
+use std::slice;
+use std::sync::Arc;
+
+use pi::gpio::Gpio;
+use pi::rng::Rng;
+
+use console::{self, CONSOLE};
+use fbcon::FB_CONSOLE;
+use vfs;
+use super::{CharDevice, DevFs};
+
+/// The GPIO pins on the 40-pin header, other than 14 and 15, which carry the
+/// console's UART.
+const GPIO_PINS: &[u8] = &[
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13,
+    16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+];
+
+/// The byte that ends input read from `uart0`: Ctrl-D.
+const END_OF_TRANSMISSION: u8 = 0x04;
+
+/// The control requests `fb0` supports. Each returns a property of the
+/// framebuffer and ignores its argument.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FbControl {
+    /// The width of the screen in pixels.
+    Width = 0,
+    /// The height of the screen in pixels.
+    Height = 1,
+    /// The distance between the starts of rows in bytes.
+    Pitch = 2,
+    /// The number of bits per pixel.
+    Depth = 3,
+}
+
+/// Registers every built-in device in `devfs`. `fb0` is only registered if
+/// the framebuffer console was initialized.
+pub fn register_all(devfs: &Arc<DevFs>) -> io::Result<()> {
+    devfs.register("null", || Ok(Box::new(Null) as Box<dyn CharDevice>))?;
+    devfs.register("zero", || Ok(Box::new(Zero) as Box<dyn CharDevice>))?;
+    devfs.register("random", || Ok(Box::new(Random(Rng::new())) as Box<dyn CharDevice>))?;
+    devfs.register("uart0", || Ok(Box::new(Uart { ended: false }) as Box<dyn CharDevice>))?;
+
+    for &pin in GPIO_PINS {
+        devfs.register(&("gpio/".to_string() + &pin.to_string()), move || {
+            Ok(Box::new(GpioPin { pin, line: None, position: 0 }) as Box<dyn CharDevice>)
+        })?;
+    }
+
+    if FB_CONSOLE.lock().is_some() {
+        devfs.register("fb0", || Ok(Box::new(Fb { position: 0 }) as Box<dyn CharDevice>))?;
+    }
+
+    Ok(())
+}
+
+/// `null`: reads end immediately and writes are discarded.
+struct Null;
+
+impl Read for Null {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Null {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CharDevice for Null {}
+
+/// `zero`: reads return zeroes without end and writes are discarded.
+struct Zero;
+
+impl Read for Zero {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        for byte in buf.iter_mut() {
+            *byte = 0;
+        }
+
+        Ok(buf.len())
+    }
+}
+
+impl Write for Zero {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CharDevice for Zero {}
+
+/// `random`: reads return bytes from the hardware random number generator
+/// and writes are discarded.
+struct Random(Rng);
+
+impl Read for Random {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.fill(buf);
+        Ok(buf.len())
+    }
+}
+
+impl Write for Random {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CharDevice for Random {}
+
+/// `uart0`: the console's serial port. Reads wait, letting other threads run,
+/// until input arrives and end at a Ctrl-D. Writes are mirrored to the
+/// framebuffer console like all console output.
+struct Uart {
+    /// Whether a Ctrl-D has been read.
+    ended: bool,
+}
+
+impl Read for Uart {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.ended || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let read = console::read(buf);
+        match buf[..read].iter().position(|&byte| byte == END_OF_TRANSMISSION) {
+            Some(end) => {
+                self.ended = true;
+                Ok(end)
+            }
+            None => Ok(read)
+        }
+    }
+}
+
+impl Write for Uart {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        CONSOLE.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CharDevice for Uart {}
+
+/// `gpio/<pin>`: a GPIO pin. Reads return the pin's level as a line, `1` or
+/// `0`, without changing its function. The level is sampled by the first read
+/// and the line can be read in pieces of any size. Writing `1` or `0` makes
+/// the pin an output and drives it high or low; whitespace is ignored.
+struct GpioPin {
+    pin: u8,
+    /// The line the first read sampled, `1\n` or `0\n`.
+    line: Option<[u8; 2]>,
+    /// The offset of the next byte read in `line`.
+    position: usize,
+}
+
+impl Read for GpioPin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let pin = self.pin;
+        let line = *self.line.get_or_insert_with(|| {
+            [if Gpio::new(pin).level() { b'1' } else { b'0' }, b'\n']
+        });
+
+        let rest = &line[self.position..];
+        let n = cmp::min(buf.len(), rest.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl Write for GpioPin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            match byte {
+                b'1' => Gpio::new(self.pin).into_output().set(),
+                b'0' => Gpio::new(self.pin).into_output().clear(),
+                b' ' | b'\t' | b'\r' | b'\n' => {}
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "expected 1 or 0"))
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CharDevice for GpioPin {}
+
+/// `fb0`: the framebuffer's pixels as raw bytes, row after row, in the
+/// framebuffer's own pixel format. Reads and writes start at the first pixel
+/// and end at the end of the framebuffer. Anything the console prints
+/// afterwards is drawn over them.
+struct Fb {
+    /// The offset of the next byte read or written.
+    position: usize,
+}
+
+impl Fb {
+    /// Calls `f` with the framebuffer's bytes from the offset of the next
+    /// byte on, then advances the offset by the number of bytes `f` returns.
+    fn transfer<F: FnOnce(&mut [u8]) -> usize>(&mut self, f: F) -> io::Result<usize> {
+        let mut console = FB_CONSOLE.lock();
+        let console = match console.as_mut() {
+            Some(console) => console,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no framebuffer"))
+        };
+
+        let pixels = console.buffer_mut().pixels_mut();
+        let bytes = unsafe {
+            slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, pixels.len() * 4)
+        };
+
+        let start = cmp::min(self.position, bytes.len());
+        let n = f(&mut bytes[start..]);
+        self.position = start + n;
+        Ok(n)
+    }
+}
+
+impl Read for Fb {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.transfer(|bytes| {
+            let n = cmp::min(buf.len(), bytes.len());
+            buf[..n].copy_from_slice(&bytes[..n]);
+            n
+        })
+    }
+}
+
+impl Write for Fb {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.transfer(|bytes| {
+            let n = cmp::min(buf.len(), bytes.len());
+            bytes[..n].copy_from_slice(&buf[..n]);
+            n
+        })?;
+
+        match written {
+            0 if !buf.is_empty() => Err(io::Error::new(io::ErrorKind::WriteZero, "end of framebuffer")),
+            n => Ok(n)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CharDevice for Fb {
+    fn control(&mut self, request: u32, _arg: u64) -> io::Result<u64> {
+        let console = FB_CONSOLE.lock();
+        let buffer = match console.as_ref() {
+            Some(console) => console.buffer(),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no framebuffer"))
+        };
+
+        let value = match request {
+            r if r == FbControl::Width as u32 => buffer.width(),
+            r if r == FbControl::Height as u32 => buffer.height(),
+            r if r == FbControl::Pitch as u32 => buffer.stride() * 4,
+            r if r == FbControl::Depth as u32 => 32,
+            _ => return Err(vfs::unsupported_control())
+        };
+
+        Ok(value as u64)
+    }
+}
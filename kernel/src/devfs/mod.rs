@@ -0,0 +1,316 @@
+//! The device file system: drivers exposed as files.
+//!
+//! Drivers register character devices at paths such as `gpio/16` with a
+//! function that opens a new handle to the device, which is called every time
+//! the device's file is opened. Directories exist for as long as a device is
+//! registered below them. Device files have no size and can't be seeked;
+//! their reads and writes go straight to the driver, and `File::control()`
+//! passes on the driver's device-specific operations.
+
+mod devices;
+
+pub use self::devices::FbControl;
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+use mutex::Mutex;
+use vfs::{self, File, FileSystem, FileType, Metadata, VFS};
+
+/// Where the device file system is mounted in the `VFS`.
+pub const MOUNT_POINT: &str = "/dev";
+
+/// A device that reads and writes a stream of bytes.
+pub trait CharDevice: io::Read + io::Write + Send {
+    /// Performs the device-specific operation `request` with the argument
+    /// `arg` and returns its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the device doesn't support
+    /// `request`, which is all of them unless this is implemented.
+    fn control(&mut self, _request: u32, _arg: u64) -> io::Result<u64> {
+        Err(vfs::unsupported_control())
+    }
+}
+
+/// A function that opens a new handle to a device.
+pub type Open = Arc<dyn Fn() -> io::Result<Box<dyn CharDevice>> + Send + Sync>;
+
+/// The mounted device file system, or `None` if `init()` hasn't succeeded.
+pub static DEVFS: Mutex<Option<Arc<DevFs>>> = Mutex::new(None);
+
+/// Mounts a device file system at `MOUNT_POINT` and registers the kernel's
+/// built-in devices in it.
+pub fn init() -> io::Result<()> {
+    let devfs = Arc::new(DevFs::new());
+    VFS.mount(MOUNT_POINT, devfs.clone())?;
+    *DEVFS.lock() = Some(devfs.clone());
+    devices::register_all(&devfs)
+}
+
+/// Registers a device in the mounted device file system. See
+/// `DevFs::register()`.
+///
+/// # Errors
+///
+/// Returns an error of kind `NotFound` if no device file system is mounted,
+/// and the errors of `DevFs::register()`.
+pub fn register<F>(path: &str, open: F) -> io::Result<()>
+    where F: Fn() -> io::Result<Box<dyn CharDevice>> + Send + Sync + 'static
+{
+    let devfs = DEVFS.lock().clone();
+    match devfs {
+        Some(devfs) => devfs.register(path, open),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "no device file system mounted"))
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+}
+
+fn exists() -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, "file exists")
+}
+
+fn not_a_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "not a directory")
+}
+
+fn is_a_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "is a directory")
+}
+
+/// A registered device.
+struct Device {
+    /// The device's path relative to the file system's root.
+    path: String,
+    open: Open,
+}
+
+/// The registered devices, sorted by path.
+type Devices = Arc<Mutex<Vec<Device>>>;
+
+/// Returns `true` if the relative path `path` is strictly inside the
+/// directory `dir`, where `""` is the root.
+fn is_inside(path: &str, dir: &str) -> bool {
+    dir.is_empty() || (path.len() > dir.len() && path.starts_with(dir) && path.as_bytes()[dir.len()] == b'/')
+}
+
+/// A file system of devices registered by drivers.
+pub struct DevFs {
+    devices: Devices,
+}
+
+impl DevFs {
+    /// Returns a file system with no devices.
+    pub fn new() -> DevFs {
+        DevFs { devices: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    /// Registers the device opened by `open` at `path`, a relative path such
+    /// as `null` or `gpio/16`. Directories leading to it are created as
+    /// needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `path` has empty, `.` or
+    /// `..` components or a device is registered at one of its ancestors,
+    /// and `AlreadyExists` if a device or directory is already at `path`.
+    pub fn register<F>(&self, path: &str, open: F) -> io::Result<()>
+        where F: Fn() -> io::Result<Box<dyn CharDevice>> + Send + Sync + 'static
+    {
+        if path.split('/').any(|c| c.is_empty() || c == "." || c == "..") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid device path"));
+        }
+
+        let mut devices = self.devices.lock();
+        for device in devices.iter() {
+            if device.path == path || is_inside(&device.path, path) {
+                return Err(exists());
+            } else if is_inside(path, &device.path) {
+                return Err(not_a_directory());
+            }
+        }
+
+        let i = devices.binary_search_by(|device| device.path.as_str().cmp(path)).unwrap_err();
+        devices.insert(i, Device { path: path.to_string(), open: Arc::new(open) });
+        Ok(())
+    }
+
+    /// Removes the device at `path`. Handles to it that are already open
+    /// stay usable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if no device is registered at
+    /// `path`.
+    pub fn unregister(&self, path: &str) -> io::Result<()> {
+        let mut devices = self.devices.lock();
+        match devices.binary_search_by(|device| device.path.as_str().cmp(path)) {
+            Ok(i) => {
+                devices.remove(i);
+                Ok(())
+            }
+            Err(_) => Err(not_found())
+        }
+    }
+
+    /// Finds the device or directory at `path`.
+    fn node(&self, path: &[&str]) -> io::Result<Node> {
+        let path = path.join("/");
+        let devices = self.devices.lock();
+        if let Some(device) = devices.iter().find(|device| device.path == path) {
+            return Ok(Node::Device { path, open: device.open.clone() });
+        }
+
+        match devices.iter().any(|device| is_inside(&device.path, &path)) {
+            true => Ok(Node::Dir { path, devices: self.devices.clone() }),
+            false => Err(not_found())
+        }
+    }
+}
+
+impl FileSystem for DevFs {
+    fn kind(&self) -> &str {
+        "devfs"
+    }
+
+    fn open(&self, path: &[&str]) -> io::Result<Box<dyn vfs::Entry>> {
+        match path.is_empty() {
+            true => Ok(Box::new(Node::Dir { path: String::new(), devices: self.devices.clone() })),
+            false => Ok(Box::new(self.node(path)?))
+        }
+    }
+
+    /// Opens the device at `path`, so that devices can be written to like
+    /// files. Devices can't be created or truncated.
+    fn create(&self, path: &[&str]) -> io::Result<Box<dyn File>> {
+        match self.node(path) {
+            Ok(node @ Node::Device { .. }) => vfs::Entry::into_file(Box::new(node)),
+            Ok(Node::Dir { .. }) => Err(is_a_directory()),
+            Err(_) => Err(vfs::read_only())
+        }
+    }
+}
+
+/// A device or a directory of devices.
+enum Node {
+    Device { path: String, open: Open },
+    Dir { path: String, devices: Devices },
+}
+
+impl Node {
+    fn path(&self) -> &str {
+        match *self {
+            Node::Device { ref path, .. } | Node::Dir { ref path, .. } => path
+        }
+    }
+}
+
+impl vfs::Entry for Node {
+    fn name(&self) -> &str {
+        let path = self.path();
+        path.rfind('/').map_or(path, |i| &path[i + 1..])
+    }
+
+    fn metadata(&self) -> Metadata {
+        match *self {
+            Node::Device { .. } => Metadata { kind: FileType::CharDevice, ..Metadata::file(0) },
+            Node::Dir { .. } => Metadata { read_only: true, ..Metadata::dir() }
+        }
+    }
+
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn File>> {
+        match *self {
+            Node::Device { ref open, .. } => Ok(Box::new(DeviceFile(open()?))),
+            Node::Dir { .. } => Err(is_a_directory())
+        }
+    }
+
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn vfs::Dir>> {
+        match *self {
+            Node::Device { .. } => Err(not_a_directory()),
+            Node::Dir { .. } => Ok(self)
+        }
+    }
+}
+
+impl vfs::Dir for Node {
+    fn entries(&self) -> io::Result<Vec<Box<dyn vfs::Entry>>> {
+        let (dir, devices) = match *self {
+            Node::Dir { ref path, ref devices } => (path, devices),
+            Node::Device { .. } => return Err(not_a_directory())
+        };
+
+        let start = if dir.is_empty() { 0 } else { dir.len() + 1 };
+        let mut entries: Vec<Box<dyn vfs::Entry>> = Vec::new();
+        let mut last_dir: Option<&str> = None;
+        let registered = devices.lock();
+        for device in registered.iter().filter(|device| is_inside(&device.path, dir)) {
+            let rest = &device.path[start..];
+            match rest.find('/') {
+                None => entries.push(Box::new(Node::Device {
+                    path: device.path.clone(),
+                    open: device.open.clone(),
+                })),
+                // Devices are sorted, so a directory's devices are adjacent.
+                Some(i) if last_dir != Some(&rest[..i]) => {
+                    last_dir = Some(&rest[..i]);
+                    entries.push(Box::new(Node::Dir {
+                        path: device.path[..start + i].to_string(),
+                        devices: devices.clone(),
+                    }));
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// An open device.
+struct DeviceFile(Box<dyn CharDevice>);
+
+impl Read for DeviceFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for DeviceFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for DeviceFile {
+    /// Streams have no position: it's always reported as `0`, and seeking
+    /// to any other offset fails.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(0) | SeekFrom::Current(0) | SeekFrom::End(0) => Ok(0),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "can't seek on a device"))
+        }
+    }
+}
+
+impl File for DeviceFile {
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn set_len(&mut self, _len: u64) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "can't resize a device"))
+    }
+
+    fn control(&mut self, request: u32, arg: u64) -> io::Result<u64> {
+        self.0.control(request, arg)
+    }
+}
@@ -0,0 +1,48 @@
+//! The kernel's framebuffer console: an `fbtext::TextConsole` drawing into
+//! the HDMI framebuffer.
+
+use std::slice;
+
+use pi::framebuffer::{self, FrameBuffer, FrameBufferConfig};
+use pi::mailbox::PixelOrder;
+
+use mutex::Mutex;
+
+pub use fbtext::{PixelBuffer, TextConsole};
+
+/// The factor glyphs are scaled by when drawn to the HDMI framebuffer.
+const FONT_SCALE: usize = 2;
+
+/// The framebuffer console, if one has been initialized.
+pub static FB_CONSOLE: Mutex<Option<TextConsole<'static>>> = Mutex::new(None);
+
+/// Allocates an HDMI framebuffer and starts a text console on it. Once this
+/// returns successfully, all console output is mirrored to the framebuffer.
+pub fn init() -> Result<(), framebuffer::Error> {
+    let fb = FrameBuffer::new(FrameBufferConfig::default())?;
+    let (width, height) = (fb.width() as usize, fb.height() as usize);
+    let stride = fb.pitch() as usize / 4;
+    let swap = fb.pixel_order() == PixelOrder::Rgb;
+
+    let bytes = fb.into_slice();
+    let pixels = unsafe {
+        slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut u32, bytes.len() / 4)
+    };
+
+    let mut buffer = PixelBuffer::new(pixels, width, height, stride);
+    buffer.set_swap_red_blue(swap);
+    *FB_CONSOLE.lock() = Some(TextConsole::new(buffer, FONT_SCALE));
+    Ok(())
+}
+
+/// Writes `bytes` to the framebuffer console, if there is one. The bytes are
+/// dropped if the console is locked, as it is when an exception handler's
+/// output interrupts another write, rather than waiting for a holder that
+/// can't run.
+pub fn mirror(bytes: &[u8]) {
+    if let Some(mut console) = FB_CONSOLE.try_lock() {
+        if let Some(ref mut console) = *console {
+            console.write_bytes(bytes);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! The FAT32 file system on the SD card.
+//!
+//! The card is read and written through a write-back sector cache, so changes
+//! only reach the card when the cache evicts them or the file system is
+//! synced.
+
+use std::io;
+use std::sync::Arc;
+
+use fat32::{self, Shared, VFat};
+use pi::emmc::{self, Sd};
+use storage::{CachedDevice, CacheStats};
+
+use mutex::Mutex;
+use vfs::VFS;
+
+/// The number of SD card sectors kept in the cache: 128KiB worth.
+pub const CACHE_SECTORS: usize = 256;
+
+/// Where the SD card's volume is mounted in the `VFS`, inside the initramfs.
+pub const MOUNT_POINT: &str = "/sd";
+
+/// The SD card's FAT32 volume, mounted through a sector cache.
+pub type SdVolume = Shared<VFat<CachedDevice<Sd>>>;
+
+/// The mounted volume, or `None` if `init()` hasn't succeeded.
+pub static SD_VOLUME: Mutex<Option<SdVolume>> = Mutex::new(None);
+
+/// Error type for `init()` failures.
+#[derive(Debug)]
+pub enum Error {
+    /// The SD card couldn't be initialized.
+    Sd(emmc::Error),
+    /// The card doesn't hold a mountable FAT32 volume.
+    Fat(fat32::Error),
+    /// The volume couldn't be attached to the `VFS`.
+    Mount(io::Error),
+}
+
+/// Initializes the SD card and mounts its FAT32 volume at `MOUNT_POINT`.
+pub fn init() -> Result<(), Error> {
+    let sd = Sd::new().map_err(Error::Sd)?;
+    let volume = VFat::from(CachedDevice::new(sd, CACHE_SECTORS)).map_err(Error::Fat)?;
+    VFS.mount(MOUNT_POINT, Arc::new(volume.clone())).map_err(Error::Mount)?;
+    *SD_VOLUME.lock() = Some(volume);
+    Ok(())
+}
+
+/// Returns the mounted volume, if any.
+pub fn sd_volume() -> Option<SdVolume> {
+    SD_VOLUME.lock().clone()
+}
+
+/// Returns the sector cache's counters and number of dirty sectors, if a
+/// volume is mounted.
+pub fn cache_stats() -> Option<(CacheStats, usize)> {
+    sd_volume().map(|volume| {
+        let vfat = volume.lock();
+        (vfat.device().stats(), vfat.device().dirty())
+    })
+}
+
+/// Resets the sector cache's counters, if a volume is mounted.
+pub fn reset_cache_stats() {
+    if let Some(volume) = sd_volume() {
+        volume.lock().device_mut().reset_stats();
+    }
+}
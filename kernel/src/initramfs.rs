@@ -0,0 +1,191 @@
+//! The initramfs: a cpio archive linked into the kernel image and served as a
+//! read-only file system.
+//!
+//! The Makefile packs the `initramfs` directory into a `newc` archive with
+//! `mkcpio` and links it into the `.initramfs` section, which `ext/layout.ld`
+//! brackets with `__initramfs_start` and `__initramfs_end`. Only the archive's
+//! files and directories are served; links and device nodes are ignored.
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::slice;
+use std::sync::Arc;
+
+use cpio::{self, Archive};
+
+use vfs::{self, Dir, File, FileSystem, FileType, Metadata, Timestamp, Usage, VFS};
+
+/// Where the initramfs is mounted in the `VFS`.
+pub const MOUNT_POINT: &str = "/";
+
+extern "C" {
+    static __initramfs_start: u8;
+    static __initramfs_end: u8;
+}
+
+/// Returns the archive linked into the kernel image.
+pub fn image() -> &'static [u8] {
+    unsafe {
+        let start = &__initramfs_start as *const u8;
+        let end = &__initramfs_end as *const u8;
+        slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
+
+/// Error type for `init()` failures.
+#[derive(Debug)]
+pub enum Error {
+    /// The linked archive is malformed.
+    Archive(cpio::Error),
+    /// The file system couldn't be attached to the `VFS`.
+    Mount(io::Error),
+}
+
+/// Parses the linked archive and mounts it at `MOUNT_POINT`.
+pub fn init() -> Result<(), Error> {
+    let archive = Archive::parse(image()).map_err(Error::Archive)?;
+    VFS.mount(MOUNT_POINT, Arc::new(Initramfs::new(archive))).map_err(Error::Mount)
+}
+
+/// Returns `true` if `entry` is served by the file system.
+fn served(entry: &cpio::Entry) -> bool {
+    entry.is_file() || entry.is_dir()
+}
+
+/// A linked archive as a read-only file system.
+pub struct Initramfs {
+    archive: Arc<Archive<'static>>,
+}
+
+impl Initramfs {
+    pub fn new(archive: Archive<'static>) -> Initramfs {
+        Initramfs { archive: Arc::new(archive) }
+    }
+
+    /// The archive being served.
+    pub fn archive(&self) -> &Archive<'static> {
+        &self.archive
+    }
+}
+
+impl FileSystem for Initramfs {
+    fn kind(&self) -> &str {
+        "initramfs"
+    }
+
+    fn open(&self, path: &[&str]) -> io::Result<Box<dyn vfs::Entry>> {
+        let entry = match path.is_empty() {
+            true => None,
+            false => match self.archive.get(&path.join("/")) {
+                Some(entry) if served(entry) => Some(*entry),
+                _ => return Err(io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+            }
+        };
+
+        Ok(Box::new(Node { archive: self.archive.clone(), entry }))
+    }
+
+    /// The space of the archive's file contents, all of it in use.
+    fn usage(&self) -> io::Result<Usage> {
+        let used = self.archive.entries().iter()
+            .filter(|entry| entry.is_file())
+            .map(|entry| entry.data().len() as u64)
+            .sum();
+        Ok(Usage { total: used, used })
+    }
+}
+
+/// A file or directory in an archive.
+struct Node {
+    archive: Arc<Archive<'static>>,
+    /// The archived entry, or `None` for the root directory.
+    entry: Option<cpio::Entry<'static>>,
+}
+
+impl Node {
+    fn is_dir(&self) -> bool {
+        self.entry.map_or(true, |entry| entry.is_dir())
+    }
+}
+
+impl vfs::Entry for Node {
+    fn name(&self) -> &str {
+        self.entry.map_or("", |entry| entry.name())
+    }
+
+    fn metadata(&self) -> Metadata {
+        let (kind, size) = match self.entry {
+            Some(entry) if entry.is_file() => (FileType::File, entry.data().len() as u64),
+            _ => (FileType::Dir, 0)
+        };
+
+        Metadata {
+            kind,
+            size,
+            read_only: true,
+            hidden: false,
+            modified: match self.entry.map_or(0, |entry| entry.mtime()) {
+                0 => None,
+                mtime => Some(Timestamp::from_unix(mtime as u64))
+            }
+        }
+    }
+
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn File>> {
+        match self.entry {
+            Some(entry) if entry.is_file() => Ok(Box::new(ArchiveFile(Cursor::new(entry.data())))),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"))
+        }
+    }
+
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn Dir>> {
+        match self.is_dir() {
+            true => Ok(self),
+            false => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+        }
+    }
+}
+
+impl Dir for Node {
+    fn entries(&self) -> io::Result<Vec<Box<dyn vfs::Entry>>> {
+        let path = self.entry.map_or("", |entry| entry.path());
+        Ok(self.archive.children(path)
+            .filter(|entry| served(entry))
+            .map(|entry| Box::new(Node { archive: self.archive.clone(), entry: Some(*entry) }) as Box<dyn vfs::Entry>)
+            .collect())
+    }
+}
+
+/// An open file in an archive.
+struct ArchiveFile(Cursor<&'static [u8]>);
+
+impl Read for ArchiveFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for ArchiveFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(vfs::read_only())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for ArchiveFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl File for ArchiveFile {
+    fn size(&self) -> u64 {
+        self.0.get_ref().len() as u64
+    }
+
+    fn set_len(&mut self, _len: u64) -> io::Result<()> {
+        Err(vfs::read_only())
+    }
+}
@@ -8,19 +8,82 @@
 #![feature(attr_literals)]
 #![feature(never_type)]
 #![feature(ptr_internals)]
+#![feature(time_source)]
 
 extern crate pi;
+extern crate lock;
+extern crate storage;
+extern crate fat32;
+extern crate cpio;
+extern crate fbtext;
+extern crate vfs;
 extern crate stack_vec;
 
 pub mod lang_items;
+pub mod allocator;
+pub mod mmu;
 pub mod mutex;
 pub mod console;
 pub mod shell;
+pub mod aarch64;
+pub mod traps;
+pub mod fbcon;
+pub mod initramfs;
+pub mod ramfs;
+pub mod devfs;
+pub mod procfs;
+pub mod fs;
+pub mod thread;
+pub mod process;
 
 use console::{kprint, kprintln, CONSOLE};
+use allocator::Allocator;
+
+#[global_allocator]
+pub static ALLOCATOR: Allocator = Allocator::uninitialized();
 
 #[no_mangle]
 pub extern "C" fn kmain() {
+    mutex::init();
+    unsafe {
+        std::time::set_source(pi::generic_timer::counter, pi::generic_timer::frequency);
+    }
+    ALLOCATOR.initialize();
+    mmu::init();
+    if let Err(e) = thread::init() {
+        kprintln!("threads unavailable: {:?}", e);
+    }
+
+    traps::init();
+    CONSOLE.lock().enable_interrupts();
+    aarch64::enable_irq();
+    thread::start_preemption(thread::DEFAULT_TIME_SLICE);
+
+    if let Err(e) = fbcon::init() {
+        kprintln!("framebuffer console unavailable: {:?}", e);
+    }
+
+    vfs::VFS.initialize();
+    if let Err(e) = initramfs::init() {
+        kprintln!("initramfs unavailable: {:?}", e);
+    }
+
+    if let Err(e) = ramfs::init() {
+        kprintln!("scratch file system unavailable: {}", e);
+    }
+
+    if let Err(e) = fs::init() {
+        kprintln!("file system unavailable: {:?}", e);
+    }
+
+    if let Err(e) = devfs::init() {
+        kprintln!("device file system unavailable: {}", e);
+    }
+
+    if let Err(e) = procfs::init() {
+        kprintln!("process file system unavailable: {}", e);
+    }
+
     kprintln!("
   ██████╗  ██████╗ ██╗  ██╗██╗   ██╗ ██████╗ ███████╗
   ╚════██╗██╔═████╗╚██╗██╔╝╚██╗ ██╔╝██╔═══██╗██╔════╝
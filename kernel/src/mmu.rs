@@ -0,0 +1,254 @@
+//! Stage-1 address translation at EL1 and EL0.
+//!
+//! The kernel runs on an identity map of the low 2GiB, with 4KiB pages and a
+//! 32-bit address space walked from `TTBR0_EL1`: the ARM's memory is normal
+//! memory, the VideoCore's memory above it, which holds the framebuffer, is
+//! non-cacheable, and the peripherals from `IO_BASE` on are device memory.
+//! None of it is accessible from EL0, and none of it is executable at EL0.
+//!
+//! Each process has an `AddressSpace`: a copy of the kernel's map in which the
+//! pages of the process's memory are readable, writable and executable at EL0
+//! and not executable at EL1. Addresses are translated to themselves in every
+//! address space, so the kernel reaches a process's memory at the addresses
+//! it was allocated at. Every thread has its own `TTBR0_EL1`, which
+//! `context_switch` in `ext/init.S` saves and restores, flushing the TLB when
+//! it changes.
+//!
+//! The data and instruction caches are on. Memory the VideoCore reads or
+//! writes in RAM, such as mailbox buffers, is cleaned and invalidated around
+//! each access, and instructions written as data, such as a process's
+//! program, are synchronized with `aarch64::sync_icache()`.
+
+use std::alloc::{self, Layout};
+
+use pi::aarch64;
+use pi::common::IO_BASE;
+
+use allocator;
+
+/// The size of a page, and of a translation table, in bytes.
+pub const PAGE_SIZE: usize = 4096;
+
+/// The size of the memory a level 2 block descriptor maps: 2MiB.
+const BLOCK_SIZE: usize = 2 * 1024 * 1024;
+
+/// The size of the memory a level 1 block descriptor maps: 1GiB.
+const GIB: usize = 1024 * 1024 * 1024;
+
+/// The number of descriptors in a translation table.
+const ENTRIES: usize = PAGE_SIZE / 8;
+
+// Descriptor fields.
+const VALID: u64 = 1 << 0;
+/// A level 1 or 2 block descriptor.
+const BLOCK: u64 = 0 << 1;
+/// A level 1 or 2 table descriptor, or a level 3 page descriptor.
+const TABLE: u64 = 1 << 1;
+const PAGE: u64 = 1 << 1;
+/// Index 0 in `MAIR`: normal memory, write-back cacheable.
+const NORMAL: u64 = 0 << 2;
+/// Index 1 in `MAIR`: device-nGnRnE memory.
+const DEVICE: u64 = 1 << 2;
+/// Index 2 in `MAIR`: normal memory, non-cacheable.
+const NORMAL_NC: u64 = 2 << 2;
+/// Read/write at EL1 and EL0. Without it, memory is read/write at EL1 only.
+const EL0_RW: u64 = 0b01 << 6;
+const INNER_SHAREABLE: u64 = 0b11 << 8;
+/// The access flag. Descriptors without it fault on their first access.
+const ACCESSED: u64 = 1 << 10;
+/// Not executable at EL1.
+const PXN: u64 = 1 << 53;
+/// Not executable at EL0.
+const UXN: u64 = 1 << 54;
+
+/// `MAIR_EL1`: the memory attributes `NORMAL`, `DEVICE` and `NORMAL_NC`
+/// index.
+const MAIR: u64 = 0xFF | 0x00 << 8 | 0x44 << 16;
+
+/// `TCR_EL1`: a 32-bit address space for `TTBR0_EL1` with 4KiB pages and
+/// write-back cacheable, inner shareable table walks, no walks from
+/// `TTBR1_EL1`, and 32-bit physical addresses.
+const TCR: u64 = 32 | 0b01 << 8 | 0b01 << 10 | 0b11 << 12 | 1 << 23 | 32 << 16;
+
+/// The MMU enable bit of `SCTLR_EL1`.
+const SCTLR_M: u64 = 1 << 0;
+
+/// The data cache enable bit of `SCTLR_EL1`.
+const SCTLR_C: u64 = 1 << 2;
+
+/// The instruction cache enable bit of `SCTLR_EL1`.
+const SCTLR_I: u64 = 1 << 12;
+
+/// The kernel's descriptor for RAM.
+const KERNEL_RAM: u64 = VALID | NORMAL | INNER_SHAREABLE | ACCESSED | UXN;
+
+/// The kernel's descriptor for the VideoCore's memory.
+const KERNEL_GPU: u64 = VALID | NORMAL_NC | INNER_SHAREABLE | ACCESSED | UXN | PXN;
+
+/// The kernel's descriptor for peripherals.
+const KERNEL_IO: u64 = VALID | DEVICE | ACCESSED | UXN | PXN;
+
+/// A process's descriptor for its own memory.
+const USER_RAM: u64 = VALID | PAGE | NORMAL | INNER_SHAREABLE | ACCESSED | EL0_RW | PXN;
+
+/// A translation table.
+#[repr(align(4096))]
+struct Table([u64; ENTRIES]);
+
+impl Table {
+    /// Allocates a table of invalid descriptors. Returns `None` if the heap
+    /// is exhausted.
+    fn new() -> Option<Box<Table>> {
+        let ptr = unsafe { alloc::alloc_zeroed(Layout::new::<Table>()) as *mut Table };
+        match ptr.is_null() {
+            true => None,
+            false => Some(unsafe { Box::from_raw(ptr) })
+        }
+    }
+
+    /// Allocates a copy of `table`. Returns `None` if the heap is exhausted.
+    fn copy(table: &Table) -> Option<Box<Table>> {
+        let mut copy = Table::new()?;
+        copy.0.copy_from_slice(&table.0);
+        Some(copy)
+    }
+
+    /// The table's address.
+    fn address(&self) -> u64 {
+        self as *const Table as u64
+    }
+
+    /// The descriptor pointing to this table from the level above.
+    fn descriptor(&self) -> u64 {
+        self.address() | VALID | TABLE
+    }
+}
+
+/// The kernel's level 1 table: its first GiB points to `KERNEL_L2` and its
+/// second is a block of peripherals.
+static mut KERNEL_L1: Table = Table([0; ENTRIES]);
+
+/// The kernel's level 2 table, mapping the first GiB in 2MiB blocks.
+static mut KERNEL_L2: Table = Table([0; ENTRIES]);
+
+/// Builds the kernel's identity map and turns the MMU and caches on.
+///
+/// Must be called once, by the boot thread, after the allocator is
+/// initialized and before any thread or process is spawned.
+///
+/// # Panics
+///
+/// Panics if the system's memory map can't be retrieved.
+pub fn init() {
+    let (_, memory_end) = allocator::memory_map().expect("failed to find memory map");
+    unsafe {
+        for (i, entry) in KERNEL_L2.0.iter_mut().enumerate() {
+            let address = i * BLOCK_SIZE;
+            let attributes = match address {
+                _ if address >= IO_BASE => KERNEL_IO,
+                _ if address >= memory_end => KERNEL_GPU,
+                _ => KERNEL_RAM
+            };
+
+            *entry = address as u64 | BLOCK | attributes;
+        }
+
+        KERNEL_L1.0[0] = KERNEL_L2.descriptor();
+        KERNEL_L1.0[1] = GIB as u64 | BLOCK | KERNEL_IO;
+
+        asm!("msr mair_el1, $0" :: "r"(MAIR) :: "volatile");
+        asm!("msr tcr_el1, $0" :: "r"(TCR) :: "volatile");
+        switch_to(kernel_ttbr0());
+
+        // Nothing was cached while the caches were off, but the instruction
+        // cache may hold lines from before the kernel was loaded.
+        aarch64::invalidate_icache();
+
+        let sctlr: u64;
+        asm!("mrs $0, sctlr_el1" : "=r"(sctlr) ::: "volatile");
+        asm!("msr sctlr_el1, $0" :: "r"(sctlr | SCTLR_M | SCTLR_C | SCTLR_I) :: "volatile");
+        asm!("isb" :::: "volatile");
+    }
+}
+
+/// The `TTBR0_EL1` of the kernel's identity map, which threads that aren't
+/// processes run on.
+pub fn kernel_ttbr0() -> u64 {
+    unsafe { KERNEL_L1.address() }
+}
+
+/// Makes `ttbr0` the running thread's translation table and discards the
+/// translations of the previous one. `context_switch` switches to it
+/// whenever the thread runs from then on.
+///
+/// # Safety
+///
+/// `ttbr0` must be `kernel_ttbr0()` or the `ttbr0()` of an `AddressSpace`
+/// that outlives its use.
+pub unsafe fn switch_to(ttbr0: u64) {
+    let daif = aarch64::mask_irq();
+    asm!("dsb ish" :::: "volatile");
+    asm!("msr ttbr0_el1, $0" :: "r"(ttbr0) :: "volatile");
+    asm!("isb" :::: "volatile");
+    asm!("tlbi vmalle1" :::: "volatile");
+    asm!("dsb ish" :::: "volatile");
+    asm!("isb" :::: "volatile");
+    aarch64::restore_irq(daif);
+}
+
+/// A process's translation tables: the kernel's map, with the process's
+/// memory mapped for EL0. The tables are freed when it's dropped, so the
+/// running thread must not be using it by then.
+pub struct AddressSpace {
+    l1: Box<Table>,
+    /// The copy of `KERNEL_L2` that `l1` points to.
+    #[allow(unused)]
+    l2: Box<Table>,
+    /// The level 3 tables of the 2MiB blocks the memory lies in.
+    #[allow(unused)]
+    l3: Vec<Box<Table>>,
+}
+
+impl AddressSpace {
+    /// Returns an address space in which the memory from `start` to `end` is
+    /// accessible from EL0. Returns `None` if the heap is exhausted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` and `end` aren't page aligned or the memory doesn't
+    /// lie in the ARM's share of RAM.
+    pub fn new(start: usize, end: usize) -> Option<AddressSpace> {
+        assert!(start % PAGE_SIZE == 0 && end % PAGE_SIZE == 0, "unaligned process memory");
+
+        let (mut l1, mut l2) = unsafe { (Table::copy(&KERNEL_L1)?, Table::copy(&KERNEL_L2)?) };
+        let mut l3 = Vec::new();
+        let mut block = start / BLOCK_SIZE * BLOCK_SIZE;
+        while block < end {
+            assert!(l2.0[block / BLOCK_SIZE] == block as u64 | BLOCK | KERNEL_RAM,
+                    "process memory outside of RAM");
+
+            // Split the block into pages with the same attributes, then give
+            // the process the ones that are its own.
+            let mut table = Table::new()?;
+            for (i, entry) in table.0.iter_mut().enumerate() {
+                let address = block + i * PAGE_SIZE;
+                *entry = address as u64 | match address >= start && address < end {
+                    true => USER_RAM,
+                    false => KERNEL_RAM | PAGE
+                };
+            }
+
+            l2.0[block / BLOCK_SIZE] = table.descriptor();
+            l3.push(table);
+            block += BLOCK_SIZE;
+        }
+
+        l1.0[0] = l2.descriptor();
+        Some(AddressSpace { l1, l2, l3 })
+    }
+
+    /// The `TTBR0_EL1` to pass to `switch_to()` to run in this address space.
+    pub fn ttbr0(&self) -> u64 {
+        self.l1.address()
+    }
+}
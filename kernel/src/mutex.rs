@@ -1,66 +1,26 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::cell::UnsafeCell;
-use std::ops::{DerefMut, Deref, Drop};
+//! The kernel's locks, which are the `lock` crate's. `init()` makes them
+//! disable preemption while they're held; see `thread::disable_preemption()`.
 
-#[repr(align(32))]
-pub struct Mutex<T> {
-    data: UnsafeCell<T>,
-    #[allow(unused)]
-    lock: AtomicBool,
-}
-
-unsafe impl<T: Send> Send for Mutex<T> { }
-unsafe impl<T: Send> Sync for Mutex<T> { }
-
-pub struct MutexGuard<'a, T: 'a> {
-    lock: &'a Mutex<T>
-}
+use lock;
+use thread;
 
-impl<'a, T> !Send for MutexGuard<'a, T> { }
-unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> { }
+pub use lock::{Mutex, MutexGuard};
 
-impl<T> Mutex<T> {
-    pub const fn new(val: T) -> Mutex<T> {
-        Mutex {
-            lock: AtomicBool::new(false),
-            data: UnsafeCell::new(val)
-        }
-    }
-}
+/// Disables preemption of the running thread while it holds a lock.
+struct ThreadPreemption;
 
-impl<T> Mutex<T> {
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
-    #[inline(never)]
-    pub fn lock(&self) -> MutexGuard<T> {
-        // Wait until we can "aquire" the lock, then "acquire" it.
-        while self.lock.load(Ordering::Relaxed) { }
-        self.lock.store(true, Ordering::Relaxed);
-
-        MutexGuard { lock: &self }
+impl lock::Preemption for ThreadPreemption {
+    fn disable(&self) {
+        thread::disable_preemption();
     }
 
-    fn unlock(&self) {
-        self.lock.store(false, Ordering::Relaxed);
+    fn enable(&self) {
+        thread::enable_preemption();
     }
 }
 
-impl<'a, T: 'a> Deref for MutexGuard<'a, T> {
-    type Target = T;
-
-    fn deref(&self) -> &T {
-        unsafe { & *self.lock.data.get() }
-    }
-}
-
-impl<'a, T: 'a> DerefMut for MutexGuard<'a, T> {
-    fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.lock.data.get() }
-    }
-}
-
-impl<'a, T: 'a> Drop for MutexGuard<'a, T> {
-    fn drop(&mut self) {
-        self.lock.unlock()
-    }
+/// Makes every lock, including the file systems', disable preemption while
+/// it's held. Must be called before any lock is taken.
+pub fn init() {
+    unsafe { lock::set_preemption(&ThreadPreemption) }
 }
@@ -0,0 +1,248 @@
+//! User processes: programs that run at EL0.
+//!
+//! A process is a kernel thread that loads a program into memory of its own
+//! and enters it at EL0 with `eret`. The program can't touch system registers
+//! or mask interrupts, and reaches the kernel only through the syscalls in
+//! `traps::syscall` or by faulting, which kills it. Its registers are saved in
+//! a trap frame on its thread's stack whenever it enters the kernel, so it's
+//! preempted like any other thread.
+//!
+//! Programs are flat, position-independent binaries: a process's memory holds
+//! the program, from its entry point at the first byte on, followed by zeroes
+//! and then its stack. The memory is whole pages, and the process runs in an
+//! `mmu::AddressSpace` in which they're the only pages accessible from EL0,
+//! so a program that touches the kernel's memory, or another process's,
+//! faults and is killed. Syscalls only accept buffers within the calling
+//! process's memory.
+
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::ptr;
+
+use pi::aarch64;
+
+use mmu::{self, AddressSpace};
+use mutex::Mutex;
+use thread::{self, WaitQueue};
+use traps::{self, TrapFrame};
+
+/// A process's ID: the ID of the thread it runs on.
+pub type Pid = thread::Id;
+
+/// The size of a process's stack in bytes.
+pub const STACK_SIZE: usize = 64 * 1024;
+
+/// The size of the kernel stack of a process's thread, which its syscalls and
+/// exceptions run on.
+const KERNEL_STACK_SIZE: usize = 32 * 1024;
+
+/// The alignment of a process's memory, and the unit its size is rounded up
+/// to: a page, so that no other allocation shares the pages it can access.
+const ALIGN: usize = mmu::PAGE_SIZE;
+
+/// `SPSR_EL1` for entering a program: EL0 with its own stack and every
+/// interrupt unmasked.
+const SPSR_EL0: u64 = 0;
+
+/// The processes that are running or have exited but haven't been waited
+/// for, or `None` before the first process is spawned.
+static PROCESSES: Mutex<Option<Vec<Process>>> = Mutex::new(None);
+
+/// Threads waiting in `wait()` for a process to exit.
+static EXITED: WaitQueue = WaitQueue::new();
+
+/// How a process ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// It called `exit` with this code.
+    Exited(i32),
+    /// It was killed for causing an exception.
+    Killed,
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExitStatus::Exited(code) => write!(f, "exited with status {}", code),
+            ExitStatus::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+/// Error type for `spawn()` failures.
+#[derive(Debug)]
+pub enum Error {
+    /// The program is empty.
+    Empty,
+    /// The process's memory couldn't be allocated.
+    OutOfMemory,
+    /// The process's thread couldn't be spawned.
+    Thread(thread::Error),
+}
+
+/// A process's memory and the address space it's mapped for EL0 in, freed
+/// when it's dropped.
+struct Memory {
+    ptr: *mut u8,
+    layout: Layout,
+    space: AddressSpace,
+}
+
+// The memory is only ever used by the process that owns it.
+unsafe impl Send for Memory {}
+
+impl Memory {
+    /// Allocates memory holding `program` followed by a `STACK_SIZE` byte
+    /// stack, zeroing the rest, and maps it for EL0 in a new address space.
+    /// Returns `None` if the heap is exhausted.
+    fn load(program: &[u8]) -> Option<Memory> {
+        let image = (program.len() + ALIGN - 1) / ALIGN * ALIGN;
+        let size = image.checked_add(STACK_SIZE)?;
+        let layout = Layout::from_size_align(size, ALIGN).ok()?;
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            return None;
+        }
+
+        let space = match AddressSpace::new(ptr as usize, ptr as usize + size) {
+            Some(space) => space,
+            None => {
+                unsafe { alloc::dealloc(ptr, layout) }
+                return None;
+            }
+        };
+
+        unsafe { ptr::copy_nonoverlapping(program.as_ptr(), ptr, program.len()) }
+        aarch64::sync_icache(ptr as usize, program.len());
+        Some(Memory { ptr, layout, space })
+    }
+
+    /// The address of the first byte of memory: the program's entry point.
+    fn start(&self) -> usize {
+        self.ptr as usize
+    }
+
+    /// The address just past the last byte of memory, where the stack
+    /// pointer starts.
+    fn end(&self) -> usize {
+        self.ptr as usize + self.layout.size()
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, self.layout) }
+    }
+}
+
+/// An entry in the process table.
+struct Process {
+    pid: Pid,
+    /// The process's memory, until it exits.
+    memory: Option<Memory>,
+    /// How the process ended, once it has.
+    status: Option<ExitStatus>,
+}
+
+/// Starts a process named `name` running `program`, and returns its ID.
+///
+/// # Errors
+///
+/// Returns `Error::Empty` if `program` is empty, `Error::OutOfMemory` if the
+/// process's memory can't be allocated and `Error::Thread` if its thread
+/// can't be spawned.
+pub fn spawn(name: &str, program: &[u8]) -> Result<Pid, Error> {
+    if program.is_empty() {
+        return Err(Error::Empty);
+    }
+
+    let memory = Memory::load(program).ok_or(Error::OutOfMemory)?;
+    let (entry, stack_top) = (memory.start() as u64, memory.end() as u64);
+    let ttbr0 = memory.space.ttbr0();
+
+    // The thread can't run, and look itself up, until it's in the table.
+    let mut processes = PROCESSES.lock();
+    let pid = thread::Builder::new()
+        .name(name)
+        .stack_size(KERNEL_STACK_SIZE)
+        .spawn(move || {
+            let mut tf = TrapFrame::default();
+            tf.elr = entry;
+            tf.spsr = SPSR_EL0;
+            tf.sp = stack_top;
+            unsafe {
+                mmu::switch_to(ttbr0);
+                traps::enter_user(&tf)
+            }
+        })
+        .map_err(Error::Thread)?;
+
+    let process = Process { pid, memory: Some(memory), status: None };
+    processes.get_or_insert_with(Vec::new).push(process);
+    Ok(pid)
+}
+
+/// Returns the bounds of the memory of the process `pid`, as the address of
+/// its first byte and the address just past its last, if it's running.
+pub fn memory(pid: Pid) -> Option<(usize, usize)> {
+    PROCESSES.lock().iter()
+        .flat_map(|processes| processes.iter())
+        .find(|process| process.pid == pid)
+        .and_then(|process| process.memory.as_ref())
+        .map(|memory| (memory.start(), memory.end()))
+}
+
+/// Ends the calling process with `status`, switching back to the kernel's
+/// translation table and freeing its memory, and wakes the threads waiting
+/// for it in `wait()`.
+///
+/// # Panics
+///
+/// Panics if the calling thread isn't a process.
+pub fn exit(status: ExitStatus) -> ! {
+    let pid = thread::current();
+    let memory = {
+        let mut processes = PROCESSES.lock();
+        let process = processes.iter_mut()
+            .flat_map(|processes| processes.iter_mut())
+            .find(|process| process.pid == pid)
+            .expect("process::exit(): not a process");
+
+        process.status = Some(status);
+        process.memory.take()
+    };
+
+    // The tables are freed with the memory, so stop using them first.
+    unsafe { mmu::switch_to(mmu::kernel_ttbr0()) }
+    drop(memory);
+    EXITED.wake_all();
+    thread::exit()
+}
+
+/// Blocks until the process `pid` exits, removes it from the process table
+/// and returns how it ended. Returns `None` if there's no such process or
+/// another thread waited for it first.
+pub fn wait(pid: Pid) -> Option<ExitStatus> {
+    let mut status = None;
+    EXITED.wait_until(|| {
+        let mut guard = PROCESSES.lock();
+        let processes = match guard.as_mut() {
+            Some(processes) => processes,
+            None => return true
+        };
+
+        match processes.iter().position(|process| process.pid == pid) {
+            Some(i) => match processes[i].status {
+                Some(exited) => {
+                    processes.remove(i);
+                    status = Some(exited);
+                    true
+                }
+                None => false
+            },
+            None => true
+        }
+    });
+
+    status
+}
@@ -0,0 +1,302 @@
+//! The process file system: a read-only view of the kernel's state.
+//!
+//! Every file's contents are generated when it's opened, into a buffer the
+//! open file then reads from. Generating a file only takes the locks it needs
+//! for as long as it takes to copy the state out, so reads never hold a lock
+//! and a file's contents are a consistent snapshot however it's read. Files
+//! report a size of `0` until they're opened.
+
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+use pi::common::CORE_COUNT;
+use pi::interrupt::Interrupt;
+use pi::mailbox::{self, GetBoardRevision, GetBoardSerial, GetFirmwareRevision};
+use pi::timer;
+
+use aarch64;
+use thread;
+use traps;
+use vfs::{self, File, FileSystem, Metadata, Timestamp, VFS};
+use ALLOCATOR;
+
+/// Where the process file system is mounted in the `VFS`.
+pub const MOUNT_POINT: &str = "/proc";
+
+/// Mounts the process file system at `MOUNT_POINT`.
+pub fn init() -> io::Result<()> {
+    VFS.mount(MOUNT_POINT, Arc::new(ProcFs))
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+}
+
+/// Writes a file's contents.
+type Generate = fn(&mut String) -> fmt::Result;
+
+/// Writes the contents of a file describing a process.
+type GenerateProcess = fn(&Process, &mut String) -> fmt::Result;
+
+/// The files in the root directory.
+const FILES: &[(&str, Generate)] = &[
+    ("cpuinfo", cpuinfo),
+    ("interrupts", interrupts),
+    ("meminfo", meminfo),
+    ("uptime", uptime),
+    ("version", version),
+];
+
+/// The files in each process's directory.
+const PROCESS_FILES: &[(&str, GenerateProcess)] = &[
+    ("status", status),
+];
+
+/// A process listed in the file system.
+struct Process {
+    pid: u64,
+    name: String,
+    state: &'static str,
+}
+
+/// Returns every process: the kernel's threads, by ID.
+fn processes() -> Vec<Process> {
+    thread::threads().into_iter().map(|info| {
+        let state = match info.state {
+            thread::State::Running => "R (running)",
+            thread::State::Ready => "R (ready)",
+            thread::State::Waiting => "S (sleeping)",
+            thread::State::Dead => "X (dead)",
+        };
+
+        Process { pid: info.id, name: info.name, state }
+    }).collect()
+}
+
+/// Returns the process `pid`, if it exists.
+fn process(pid: u64) -> Option<Process> {
+    processes().into_iter().find(|process| process.pid == pid)
+}
+
+/// `cpuinfo`: the booted core's identification, the number of cores and the
+/// board's revision and serial number.
+fn cpuinfo(out: &mut String) -> fmt::Result {
+    let midr = aarch64::midr();
+    writeln!(out, "processor\t: 0")?;
+    writeln!(out, "MIDR\t\t: {:#010x}", midr)?;
+    writeln!(out, "implementer\t: {:#04x}", midr >> 24 & 0xFF)?;
+    writeln!(out, "variant\t\t: {:#x}", midr >> 20 & 0xF)?;
+    writeln!(out, "part\t\t: {:#05x}", midr >> 4 & 0xFFF)?;
+    writeln!(out, "revision\t: {}", midr & 0xF)?;
+    writeln!(out)?;
+
+    writeln!(out, "cores\t\t: {} (1 online)", CORE_COUNT)?;
+    match mailbox::property(&GetBoardRevision) {
+        Ok(revision) => writeln!(out, "board revision\t: {:x}", revision)?,
+        Err(_) => writeln!(out, "board revision\t: unknown")?
+    }
+
+    match mailbox::property(&GetBoardSerial) {
+        Ok(serial) => writeln!(out, "serial\t\t: {:016x}", serial)?,
+        Err(_) => writeln!(out, "serial\t\t: unknown")?
+    }
+
+    match mailbox::property(&GetFirmwareRevision) {
+        Ok(revision) => writeln!(out, "firmware\t: {}", revision),
+        Err(_) => writeln!(out, "firmware\t: unknown")
+    }
+}
+
+/// `interrupts`: the number of IRQs handled from each source since boot.
+fn interrupts(out: &mut String) -> fmt::Result {
+    writeln!(out, "{:>10}  LocalTimer", traps::local_timer_count())?;
+    for interrupt in Interrupt::iter() {
+        writeln!(out, "{:>10}  {:?}", traps::irq_count(interrupt), interrupt)?;
+    }
+
+    Ok(())
+}
+
+/// `meminfo`: the heap's usage in KiB and its number of allocations.
+fn meminfo(out: &mut String) -> fmt::Result {
+    let stats = ALLOCATOR.stats().unwrap_or_default();
+    writeln!(out, "HeapTotal:     {:>10} kB", stats.total / 1024)?;
+    writeln!(out, "HeapFree:      {:>10} kB", stats.free() / 1024)?;
+    writeln!(out, "HeapAllocated: {:>10} kB", stats.allocated / 1024)?;
+    writeln!(out, "HeapUntouched: {:>10} kB", stats.untouched / 1024)?;
+    writeln!(out, "Allocations:   {:>10}", stats.allocations)
+}
+
+/// `uptime`: the seconds since boot, to a hundredth of a second.
+fn uptime(out: &mut String) -> fmt::Result {
+    let us = timer::current_time();
+    writeln!(out, "{}.{:02}", us / 1_000_000, us % 1_000_000 / 10_000)
+}
+
+/// `version`: the kernel's version, the commit it was built from and when it
+/// was built.
+fn version(out: &mut String) -> fmt::Result {
+    let built = env!("KERNEL_BUILD_TIME").parse().map(Timestamp::from_unix);
+    write!(out, "{} {} (git {})", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("KERNEL_GIT_HASH"))?;
+    match built {
+        Ok(built) => writeln!(out, " built {} UTC", built),
+        Err(_) => writeln!(out)
+    }
+}
+
+/// `<pid>/status`: the process's name, ID and state.
+fn status(process: &Process, out: &mut String) -> fmt::Result {
+    writeln!(out, "Name:\t{}", process.name)?;
+    writeln!(out, "Pid:\t{}", process.pid)?;
+    writeln!(out, "State:\t{}", process.state)
+}
+
+/// The kernel's state as a file system.
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn kind(&self) -> &str {
+        "procfs"
+    }
+
+    fn open(&self, path: &[&str]) -> io::Result<Box<dyn vfs::Entry>> {
+        let node = match *path {
+            [] => Node::Root,
+            [name] => match FILES.iter().find(|&&(file, _)| file == name) {
+                Some(&(file, generate)) => Node::File(file, generate),
+                None => Node::Process(pid(name)?, name.to_string())
+            },
+            [dir, name] => {
+                let pid = pid(dir)?;
+                match PROCESS_FILES.iter().find(|&&(file, _)| file == name) {
+                    Some(&(file, generate)) => Node::ProcessFile(pid, file, generate),
+                    None => return Err(not_found())
+                }
+            }
+            _ => return Err(not_found())
+        };
+
+        Ok(Box::new(node))
+    }
+}
+
+/// Parses the name of an existing process's directory.
+fn pid(name: &str) -> io::Result<u64> {
+    match name.parse::<u64>() {
+        Ok(pid) if pid.to_string() == name && process(pid).is_some() => Ok(pid),
+        _ => Err(not_found())
+    }
+}
+
+/// A file or directory in the file system.
+enum Node {
+    Root,
+    File(&'static str, Generate),
+    /// A process's directory, named after its ID.
+    Process(u64, String),
+    ProcessFile(u64, &'static str, GenerateProcess),
+}
+
+impl vfs::Entry for Node {
+    fn name(&self) -> &str {
+        match *self {
+            Node::Root => "",
+            Node::File(name, _) | Node::ProcessFile(_, name, _) => name,
+            Node::Process(_, ref name) => name
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        match *self {
+            Node::Root | Node::Process(..) => Metadata { read_only: true, ..Metadata::dir() },
+            Node::File(..) | Node::ProcessFile(..) => Metadata { read_only: true, ..Metadata::file(0) }
+        }
+    }
+
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn File>> {
+        let mut contents = String::new();
+        let generated = match *self {
+            Node::File(_, generate) => generate(&mut contents),
+            Node::ProcessFile(pid, _, generate) => match process(pid) {
+                Some(process) => generate(&process, &mut contents),
+                None => return Err(not_found())
+            },
+            Node::Root | Node::Process(..) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"));
+            }
+        };
+
+        match generated {
+            Ok(()) => Ok(Box::new(ProcFile(Cursor::new(contents.into_bytes())))),
+            Err(_) => Err(io::Error::new(io::ErrorKind::Other, "failed to generate file"))
+        }
+    }
+
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn vfs::Dir>> {
+        match *self {
+            Node::Root | Node::Process(..) => Ok(self),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+        }
+    }
+}
+
+impl vfs::Dir for Node {
+    fn entries(&self) -> io::Result<Vec<Box<dyn vfs::Entry>>> {
+        let mut entries: Vec<Box<dyn vfs::Entry>> = Vec::new();
+        match *self {
+            Node::Root => {
+                for &(name, generate) in FILES {
+                    entries.push(Box::new(Node::File(name, generate)));
+                }
+
+                for process in processes() {
+                    entries.push(Box::new(Node::Process(process.pid, process.pid.to_string())));
+                }
+            }
+            Node::Process(pid, _) => {
+                for &(name, generate) in PROCESS_FILES {
+                    entries.push(Box::new(Node::ProcessFile(pid, name, generate)));
+                }
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+        }
+
+        Ok(entries)
+    }
+}
+
+/// An open file: a snapshot of its contents.
+struct ProcFile(Cursor<Vec<u8>>);
+
+impl Read for ProcFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for ProcFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(vfs::read_only())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for ProcFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl File for ProcFile {
+    fn size(&self) -> u64 {
+        self.0.get_ref().len() as u64
+    }
+
+    fn set_len(&mut self, _len: u64) -> io::Result<()> {
+        Err(vfs::read_only())
+    }
+}
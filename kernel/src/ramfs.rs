@@ -0,0 +1,18 @@
+//! The scratch file system: a `vfs::ramfs::RamFs` mounted at `/tmp`.
+
+use std::io;
+use std::sync::Arc;
+
+use vfs::VFS;
+use vfs::ramfs::RamFs;
+
+/// Where the scratch file system is mounted in the `VFS`.
+pub const MOUNT_POINT: &str = "/tmp";
+
+/// The capacity of the scratch file system: 16MiB.
+pub const CAPACITY: u64 = 16 * 1024 * 1024;
+
+/// Mounts an empty file system of `CAPACITY` bytes at `MOUNT_POINT`.
+pub fn init() -> io::Result<()> {
+    VFS.mount(MOUNT_POINT, Arc::new(RamFs::new(CAPACITY)))
+}
@@ -0,0 +1,379 @@
+use std::fmt;
+use std::time::Duration;
+
+use pi::i2c::{self, I2c};
+use pi::pwm::Pcm;
+
+use console::{self, kprint, kprintln, CONSOLE};
+use fs;
+use process;
+use thread;
+use vfs::VFS;
+use vfs::files::{self, Session};
+use stack_vec::StackVec;
+
+const MAX_CMDLEN : usize = 512;
+const MAX_ARGLEN : usize = 64;
+
+/// The sample rate `tone` plays at, in Hz.
+const TONE_SAMPLE_RATE: u32 = 22_050;
+
+/// Error type for `Command` parse failures.
+#[derive(Debug)]
+enum Error {
+    Empty,
+    TooManyArgs
+}
+
+#[derive(Debug)]
+enum HandleError {
+    NoSuchCommand
+}
+
+/// A structure representing a single shell command.
+struct Command<'a> {
+    args: StackVec<'a, &'a str>
+}
+
+impl<'a> Command<'a> {
+    /// Parse a command from a string `s` using `buf` as storage for the
+    /// arguments.
+    ///
+    /// # Errors
+    ///
+    /// If `s` contains no arguments, returns `Error::Empty`. If there are more
+    /// arguments than `buf` can hold, returns `Error::TooManyArgs`.
+    fn parse(s: &'a str, buf: &'a mut [&'a str]) -> Result<Command<'a>, Error> {
+        let mut args = StackVec::new(buf);
+        for arg in s.split(' ').filter(|a| !a.is_empty()) {
+            args.push(arg).map_err(|_| Error::TooManyArgs)?;
+        }
+
+        if args.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        Ok(Command { args })
+    }
+
+    /// Returns this command's path. This is equivalent to the first argument.
+    fn path(&self) -> &str {
+        assert!(!self.args.is_empty());
+        self.args[0]
+    }
+
+    /// Executes this command.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HandleError::NoSuchCommand` if the command's path isn't a known
+    /// builtin or file command.
+    fn handle(&self, session: &mut Session) -> Result<(), HandleError> {
+        match self.path() {
+            "echo" => self.echo(session),
+            "sleep" => self.sleep(),
+            "i2cdetect" => self.i2cdetect(),
+            "tone" => self.tone(),
+            "cachestat" => self.cachestat(),
+            "sync" => self.sync(),
+            "threads" => self.threads(),
+            "ps" => self.ps(),
+            "run" => self.run(session),
+            _ => match session.run(self.args.as_slice(), &mut Stdout) {
+                None => return Err(HandleError::NoSuchCommand),
+                Some(Ok(())) => {},
+                Some(Err(e @ files::Error::Usage(_))) => kprintln!("{}", e),
+                Some(Err(e)) => kprintln!("{}: {}", self.path(), e)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `echo [args...] [> path]`: prints the arguments separated by spaces,
+    /// or writes them as a line to the file at `path`.
+    fn echo(&self, session: &Session) {
+        let args = &self.args[1..];
+        let (words, path) = match args.len() {
+            n if n >= 2 && args[n - 2] == ">" => (&args[..n - 2], Some(args[n - 1])),
+            _ => (args, None)
+        };
+
+        let mut line = words.join(" ");
+        line.push('\n');
+        match path {
+            None => kprint!("{}", line),
+            Some(path) => if let Err(e) = session.write_file(path, line.as_bytes()) {
+                kprintln!("echo: {}", e);
+            }
+        }
+    }
+
+    /// `run <path>`: runs the program at `path` as a process and waits for it
+    /// to exit.
+    fn run(&self, session: &Session) {
+        if self.args.len() != 2 {
+            kprintln!("usage: run <path>");
+            return;
+        }
+
+        let path = self.args[1];
+        let program = match session.read_file(path) {
+            Ok(program) => program,
+            Err(e) => {
+                kprintln!("run: {}", e);
+                return;
+            }
+        };
+
+        let name = path.rsplit('/').next().unwrap_or(path);
+        match process::spawn(name, &program) {
+            Ok(pid) => match process::wait(pid) {
+                Some(process::ExitStatus::Exited(0)) | None => {}
+                Some(status) => kprintln!("run: {}: {}", path, status)
+            },
+            Err(e) => kprintln!("run: {}: failed to start: {:?}", path, e)
+        }
+    }
+
+    /// `sleep <seconds>`: sleeps for a possibly fractional number of seconds.
+    fn sleep(&self) {
+        match self.args.get(1).and_then(|arg| parse_seconds(arg)) {
+            Some(duration) if self.args.len() == 2 => thread::sleep(duration),
+            _ => kprintln!("usage: sleep <seconds>")
+        }
+    }
+
+    /// `tone <freq> <ms>`: plays a square wave of `freq` Hz for `ms`
+    /// milliseconds through the audio jack.
+    fn tone(&self) {
+        let parse = |i: usize| self.args.get(i).and_then(|arg| arg.parse::<u32>().ok());
+        let (freq, ms) = match (parse(1), parse(2)) {
+            (Some(freq), Some(ms)) if self.args.len() == 3 && freq > 0 => (freq, ms),
+            _ => {
+                kprintln!("usage: tone <freq> <ms>");
+                return;
+            }
+        };
+
+        let mut pcm = match Pcm::new(TONE_SAMPLE_RATE) {
+            Ok(pcm) => pcm,
+            Err(e) => {
+                kprintln!("tone: failed to start audio: {:?}", e);
+                return;
+            }
+        };
+
+        let rate = pcm.sample_rate() as u64;
+        let samples = rate * ms as u64 / 1000;
+        for i in 0..samples {
+            // Each period is two half periods, high then low.
+            let high = (i * 2 * freq as u64 / rate) % 2 == 0;
+            if let Err(e) = pcm.write_sample(if high { 0xA000 } else { 0x6000 }) {
+                kprintln!("tone: playback failed: {:?}", e);
+                return;
+            }
+        }
+    }
+
+    /// `cachestat [-r]`: prints the SD card sector cache's counters, then
+    /// resets them if `-r` is given.
+    fn cachestat(&self) {
+        let reset = match &self.args[1..] {
+            [] => false,
+            ["-r"] => true,
+            _ => {
+                kprintln!("usage: cachestat [-r]");
+                return;
+            }
+        };
+
+        let (stats, dirty) = match fs::cache_stats() {
+            Some(stats) => stats,
+            None => {
+                kprintln!("cachestat: no file system mounted");
+                return;
+            }
+        };
+
+        let accesses = stats.hits + stats.misses;
+        let hit_rate = match accesses {
+            0 => 0,
+            _ => stats.hits * 100 / accesses
+        };
+
+        kprintln!("capacity:   {} sectors", fs::CACHE_SECTORS);
+        kprintln!("hits:       {}", stats.hits);
+        kprintln!("misses:     {}", stats.misses);
+        kprintln!("hit rate:   {}%", hit_rate);
+        kprintln!("evictions:  {}", stats.evictions);
+        kprintln!("writebacks: {}", stats.writebacks);
+        kprintln!("dirty:      {} sectors", dirty);
+
+        if reset {
+            fs::reset_cache_stats();
+        }
+    }
+
+    /// `sync`: writes buffered changes of every mounted file system through
+    /// to storage.
+    fn sync(&self) {
+        if self.args.len() != 1 {
+            kprintln!("usage: sync");
+            return;
+        }
+
+        if let Err(e) = VFS.sync() {
+            kprintln!("sync: {}", e);
+        }
+    }
+
+    /// `threads`: lists the kernel's threads.
+    fn threads(&self) {
+        if self.args.len() != 1 {
+            kprintln!("usage: threads");
+            return;
+        }
+
+        kprintln!("{:>4}  {:<8}  {:>8}  {:>8}  NAME", "ID", "STATE", "STACK", "SWITCHES");
+        for info in thread::threads() {
+            let stack = match info.stack_size {
+                Some(size) => size.to_string(),
+                None => "-".to_string()
+            };
+
+            kprintln!("{:>4}  {:<8}  {:>8}  {:>8}  {}", info.id, info.state, stack, info.switches, info.name);
+        }
+    }
+
+    /// `ps`: lists the kernel's threads with how long each has run for and
+    /// how often it's been switched to and preempted.
+    fn ps(&self) {
+        if self.args.len() != 1 {
+            kprintln!("usage: ps");
+            return;
+        }
+
+        kprintln!("{:>4}  {:<8}  {:>10}  {:>8}  {:>8}  NAME", "PID", "STATE", "TIME", "SWITCHES", "PREEMPTS");
+        for info in thread::threads() {
+            kprintln!("{:>4}  {:<8}  {:>6}.{:03}  {:>8}  {:>8}  {}",
+                      info.id, info.state, info.cpu_time.as_secs(), info.cpu_time.subsec_millis(),
+                      info.switches, info.preemptions, info.name);
+        }
+    }
+
+    /// `i2cdetect`: probes every 7-bit I2C address outside the reserved ranges
+    /// and prints a grid of the addresses that acknowledged.
+    fn i2cdetect(&self) {
+        if self.args.len() != 1 {
+            kprintln!("usage: i2cdetect");
+            return;
+        }
+
+        let mut i2c = I2c::new();
+        kprintln!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+        for row in (0..=i2c::MAX_ADDRESS).step_by(16) {
+            kprint!("{:02x}:", row);
+            for addr in row..row + 16 {
+                if addr < 0x03 || addr > 0x77 {
+                    kprint!("   ");
+                    continue;
+                }
+
+                match i2c.probe(addr) {
+                    Ok(true) => kprint!(" {:02x}", addr),
+                    Ok(false) => kprint!(" --"),
+                    Err(e) => {
+                        kprintln!();
+                        kprintln!("i2cdetect: error probing 0x{:02x}: {:?}", addr, e);
+                        return;
+                    }
+                }
+            }
+            kprintln!();
+        }
+    }
+}
+
+/// Writes formatted output to the console.
+struct Stdout;
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        kprint!("{}", s);
+        Ok(())
+    }
+}
+
+/// Parses a number of seconds with an optional fraction of up to nine digits,
+/// such as `2` or `0.25`.
+fn parse_seconds(s: &str) -> Option<Duration> {
+    let mut parts = s.splitn(2, '.');
+    let secs = match parts.next() {
+        Some("") => 0,
+        Some(secs) => secs.parse::<u64>().ok()?,
+        None => return None
+    };
+
+    let nanos = match parts.next() {
+        None => 0,
+        Some(fraction) if fraction.len() <= 9 => {
+            let digits = fraction.parse::<u32>().ok()?;
+            digits * 10u32.pow(9 - fraction.len() as u32)
+        }
+        Some(_) => return None
+    };
+
+    Some(Duration::new(secs, nanos))
+}
+
+/// Starts a shell using `prefix`, after the working directory, as the prefix
+/// for each line. This function never returns: it is perpetually in a shell
+/// loop.
+pub fn shell(prefix: &str) -> ! {
+    let mut input_buf = [0; MAX_CMDLEN];
+    let mut input_vec = StackVec::new(&mut input_buf);
+    let mut session = Session::new(&VFS);
+
+    loop {
+        kprint!("{}{}", session.cwd(), prefix);
+
+        loop {
+            let input = console::read_byte();
+            let mut console = CONSOLE.lock();
+
+            if input == b'\n' || input == b'\r' { // newline
+                console.write_bytes(b"\r\n");
+                break
+            } else if input == b'\x7f' { // delete / backspace
+                if let Some(_) = input_vec.pop() {
+                    console.write_bytes(b"\x08 \x08");
+                }
+            } else if input < 32 { // unprintable uninterpreted
+                console.write_byte(b'\x07');
+            } else { // regular character
+                if let Ok(_) = input_vec.push(input) {
+                    console.write_byte(input);
+                }
+            }
+        }
+
+        {
+            let input_str = std::str::from_utf8(input_vec.as_slice())
+                                    .expect("failed to decode utf8");
+            let mut input_args = [input_str; MAX_ARGLEN];
+
+            match Command::parse(&input_str, &mut input_args[..]) {
+                Err(Error::Empty) => {},
+                Err(Error::TooManyArgs) => kprintln!("too many arguments"),
+                Ok(command) => match command.handle(&mut session) {
+                    Ok(_) => { },
+                    Err(HandleError::NoSuchCommand) =>
+                        kprintln!("unknown command: {}", command.path())
+                }
+            }
+        }
+
+        input_vec.truncate(0);
+    }
+}
@@ -0,0 +1,90 @@
+use std::alloc::{self, Layout};
+
+use mmu;
+
+/// The registers `context_switch` in `ext/init.S` saves when a thread stops
+/// running and restores when it runs again: the registers a function call
+/// must preserve, the stack pointer, and the translation table base.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+pub struct Context {
+    /// `x19` through `x30`. `x30`, the link register, holds the address the
+    /// thread resumes at.
+    pub xn: [u64; 12],
+    pub sp: u64,
+    /// The low halves of `q8` through `q15`.
+    pub dn: [u64; 8],
+    /// `TTBR0_EL1`: the kernel's, or a process's once it switches to its own.
+    pub ttbr0: u64,
+}
+
+extern "C" {
+    /// Saves the calling thread's registers to `from` and resumes the thread
+    /// whose registers are saved in `to`. Returns when the calling thread is
+    /// switched back to.
+    pub fn context_switch(from: *mut Context, to: *const Context);
+
+    /// The entry point of new threads.
+    fn thread_start();
+}
+
+impl Context {
+    /// Returns the context of a new thread that calls `entry(arg)` with its
+    /// stack pointer at `stack_top`, on the kernel's translation table.
+    pub fn new(entry: extern "C" fn(usize) -> !, arg: usize, stack_top: usize) -> Context {
+        let mut context = Context::default();
+        context.xn[0] = arg as u64;
+        context.xn[1] = entry as usize as u64;
+        context.xn[11] = thread_start as usize as u64;
+        context.sp = stack_top as u64;
+        context.ttbr0 = mmu::kernel_ttbr0();
+        context
+    }
+}
+
+/// A thread's stack, freed when it's dropped.
+#[derive(Debug)]
+pub struct Stack {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+/// The alignment of stacks, and of the stack pointer, the AAPCS64 requires.
+const STACK_ALIGN: usize = 16;
+
+// A stack is only ever used by the thread that owns it.
+unsafe impl Send for Stack {}
+
+impl Stack {
+    /// Allocates a `size` byte stack. Returns `None` if the heap is
+    /// exhausted or `size` isn't a non-zero multiple of 16.
+    pub fn new(size: usize) -> Option<Stack> {
+        if size == 0 || size % STACK_ALIGN != 0 {
+            return None;
+        }
+
+        let layout = Layout::from_size_align(size, STACK_ALIGN).ok()?;
+        let ptr = unsafe { alloc::alloc(layout) };
+        match ptr.is_null() {
+            true => None,
+            false => Some(Stack { ptr, layout })
+        }
+    }
+
+    /// The size of the stack in bytes.
+    pub fn size(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// The address just past the end of the stack, where the stack pointer
+    /// of a new thread starts.
+    pub fn top(&self) -> usize {
+        self.ptr as usize + self.layout.size()
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, self.layout) }
+    }
+}
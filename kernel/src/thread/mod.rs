@@ -0,0 +1,388 @@
+//! Kernel threads, scheduled preemptively.
+//!
+//! Threads run in round-robin order. A thread runs until it calls
+//! `yield_now()` or exits, or until its time slice ends: once
+//! `start_preemption()` is called, the core's generic timer interrupts the
+//! running thread at the end of every time slice and `preempt()` switches to the next ready
+//! thread. When no thread is ready, the idle thread, `0`, waits for an
+//! interrupt in `wfi`.
+//!
+//! The boot thread that runs `kmain` becomes thread `1` when `init()` is
+//! called; spawned threads run on stacks allocated from the heap. Threads are
+//! switched between by `context_switch` in `ext/init.S`, which only saves the
+//! registers a function call must preserve and the thread's translation
+//! table base, `TTBR0_EL1`; see `mmu`. A preempted thread is switched away
+//! from inside its IRQ handler, so the trap frame saved on its stack holds
+//! the rest, and it's restored when the handler returns.
+//!
+//! A thread blocks by waiting on a `WaitQueue` until a condition holds, or by
+//! sleeping with `sleep()`. Blocked threads aren't scheduled until they're
+//! woken, by another thread or an IRQ handler, or their timeout passes.
+//! Timeouts are checked whenever the scheduler switches threads, so they may
+//! pass up to a time slice late.
+//!
+//! IRQs are masked while the scheduler's lock is held and while switching,
+//! and the lock is never held across a context switch, so a thread that's
+//! switched to, or an IRQ handler, can always enter the scheduler. A thread
+//! isn't preempted while it holds a lock; see `disable_preemption()`. The
+//! generic timer is the scheduler's, so `pi::timer::sleep()` isn't used.
+
+mod context;
+mod scheduler;
+mod wait;
+
+use std::cmp;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use pi::aarch64;
+use pi::generic_timer::GenericTimer;
+use pi::timer;
+
+use mutex::Mutex;
+use self::context::{context_switch, Context, Stack};
+use self::scheduler::{Scheduler, Thread};
+
+pub use self::wait::WaitQueue;
+
+/// A thread's identifier. IDs aren't reused.
+pub type Id = u64;
+
+/// The idle thread's ID.
+pub const IDLE_ID: Id = 0;
+
+/// The stack size of threads spawned by a `Builder` that doesn't set one.
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// The size of the smallest stack a thread can be spawned with.
+pub const MIN_STACK_SIZE: usize = 4 * 1024;
+
+/// The size of the idle thread's stack, which IRQ handlers also run on.
+const IDLE_STACK_SIZE: usize = 16 * 1024;
+
+/// The time slice `kmain` starts preemption with.
+pub const DEFAULT_TIME_SLICE: Duration = Duration::from_millis(10);
+
+/// The scheduler, or `None` before `init()` is called.
+static SCHEDULER: Mutex<Option<Scheduler>> = Mutex::new(None);
+
+/// The length of a time slice in microseconds, or `0` if threads aren't
+/// preempted.
+static TIME_SLICE: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of calls to `disable_preemption()` the running thread hasn't
+/// matched with a call to `enable_preemption()`. Only one core runs threads;
+/// `reschedule()` keeps a thread's count on its stack while others run.
+static PREEMPTION_DISABLED: AtomicUsize = AtomicUsize::new(0);
+
+/// The state of a thread.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Waiting for its turn to run.
+    Ready,
+    /// Running.
+    Running,
+    /// Blocked, and not scheduled until it's woken.
+    Waiting,
+    /// Exited. Its stack is freed the next time any thread yields.
+    Dead,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            State::Ready => "ready",
+            State::Running => "running",
+            State::Waiting => "waiting",
+            State::Dead => "dead",
+        })
+    }
+}
+
+/// Error type for `spawn()` failures.
+#[derive(Debug)]
+pub enum Error {
+    /// `init()` hasn't been called.
+    Uninitialized,
+    /// The stack size is smaller than `MIN_STACK_SIZE` or isn't a multiple
+    /// of 16.
+    InvalidStackSize(usize),
+    /// The stack couldn't be allocated.
+    OutOfMemory,
+}
+
+/// A snapshot of a thread, as returned by `threads()`.
+#[derive(Debug, Clone)]
+pub struct Info {
+    pub id: Id,
+    pub name: String,
+    pub state: State,
+    /// The size of the thread's stack, or `None` for the boot thread's.
+    pub stack_size: Option<usize>,
+    /// The number of times the thread has been switched to.
+    pub switches: u64,
+    /// The number of times the thread has been preempted.
+    pub preemptions: u64,
+    /// How long the thread has run for.
+    pub cpu_time: Duration,
+}
+
+/// Makes the calling code, which must be the boot thread, thread `1` and
+/// starts scheduling threads.
+///
+/// # Errors
+///
+/// Returns `Error::OutOfMemory` if the idle thread's stack can't be
+/// allocated.
+pub fn init() -> Result<(), Error> {
+    let stack = Stack::new(IDLE_STACK_SIZE).ok_or(Error::OutOfMemory)?;
+    let context = Context::new(idle, 0, stack.top());
+    let idle = Thread::new(IDLE_ID, "idle".to_string(), context, Some(stack));
+
+    *SCHEDULER.lock() = Some(Scheduler::new(idle, timer::current_time()));
+    Ok(())
+}
+
+/// The idle thread: waits for an interrupt, then lets any thread it made
+/// ready run.
+extern "C" fn idle(_: usize) -> ! {
+    loop {
+        aarch64::wfi();
+        yield_now();
+    }
+}
+
+/// Starts preempting the running thread every `time_slice`, rounded down to
+/// whole microseconds but at least one. IRQs must be unmasked for threads to
+/// be preempted.
+pub fn start_preemption(time_slice: Duration) {
+    let us = cmp::max(1, micros(time_slice));
+
+    TIME_SLICE.store(us as usize, Ordering::Relaxed);
+    GenericTimer::new().arm(us);
+}
+
+/// Returns the length of a time slice, or `None` if threads aren't
+/// preempted.
+pub fn time_slice() -> Option<Duration> {
+    match TIME_SLICE.load(Ordering::Relaxed) {
+        0 => None,
+        us => Some(Duration::from_micros(us as u64))
+    }
+}
+
+/// Stops the running thread from being preempted until a matching call to
+/// `enable_preemption()`. Calls nest. Locking a `Mutex` calls this, and
+/// unlocking it calls `enable_preemption()`.
+pub fn disable_preemption() {
+    let daif = aarch64::mask_irq();
+    PREEMPTION_DISABLED.store(PREEMPTION_DISABLED.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    aarch64::restore_irq(daif);
+}
+
+/// Undoes a call to `disable_preemption()`.
+pub fn enable_preemption() {
+    let daif = aarch64::mask_irq();
+    PREEMPTION_DISABLED.store(PREEMPTION_DISABLED.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
+    aarch64::restore_irq(daif);
+}
+
+/// Handles a generic timer interrupt: starts the next time slice and, unless the
+/// running thread has disabled preemption, switches to the next ready thread.
+/// Returns when the running thread is scheduled again.
+///
+/// Must only be called from the IRQ handler, after every other pending IRQ
+/// has been handled.
+pub fn preempt() {
+    match TIME_SLICE.load(Ordering::Relaxed) {
+        0 => return,
+        us => GenericTimer::new().arm(us as u64)
+    }
+
+    if PREEMPTION_DISABLED.load(Ordering::Relaxed) == 0 {
+        reschedule(|scheduler, now| scheduler.switch(State::Ready, now, true));
+    }
+}
+
+/// Converts `duration` to microseconds, saturating on overflow.
+fn micros(duration: Duration) -> u64 {
+    duration.as_secs()
+        .saturating_mul(1_000_000)
+        .saturating_add(duration.subsec_micros() as u64)
+}
+
+/// Calls `f` with the scheduler, with IRQs masked so that an IRQ handler that
+/// enters the scheduler can't find it locked. Returns `None` if `init()`
+/// hasn't been called.
+fn with_scheduler<R, F: FnOnce(&mut Scheduler) -> R>(f: F) -> Option<R> {
+    let daif = aarch64::mask_irq();
+    let result = SCHEDULER.lock().as_mut().map(f);
+    aarch64::restore_irq(daif);
+    result
+}
+
+/// Configuration for a new thread.
+#[derive(Debug)]
+pub struct Builder {
+    name: String,
+    stack_size: usize,
+}
+
+impl Builder {
+    /// Returns a builder for a thread named `kthread` with a stack of
+    /// `DEFAULT_STACK_SIZE` bytes.
+    pub fn new() -> Builder {
+        Builder { name: "kthread".to_string(), stack_size: DEFAULT_STACK_SIZE }
+    }
+
+    /// Sets the thread's name.
+    pub fn name(mut self, name: &str) -> Builder {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Sets the size of the thread's stack in bytes.
+    pub fn stack_size(mut self, size: usize) -> Builder {
+        self.stack_size = size;
+        self
+    }
+
+    /// Spawns a thread that runs `f` and then exits, and returns its ID. The
+    /// thread first runs once the threads already ready have had a turn.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidStackSize` if the stack size is smaller than
+    /// `MIN_STACK_SIZE` or isn't a multiple of 16, and `Error::OutOfMemory`
+    /// if the stack can't be allocated.
+    pub fn spawn<F: FnOnce() + Send + 'static>(self, f: F) -> Result<Id, Error> {
+        if self.stack_size < MIN_STACK_SIZE || self.stack_size % 16 != 0 {
+            return Err(Error::InvalidStackSize(self.stack_size));
+        }
+
+        let stack = Stack::new(self.stack_size).ok_or(Error::OutOfMemory)?;
+
+        // `start` receives the closure through a thin pointer.
+        let mut f = Some(f);
+        let main: Box<dyn FnMut() + Send> = Box::new(move || {
+            if let Some(f) = f.take() {
+                f()
+            }
+        });
+        let main = Box::into_raw(Box::new(main)) as usize;
+
+        let context = Context::new(start, main, stack.top());
+        let mut thread = Box::new(Thread::new(0, self.name, context, Some(stack)));
+        let id = with_scheduler(move |scheduler| {
+            let id = scheduler.allocate_id();
+            thread.id = id;
+            scheduler.add(thread);
+            id
+        });
+
+        match id {
+            Some(id) => Ok(id),
+            None => {
+                drop(unsafe { Box::from_raw(main as *mut Box<dyn FnMut() + Send>) });
+                Err(Error::Uninitialized)
+            }
+        }
+    }
+}
+
+/// Spawns a thread with a `stack_size` byte stack that runs `f` and then
+/// exits. See `Builder::spawn()`.
+pub fn spawn<F: FnOnce() + Send + 'static>(f: F, stack_size: usize) -> Result<Id, Error> {
+    Builder::new().stack_size(stack_size).spawn(f)
+}
+
+/// Where new threads start: runs the closure `main` points to, then exits.
+extern "C" fn start(main: usize) -> ! {
+    let mut main = unsafe { Box::from_raw(main as *mut Box<dyn FnMut() + Send>) };
+    (**main)();
+    drop(main);
+    exit()
+}
+
+/// Calls `switch` with the scheduler and the time in microseconds since boot
+/// to stop the running thread, then switches to the thread it chose. Returns
+/// when the running thread is scheduled again, or right away if `switch`
+/// returns `None`. Does nothing before `init()` is called.
+///
+/// IRQs are masked until the switch is complete: an IRQ handler that
+/// preempted the thread after the scheduler's lock was released, but before
+/// `context_switch`, would save its registers as the next thread's.
+fn reschedule<F>(switch: F)
+    where F: FnOnce(&mut Scheduler, u64) -> Option<(*mut Context, *const Context)>
+{
+    let daif = aarch64::mask_irq();
+    let now = timer::current_time();
+    let switch = SCHEDULER.lock().as_mut().and_then(|scheduler| switch(scheduler, now));
+    if let Some((from, to)) = switch {
+        // New threads start with preemption enabled; resumed ones restore
+        // their own count.
+        let disabled = PREEMPTION_DISABLED.load(Ordering::Relaxed);
+        PREEMPTION_DISABLED.store(0, Ordering::Relaxed);
+        unsafe { context_switch(from, to) }
+        PREEMPTION_DISABLED.store(disabled, Ordering::Relaxed);
+    }
+
+    aarch64::restore_irq(daif);
+}
+
+/// Lets the next ready thread run, if there is one. Returns when the calling
+/// thread is scheduled again. Does nothing before `init()` is called.
+pub fn yield_now() {
+    reschedule(|scheduler, now| {
+        scheduler.reap();
+        scheduler.switch(State::Ready, now, false)
+    })
+}
+
+/// Blocks the running thread until it's woken from the wait queue `queue`,
+/// if it's `Some`, or until `wake_at` microseconds since boot, if it's
+/// `Some`. Returns right away before `init()` is called.
+fn block(queue: Option<usize>, wake_at: Option<u64>) {
+    reschedule(|scheduler, now| scheduler.wait(queue, wake_at, now))
+}
+
+/// Wakes up to `max` of the threads waiting on the wait queue `queue`, and
+/// returns how many were. May be called from an IRQ handler.
+fn wake(queue: usize, max: usize) -> usize {
+    with_scheduler(|scheduler| scheduler.wake(queue, max)).unwrap_or(0)
+}
+
+/// Blocks the running thread until `duration` has passed, letting other
+/// threads run. Before `init()` is called, spins instead.
+pub fn sleep(duration: Duration) {
+    let wake_at = timer::current_time().checked_add(micros(duration));
+    loop {
+        let now = timer::current_time();
+        match wake_at {
+            Some(wake_at) if now >= wake_at => return,
+            _ => block(None, wake_at)
+        }
+    }
+}
+
+/// Ends the calling thread. Its stack is freed once another thread runs.
+///
+/// # Panics
+///
+/// Panics if `init()` hasn't been called.
+pub fn exit() -> ! {
+    reschedule(|scheduler, now| scheduler.switch(State::Dead, now, false));
+    panic!("thread::exit(): threads haven't been initialized")
+}
+
+/// Returns the running thread's ID, or `1` before `init()` is called.
+pub fn current() -> Id {
+    with_scheduler(|scheduler| scheduler.current()).unwrap_or(1)
+}
+
+/// Returns a snapshot of every thread that hasn't exited, by ID.
+pub fn threads() -> Vec<Info> {
+    let now = timer::current_time();
+    with_scheduler(|scheduler| scheduler.threads(now)).unwrap_or_default()
+}
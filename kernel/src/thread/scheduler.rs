@@ -0,0 +1,254 @@
+use std::mem;
+use std::time::Duration;
+
+use thread::context::{Context, Stack};
+use thread::{Id, Info, State, IDLE_ID};
+
+/// A thread control block.
+pub struct Thread {
+    pub id: Id,
+    pub name: String,
+    pub state: State,
+    /// The registers saved when the thread last stopped running. A thread
+    /// that was preempted also has a trap frame, holding every register it
+    /// was using, on its stack.
+    pub context: Context,
+    /// The thread's stack, or `None` for the boot thread, which runs on the
+    /// stack `ext/init.S` set up.
+    pub stack: Option<Stack>,
+    /// The number of times the thread has been switched to.
+    pub switches: u64,
+    /// The number of times the thread has been preempted.
+    pub preemptions: u64,
+    /// The microseconds the thread has run for, up to when it last started
+    /// running.
+    pub cpu_time: u64,
+    /// When, in microseconds since boot, the thread last started running.
+    pub started: u64,
+    /// The key of the wait queue the thread is waiting on, if any.
+    pub queue: Option<usize>,
+    /// When, in microseconds since boot, a waiting thread is woken if it
+    /// hasn't been already, if ever.
+    pub wake_at: Option<u64>,
+}
+
+impl Thread {
+    /// Returns a thread that hasn't run yet.
+    pub fn new(id: Id, name: String, context: Context, stack: Option<Stack>) -> Thread {
+        Thread {
+            id,
+            name,
+            state: State::Ready,
+            context,
+            stack,
+            switches: 0,
+            preemptions: 0,
+            cpu_time: 0,
+            started: 0,
+            queue: None,
+            wake_at: None,
+        }
+    }
+
+    /// Returns a snapshot of the thread. `now` is the time, in microseconds
+    /// since boot, to count a running thread's CPU time up to.
+    fn info(&self, now: u64) -> Info {
+        let cpu_time = match self.state {
+            State::Running => self.cpu_time + now.saturating_sub(self.started),
+            _ => self.cpu_time
+        };
+
+        Info {
+            id: self.id,
+            name: self.name.clone(),
+            state: self.state,
+            stack_size: self.stack.as_ref().map(|stack| stack.size()),
+            switches: self.switches,
+            preemptions: self.preemptions,
+            cpu_time: Duration::from_micros(cpu_time),
+        }
+    }
+}
+
+/// A round-robin scheduler.
+///
+/// Threads are boxed so that their contexts stay put while the scheduler's
+/// lock isn't held during a context switch. Every queue has room for every
+/// thread, so moving threads between them never allocates: IRQ handlers wake
+/// threads and preempt them, and may have interrupted a thread that holds the
+/// allocator's lock.
+pub struct Scheduler {
+    /// The running thread.
+    current: Box<Thread>,
+    /// The threads ready to run, in the order they'll run in.
+    ready: Vec<Box<Thread>>,
+    /// The threads waiting to be woken.
+    waiting: Vec<Box<Thread>>,
+    /// The idle thread, while it isn't running. It runs when no other thread
+    /// is ready, and never waits in the ready queue.
+    idle: Option<Box<Thread>>,
+    /// Threads that have exited. They're freed by the next thread to enter
+    /// the scheduler, once they're no longer running on their stacks.
+    dead: Vec<Box<Thread>>,
+    /// The ID the next spawned thread gets.
+    next_id: Id,
+}
+
+impl Scheduler {
+    /// Returns a scheduler whose running thread is the boot thread, `1`, and
+    /// which runs `idle` when no other thread is ready. `now` is the time, in
+    /// microseconds since boot.
+    pub fn new(idle: Thread, now: u64) -> Scheduler {
+        let mut boot = Thread::new(1, "kmain".to_string(), Context::default(), None);
+        boot.state = State::Running;
+        boot.switches = 1;
+        boot.started = now;
+
+        Scheduler {
+            current: Box::new(boot),
+            ready: Vec::new(),
+            waiting: Vec::new(),
+            idle: Some(Box::new(idle)),
+            dead: Vec::new(),
+            next_id: 2,
+        }
+    }
+
+    /// The running thread's ID.
+    pub fn current(&self) -> Id {
+        self.current.id
+    }
+
+    /// Returns the next thread ID.
+    pub fn allocate_id(&mut self) -> Id {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Queues `thread` to run after the threads that are already ready.
+    pub fn add(&mut self, mut thread: Box<Thread>) {
+        thread.state = State::Ready;
+        self.ready.push(thread);
+
+        let threads = 2 + self.ready.len() + self.waiting.len() + self.dead.len();
+        for queue in [&mut self.ready, &mut self.waiting, &mut self.dead].iter_mut() {
+            let len = queue.len();
+            queue.reserve(threads - len);
+        }
+    }
+
+    /// Returns a snapshot of every thread that hasn't exited, by ID. `now` is
+    /// the time, in microseconds since boot.
+    pub fn threads(&self, now: u64) -> Vec<Info> {
+        let mut threads: Vec<Info> = self.ready.iter()
+            .chain(self.waiting.iter())
+            .chain(self.idle.iter())
+            .map(|thread| thread.info(now))
+            .collect();
+
+        threads.push(self.current.info(now));
+        threads.sort_by_key(|info| info.id);
+        threads
+    }
+
+    /// Frees the threads that have exited.
+    pub fn reap(&mut self) {
+        self.dead.clear();
+    }
+
+    /// Makes the threads waiting on the wait queue `queue` ready, in the order
+    /// they started waiting, up to `max` of them. Returns how many were.
+    pub fn wake(&mut self, queue: usize, max: usize) -> usize {
+        let mut woken = 0;
+        let mut i = 0;
+        while i < self.waiting.len() && woken < max {
+            if self.waiting[i].queue == Some(queue) {
+                let thread = self.waiting.remove(i);
+                self.make_ready(thread);
+                woken += 1;
+            } else {
+                i += 1;
+            }
+        }
+
+        woken
+    }
+
+    /// Makes the waiting threads whose wake-up time is `now` or earlier
+    /// ready.
+    fn wake_expired(&mut self, now: u64) {
+        let mut i = 0;
+        while i < self.waiting.len() {
+            match self.waiting[i].wake_at {
+                Some(wake_at) if wake_at <= now => {
+                    let thread = self.waiting.remove(i);
+                    self.make_ready(thread);
+                }
+                _ => i += 1
+            }
+        }
+    }
+
+    /// Queues the waiting thread `thread` to run.
+    fn make_ready(&mut self, mut thread: Box<Thread>) {
+        thread.state = State::Ready;
+        thread.queue = None;
+        thread.wake_at = None;
+        self.ready.push(thread);
+    }
+
+    /// Stops the running thread to wait until another thread or an IRQ
+    /// handler wakes the threads waiting on the wait queue `queue`, if it's
+    /// `Some`, or until `wake_at`, if it's `Some`. See `switch()`.
+    pub fn wait(&mut self, queue: Option<usize>, wake_at: Option<u64>, now: u64)
+        -> Option<(*mut Context, *const Context)>
+    {
+        self.current.queue = queue;
+        self.current.wake_at = wake_at;
+        self.switch(State::Waiting, now, false)
+    }
+
+    /// Stops the running thread, leaving it in `state`, and makes the next
+    /// ready thread the running one, or the idle thread if none is. Waiting
+    /// threads whose wake-up time has passed are made ready first. `now` is
+    /// the time, in microseconds since boot, and `preempted` whether the
+    /// running thread is being preempted.
+    ///
+    /// Returns where to save the stopped thread's context and where to load
+    /// the next one's, to pass to `context_switch` once the scheduler's lock
+    /// is released, or `None` if the running thread should continue: it's
+    /// still ready and no other thread is.
+    pub fn switch(&mut self, state: State, now: u64, preempted: bool)
+        -> Option<(*mut Context, *const Context)>
+    {
+        self.wake_expired(now);
+        let mut next = match (self.ready.is_empty(), state) {
+            (false, _) => self.ready.remove(0),
+            (true, State::Ready) => return None,
+            (true, _) => self.idle.take().expect("idle thread isn't runnable")
+        };
+
+        next.state = State::Running;
+        next.switches += 1;
+        next.started = now;
+
+        let mut previous = mem::replace(&mut self.current, next);
+        previous.state = state;
+        previous.cpu_time += now.saturating_sub(previous.started);
+        if preempted {
+            previous.preemptions += 1;
+        }
+
+        let from = &mut previous.context as *mut Context;
+        let to = &self.current.context as *const Context;
+        match state {
+            _ if previous.id == IDLE_ID => self.idle = Some(previous),
+            State::Waiting => self.waiting.push(previous),
+            State::Dead => self.dead.push(previous),
+            _ => self.ready.push(previous)
+        }
+
+        Some((from, to))
+    }
+}
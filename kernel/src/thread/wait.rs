@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use pi::aarch64;
+use pi::timer;
+
+use super::{block, micros, wake};
+
+/// A queue of threads blocked until a condition holds.
+///
+/// A thread waits with `wait_until()` until a condition it passes holds. Code
+/// that may have made the condition hold, in another thread or an IRQ
+/// handler, then calls `wake_one()` or `wake_all()`. A woken thread checks its
+/// condition again, and keeps waiting if it doesn't hold.
+///
+/// The condition is checked with IRQs masked, so a wake-up from an IRQ handler
+/// can't be lost between the check and the thread blocking. It must not
+/// block.
+pub struct WaitQueue {
+    /// The number of threads waiting on the queue.
+    waiters: AtomicUsize,
+}
+
+impl WaitQueue {
+    /// Returns an empty wait queue.
+    pub const fn new() -> WaitQueue {
+        WaitQueue { waiters: AtomicUsize::new(0) }
+    }
+
+    /// The key that identifies the queue to the scheduler.
+    fn key(&self) -> usize {
+        self as *const WaitQueue as usize
+    }
+
+    /// Blocks the running thread until `condition` returns `true`.
+    pub fn wait_until<F: FnMut() -> bool>(&self, condition: F) {
+        self.wait(None, condition);
+    }
+
+    /// Blocks the running thread until `condition` returns `true` or `timeout`
+    /// has passed. Returns whether `condition` held.
+    pub fn wait_until_timeout<F>(&self, timeout: Duration, condition: F) -> bool
+        where F: FnMut() -> bool
+    {
+        let wake_at = timer::current_time().checked_add(micros(timeout));
+        self.wait(wake_at, condition)
+    }
+
+    /// Blocks until `condition` returns `true`, or until `wake_at`
+    /// microseconds since boot, if it's `Some`. Returns whether `condition`
+    /// held.
+    fn wait<F: FnMut() -> bool>(&self, wake_at: Option<u64>, mut condition: F) -> bool {
+        loop {
+            let daif = aarch64::mask_irq();
+            if condition() {
+                aarch64::restore_irq(daif);
+                return true;
+            }
+
+            match wake_at {
+                Some(wake_at) if timer::current_time() >= wake_at => {
+                    aarch64::restore_irq(daif);
+                    return false;
+                }
+                _ => {}
+            }
+
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+            block(Some(self.key()), wake_at);
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+            aarch64::restore_irq(daif);
+        }
+    }
+
+    /// Wakes the thread that has waited on the queue the longest, if any.
+    /// Returns whether a thread was woken. May be called from an IRQ handler.
+    pub fn wake_one(&self) -> bool {
+        self.wake(1) == 1
+    }
+
+    /// Wakes every thread waiting on the queue and returns how many were. May
+    /// be called from an IRQ handler.
+    pub fn wake_all(&self) -> usize {
+        self.wake(usize::max_value())
+    }
+
+    fn wake(&self, max: usize) -> usize {
+        match self.waiters.load(Ordering::SeqCst) {
+            0 => 0,
+            _ => wake(self.key(), max)
+        }
+    }
+}
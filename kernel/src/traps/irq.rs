@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pi::generic_timer::{self, GenericTimer};
+use pi::interrupt::{Controller, Interrupt};
+use pi::local_interrupt::{LocalController, LocalInterrupt};
+
+use console;
+use thread;
+use traps::TrapFrame;
+
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of IRQs handled from each `Interrupt`, in `Interrupt::iter()`
+/// order.
+static COUNTS: [AtomicUsize; Interrupt::MAX] = [ZERO, ZERO, ZERO, ZERO, ZERO, ZERO, ZERO, ZERO, ZERO];
+
+/// The number of core-local timer IRQs handled on any core.
+static LOCAL_TIMER_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of IRQs handled from `interrupt` since boot.
+pub fn count(interrupt: Interrupt) -> usize {
+    match Interrupt::iter().position(|i| i == interrupt) {
+        Some(i) => COUNTS[i].load(Ordering::Relaxed),
+        None => 0
+    }
+}
+
+/// Returns the number of core-local timer IRQs handled since boot.
+pub fn local_timer_count() -> usize {
+    LOCAL_TIMER_COUNT.load(Ordering::Relaxed)
+}
+
+/// Dispatches every IRQ pending on the calling core: core-local sources are
+/// read from the local interrupt controller and, if the GPU interrupt
+/// controller has something pending, each of its sources is checked. A
+/// generic timer interrupt preempts the running thread.
+pub fn dispatch(tf: &mut TrapFrame) {
+    let local = LocalController::new(generic_timer::current_core());
+
+    let tick = local.is_pending(LocalInterrupt::CntPns);
+    if tick {
+        LOCAL_TIMER_COUNT.fetch_add(1, Ordering::Relaxed);
+        handle_local_irq(LocalInterrupt::CntPns, tf);
+    }
+
+    if local.is_pending(LocalInterrupt::Gpu) {
+        let controller = Controller::new();
+        for (i, interrupt) in Interrupt::iter().enumerate() {
+            if controller.is_pending(interrupt) {
+                COUNTS[i].fetch_add(1, Ordering::Relaxed);
+                handle_irq(interrupt, tf);
+            }
+        }
+    }
+
+    // Preempting the running thread switches threads without returning, so
+    // it's done last, when no other IRQ is left waiting for the thread to run
+    // again.
+    if tick {
+        thread::preempt();
+    }
+}
+
+/// Handles the pending core-local interrupt `interrupt`.
+fn handle_local_irq(interrupt: LocalInterrupt, _tf: &mut TrapFrame) {
+    match interrupt {
+        LocalInterrupt::CntPns => GenericTimer::new().acknowledge(),
+        _ => { }
+    }
+}
+
+/// Handles the pending interrupt `interrupt`.
+fn handle_irq(interrupt: Interrupt, _tf: &mut TrapFrame) {
+    match interrupt {
+        Interrupt::Aux => {
+            pi::uart::handle_irq();
+            console::wake_readers();
+        }
+        _ => { }
+    }
+}
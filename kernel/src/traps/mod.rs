@@ -0,0 +1,81 @@
+mod irq;
+mod syscall;
+mod trap_frame;
+
+use pi::interrupt::{Controller, Interrupt};
+
+pub use self::irq::{count as irq_count, local_timer_count};
+pub use self::trap_frame::TrapFrame;
+
+use console::kprintln;
+use process::{self, ExitStatus};
+use thread;
+
+/// The exception class in `ESR_EL1` of an `svc` executed in AArch64.
+const EC_SVC64: u32 = 0b010101;
+
+extern "C" {
+    /// Returns from an exception that never happened, into the state in `tf`:
+    /// enters EL0 when `tf.spsr` says so. `tf` must be on the calling
+    /// thread's stack, which the thread's exceptions are then saved on.
+    pub fn enter_user(tf: *const TrapFrame) -> !;
+}
+
+/// The type of exception, as encoded by the vector table entry.
+#[repr(u16)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Kind {
+    Synchronous = 0,
+    Irq = 1,
+    Fiq = 2,
+    SError = 3,
+}
+
+/// The exception level and stack the exception was taken from.
+#[repr(u16)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Source {
+    CurrentSpEl0 = 0,
+    CurrentSpElx = 1,
+    LowerAArch64 = 2,
+    LowerAArch32 = 3,
+}
+
+/// Information about an exception, passed in by the vector table entry.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Info {
+    source: Source,
+    kind: Kind,
+}
+
+/// Enables the IRQs the kernel handles at the interrupt controller. IRQs are
+/// still masked on the core until `aarch64::enable_irq()` is called.
+pub fn init() {
+    Controller::new().enable(Interrupt::Aux);
+}
+
+/// This function is called when an exception occurs. The `info` parameter
+/// specifies the source and kind of exception that has occurred. The `esr` is
+/// the value of the exception syndrome register. Finally, `tf` is a pointer to
+/// the trap frame for the exception.
+///
+/// A synchronous exception from EL0 is a syscall if it's an `svc`; any other
+/// kills the process that caused it.
+#[no_mangle]
+pub extern fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
+    match (info.kind, info.source) {
+        (Kind::Irq, _) => irq::dispatch(tf),
+        (Kind::Synchronous, Source::LowerAArch64) if esr >> 26 == EC_SVC64 => syscall::handle(tf),
+        (Kind::Synchronous, Source::LowerAArch64) => {
+            kprintln!("process {} killed: unhandled exception (esr: {:#x}, elr: {:#x})",
+                      thread::current(), esr, tf.elr);
+            process::exit(ExitStatus::Killed)
+        }
+        _ => {
+            kprintln!("unhandled exception: {:?} (esr: {:#x}, elr: {:#x})",
+                      info, esr, tf.elr);
+            loop { ::pi::aarch64::wfi() }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! The syscalls processes make with `svc #0`.
+//!
+//! The syscall number is passed in `x8` and its arguments in `x0` through
+//! `x2`. On return, `x7` holds `0` if the syscall succeeded and its result is
+//! in `x0`, or an error code if it failed. No other register is changed.
+//!
+//! | number | name     | arguments           | result                         |
+//! |--------|----------|---------------------|--------------------------------|
+//! | 0      | `sleep`  | `ms`                | `0`, after `ms` milliseconds   |
+//! | 1      | `write`  | `fd`, `buf`, `len`  | the number of bytes written    |
+//! | 2      | `read`   | `fd`, `buf`, `len`  | the number of bytes read       |
+//! | 3      | `exit`   | `code`              | doesn't return                 |
+//! | 4      | `getpid` |                     | the process's ID               |
+//! | 5      | `time`   |                     | the microseconds since boot    |
+//!
+//! `write` takes `1` (output) or `2` (errors) as `fd`, both of which are the
+//! console, and writes every byte. `read` takes `0` (input), the console,
+//! and blocks until at least one byte has been typed. `buf` must lie within
+//! the process's memory. `exit`'s `code` is truncated to 32 bits.
+//!
+//! Error codes are `io::ErrorKind`s:
+//!
+//! | code | kind                       |
+//! |------|----------------------------|
+//! | 1    | `NotFound`                 |
+//! | 2    | `PermissionDenied`         |
+//! | 3    | `InvalidInput`             |
+//! | 4    | `InvalidData`              |
+//! | 5    | `TimedOut`                 |
+//! | 6    | `WriteZero`                |
+//! | 7    | `Interrupted`              |
+//! | 8    | `UnexpectedEof`            |
+//! | 9    | `Other` and any other kind |
+//!
+//! `write` and `read` fail with `NotFound` for an unknown `fd` and with
+//! `PermissionDenied` for a `buf` outside the process's memory. An unknown
+//! syscall number fails with `InvalidInput`.
+
+use std::io::{self, Write};
+use std::slice;
+use std::time::Duration;
+
+use pi::timer;
+
+use aarch64;
+use console::{self, CONSOLE};
+use process::{self, ExitStatus};
+use thread;
+use traps::TrapFrame;
+
+/// The syscall numbers.
+const SLEEP: u64 = 0;
+const WRITE: u64 = 1;
+const READ: u64 = 2;
+const EXIT: u64 = 3;
+const GETPID: u64 = 4;
+const TIME: u64 = 5;
+
+/// The file descriptors `read` and `write` accept.
+const STDIN: u64 = 0;
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+/// Handles the syscall the calling process made, which trapped with `tf`.
+///
+/// IRQs are unmasked while the syscall runs, so a syscall that blocks, or
+/// takes long, lets other threads run.
+pub fn handle(tf: &mut TrapFrame) {
+    aarch64::enable_irq();
+    let (number, args) = (tf.xn[8], [tf.xn[0], tf.xn[1], tf.xn[2]]);
+    let result = match number {
+        SLEEP => sleep(args[0]),
+        WRITE => write(args[0], args[1], args[2]),
+        READ => read(args[0], args[1], args[2]),
+        EXIT => process::exit(ExitStatus::Exited(args[0] as i32)),
+        GETPID => Ok(thread::current()),
+        TIME => Ok(timer::current_time()),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "unknown syscall"))
+    };
+
+    aarch64::disable_irq();
+    match result {
+        Ok(value) => {
+            tf.xn[0] = value;
+            tf.xn[7] = 0;
+        }
+        Err(error) => tf.xn[7] = error_code(error.kind())
+    }
+}
+
+/// Returns the code `kind` is returned to processes as.
+fn error_code(kind: io::ErrorKind) -> u64 {
+    match kind {
+        io::ErrorKind::NotFound => 1,
+        io::ErrorKind::PermissionDenied => 2,
+        io::ErrorKind::InvalidInput => 3,
+        io::ErrorKind::InvalidData => 4,
+        io::ErrorKind::TimedOut => 5,
+        io::ErrorKind::WriteZero => 6,
+        io::ErrorKind::Interrupted => 7,
+        io::ErrorKind::UnexpectedEof => 8,
+        _ => 9
+    }
+}
+
+/// Returns the `len` bytes at `buf` in the calling process's memory.
+///
+/// # Errors
+///
+/// Returns a `PermissionDenied` error if any of the bytes lie outside the
+/// process's memory.
+fn user_buffer(buf: u64, len: u64) -> io::Result<&'static mut [u8]> {
+    let (start, end) = process::memory(thread::current())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "not a process"))?;
+
+    let (buf, len) = (buf as usize, len as usize);
+    match buf.checked_add(len) {
+        Some(buf_end) if buf >= start && buf_end <= end => {
+            Ok(unsafe { slice::from_raw_parts_mut(buf as *mut u8, len) })
+        }
+        _ => Err(io::Error::new(io::ErrorKind::PermissionDenied, "bad address"))
+    }
+}
+
+fn bad_fd() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "bad file descriptor")
+}
+
+/// `sleep(ms)`.
+fn sleep(ms: u64) -> io::Result<u64> {
+    thread::sleep(Duration::from_millis(ms));
+    Ok(0)
+}
+
+/// `write(fd, buf, len)`.
+fn write(fd: u64, buf: u64, len: u64) -> io::Result<u64> {
+    if fd != STDOUT && fd != STDERR {
+        return Err(bad_fd());
+    }
+
+    let buf = user_buffer(buf, len)?;
+    CONSOLE.lock().write_all(buf)?;
+    Ok(buf.len() as u64)
+}
+
+/// `read(fd, buf, len)`.
+fn read(fd: u64, buf: u64, len: u64) -> io::Result<u64> {
+    if fd != STDIN {
+        return Err(bad_fd());
+    }
+
+    let buf = user_buffer(buf, len)?;
+    Ok(console::read(buf) as u64)
+}
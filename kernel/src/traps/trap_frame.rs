@@ -0,0 +1,20 @@
+/// The state saved by `context_save` in `ext/init.S` when an exception is
+/// taken. Changes made to a `TrapFrame` are restored when the exception
+/// returns.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug)]
+pub struct TrapFrame {
+    /// The exception link register: the address execution resumes at.
+    pub elr: u64,
+    /// The saved program status register.
+    pub spsr: u64,
+    /// The EL0 stack pointer, `SP_EL0`.
+    pub sp: u64,
+    /// The EL0 thread ID register, `TPIDR_EL0`.
+    pub tpidr: u64,
+    /// SIMD/floating point registers `q0` through `q31`.
+    pub qn: [u128; 32],
+    __r0: u64,
+    /// General purpose registers `x0` through `x30`.
+    pub xn: [u64; 31],
+}
@@ -0,0 +1,125 @@
+//! The spin lock shared by the kernel and the file system crates.
+//!
+//! A thread holding a `Mutex` must not be preempted, or every other thread
+//! wanting the lock spins until it is scheduled again. The crates taking locks
+//! don't know about threads, so the kernel installs its `Preemption` with
+//! `set_preemption()` before taking any lock; every lock then disables
+//! preemption while it's held. Without one, as in host tests, locks don't
+//! touch preemption.
+//!
+//! The lock is taken and released with plain atomic loads and stores, which is
+//! enough on a single core with preemption disabled, and doesn't depend on
+//! exclusive memory accesses working.
+
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Control over preemption of the running thread.
+///
+/// Calls nest: preemption is enabled again once `enable()` has been called as
+/// many times as `disable()`.
+pub trait Preemption: Sync {
+    /// Keeps the running thread from being preempted.
+    fn disable(&self);
+
+    /// Undoes one call to `disable()`.
+    fn enable(&self);
+}
+
+/// The `Preemption` installed with `set_preemption()`, if any.
+static mut PREEMPTION: Option<&'static dyn Preemption> = None;
+
+/// Makes every lock disable preemption through `preemption` while it's held.
+///
+/// # Safety
+///
+/// Must be called before any lock is taken, while no other thread is running.
+pub unsafe fn set_preemption(preemption: &'static dyn Preemption) {
+    PREEMPTION = Some(preemption);
+}
+
+fn disable_preemption() {
+    if let Some(preemption) = unsafe { PREEMPTION } {
+        preemption.disable();
+    }
+}
+
+fn enable_preemption() {
+    if let Some(preemption) = unsafe { PREEMPTION } {
+        preemption.enable();
+    }
+}
+
+/// A spin lock protecting a value.
+///
+/// Locking it twice from the same thread deadlocks, and a lock must not be
+/// held across a yield.
+#[repr(align(32))]
+pub struct Mutex<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+/// A guard giving access to the value in a `Mutex`. The lock is released and
+/// preemption enabled again when the guard is dropped, on the same thread.
+pub struct MutexGuard<'a, T: 'a> {
+    lock: &'a Mutex<T>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> {}
+
+impl<T> Mutex<T> {
+    /// Wraps `value` in an unlocked `Mutex`.
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex { locked: AtomicBool::new(false), value: UnsafeCell::new(value) }
+    }
+
+    /// Locks the value, spinning until it is available.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+        }
+    }
+
+    /// Locks the value if it is available. Returns `None` without waiting if
+    /// it's already locked.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        disable_preemption();
+        if self.locked.load(Ordering::Acquire) {
+            enable_preemption();
+            return None;
+        }
+
+        self.locked.store(true, Ordering::Release);
+        Some(MutexGuard { lock: self, _not_send: PhantomData })
+    }
+}
+
+impl<'a, T> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<'a, T> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        enable_preemption();
+    }
+}
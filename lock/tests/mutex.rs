@@ -0,0 +1,49 @@
+extern crate lock;
+
+use std::sync::Arc;
+use std::thread;
+
+use lock::Mutex;
+
+#[test]
+fn lock_gives_access_to_the_value() {
+    let mutex = Mutex::new(vec![1, 2]);
+    mutex.lock().push(3);
+    assert_eq!(*mutex.lock(), vec![1, 2, 3]);
+}
+
+#[test]
+fn try_lock_fails_while_locked() {
+    let mutex = Mutex::new(0);
+    let guard = mutex.lock();
+    assert!(mutex.try_lock().is_none());
+
+    drop(guard);
+    *mutex.try_lock().expect("unlocked") += 1;
+    assert_eq!(*mutex.lock(), 1);
+}
+
+#[test]
+fn statics_start_unlocked() {
+    static COUNT: Mutex<usize> = Mutex::new(0);
+    *COUNT.lock() += 2;
+    assert_eq!(*COUNT.try_lock().expect("unlocked"), 2);
+}
+
+#[test]
+fn lock_waits_for_the_holder() {
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+
+    let waiter = {
+        let mutex = mutex.clone();
+        thread::spawn(move || *mutex.lock() += 1)
+    };
+
+    thread::sleep(std::time::Duration::from_millis(20));
+    assert_eq!(*guard, 0);
+    drop(guard);
+
+    waiter.join().unwrap();
+    assert_eq!(*mutex.lock(), 1);
+}
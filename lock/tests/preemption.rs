@@ -0,0 +1,51 @@
+extern crate lock;
+
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use lock::{Mutex, Preemption};
+
+/// Counts the calls to `disable()` that haven't been undone.
+struct Counting(AtomicIsize);
+
+impl Preemption for Counting {
+    fn disable(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn enable(&self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+static DEPTH: Counting = Counting(AtomicIsize::new(0));
+
+fn depth() -> isize {
+    DEPTH.0.load(Ordering::SeqCst)
+}
+
+// The hook is global, so everything using it is in one test.
+#[test]
+fn preemption_is_disabled_while_a_lock_is_held() {
+    unsafe { lock::set_preemption(&DEPTH) };
+
+    let first = Mutex::new(());
+    let second = Mutex::new(());
+
+    let outer = first.lock();
+    assert_eq!(depth(), 1);
+    let inner = second.lock();
+    assert_eq!(depth(), 2);
+
+    assert!(first.try_lock().is_none());
+    assert_eq!(depth(), 2);
+
+    drop(inner);
+    assert_eq!(depth(), 1);
+    drop(outer);
+    assert_eq!(depth(), 0);
+
+    let guard = first.try_lock().expect("unlocked");
+    assert_eq!(depth(), 1);
+    drop(guard);
+    assert_eq!(depth(), 0);
+}
@@ -0,0 +1,192 @@
+//! Access to the calling core's system registers.
+//!
+//! Every other module reaches the core through these functions, so the crate
+//! also builds for the host, where its pure logic is tested. There, reads
+//! return `0` and writes do nothing.
+
+/// The IRQ mask bit of `DAIF`.
+const DAIF_I: u64 = 1 << 7;
+
+/// Masks IRQs, returning the previous value of `DAIF`.
+#[cfg(target_arch = "aarch64")]
+pub fn mask_irq() -> u64 {
+    let daif: u64;
+    unsafe {
+        asm!("mrs $0, daif" : "=r"(daif) ::: "volatile");
+        asm!("msr daifset, #2" :::: "volatile");
+    }
+    daif
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn mask_irq() -> u64 {
+    0
+}
+
+/// Restores `DAIF` to `daif`, as returned by `mask_irq()`.
+#[cfg(target_arch = "aarch64")]
+pub fn restore_irq(daif: u64) {
+    unsafe { asm!("msr daif, $0" :: "r"(daif) :: "volatile"); }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn restore_irq(_daif: u64) {}
+
+/// Returns `true` if `daif`, as returned by `mask_irq()`, has IRQs masked.
+pub fn irq_masked(daif: u64) -> bool {
+    daif & DAIF_I != 0
+}
+
+/// Waits for an interrupt.
+#[cfg(target_arch = "aarch64")]
+pub fn wfi() {
+    unsafe { asm!("wfi" :::: "volatile"); }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn wfi() {}
+
+/// Returns `MPIDR_EL1`, which identifies the calling core.
+#[cfg(target_arch = "aarch64")]
+pub fn mpidr() -> u64 {
+    let mpidr: u64;
+    unsafe { asm!("mrs $0, mpidr_el1" : "=r"(mpidr)); }
+    mpidr
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn mpidr() -> u64 {
+    0
+}
+
+/// Returns `CNTFRQ_EL0`, the frequency of the system counter in Hz.
+#[cfg(target_arch = "aarch64")]
+pub fn cntfrq() -> u64 {
+    let frequency: u64;
+    unsafe { asm!("mrs $0, cntfrq_el0" : "=r"(frequency)); }
+    frequency
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn cntfrq() -> u64 {
+    0
+}
+
+/// Returns `CNTPCT_EL0`, the system counter, once every earlier instruction
+/// has completed.
+#[cfg(target_arch = "aarch64")]
+pub fn cntpct() -> u64 {
+    let count: u64;
+    unsafe { asm!("isb; mrs $0, cntpct_el0" : "=r"(count) ::: "volatile"); }
+    count
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn cntpct() -> u64 {
+    0
+}
+
+/// Returns `CNTP_CTL_EL0`, the physical timer's control register.
+#[cfg(target_arch = "aarch64")]
+pub fn cntp_ctl() -> u64 {
+    let control: u64;
+    unsafe { asm!("mrs $0, cntp_ctl_el0" : "=r"(control) ::: "volatile"); }
+    control
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn cntp_ctl() -> u64 {
+    0
+}
+
+/// Writes `control` to `CNTP_CTL_EL0`.
+#[cfg(target_arch = "aarch64")]
+pub fn set_cntp_ctl(control: u64) {
+    unsafe { asm!("msr cntp_ctl_el0, $0" :: "r"(control) :: "volatile"); }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn set_cntp_ctl(_control: u64) {}
+
+/// Writes `ticks` to `CNTP_TVAL_EL0`, the physical timer's down-counter.
+#[cfg(target_arch = "aarch64")]
+pub fn set_cntp_tval(ticks: u64) {
+    unsafe { asm!("msr cntp_tval_el0, $0" :: "r"(ticks) :: "volatile"); }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn set_cntp_tval(_ticks: u64) {}
+
+/// Returns the size in bytes of the smallest data cache line, from `CTR_EL0`.
+#[cfg(target_arch = "aarch64")]
+fn dcache_line_size() -> usize {
+    let ctr: u64;
+    unsafe { asm!("mrs $0, ctr_el0" : "=r"(ctr)); }
+    4 << ((ctr >> 16) & 0xF)
+}
+
+/// Calls `f` with the address of every data cache line holding part of the
+/// `len` bytes at `start`, then waits for the maintenance to complete.
+#[cfg(target_arch = "aarch64")]
+fn for_each_dcache_line<F: Fn(usize)>(start: usize, len: usize, f: F) {
+    let line = dcache_line_size();
+    let mut address = start & !(line - 1);
+    while address < start + len {
+        f(address);
+        address += line;
+    }
+
+    unsafe { asm!("dsb sy" :::: "volatile"); }
+}
+
+/// Writes the cached data for the `len` bytes at `start` back to memory, so
+/// that other bus masters, such as the VideoCore, see it.
+#[cfg(target_arch = "aarch64")]
+pub fn clean_dcache(start: usize, len: usize) {
+    for_each_dcache_line(start, len, |address| unsafe {
+        asm!("dc cvac, $0" :: "r"(address) :: "volatile");
+    });
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn clean_dcache(_start: usize, _len: usize) {}
+
+/// Discards the cached data for the `len` bytes at `start`, so that what other
+/// bus masters wrote to memory is read. Any other data in the same cache lines
+/// is lost.
+#[cfg(target_arch = "aarch64")]
+pub fn invalidate_dcache(start: usize, len: usize) {
+    for_each_dcache_line(start, len, |address| unsafe {
+        asm!("dc ivac, $0" :: "r"(address) :: "volatile");
+    });
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn invalidate_dcache(_start: usize, _len: usize) {}
+
+/// Discards every instruction cache line, so that instructions are fetched
+/// from memory again.
+#[cfg(target_arch = "aarch64")]
+pub fn invalidate_icache() {
+    unsafe {
+        asm!("ic iallu" :::: "volatile");
+        asm!("dsb ish" :::: "volatile");
+        asm!("isb" :::: "volatile");
+    }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn invalidate_icache() {}
+
+/// Makes instructions written as data to the `len` bytes at `start` visible to
+/// instruction fetches.
+#[cfg(target_arch = "aarch64")]
+pub fn sync_icache(start: usize, len: usize) {
+    for_each_dcache_line(start, len, |address| unsafe {
+        asm!("dc cvau, $0" :: "r"(address) :: "volatile");
+    });
+    invalidate_icache();
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn sync_icache(_start: usize, _len: usize) {}
@@ -1,6 +1,9 @@
 /// The address where I/O peripherals are mapped to.
 pub const IO_BASE: usize = 0x3F000000;
 
+/// The number of cores on the BCM2837.
+pub const CORE_COUNT: usize = 4;
+
 /// Generates `pub enums` with no variants for each `ident` passed in.
 pub macro states($($name:ident),*) {
     $(pub enum $name {  })*
@@ -0,0 +1,595 @@
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use timer::{self, Duration};
+use common::IO_BASE;
+use mailbox::{self, Clock, Device, SetPowerState};
+
+/// The base address of the `EMMC` registers.
+const EMMC_BASE: usize = IO_BASE + 0x300000;
+
+/// The size of a block in bytes.
+pub const BLOCK_SIZE: usize = 512;
+
+/// The base clock assumed if the VideoCore can't be asked for it.
+const DEFAULT_BASE_CLOCK: u32 = 41_666_666;
+
+/// The SD clock rate during card identification.
+const IDENTIFICATION_CLOCK: u32 = 400_000;
+
+/// The SD clock rate once the card is identified.
+const TRANSFER_CLOCK: u32 = 25_000_000;
+
+/// How long the controller may take to reset or to stabilize its clock.
+const CONTROLLER_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long a command may take to complete.
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long a block transfer may take to complete.
+const DATA_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the card may stay busy while powering up.
+const POWER_UP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The check pattern echoed by `SEND_IF_COND`.
+const CHECK_PATTERN: u32 = 0xAA;
+
+/// Enum representing bit fields of the `STATUS` register.
+#[repr(u32)]
+enum Status {
+    CommandInhibit = 1,
+    DataInhibit = 1 << 1,
+}
+
+/// Enum representing bit fields of the `CONTROL0` register.
+#[repr(u32)]
+enum Control0 {
+    FourBitBus = 1 << 1,
+}
+
+/// Enum representing bit fields of the `CONTROL1` register.
+#[repr(u32)]
+enum Control1 {
+    ClockInternalEnable = 1,
+    ClockStable = 1 << 1,
+    ClockEnable = 1 << 2,
+    ClockDivider = 0xFFC0,
+    DataTimeout = 0xF << 16,
+    ResetHost = 1 << 24,
+}
+
+/// Enum representing bit fields of the `INTERRUPT` register.
+#[repr(u32)]
+enum Interrupt {
+    CommandDone = 1,
+    DataDone = 1 << 1,
+    WriteReady = 1 << 4,
+    ReadReady = 1 << 5,
+    Error = 1 << 15,
+    CommandTimeout = 1 << 16,
+    DataTimeout = 1 << 20,
+    /// Every error bit.
+    Errors = 0xFFFF_0000,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    ARG2: Volatile<u32>,
+    BLKSIZECNT: Volatile<u32>,
+    ARG1: Volatile<u32>,
+    CMDTM: Volatile<u32>,
+    RESP: [ReadVolatile<u32>; 4],
+    DATA: Volatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONTROL0: Volatile<u32>,
+    CONTROL1: Volatile<u32>,
+    INTERRUPT: Volatile<u32>,
+    IRPT_MASK: Volatile<u32>,
+    IRPT_EN: Volatile<u32>,
+    CONTROL2: Volatile<u32>,
+    __r0: [Reserved<u32>; 47],
+    SLOTISR_VER: ReadVolatile<u32>,
+}
+
+/// The kind of response a command expects.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Response {
+    None,
+    /// R2: a 136-bit CID or CSD.
+    Long,
+    /// R1, R6 and R7.
+    Short,
+    /// R3: a short response without a valid CRC or command index.
+    ShortUnchecked,
+    /// R1b: a short response followed by a busy signal on DAT0.
+    ShortBusy,
+}
+
+/// An SD command and how it is issued.
+#[derive(Debug, Copy, Clone)]
+struct Command {
+    index: u8,
+    response: Response,
+    /// `Some(true)` for a single block read, `Some(false)` for a single block
+    /// write, `None` for commands without data.
+    read: Option<bool>,
+    /// Whether the command must be preceded by `APP_CMD`.
+    app: bool,
+}
+
+impl Command {
+    /// The value written to the `CMDTM` register to issue this command.
+    fn cmdtm(&self) -> u32 {
+        let response = match self.response {
+            Response::None => 0,
+            Response::Long => 0b01 << 16 | 1 << 19,
+            Response::Short => 0b10 << 16 | 1 << 19 | 1 << 20,
+            Response::ShortUnchecked => 0b10 << 16,
+            Response::ShortBusy => 0b11 << 16 | 1 << 19 | 1 << 20,
+        };
+
+        let data = match self.read {
+            Some(true) => 1 << 21 | 1 << 4,
+            Some(false) => 1 << 21,
+            None => 0
+        };
+
+        (self.index as u32) << 24 | response | data
+    }
+}
+
+macro_rules! commands {
+    ($($name:ident = $index:expr, $response:ident, $read:expr, $app:expr;)*) => {
+        $(const $name: Command = Command {
+            index: $index,
+            response: Response::$response,
+            read: $read,
+            app: $app,
+        };)*
+    }
+}
+
+commands! {
+    GO_IDLE_STATE = 0, None, None, false;
+    ALL_SEND_CID = 2, Long, None, false;
+    SEND_RELATIVE_ADDR = 3, Short, None, false;
+    SELECT_CARD = 7, ShortBusy, None, false;
+    SEND_IF_COND = 8, Short, None, false;
+    SEND_CSD = 9, Long, None, false;
+    SET_BLOCKLEN = 16, Short, None, false;
+    READ_SINGLE_BLOCK = 17, Short, Some(true), false;
+    WRITE_BLOCK = 24, Short, Some(false), false;
+    APP_CMD = 55, Short, None, false;
+    SET_BUS_WIDTH = 6, Short, None, true;
+    SD_SEND_OP_COND = 41, ShortUnchecked, None, true;
+    SEND_SCR = 51, Short, Some(true), true;
+}
+
+/// Bits of the OCR returned by `SD_SEND_OP_COND`.
+const OCR_VOLTAGE_WINDOW: u32 = 0x00FF_8000;
+const OCR_HIGH_CAPACITY: u32 = 1 << 30;
+const OCR_READY: u32 = 1 << 31;
+
+/// Error type for SD card failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The controller didn't finish resetting.
+    ResetTimeout,
+    /// The SD clock didn't stabilize.
+    ClockTimeout,
+    /// The card didn't respond to the command with the given index.
+    CommandTimeout(u8),
+    /// The controller flagged an error, such as a CRC or end bit error, in
+    /// the response to the command with the given index. `status` holds the
+    /// `INTERRUPT` register's error bits.
+    CommandFailed { index: u8, status: u32 },
+    /// A block transfer didn't complete in time.
+    DataTimeout,
+    /// The controller flagged an error during a block transfer. Holds the
+    /// `INTERRUPT` register's error bits.
+    DataFailed(u32),
+    /// The card doesn't support the host's voltage range or echoed the wrong
+    /// check pattern.
+    UnusableCard,
+    /// The card didn't finish powering up.
+    PowerUpTimeout,
+    /// The block number is past the end of the card.
+    OutOfRange(u64),
+    /// The buffer isn't exactly `BLOCK_SIZE` bytes long.
+    InvalidBuffer(usize),
+}
+
+/// An initialized SD card on the EMMC controller.
+pub struct Sd {
+    registers: &'static mut Registers,
+    base_clock: u32,
+    rca: u32,
+    high_capacity: bool,
+    blocks: u64,
+}
+
+impl Sd {
+    /// Resets the EMMC controller and runs the SD initialization sequence:
+    /// `GO_IDLE_STATE`, `SEND_IF_COND`, `SD_SEND_OP_COND` until the card is
+    /// ready, `ALL_SEND_CID`, `SEND_RELATIVE_ADDR`, `SEND_CSD` and
+    /// `SELECT_CARD`. The bus is then switched to 4 bits if the card supports
+    /// it and the clock is raised from 400kHz to 25MHz.
+    ///
+    /// The firmware routes the SD card slot to the EMMC controller, so no GPIO
+    /// pins are configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered during initialization.
+    pub fn new() -> Result<Sd, Error> {
+        // Failing to power the card on is harmless if it's already on.
+        let _ = mailbox::property(&SetPowerState { device: Device::SdCard, on: true, wait: true });
+        let base_clock = mailbox::clock_rate(Clock::Emmc).unwrap_or(DEFAULT_BASE_CLOCK);
+
+        let mut sd = Sd {
+            registers: unsafe { &mut *(EMMC_BASE as *mut Registers) },
+            base_clock: base_clock,
+            rca: 0,
+            high_capacity: false,
+            blocks: 0,
+        };
+
+        sd.reset()?;
+        sd.set_clock(IDENTIFICATION_CLOCK)?;
+        sd.identify()?;
+        sd.set_clock(TRANSFER_CLOCK)?;
+        Ok(sd)
+    }
+
+    /// The number of blocks on the card.
+    pub fn block_count(&self) -> u64 {
+        self.blocks
+    }
+
+    /// Returns `true` if the card is SDHC or SDXC and addressed by block.
+    pub fn is_high_capacity(&self) -> bool {
+        self.high_capacity
+    }
+
+    /// Spins until `done()` returns `true`, returning `error` if `timeout`
+    /// passes first.
+    fn wait<F: Fn(&Registers) -> bool>(&self, timeout: Duration, error: Error, done: F) -> Result<(), Error> {
+        let deadline = timer::deadline(timeout);
+        while !done(&*self.registers) {
+            if deadline.has_passed() {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resets the controller and routes every interrupt to `INTERRUPT`
+    /// without signaling the ARM.
+    fn reset(&mut self) -> Result<(), Error> {
+        self.registers.CONTROL0.write(0);
+        self.registers.CONTROL2.write(0);
+        self.registers.CONTROL1.write(Control1::ResetHost as u32);
+        self.wait(CONTROLLER_TIMEOUT, Error::ResetTimeout, |r| {
+            !r.CONTROL1.has_mask(Control1::ResetHost as u32)
+        })?;
+
+        self.registers.IRPT_EN.write(0);
+        self.registers.IRPT_MASK.write(!0);
+        self.registers.INTERRUPT.write(!0);
+        Ok(())
+    }
+
+    /// Stops the SD clock, sets it to at most `rate` Hz and restarts it.
+    fn set_clock(&mut self, rate: u32) -> Result<(), Error> {
+        self.wait(CONTROLLER_TIMEOUT, Error::ClockTimeout, |r| {
+            !r.STATUS.has_mask(Status::CommandInhibit as u32 | Status::DataInhibit as u32)
+        })?;
+
+        // Host controller version 3 and later divide by `2 * divider`; older
+        // controllers only support powers of two.
+        let version = (self.registers.SLOTISR_VER.read() >> 16) & 0xFF;
+        let divider = match (self.base_clock + 2 * rate - 1) / (2 * rate) {
+            d if version >= 2 => d.min(0x3FF),
+            d => d.next_power_of_two().min(0x80),
+        };
+
+        let divider_bits = (divider & 0xFF) << 8 | (divider >> 8 & 0b11) << 6;
+        self.registers.CONTROL1.and_mask(!(Control1::ClockEnable as u32));
+        self.registers.CONTROL1.and_mask(!(Control1::ClockDivider as u32 | Control1::DataTimeout as u32));
+        self.registers.CONTROL1.or_mask(divider_bits | 0xE << 16 | Control1::ClockInternalEnable as u32);
+        self.wait(CONTROLLER_TIMEOUT, Error::ClockTimeout, |r| {
+            r.CONTROL1.has_mask(Control1::ClockStable as u32)
+        })?;
+
+        self.registers.CONTROL1.or_mask(Control1::ClockEnable as u32);
+        timer::spin_sleep_us(10);
+        Ok(())
+    }
+
+    /// Runs the identification sequence and selects the card.
+    fn identify(&mut self) -> Result<(), Error> {
+        self.command(GO_IDLE_STATE, 0)?;
+
+        // Version 1 cards don't know `SEND_IF_COND` and can't be high capacity.
+        let v2 = match self.command(SEND_IF_COND, 0x100 | CHECK_PATTERN) {
+            Ok(response) if response[0] & 0xFFF == 0x100 | CHECK_PATTERN => true,
+            Ok(_) => return Err(Error::UnusableCard),
+            Err(Error::CommandTimeout(_)) => {
+                self.reset_command_line()?;
+                false
+            }
+            Err(error) => return Err(error)
+        };
+
+        let request = OCR_VOLTAGE_WINDOW | if v2 { OCR_HIGH_CAPACITY } else { 0 };
+        let deadline = timer::deadline(POWER_UP_TIMEOUT);
+        let ocr = loop {
+            let ocr = self.command(SD_SEND_OP_COND, request)?[0];
+            if ocr & OCR_READY != 0 {
+                break ocr;
+            } else if deadline.has_passed() {
+                return Err(Error::PowerUpTimeout);
+            }
+
+            timer::spin_sleep_ms(10);
+        };
+
+        if ocr & OCR_VOLTAGE_WINDOW == 0 {
+            return Err(Error::UnusableCard);
+        }
+
+        self.high_capacity = ocr & OCR_HIGH_CAPACITY != 0;
+        self.command(ALL_SEND_CID, 0)?;
+        self.rca = self.command(SEND_RELATIVE_ADDR, 0)?[0] & 0xFFFF_0000;
+        self.blocks = capacity(&self.command(SEND_CSD, self.rca)?);
+        self.command(SELECT_CARD, self.rca)?;
+
+        if !self.high_capacity {
+            self.command(SET_BLOCKLEN, BLOCK_SIZE as u32)?;
+        }
+
+        if self.supports_four_bit_bus()? {
+            self.command(SET_BUS_WIDTH, 0b10)?;
+            self.registers.CONTROL0.or_mask(Control0::FourBitBus as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the SCR and returns whether the card supports a 4-bit bus.
+    fn supports_four_bit_bus(&mut self) -> Result<bool, Error> {
+        let mut scr = [0u32; 2];
+        self.registers.BLKSIZECNT.write(1 << 16 | 8);
+        self.command(SEND_SCR, 0)?;
+        self.transfer(|registers| {
+            for word in scr.iter_mut() {
+                *word = registers.DATA.read();
+            }
+        }, Interrupt::ReadReady)?;
+
+        // The SCR is sent most significant byte first; `SD_BUS_WIDTHS` is the
+        // low nibble of its second byte.
+        Ok((scr[0] >> 8) & 0b0100 != 0)
+    }
+
+    /// Resets the command line after a command error.
+    fn reset_command_line(&mut self) -> Result<(), Error> {
+        const RESET_COMMAND: u32 = 1 << 25;
+        self.registers.CONTROL1.or_mask(RESET_COMMAND);
+        self.wait(CONTROLLER_TIMEOUT, Error::ResetTimeout, |r| {
+            !r.CONTROL1.has_mask(RESET_COMMAND)
+        })
+    }
+
+    /// Issues `command` with argument `arg`, preceded by `APP_CMD` if it is an
+    /// application command, and returns the response registers.
+    fn command(&mut self, command: Command, arg: u32) -> Result<[u32; 4], Error> {
+        if command.app {
+            let rca = self.rca;
+            self.command(APP_CMD, rca)?;
+        }
+
+        let inhibit = match command.response {
+            Response::ShortBusy => Status::CommandInhibit as u32 | Status::DataInhibit as u32,
+            _ => Status::CommandInhibit as u32
+        };
+
+        self.wait(COMMAND_TIMEOUT, Error::CommandTimeout(command.index), |r| {
+            !r.STATUS.has_mask(inhibit)
+        })?;
+
+        self.registers.INTERRUPT.write(!0);
+        self.registers.ARG1.write(arg);
+        self.registers.CMDTM.write(command.cmdtm());
+
+        let done = Interrupt::CommandDone as u32 | Interrupt::Error as u32;
+        self.wait(COMMAND_TIMEOUT, Error::CommandTimeout(command.index), |r| {
+            r.INTERRUPT.read() & done != 0
+        })?;
+
+        let status = self.registers.INTERRUPT.read();
+        self.registers.INTERRUPT.write(Interrupt::CommandDone as u32 | Interrupt::Errors as u32);
+        if status & Interrupt::CommandTimeout as u32 != 0 {
+            return Err(Error::CommandTimeout(command.index));
+        } else if status & Interrupt::Errors as u32 != 0 {
+            return Err(Error::CommandFailed {
+                index: command.index,
+                status: status & Interrupt::Errors as u32
+            });
+        }
+
+        let mut response = [0; 4];
+        for (value, register) in response.iter_mut().zip(self.registers.RESP.iter()) {
+            *value = register.read();
+        }
+
+        Ok(response)
+    }
+
+    /// Waits for `ready`, moves the block through `DATA` with `move_data` and
+    /// waits for the transfer to complete.
+    fn transfer<F: FnOnce(&mut Registers)>(&mut self, move_data: F, ready: Interrupt) -> Result<(), Error> {
+        let ready = ready as u32 | Interrupt::Error as u32;
+        self.wait(DATA_TIMEOUT, Error::DataTimeout, |r| r.INTERRUPT.read() & ready != 0)?;
+        self.check_data_error()?;
+
+        self.registers.INTERRUPT.write(ready);
+        move_data(&mut *self.registers);
+
+        let done = Interrupt::DataDone as u32 | Interrupt::Error as u32;
+        self.wait(DATA_TIMEOUT, Error::DataTimeout, |r| r.INTERRUPT.read() & done != 0)?;
+        self.check_data_error()?;
+        self.registers.INTERRUPT.write(Interrupt::DataDone as u32);
+        Ok(())
+    }
+
+    /// Returns and clears an error flagged during a transfer.
+    fn check_data_error(&mut self) -> Result<(), Error> {
+        let status = self.registers.INTERRUPT.read() & Interrupt::Errors as u32;
+        if status == 0 {
+            return Ok(());
+        }
+
+        self.registers.INTERRUPT.write(status);
+        match status & Interrupt::DataTimeout as u32 {
+            0 => Err(Error::DataFailed(status)),
+            _ => Err(Error::DataTimeout)
+        }
+    }
+
+    /// Checks that `n` is on the card and `len` is a block's length, then
+    /// returns the argument addressing block `n`.
+    fn block_argument(&self, n: u64, len: usize) -> Result<u32, Error> {
+        if len != BLOCK_SIZE {
+            return Err(Error::InvalidBuffer(len));
+        }
+
+        if n >= self.blocks || n > u32::max_value() as u64 {
+            return Err(Error::OutOfRange(n));
+        }
+
+        match self.high_capacity {
+            true => Ok(n as u32),
+            false => Ok(n as u32 * BLOCK_SIZE as u32)
+        }
+    }
+
+    /// Reads block `n` with `READ_SINGLE_BLOCK`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidBuffer` if `buf` isn't `BLOCK_SIZE` bytes long,
+    /// `Error::OutOfRange` if `n` is past the end of the card, and a command
+    /// or data error if the transfer fails.
+    pub fn read_block(&mut self, n: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let arg = self.block_argument(n, buf.len())?;
+        self.registers.BLKSIZECNT.write(1 << 16 | BLOCK_SIZE as u32);
+        self.command(READ_SINGLE_BLOCK, arg)?;
+        self.transfer(|registers| {
+            for chunk in buf.chunks_mut(4) {
+                let word = registers.DATA.read();
+                for (i, byte) in chunk.iter_mut().enumerate() {
+                    *byte = (word >> (8 * i)) as u8;
+                }
+            }
+        }, Interrupt::ReadReady)
+    }
+
+    /// Writes block `n` with `WRITE_BLOCK`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `read_block()`.
+    pub fn write_block(&mut self, n: u64, buf: &[u8]) -> Result<(), Error> {
+        let arg = self.block_argument(n, buf.len())?;
+        self.registers.BLKSIZECNT.write(1 << 16 | BLOCK_SIZE as u32);
+        self.command(WRITE_BLOCK, arg)?;
+        self.transfer(|registers| {
+            for chunk in buf.chunks(4) {
+                let word = chunk.iter().enumerate()
+                    .fold(0, |word, (i, &byte)| word | (byte as u32) << (8 * i));
+                registers.DATA.write(word);
+            }
+        }, Interrupt::WriteReady)
+    }
+}
+
+/// Returns the number of blocks on a card given its CSD as returned in the
+/// response registers, which omit the CSD's CRC byte.
+fn capacity(csd: &[u32; 4]) -> u64 {
+    match csd[3] >> 22 & 0b11 {
+        // CSD version 1: capacity = (C_SIZE + 1) << (C_SIZE_MULT + 2 + READ_BL_LEN).
+        0 => {
+            let c_size = (csd[1] >> 22 | (csd[2] & 0b11) << 10) as u64;
+            let c_size_mult = (csd[1] >> 7 & 0b111) as u64;
+            let read_bl_len = (csd[2] >> 8 & 0xF) as u64;
+            ((c_size + 1) << (c_size_mult + 2 + read_bl_len)) / BLOCK_SIZE as u64
+        }
+        // CSD version 2: capacity = (C_SIZE + 1) * 512KiB.
+        _ => ((csd[1] >> 8 & 0x3F_FFFF) as u64 + 1) * 1024
+    }
+}
+
+#[cfg(feature = "std")]
+mod block_device {
+    use std::io;
+    use storage::BlockDevice;
+
+    use super::{Sd, Error, BLOCK_SIZE};
+
+    impl From<Error> for io::Error {
+        fn from(error: Error) -> io::Error {
+            let (kind, message) = match error {
+                Error::ResetTimeout | Error::ClockTimeout | Error::CommandTimeout(_)
+                    | Error::DataTimeout | Error::PowerUpTimeout =>
+                    (io::ErrorKind::TimedOut, "SD card timed out"),
+                Error::OutOfRange(_) | Error::InvalidBuffer(_) =>
+                    (io::ErrorKind::InvalidInput, "invalid SD card block access"),
+                Error::CommandFailed { .. } | Error::DataFailed(_) =>
+                    (io::ErrorKind::Other, "SD card transfer failed"),
+                Error::UnusableCard => (io::ErrorKind::Other, "unusable SD card"),
+            };
+
+            io::Error::new(kind, message)
+        }
+    }
+
+    impl BlockDevice for Sd {
+        fn sector_size(&self) -> u64 {
+            BLOCK_SIZE as u64
+        }
+
+        fn sector_count(&self) -> u64 {
+            self.block_count()
+        }
+
+        fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+            if buf.len() >= BLOCK_SIZE {
+                self.read_block(n, &mut buf[..BLOCK_SIZE])?;
+                return Ok(BLOCK_SIZE);
+            }
+
+            let mut block = [0; BLOCK_SIZE];
+            self.read_block(n, &mut block)?;
+            buf.copy_from_slice(&block[..buf.len()]);
+            Ok(buf.len())
+        }
+
+        fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() >= BLOCK_SIZE {
+                self.write_block(n, &buf[..BLOCK_SIZE])?;
+                return Ok(BLOCK_SIZE);
+            }
+
+            // A partial write keeps the rest of the block intact.
+            let mut block = [0; BLOCK_SIZE];
+            self.read_block(n, &mut block)?;
+            block[..buf.len()].copy_from_slice(buf);
+            self.write_block(n, &block)?;
+            Ok(buf.len())
+        }
+    }
+}
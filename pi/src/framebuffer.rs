@@ -0,0 +1,177 @@
+use core::slice;
+
+use mailbox::{self, PropertyBuffer, PixelOrder};
+use mailbox::{AllocateBuffer, GetPitch, SetDepth, SetPhysicalSize, SetPixelOrder};
+use mailbox::{SetVirtualOffset, SetVirtualSize};
+
+/// Mask converting a VideoCore bus address into an ARM physical address.
+const BUS_ADDRESS_MASK: u32 = 0x3FFF_FFFF;
+
+/// The alignment requested for the framebuffer, in bytes.
+const BUFFER_ALIGNMENT: u32 = 16;
+
+/// Error type for framebuffer allocation failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The mailbox request failed.
+    Mailbox(mailbox::Error),
+    /// The VideoCore didn't allocate a framebuffer.
+    AllocationFailed,
+    /// The VideoCore picked a different depth than the one requested.
+    UnsupportedDepth(u32),
+}
+
+impl From<mailbox::Error> for Error {
+    fn from(error: mailbox::Error) -> Error {
+        Error::Mailbox(error)
+    }
+}
+
+/// The requested geometry of a framebuffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameBufferConfig {
+    /// The displayed width in pixels.
+    pub width: u32,
+    /// The displayed height in pixels.
+    pub height: u32,
+    /// The width of the buffer in pixels. May exceed `width` for panning.
+    pub virtual_width: u32,
+    /// The height of the buffer in pixels. May exceed `height` for panning or
+    /// double buffering.
+    pub virtual_height: u32,
+    /// The number of bits per pixel.
+    pub depth: u32,
+    /// The requested order of color components.
+    pub pixel_order: PixelOrder,
+}
+
+impl Default for FrameBufferConfig {
+    /// 640x480 with 32 bits per pixel, `0x00RRGGBB` when read as a `u32`.
+    fn default() -> FrameBufferConfig {
+        FrameBufferConfig {
+            width: 640,
+            height: 480,
+            virtual_width: 640,
+            virtual_height: 480,
+            depth: 32,
+            pixel_order: PixelOrder::Bgr,
+        }
+    }
+}
+
+/// A framebuffer allocated by the VideoCore.
+pub struct FrameBuffer {
+    buffer: &'static mut [u8],
+    width: u32,
+    height: u32,
+    virtual_width: u32,
+    virtual_height: u32,
+    depth: u32,
+    pitch: u32,
+    pixel_order: PixelOrder,
+}
+
+impl FrameBuffer {
+    /// Asks the VideoCore to allocate a framebuffer with geometry `config`.
+    /// The VideoCore may adjust the sizes; the accessors report the values
+    /// actually in use.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Mailbox` if the request fails,
+    /// `Error::AllocationFailed` if no buffer was allocated and
+    /// `Error::UnsupportedDepth` if the requested depth wasn't honored.
+    pub fn new(config: FrameBufferConfig) -> Result<FrameBuffer, Error> {
+        let mut request = PropertyBuffer::new();
+        let physical = request.push(&SetPhysicalSize {
+            width: config.width,
+            height: config.height
+        })?;
+        let virt = request.push(&SetVirtualSize {
+            width: config.virtual_width,
+            height: config.virtual_height
+        })?;
+        let depth = request.push(&SetDepth(config.depth))?;
+        let order = request.push(&SetPixelOrder(config.pixel_order))?;
+        request.push(&SetVirtualOffset { x: 0, y: 0 })?;
+        let memory = request.push(&AllocateBuffer { alignment: BUFFER_ALIGNMENT })?;
+        let pitch = request.push(&GetPitch)?;
+        request.send()?;
+
+        let (width, height) = request.get(physical)?;
+        let (virtual_width, virtual_height) = request.get(virt)?;
+        let depth = request.get(depth)?;
+        let memory = request.get(memory)?;
+        if memory.base == 0 || memory.size == 0 {
+            return Err(Error::AllocationFailed);
+        }
+
+        if depth != config.depth {
+            return Err(Error::UnsupportedDepth(depth));
+        }
+
+        let base = (memory.base & BUS_ADDRESS_MASK) as usize;
+        Ok(FrameBuffer {
+            buffer: unsafe { slice::from_raw_parts_mut(base as *mut u8, memory.size as usize) },
+            width: width,
+            height: height,
+            virtual_width: virtual_width,
+            virtual_height: virtual_height,
+            depth: depth,
+            pitch: request.get(pitch)?,
+            pixel_order: request.get(order)?,
+        })
+    }
+
+    /// The displayed width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The displayed height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The width of the whole buffer in pixels.
+    pub fn virtual_width(&self) -> u32 {
+        self.virtual_width
+    }
+
+    /// The height of the whole buffer in pixels.
+    pub fn virtual_height(&self) -> u32 {
+        self.virtual_height
+    }
+
+    /// The number of bits per pixel.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// The number of bytes per line.
+    pub fn pitch(&self) -> u32 {
+        self.pitch
+    }
+
+    /// The order of color components of a pixel.
+    pub fn pixel_order(&self) -> PixelOrder {
+        self.pixel_order
+    }
+
+    /// Pans the displayed area to start at (`x`, `y`) within the buffer.
+    pub fn set_virtual_offset(&mut self, x: u32, y: u32) -> Result<(), Error> {
+        mailbox::property(&SetVirtualOffset { x: x, y: y })?;
+        Ok(())
+    }
+
+    /// Returns the framebuffer's memory.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut *self.buffer
+    }
+
+    /// Consumes the framebuffer, returning its memory. The framebuffer is
+    /// never released, so the memory remains valid forever.
+    pub fn into_slice(self) -> &'static mut [u8] {
+        self.buffer
+    }
+}
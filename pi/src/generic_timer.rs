@@ -0,0 +1,96 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aarch64;
+use local_interrupt::{LocalController, LocalInterrupt};
+
+/// Enum representing bit fields of the `CNTP_CTL_EL0` register.
+#[repr(u64)]
+enum Control {
+    Enable = 1,
+    IMask = 1 << 1,
+    IStatus = 1 << 2,
+}
+
+/// The counter frequency in Hz, read from `CNTFRQ_EL0` on first use. `0` if it
+/// hasn't been read yet.
+static FREQUENCY: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the frequency of the system counter in Hz.
+pub fn frequency() -> u64 {
+    match FREQUENCY.load(Ordering::Relaxed) {
+        0 => {
+            let frequency = aarch64::cntfrq();
+            FREQUENCY.store(frequency as usize, Ordering::Relaxed);
+            frequency
+        }
+        frequency => frequency as u64
+    }
+}
+
+/// Returns the current value of the system counter.
+pub fn counter() -> u64 {
+    aarch64::cntpct()
+}
+
+/// Converts `ticks` of the system counter to microseconds.
+pub fn ticks_to_us(ticks: u64) -> u64 {
+    let frequency = frequency();
+    (ticks / frequency) * 1_000_000 + (ticks % frequency) * 1_000_000 / frequency
+}
+
+/// Converts `us` microseconds to ticks of the system counter, saturating on
+/// overflow.
+pub fn us_to_ticks(us: u64) -> u64 {
+    let frequency = frequency();
+    (us / 1_000_000).saturating_mul(frequency)
+        .saturating_add((us % 1_000_000) * frequency / 1_000_000)
+}
+
+/// Returns the index of the core executing this function.
+pub fn current_core() -> usize {
+    (aarch64::mpidr() & 0b11) as usize
+}
+
+/// The calling core's non-secure physical generic timer (`CNTP_*_EL0`).
+///
+/// The timer interrupt is routed to the calling core through the local
+/// interrupt controller, where it shows up as `LocalInterrupt::CntPns`.
+pub struct GenericTimer {
+    controller: LocalController
+}
+
+impl GenericTimer {
+    /// Returns a handle to the calling core's generic timer.
+    pub fn new() -> GenericTimer {
+        GenericTimer { controller: LocalController::new(current_core()) }
+    }
+
+    /// Arms the timer to raise an interrupt in `us` microseconds, replacing
+    /// any previously armed deadline.
+    pub fn arm(&mut self, us: u64) {
+        // TVAL is a signed 32-bit down-counter; clamp long waits.
+        let ticks = us_to_ticks(us).min(i32::max_value() as u64);
+        aarch64::set_cntp_tval(ticks);
+        aarch64::set_cntp_ctl(Control::Enable as u64);
+
+        self.controller.enable_timer(LocalInterrupt::CntPns);
+    }
+
+    /// Disarms the timer. No interrupt will be raised until it is re-armed.
+    pub fn disarm(&mut self) {
+        aarch64::set_cntp_ctl(0);
+        self.controller.disable_timer(LocalInterrupt::CntPns);
+    }
+
+    /// Acknowledges a fired timer interrupt by masking it at the timer. The
+    /// interrupt stays deasserted until the timer is re-armed with `arm()`.
+    pub fn acknowledge(&mut self) {
+        let mask = Control::Enable as u64 | Control::IMask as u64;
+        aarch64::set_cntp_ctl(mask);
+    }
+
+    /// Returns `true` if the armed deadline has passed.
+    pub fn is_pending(&self) -> bool {
+        aarch64::cntp_ctl() & Control::IStatus as u64 != 0
+    }
+}
@@ -153,9 +153,10 @@ impl Gpio<Output> {
     }
 }
 
-impl Gpio<Input> {
+impl<T> Gpio<T> {
     /// Reads the pin's value. Returns `true` if the level is high and `false`
-    /// if the level is low.
+    /// if the level is low. Output pins read back the level they're driven
+    /// to.
     pub fn level(&mut self) -> bool {
         // Ten pins to a GPIO reg.
         let pin_no = self.pin % 32;
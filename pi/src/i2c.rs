@@ -0,0 +1,363 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use timer::{self, Duration};
+use common::IO_BASE;
+use gpio::{Gpio, Function};
+use mailbox::{self, Clock};
+
+/// The base address of the `BSC1` registers. BSC1 is the controller wired to
+/// the I2C pins of the 40-pin header; BSC0 is reserved for the HAT EEPROM.
+const BSC1_BASE: usize = IO_BASE + 0x804000;
+
+/// The depth of the BSC FIFO in bytes.
+const FIFO_SIZE: usize = 16;
+
+/// The largest number of bytes a single transaction can carry.
+pub const MAX_TRANSFER: usize = 0xFFFF;
+
+/// The largest 7-bit address.
+pub const MAX_ADDRESS: u8 = 0x7F;
+
+/// The time a transaction may take before it is abandoned.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Enum representing bit fields of the `C` register.
+#[repr(u32)]
+enum Control {
+    Read = 1,
+    ClearFifo = 0b11 << 4,
+    Start = 1 << 7,
+    Enable = 1 << 15,
+}
+
+/// Enum representing bit fields of the `S` register.
+#[repr(u32)]
+enum Status {
+    TransferActive = 1,
+    Done = 1 << 1,
+    TxData = 1 << 4,
+    RxData = 1 << 5,
+    Nack = 1 << 8,
+    ClockTimeout = 1 << 9,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    C: Volatile<u32>,
+    S: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    A: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    DIV: Volatile<u32>,
+    DEL: Volatile<u32>,
+    CLKT: Volatile<u32>,
+}
+
+/// Configuration for `I2c::with_config()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct I2cConfig {
+    /// The desired SCL frequency in Hz.
+    pub speed: u32,
+    /// The number of SCL cycles a slave may stretch the clock for before the
+    /// transaction fails with `Error::ClockStretchTimeout`. `0` lets slaves
+    /// stretch the clock indefinitely.
+    pub clock_stretch_timeout: u16,
+}
+
+impl Default for I2cConfig {
+    /// 100kHz standard mode with the hardware's default stretch timeout of 64
+    /// SCL cycles.
+    fn default() -> I2cConfig {
+        I2cConfig { speed: 100_000, clock_stretch_timeout: 0x40 }
+    }
+}
+
+/// Error type for I2C failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The core clock rate couldn't be queried from the VideoCore.
+    ClockUnavailable,
+    /// The requested speed can't be generated from the core clock.
+    UnsupportedSpeed(u32),
+    /// The address doesn't fit in 7 bits.
+    InvalidAddress(u8),
+    /// The buffer is longer than `MAX_TRANSFER` bytes.
+    TooLong(usize),
+    /// The slave didn't acknowledge its address or a data byte.
+    Nack,
+    /// The slave held SCL low for longer than the clock stretch timeout.
+    ClockStretchTimeout,
+    /// The transaction didn't complete within the transaction timeout.
+    Timeout,
+}
+
+/// An I2C master on the BSC1 controller.
+pub struct I2c {
+    registers: &'static mut Registers,
+    timeout: Duration,
+    speed: u32,
+}
+
+impl I2c {
+    /// Initializes BSC1 with the default configuration: 100kHz with a clock
+    /// stretch timeout of 64 cycles. See `with_config()`.
+    ///
+    /// If the core clock can't be queried, it is assumed to run at 250MHz.
+    pub fn new() -> I2c {
+        I2c::with_config(I2cConfig::default()).unwrap_or_else(|_| {
+            I2c::init(2500, 100_000, I2cConfig::default().clock_stretch_timeout)
+        })
+    }
+
+    /// Initializes BSC1 by setting GPIO pins 2 and 3 to alternative function
+    /// 0 (SDA1/SCL1), setting the clock divider to the one closest to, but not
+    /// faster than, `config.speed`, and setting the clock stretch timeout.
+    ///
+    /// Transactions time out after 100ms by default. To change the timeout,
+    /// use `set_timeout()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ClockUnavailable` if the core clock can't be queried.
+    /// Returns `Error::UnsupportedSpeed` if `config.speed` is zero or too slow
+    /// for the 16-bit clock divider.
+    pub fn with_config(config: I2cConfig) -> Result<I2c, Error> {
+        let clock = mailbox::clock_rate(Clock::Core)
+            .map_err(|_| Error::ClockUnavailable)?;
+        if config.speed == 0 {
+            return Err(Error::UnsupportedSpeed(config.speed));
+        }
+
+        // The divider is always rounded down to an even number by hardware.
+        let speed = config.speed as u64;
+        let divider = (clock as u64 + speed - 1) / speed;
+        let divider = (divider + (divider & 1)).max(2);
+        if divider > 0xFFFE {
+            return Err(Error::UnsupportedSpeed(config.speed));
+        }
+
+        let divider = divider as u32;
+        Ok(I2c::init(divider as u16, clock / divider, config.clock_stretch_timeout))
+    }
+
+    /// Enables BSC1 with the clock divider `divider`.
+    fn init(divider: u16, speed: u32, clock_stretch_timeout: u16) -> I2c {
+        Gpio::new(2).into_alt(Function::Alt0);
+        Gpio::new(3).into_alt(Function::Alt0);
+
+        let registers = unsafe { &mut *(BSC1_BASE as *mut Registers) };
+        registers.DIV.write(divider as u32);
+        registers.CLKT.write(clock_stretch_timeout as u32);
+        registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+
+        let mut i2c = I2c { registers, timeout: DEFAULT_TIMEOUT, speed };
+        i2c.clear_status();
+        i2c
+    }
+
+    /// The SCL frequency actually generated, in Hz.
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// Sets the time a transaction may take before it fails with
+    /// `Error::Timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the number of SCL cycles a slave may stretch the clock for. `0`
+    /// disables the timeout.
+    pub fn set_clock_stretch_timeout(&mut self, cycles: u16) {
+        self.registers.CLKT.write(cycles as u32);
+    }
+
+    /// Clears the sticky status bits.
+    fn clear_status(&mut self) {
+        let sticky = Status::Done as u32 | Status::Nack as u32 | Status::ClockTimeout as u32;
+        self.registers.S.write(sticky);
+    }
+
+    /// Returns the error latched in the status register, if any.
+    fn latched_error(&self) -> Option<Error> {
+        if self.registers.S.has_mask(Status::Nack as u32) {
+            Some(Error::Nack)
+        } else if self.registers.S.has_mask(Status::ClockTimeout as u32) {
+            Some(Error::ClockStretchTimeout)
+        } else {
+            None
+        }
+    }
+
+    /// Ends the current transaction, returning `result` unless the status
+    /// register latched an error.
+    fn finish(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        let result = match self.latched_error() {
+            Some(error) => Err(error),
+            None => result
+        };
+
+        if result.is_err() {
+            self.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        }
+
+        self.clear_status();
+        result
+    }
+
+    /// Checks `addr` and `len` and loads them into the `A` and `DLEN`
+    /// registers after clearing the FIFO and status.
+    fn prepare(&mut self, addr: u8, len: usize) -> Result<(), Error> {
+        if addr > MAX_ADDRESS {
+            return Err(Error::InvalidAddress(addr));
+        }
+
+        if len > MAX_TRANSFER {
+            return Err(Error::TooLong(len));
+        }
+
+        self.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        self.clear_status();
+        self.registers.A.write(addr as u32);
+        self.registers.DLEN.write(len as u32);
+        Ok(())
+    }
+
+    /// Feeds `data` into the FIFO of a running write until it completes.
+    fn pump_write(&mut self, data: &[u8], mut sent: usize) -> Result<(), Error> {
+        let deadline = timer::deadline(self.timeout);
+        while !self.registers.S.has_mask(Status::Done as u32) {
+            if self.latched_error().is_some() {
+                return Ok(());
+            } else if deadline.has_passed() {
+                return Err(Error::Timeout);
+            }
+
+            while sent < data.len() && self.registers.S.has_mask(Status::TxData as u32) {
+                self.registers.FIFO.write(data[sent] as u32);
+                sent += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drains the FIFO of a running read into `buf` until it completes.
+    fn pump_read(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let deadline = timer::deadline(self.timeout);
+        let mut received = 0;
+        while received < buf.len() {
+            if self.latched_error().is_some() {
+                return Ok(());
+            } else if deadline.has_passed() {
+                return Err(Error::Timeout);
+            }
+
+            while received < buf.len() && self.registers.S.has_mask(Status::RxData as u32) {
+                buf[received] = self.registers.FIFO.read() as u8;
+                received += 1;
+            }
+        }
+
+        while !self.registers.S.has_mask(Status::Done as u32) {
+            if deadline.has_passed() {
+                return Err(Error::Timeout);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data` to the slave at `addr`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidAddress` if `addr` isn't a 7-bit address,
+    /// `Error::TooLong` if `data` is longer than `MAX_TRANSFER`, `Error::Nack`
+    /// if the slave doesn't acknowledge, `Error::ClockStretchTimeout` if the
+    /// slave stretches the clock for too long and `Error::Timeout` if the
+    /// transaction doesn't complete in time.
+    pub fn write(&mut self, addr: u8, data: &[u8]) -> Result<(), Error> {
+        self.prepare(addr, data.len())?;
+
+        let prefill = data.len().min(FIFO_SIZE);
+        for &byte in &data[..prefill] {
+            self.registers.FIFO.write(byte as u32);
+        }
+
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32);
+        let result = self.pump_write(data, prefill);
+        self.finish(result)
+    }
+
+    /// Fills `buf` with bytes read from the slave at `addr`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `write()`.
+    pub fn read(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), Error> {
+        self.prepare(addr, buf.len())?;
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32 | Control::Read as u32);
+        let result = self.pump_read(buf);
+        self.finish(result)
+    }
+
+    /// Writes `data` to the slave at `addr`, then fills `buf` with bytes read
+    /// from it. This is how most devices expose registers: `data` selects a
+    /// register and `buf` receives its contents.
+    ///
+    /// When `data` fits in the FIFO the read follows a repeated start, without
+    /// releasing the bus. Otherwise the write and the read are two separate
+    /// transactions.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `write()`.
+    pub fn write_read(&mut self, addr: u8, data: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+        if data.len() > FIFO_SIZE || buf.len() > MAX_TRANSFER {
+            self.write(addr, data)?;
+            return self.read(addr, buf);
+        }
+
+        self.prepare(addr, data.len())?;
+        for &byte in data {
+            self.registers.FIFO.write(byte as u32);
+        }
+
+        // Once the write is underway, queueing a read makes the controller
+        // issue a repeated start instead of a stop when the write finishes.
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32);
+        let deadline = timer::deadline(self.timeout);
+        while !self.registers.S.has_mask(Status::TransferActive as u32) {
+            if self.registers.S.has_mask(Status::Done as u32) || self.latched_error().is_some() {
+                break;
+            } else if deadline.has_passed() {
+                return self.finish(Err(Error::Timeout));
+            }
+        }
+
+        if let Some(error) = self.latched_error() {
+            return self.finish(Err(error));
+        }
+
+        self.registers.DLEN.write(buf.len() as u32);
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32 | Control::Read as u32);
+        let result = self.pump_read(buf);
+        self.finish(result)
+    }
+
+    /// Returns `true` if a slave acknowledges a one byte read at `addr`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error other than `Error::Nack`.
+    pub fn probe(&mut self, addr: u8) -> Result<bool, Error> {
+        match self.read(addr, &mut [0]) {
+            Ok(()) => Ok(true),
+            Err(Error::Nack) => Ok(false),
+            Err(error) => Err(error)
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use common::IO_BASE;
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile};
+
+/// The base address of the ARM interrupt controller registers.
+const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
+
+/// A peripheral interrupt source from the BCM2837's GPU interrupt table.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Interrupt {
+    Timer1 = 1,
+    Timer3 = 3,
+    Usb = 9,
+    Aux = 29,
+    Gpio0 = 49,
+    Gpio1 = 50,
+    Gpio2 = 51,
+    Gpio3 = 52,
+    Uart = 57,
+}
+
+impl Interrupt {
+    /// The number of interrupt sources in `Interrupt`.
+    pub const MAX: usize = 9;
+
+    /// Returns an iterator over every interrupt source.
+    pub fn iter() -> impl Iterator<Item = Interrupt> {
+        use self::Interrupt::*;
+        [Timer1, Timer3, Usb, Aux, Gpio0, Gpio1, Gpio2, Gpio3, Uart].iter().cloned()
+    }
+
+    /// Returns the register index and bit mask for this interrupt.
+    fn reg_and_mask(self) -> (usize, u32) {
+        let irq = self as usize;
+        (irq / 32, 1 << (irq % 32))
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    IRQ_BASIC_PENDING: ReadVolatile<u32>,
+    IRQ_PENDING: [ReadVolatile<u32>; 2],
+    FIQ_CONTROL: Volatile<u32>,
+    ENABLE_IRQ: [Volatile<u32>; 2],
+    ENABLE_BASIC_IRQ: Volatile<u32>,
+    DISABLE_IRQ: [Volatile<u32>; 2],
+    DISABLE_BASIC_IRQ: Volatile<u32>,
+}
+
+/// An interrupt controller. Used to enable and disable interrupts as well as
+/// to check if an interrupt is pending.
+pub struct Controller {
+    registers: &'static mut Registers
+}
+
+impl Controller {
+    /// Returns a new handle to the interrupt controller.
+    pub fn new() -> Controller {
+        Controller {
+            registers: unsafe { &mut *(INT_BASE as *mut Registers) },
+        }
+    }
+
+    /// Enables the interrupt `int`.
+    pub fn enable(&mut self, int: Interrupt) {
+        let (reg, mask) = int.reg_and_mask();
+        self.registers.ENABLE_IRQ[reg].write(mask);
+    }
+
+    /// Disables the interrupt `int`.
+    pub fn disable(&mut self, int: Interrupt) {
+        let (reg, mask) = int.reg_and_mask();
+        self.registers.DISABLE_IRQ[reg].write(mask);
+    }
+
+    /// Returns `true` if `int` is pending. Otherwise, returns `false`.
+    pub fn is_pending(&self, int: Interrupt) -> bool {
+        let (reg, mask) = int.reg_and_mask();
+        self.registers.IRQ_PENDING[reg].has_mask(mask)
+    }
+}
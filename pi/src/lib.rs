@@ -11,8 +11,22 @@
 #[cfg(feature = "std")]
 extern crate core;
 extern crate volatile;
+#[cfg(feature = "std")]
+extern crate storage;
 
+pub mod aarch64;
 pub mod timer;
 pub mod uart;
 pub mod gpio;
 pub mod common;
+pub mod interrupt;
+pub mod ring;
+pub mod mailbox;
+pub mod framebuffer;
+pub mod local_interrupt;
+pub mod generic_timer;
+pub mod spi;
+pub mod i2c;
+pub mod pwm;
+pub mod emmc;
+pub mod rng;
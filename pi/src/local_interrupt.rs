@@ -0,0 +1,112 @@
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+/// The base address of the Pi 3's ARM local peripherals (QA7).
+const LOCAL_BASE: usize = 0x4000_0000;
+
+/// The number of cores served by the local interrupt controller.
+pub const NUM_CORES: usize = 4;
+
+/// A per-core interrupt source from the local peripherals' IRQ source
+/// register.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LocalInterrupt {
+    /// Secure physical generic timer.
+    CntPs = 0,
+    /// Non-secure physical generic timer.
+    CntPns = 1,
+    /// Hypervisor generic timer.
+    CntHp = 2,
+    /// Virtual generic timer.
+    CntV = 3,
+    Mailbox0 = 4,
+    Mailbox1 = 5,
+    Mailbox2 = 6,
+    Mailbox3 = 7,
+    /// Any interrupt from the GPU interrupt controller (`interrupt::Controller`).
+    Gpu = 8,
+    Pmu = 9,
+    AxiOutstanding = 10,
+    LocalTimer = 11,
+}
+
+impl LocalInterrupt {
+    /// Returns `true` if this is one of the four generic timer interrupts.
+    fn is_timer(self) -> bool {
+        (self as usize) < 4
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CONTROL: Volatile<u32>,
+    __r0: Reserved<u32>,
+    PRESCALER: Volatile<u32>,
+    GPU_ROUTING: Volatile<u32>,
+    PMU_SET: Volatile<u32>,
+    PMU_CLEAR: Volatile<u32>,
+    __r1: Reserved<u32>,
+    TIMER_LS: Volatile<u32>,
+    TIMER_MS: Volatile<u32>,
+    LOCAL_ROUTING: Volatile<u32>,
+    __r2: Reserved<u32>,
+    AXI_COUNTERS: Volatile<u32>,
+    AXI_IRQ: Volatile<u32>,
+    LOCAL_TIMER_CONTROL: Volatile<u32>,
+    LOCAL_TIMER_FLAGS: Volatile<u32>,
+    __r3: Reserved<u32>,
+    TIMER_INT_CONTROL: [Volatile<u32>; NUM_CORES],
+    MAILBOX_INT_CONTROL: [Volatile<u32>; NUM_CORES],
+    IRQ_SOURCE: [ReadVolatile<u32>; NUM_CORES],
+    FIQ_SOURCE: [ReadVolatile<u32>; NUM_CORES],
+}
+
+/// The local interrupt controller of a single core.
+pub struct LocalController {
+    core: usize,
+    registers: &'static mut Registers
+}
+
+impl LocalController {
+    /// Returns a handle to the local interrupt controller of core `core`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` >= `NUM_CORES`.
+    pub fn new(core: usize) -> LocalController {
+        if core >= NUM_CORES {
+            panic!("LocalController::new(): core {} exceeds maximum of {}", core, NUM_CORES - 1);
+        }
+
+        LocalController {
+            core: core,
+            registers: unsafe { &mut *(LOCAL_BASE as *mut Registers) },
+        }
+    }
+
+    /// Routes the generic timer interrupt `int` to this core as an IRQ.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `int` is not a generic timer interrupt.
+    pub fn enable_timer(&mut self, int: LocalInterrupt) {
+        assert!(int.is_timer(), "LocalController::enable_timer(): {:?} is not a timer", int);
+        self.registers.TIMER_INT_CONTROL[self.core].or_mask(1 << int as u32);
+    }
+
+    /// Stops routing the generic timer interrupt `int` to this core.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `int` is not a generic timer interrupt.
+    pub fn disable_timer(&mut self, int: LocalInterrupt) {
+        assert!(int.is_timer(), "LocalController::disable_timer(): {:?} is not a timer", int);
+        self.registers.TIMER_INT_CONTROL[self.core].and_mask(!(1 << int as u32));
+    }
+
+    /// Returns `true` if `int` is pending as an IRQ on this core.
+    pub fn is_pending(&self, int: LocalInterrupt) -> bool {
+        self.registers.IRQ_SOURCE[self.core].has_mask(1 << int as u32)
+    }
+}
@@ -0,0 +1,314 @@
+//! The VideoCore mailbox and its property tag interface.
+//!
+//! Requests to the VideoCore are made by building a `PropertyBuffer` holding
+//! one or more typed tags (see the `tags` module), sending it, and decoding
+//! each tag's response:
+//!
+//! ```rust,ignore
+//! let mut buffer = PropertyBuffer::new();
+//! let serial = buffer.push(&GetBoardSerial)?;
+//! let temp = buffer.push(&GetTemperature)?;
+//! buffer.send()?;
+//!
+//! let serial = buffer.get(serial)?;
+//! let millicelsius = buffer.get(temp)?;
+//! ```
+//!
+//! Encoding and decoding only operate on the buffer's words, so everything but
+//! `PropertyBuffer::send()` can be exercised on the host.
+
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{fence, Ordering};
+
+use aarch64;
+use common::IO_BASE;
+use timer::{self, Duration};
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, WriteVolatile, Reserved};
+
+pub mod tags;
+
+pub use self::tags::*;
+
+/// The base address of the VideoCore mailbox registers.
+const MAILBOX_BASE: usize = IO_BASE + 0xB880;
+
+/// How long the VideoCore is given to accept a request and to respond to it.
+const CALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The mailbox channel used for the property tag interface (ARM to VC).
+pub const PROPERTY_CHANNEL: u32 = 8;
+
+/// The size of a `PropertyBuffer` in 32-bit words.
+pub const BUFFER_WORDS: usize = 1024;
+
+/// Code in a buffer's header marking a request.
+const REQUEST: u32 = 0;
+
+/// Code in a buffer's header marking a successful response.
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+
+/// Code in a buffer's header marking a request the VideoCore couldn't parse.
+const RESPONSE_ERROR: u32 = 0x8000_0001;
+
+/// Bit set in a tag's request/response code once the VideoCore processed it.
+const TAG_RESPONSE: u32 = 1 << 31;
+
+/// The number of header words of a buffer: total size and code.
+const HEADER_WORDS: usize = 2;
+
+/// The number of header words of a tag: identifier, value buffer size and
+/// request/response code.
+const TAG_HEADER_WORDS: usize = 3;
+
+/// Enum representing bit fields of the mailbox `STATUS` register.
+#[repr(u32)]
+enum Status {
+    Empty = 1 << 30,
+    Full = 1 << 31,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    READ: ReadVolatile<u32>,
+    __r0: [Reserved<u32>; 3],
+    PEEK: ReadVolatile<u32>,
+    SENDER: ReadVolatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONFIG: Volatile<u32>,
+    WRITE: WriteVolatile<u32>,
+    __r1: [Reserved<u32>; 5],
+    /// The `STATUS` register of mailbox 1, which `WRITE` belongs to.
+    WRITE_STATUS: ReadVolatile<u32>,
+}
+
+/// Error type for property interface failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// There is no room left in the buffer for another tag.
+    BufferFull,
+    /// The VideoCore couldn't parse the request buffer.
+    RequestFailed,
+    /// The buffer's header doesn't hold a response code. The raw code is
+    /// included.
+    InvalidResponse(u32),
+    /// The VideoCore didn't process the tag with the given identifier.
+    TagNotProcessed(u32),
+    /// The response for the tag with the given identifier was longer than its
+    /// value buffer.
+    ResponseTruncated(u32),
+    /// The VideoCore didn't accept the request or respond to it in time.
+    TimedOut,
+}
+
+/// The VideoCore mailbox (mailbox 0 for reads, mailbox 1 for writes).
+pub struct Mailbox {
+    registers: &'static mut Registers
+}
+
+impl Mailbox {
+    /// Returns a new handle to the mailbox.
+    pub fn new() -> Mailbox {
+        Mailbox {
+            registers: unsafe { &mut *(MAILBOX_BASE as *mut Registers) },
+        }
+    }
+
+    /// Sends the 16-byte aligned buffer at `addr` on channel `channel` and
+    /// blocks until the VideoCore responds on the same channel. If the buffer
+    /// is cacheable, the caller must clean it from the data cache before the
+    /// call and invalidate it after.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::TimedOut` if the VideoCore doesn't accept the buffer
+    /// or respond to it within `CALL_TIMEOUT`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addr` is not 16-byte aligned or `channel` is larger than 15.
+    pub fn call(&mut self, channel: u32, addr: usize) -> Result<(), Error> {
+        assert!(addr & 0xF == 0, "Mailbox::call(): buffer is not 16-byte aligned");
+        assert!(channel <= 0xF, "Mailbox::call(): invalid channel {}", channel);
+
+        let message = addr as u32 | channel;
+        let deadline = timer::deadline(CALL_TIMEOUT);
+        fence(Ordering::SeqCst);
+        while self.registers.WRITE_STATUS.has_mask(Status::Full as u32) {
+            if deadline.has_passed() {
+                return Err(Error::TimedOut);
+            }
+        }
+        self.registers.WRITE.write(message);
+
+        loop {
+            while self.registers.STATUS.has_mask(Status::Empty as u32) {
+                if deadline.has_passed() {
+                    return Err(Error::TimedOut);
+                }
+            }
+            if self.registers.READ.read() == message {
+                fence(Ordering::SeqCst);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A typed property tag.
+///
+/// A tag's value buffer is used for both the request and the response, so
+/// `WORDS` must be large enough to hold either.
+pub trait Tag {
+    /// The tag identifier.
+    const ID: u32;
+
+    /// The size of the tag's value buffer in 32-bit words.
+    const WORDS: usize;
+
+    /// The decoded response.
+    type Response;
+
+    /// Writes the request values into `values`, which is `WORDS` long and
+    /// zeroed.
+    fn encode(&self, values: &mut [u32]);
+
+    /// Decodes the response from `values`, which is `WORDS` long. `len` is the
+    /// length of the response in bytes as reported by the VideoCore.
+    fn decode(values: &[u32], len: usize) -> Self::Response;
+}
+
+/// A handle to a tag pushed into a `PropertyBuffer`, used to retrieve the
+/// tag's response.
+#[derive(Debug)]
+pub struct TagHandle<T: Tag> {
+    offset: usize,
+    _tag: PhantomData<T>
+}
+
+/// A property tag buffer: a header followed by any number of tags and an end
+/// tag. The buffer is 16-byte aligned as the mailbox requires, and aligned to
+/// a cache line so that cache maintenance on its words touches nothing else.
+#[repr(C, align(64))]
+pub struct PropertyBuffer {
+    words: [u32; BUFFER_WORDS],
+    len: usize,
+}
+
+impl PropertyBuffer {
+    /// Returns a new, empty request buffer.
+    pub fn new() -> PropertyBuffer {
+        let mut buffer = PropertyBuffer { words: [0; BUFFER_WORDS], len: HEADER_WORDS };
+        buffer.finish();
+        buffer
+    }
+
+    /// Appends the request for `tag` to the buffer, returning a handle that
+    /// can be passed to `get()` after the buffer has been sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferFull` if the tag doesn't fit in the buffer.
+    pub fn push<T: Tag>(&mut self, tag: &T) -> Result<TagHandle<T>, Error> {
+        let offset = self.len;
+        // Keep one word for the end tag.
+        if offset + TAG_HEADER_WORDS + T::WORDS + 1 > BUFFER_WORDS {
+            return Err(Error::BufferFull);
+        }
+
+        self.words[offset] = T::ID;
+        self.words[offset + 1] = (T::WORDS * 4) as u32;
+        self.words[offset + 2] = REQUEST;
+
+        let values = &mut self.words[offset + TAG_HEADER_WORDS..][..T::WORDS];
+        for value in values.iter_mut() {
+            *value = 0;
+        }
+        tag.encode(values);
+
+        self.len = offset + TAG_HEADER_WORDS + T::WORDS;
+        self.finish();
+        Ok(TagHandle { offset: offset, _tag: PhantomData })
+    }
+
+    /// Writes the header and end tag for the tags pushed so far.
+    fn finish(&mut self) {
+        self.words[self.len] = 0;
+        self.words[0] = ((self.len + 1) * 4) as u32;
+        self.words[1] = REQUEST;
+    }
+
+    /// Returns the encoded words of the buffer, including the header and the
+    /// end tag.
+    pub fn as_words(&self) -> &[u32] {
+        &self.words[..self.len + 1]
+    }
+
+    /// Returns the encoded words of the buffer mutably. Useful to simulate a
+    /// VideoCore response.
+    pub fn as_words_mut(&mut self) -> &mut [u32] {
+        &mut self.words[..self.len + 1]
+    }
+
+    /// Sends the buffer to the VideoCore and blocks until it responds.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::TimedOut` if the VideoCore didn't respond,
+    /// `Error::RequestFailed` if it couldn't parse the buffer and
+    /// `Error::InvalidResponse` if it returned an unknown code.
+    pub fn send(&mut self) -> Result<(), Error> {
+        let addr = self.words.as_mut_ptr() as usize;
+        let len = mem::size_of_val(&self.words);
+        aarch64::clean_dcache(addr, len);
+        Mailbox::new().call(PROPERTY_CHANNEL, addr)?;
+        aarch64::invalidate_dcache(addr, len);
+        self.check()
+    }
+
+    /// Checks the response code in the buffer's header.
+    pub fn check(&self) -> Result<(), Error> {
+        match self.words[1] {
+            RESPONSE_SUCCESS => Ok(()),
+            RESPONSE_ERROR => Err(Error::RequestFailed),
+            code => Err(Error::InvalidResponse(code))
+        }
+    }
+
+    /// Decodes the response for the tag referred to by `handle`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::TagNotProcessed` if the VideoCore didn't mark the tag
+    /// as processed and `Error::ResponseTruncated` if the response didn't fit
+    /// in the tag's value buffer.
+    pub fn get<T: Tag>(&self, handle: TagHandle<T>) -> Result<T::Response, Error> {
+        let code = self.words[handle.offset + 2];
+        if code & TAG_RESPONSE == 0 {
+            return Err(Error::TagNotProcessed(T::ID));
+        }
+
+        let len = (code & !TAG_RESPONSE) as usize;
+        if len > T::WORDS * 4 {
+            return Err(Error::ResponseTruncated(T::ID));
+        }
+
+        let values = &self.words[handle.offset + TAG_HEADER_WORDS..][..T::WORDS];
+        Ok(T::decode(values, len))
+    }
+}
+
+/// Sends a buffer holding only `tag` and returns its decoded response.
+pub fn property<T: Tag>(tag: &T) -> Result<T::Response, Error> {
+    let mut buffer = PropertyBuffer::new();
+    let handle = buffer.push(tag)?;
+    buffer.send()?;
+    buffer.get(handle)
+}
+
+/// Queries the VideoCore for the current rate of `clock` in Hz.
+pub fn clock_rate(clock: Clock) -> Result<u32, Error> {
+    property(&GetClockRate(clock))
+}
@@ -0,0 +1,552 @@
+use core::{fmt, str};
+
+use super::Tag;
+
+/// A clock managed by the VideoCore.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// A voltage rail managed by the VideoCore.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Voltage {
+    Core = 1,
+    SdramC = 2,
+    SdramP = 3,
+    SdramI = 4,
+}
+
+/// A device whose power can be controlled by the VideoCore.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Device {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+/// A range of physical memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryRange {
+    /// The base address of the range.
+    pub base: u32,
+    /// The size of the range in bytes.
+    pub size: u32,
+}
+
+/// The power state of a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PowerState {
+    /// Whether the device is powered on.
+    pub on: bool,
+    /// Whether the device exists.
+    pub exists: bool,
+}
+
+impl PowerState {
+    fn decode(state: u32) -> PowerState {
+        PowerState { on: state & 0b01 != 0, exists: state & 0b10 == 0 }
+    }
+}
+
+/// Gets the VideoCore firmware revision.
+pub struct GetFirmwareRevision;
+
+impl Tag for GetFirmwareRevision {
+    const ID: u32 = 0x0000_0001;
+    const WORDS: usize = 1;
+    type Response = u32;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[0]
+    }
+}
+
+/// Gets the board model.
+pub struct GetBoardModel;
+
+impl Tag for GetBoardModel {
+    const ID: u32 = 0x0001_0001;
+    const WORDS: usize = 1;
+    type Response = u32;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[0]
+    }
+}
+
+/// Gets the board revision code.
+pub struct GetBoardRevision;
+
+impl Tag for GetBoardRevision {
+    const ID: u32 = 0x0001_0002;
+    const WORDS: usize = 1;
+    type Response = u32;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[0]
+    }
+}
+
+/// Gets the board's ethernet MAC address, in network byte order.
+pub struct GetMacAddress;
+
+impl Tag for GetMacAddress {
+    const ID: u32 = 0x0001_0003;
+    const WORDS: usize = 2;
+    type Response = [u8; 6];
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> [u8; 6] {
+        let (lo, hi) = (values[0].to_le_bytes(), values[1].to_le_bytes());
+        [lo[0], lo[1], lo[2], lo[3], hi[0], hi[1]]
+    }
+}
+
+/// Gets the board's serial number.
+pub struct GetBoardSerial;
+
+impl Tag for GetBoardSerial {
+    const ID: u32 = 0x0001_0004;
+    const WORDS: usize = 2;
+    type Response = u64;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u64 {
+        values[0] as u64 | (values[1] as u64) << 32
+    }
+}
+
+/// Gets the range of memory reserved for the ARM.
+pub struct GetArmMemory;
+
+impl Tag for GetArmMemory {
+    const ID: u32 = 0x0001_0005;
+    const WORDS: usize = 2;
+    type Response = MemoryRange;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> MemoryRange {
+        MemoryRange { base: values[0], size: values[1] }
+    }
+}
+
+/// Gets the range of memory reserved for the VideoCore.
+pub struct GetVcMemory;
+
+impl Tag for GetVcMemory {
+    const ID: u32 = 0x0001_0006;
+    const WORDS: usize = 2;
+    type Response = MemoryRange;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> MemoryRange {
+        MemoryRange { base: values[0], size: values[1] }
+    }
+}
+
+/// Gets the power state of a device.
+pub struct GetPowerState(pub Device);
+
+impl Tag for GetPowerState {
+    const ID: u32 = 0x0002_0001;
+    const WORDS: usize = 2;
+    type Response = PowerState;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0 as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> PowerState {
+        PowerState::decode(values[1])
+    }
+}
+
+/// Powers a device on or off. If `wait` is set, the VideoCore waits for the
+/// power state to stabilize before responding.
+pub struct SetPowerState {
+    pub device: Device,
+    pub on: bool,
+    pub wait: bool,
+}
+
+impl Tag for SetPowerState {
+    const ID: u32 = 0x0002_8001;
+    const WORDS: usize = 2;
+    type Response = PowerState;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.device as u32;
+        values[1] = (self.on as u32) | (self.wait as u32) << 1;
+    }
+
+    fn decode(values: &[u32], _: usize) -> PowerState {
+        PowerState::decode(values[1])
+    }
+}
+
+/// Gets the current rate of a clock in Hz.
+pub struct GetClockRate(pub Clock);
+
+impl Tag for GetClockRate {
+    const ID: u32 = 0x0003_0002;
+    const WORDS: usize = 2;
+    type Response = u32;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0 as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[1]
+    }
+}
+
+/// Gets the maximum supported rate of a clock in Hz.
+pub struct GetMaxClockRate(pub Clock);
+
+impl Tag for GetMaxClockRate {
+    const ID: u32 = 0x0003_0004;
+    const WORDS: usize = 2;
+    type Response = u32;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0 as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[1]
+    }
+}
+
+/// Gets the minimum supported rate of a clock in Hz.
+pub struct GetMinClockRate(pub Clock);
+
+impl Tag for GetMinClockRate {
+    const ID: u32 = 0x0003_0007;
+    const WORDS: usize = 2;
+    type Response = u32;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0 as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[1]
+    }
+}
+
+/// Sets the rate of a clock in Hz. Responds with the rate actually set. If
+/// `skip_turbo` is set, changing the ARM clock doesn't raise the other clocks
+/// and voltages to their turbo settings.
+pub struct SetClockRate {
+    pub clock: Clock,
+    pub rate: u32,
+    pub skip_turbo: bool,
+}
+
+impl Tag for SetClockRate {
+    const ID: u32 = 0x0003_8002;
+    const WORDS: usize = 3;
+    type Response = u32;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.clock as u32;
+        values[1] = self.rate;
+        values[2] = self.skip_turbo as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[1]
+    }
+}
+
+/// Gets the SoC temperature in thousandths of a degree Celsius.
+pub struct GetTemperature;
+
+impl Tag for GetTemperature {
+    const ID: u32 = 0x0003_0006;
+    const WORDS: usize = 2;
+    type Response = u32;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[1]
+    }
+}
+
+/// Gets the temperature in thousandths of a degree Celsius at which the
+/// firmware starts throttling the clocks.
+pub struct GetMaxTemperature;
+
+impl Tag for GetMaxTemperature {
+    const ID: u32 = 0x0003_000A;
+    const WORDS: usize = 2;
+    type Response = u32;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[1]
+    }
+}
+
+/// Gets the voltage of a rail in microvolts. Responds with `None` if the rail
+/// doesn't exist.
+pub struct GetVoltage(pub Voltage);
+
+impl Tag for GetVoltage {
+    const ID: u32 = 0x0003_0003;
+    const WORDS: usize = 2;
+    type Response = Option<u32>;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0 as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> Option<u32> {
+        match values[1] {
+            0x8000_0000 => None,
+            microvolts => Some(microvolts)
+        }
+    }
+}
+
+/// The maximum length of the kernel command line in bytes.
+pub const COMMAND_LINE_MAX: usize = 1024;
+
+/// The kernel command line passed by the firmware.
+pub struct CommandLine {
+    bytes: [u8; COMMAND_LINE_MAX],
+    len: usize,
+}
+
+impl CommandLine {
+    /// Returns the raw bytes of the command line.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Returns the command line as a string, or `None` if it isn't UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        str::from_utf8(self.as_bytes()).ok()
+    }
+}
+
+impl fmt::Debug for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.as_str() {
+            Some(s) => write!(f, "{:?}", s),
+            None => write!(f, "{:?}", self.as_bytes())
+        }
+    }
+}
+
+/// Gets the kernel command line.
+pub struct GetCommandLine;
+
+impl Tag for GetCommandLine {
+    const ID: u32 = 0x0005_0001;
+    const WORDS: usize = COMMAND_LINE_MAX / 4;
+    type Response = CommandLine;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], len: usize) -> CommandLine {
+        let mut line = CommandLine { bytes: [0; COMMAND_LINE_MAX], len: 0 };
+        for (i, value) in values.iter().enumerate() {
+            line.bytes[i * 4..][..4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        // The firmware may include a trailing NUL in the reported length.
+        line.len = line.bytes[..len].iter().position(|&b| b == 0).unwrap_or(len);
+        line
+    }
+}
+
+/// The order of the color components of a framebuffer pixel.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelOrder {
+    Bgr = 0,
+    Rgb = 1,
+}
+
+/// Allocates a framebuffer with the given alignment. Responds with the bus
+/// address and size of the framebuffer; a size of `0` means the allocation
+/// failed.
+pub struct AllocateBuffer {
+    pub alignment: u32,
+}
+
+impl Tag for AllocateBuffer {
+    const ID: u32 = 0x0004_0001;
+    const WORDS: usize = 2;
+    type Response = MemoryRange;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.alignment;
+    }
+
+    fn decode(values: &[u32], _: usize) -> MemoryRange {
+        MemoryRange { base: values[0], size: values[1] }
+    }
+}
+
+/// Releases the framebuffer.
+pub struct ReleaseBuffer;
+
+impl Tag for ReleaseBuffer {
+    const ID: u32 = 0x0004_8001;
+    const WORDS: usize = 0;
+    type Response = ();
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(_: &[u32], _: usize) {  }
+}
+
+/// Gets the number of bytes per framebuffer line.
+pub struct GetPitch;
+
+impl Tag for GetPitch {
+    const ID: u32 = 0x0004_0008;
+    const WORDS: usize = 1;
+    type Response = u32;
+
+    fn encode(&self, _: &mut [u32]) {  }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[0]
+    }
+}
+
+/// Sets the physical (display) size in pixels. Responds with the size set.
+pub struct SetPhysicalSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tag for SetPhysicalSize {
+    const ID: u32 = 0x0004_8003;
+    const WORDS: usize = 2;
+    type Response = (u32, u32);
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.width;
+        values[1] = self.height;
+    }
+
+    fn decode(values: &[u32], _: usize) -> (u32, u32) {
+        (values[0], values[1])
+    }
+}
+
+/// Sets the virtual (buffer) size in pixels. Responds with the size set.
+pub struct SetVirtualSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tag for SetVirtualSize {
+    const ID: u32 = 0x0004_8004;
+    const WORDS: usize = 2;
+    type Response = (u32, u32);
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.width;
+        values[1] = self.height;
+    }
+
+    fn decode(values: &[u32], _: usize) -> (u32, u32) {
+        (values[0], values[1])
+    }
+}
+
+/// Sets the number of bits per pixel. Responds with the depth set.
+pub struct SetDepth(pub u32);
+
+impl Tag for SetDepth {
+    const ID: u32 = 0x0004_8005;
+    const WORDS: usize = 1;
+    type Response = u32;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0;
+    }
+
+    fn decode(values: &[u32], _: usize) -> u32 {
+        values[0]
+    }
+}
+
+/// Sets the pixel order. Responds with the order set.
+pub struct SetPixelOrder(pub PixelOrder);
+
+impl Tag for SetPixelOrder {
+    const ID: u32 = 0x0004_8006;
+    const WORDS: usize = 1;
+    type Response = PixelOrder;
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.0 as u32;
+    }
+
+    fn decode(values: &[u32], _: usize) -> PixelOrder {
+        match values[0] {
+            0 => PixelOrder::Bgr,
+            _ => PixelOrder::Rgb
+        }
+    }
+}
+
+/// Sets the offset of the displayed area within the virtual buffer. Responds
+/// with the offset set.
+pub struct SetVirtualOffset {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Tag for SetVirtualOffset {
+    const ID: u32 = 0x0004_8009;
+    const WORDS: usize = 2;
+    type Response = (u32, u32);
+
+    fn encode(&self, values: &mut [u32]) {
+        values[0] = self.x;
+        values[1] = self.y;
+    }
+
+    fn decode(values: &[u32], _: usize) -> (u32, u32) {
+        (values[0], values[1])
+    }
+}
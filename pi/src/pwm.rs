@@ -0,0 +1,455 @@
+use volatile::prelude::*;
+use volatile::{Volatile, Reserved};
+
+use timer::{self, Duration};
+use common::IO_BASE;
+use gpio::{Gpio, Function};
+
+/// The base address of the `PWM` registers.
+const PWM_BASE: usize = IO_BASE + 0x20C000;
+
+/// The base address of the PWM clock's `CM_PWMCTL`/`CM_PWMDIV` registers.
+const PWM_CLOCK_BASE: usize = IO_BASE + 0x1010A0;
+
+/// Must be written to the top byte of every clock manager register write.
+const CLOCK_PASSWORD: u32 = 0x5A << 24;
+
+/// How long the clock manager may take to stop or start the PWM clock.
+const CLOCK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// How long the FIFO may stay full, or take to drain.
+const FIFO_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Enum representing bit fields of the `CM_PWMCTL` register.
+#[repr(u32)]
+enum ClockControl {
+    Source = 0b1111,
+    Enable = 1 << 4,
+    Busy = 1 << 7,
+}
+
+/// Enum representing the per-channel bit fields of the `CTL` register. Shift
+/// left by 8 for channel 2.
+#[repr(u32)]
+enum Control {
+    Enable = 1,
+    UseFifo = 1 << 5,
+    MarkSpace = 1 << 7,
+}
+
+/// The `CLRF1` bit of the `CTL` register, which clears the shared FIFO.
+const CLEAR_FIFO: u32 = 1 << 6;
+
+/// Enum representing bit fields of the `STA` register.
+#[repr(u32)]
+enum Status {
+    Full = 1,
+    Empty = 1 << 1,
+    /// The write error, read error, gap and bus error flags.
+    Errors = 0b1_0011_1100,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTL: Volatile<u32>,
+    STA: Volatile<u32>,
+    DMAC: Volatile<u32>,
+    __r0: Reserved<u32>,
+    RNG1: Volatile<u32>,
+    DAT1: Volatile<u32>,
+    FIF1: Volatile<u32>,
+    __r1: Reserved<u32>,
+    RNG2: Volatile<u32>,
+    DAT2: Volatile<u32>,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct ClockRegisters {
+    CTL: Volatile<u32>,
+    DIV: Volatile<u32>,
+}
+
+/// One of the two PWM channels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    /// PWM0: GPIO 12 and 40 (Alt 0), GPIO 18 (Alt 5). GPIO 40 is the left
+    /// audio channel.
+    One,
+    /// PWM1: GPIO 13, 41 and 45 (Alt 0), GPIO 19 (Alt 5). GPIO 41 is the right
+    /// audio channel.
+    Two,
+}
+
+impl Channel {
+    /// The shift applied to `Control` bits for this channel.
+    fn shift(&self) -> u32 {
+        match *self {
+            Channel::One => 0,
+            Channel::Two => 8,
+        }
+    }
+
+    /// Returns the channel driven by GPIO pin `pin` and the alternative
+    /// function selecting it, if the pin has a PWM function.
+    fn for_pin(pin: u8) -> Option<(Channel, Function)> {
+        match pin {
+            12 | 40 => Some((Channel::One, Function::Alt0)),
+            18 => Some((Channel::One, Function::Alt5)),
+            13 | 41 | 45 => Some((Channel::Two, Function::Alt0)),
+            19 => Some((Channel::Two, Function::Alt5)),
+            _ => None
+        }
+    }
+}
+
+/// How a channel turns a data/range ratio into a pulse train.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Spreads the high clock cycles as evenly as possible over the range.
+    /// Well suited to audio, where the output is low-pass filtered.
+    Balanced,
+    /// Outputs a single high pulse of `data` cycles every `range` cycles.
+    /// Suited to LEDs and servos.
+    MarkSpace,
+}
+
+/// The oscillator the PWM clock is derived from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockSource {
+    /// The 19.2MHz crystal oscillator.
+    Oscillator = 1,
+    /// The 500MHz PLLD.
+    PllD = 6,
+}
+
+impl ClockSource {
+    /// The source's frequency in Hz.
+    pub fn rate(&self) -> u32 {
+        match *self {
+            ClockSource::Oscillator => 19_200_000,
+            ClockSource::PllD => 500_000_000,
+        }
+    }
+}
+
+/// Error type for PWM failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The GPIO pin has no PWM function.
+    InvalidPin(u8),
+    /// The clock divider isn't in the range `2..=4095`.
+    InvalidDivider(u16),
+    /// The clock manager didn't stop or start the clock in time.
+    ClockBusy,
+    /// The sample rate can't be generated with at least 8 bits of resolution.
+    UnsupportedRate(u32),
+    /// The FIFO didn't accept a word, or didn't drain, in time: the PWM clock
+    /// isn't running or the channels stopped reading it.
+    FifoTimeout,
+}
+
+/// The PWM controller.
+pub struct Pwm {
+    registers: &'static mut Registers,
+    clock: &'static mut ClockRegisters,
+    clock_rate: u32,
+}
+
+impl Pwm {
+    /// Returns a handle to the PWM controller. The clock and channels are left
+    /// as they are; call `set_clock()` before enabling a channel.
+    pub fn new() -> Pwm {
+        Pwm {
+            registers: unsafe { &mut *(PWM_BASE as *mut Registers) },
+            clock: unsafe { &mut *(PWM_CLOCK_BASE as *mut ClockRegisters) },
+            clock_rate: 0,
+        }
+    }
+
+    /// Sets GPIO pin `pin` to the alternative function outputting its PWM
+    /// channel and returns that channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPin` if `pin` has no PWM function.
+    pub fn route_pin(&mut self, pin: u8) -> Result<Channel, Error> {
+        let (channel, function) = Channel::for_pin(pin).ok_or(Error::InvalidPin(pin))?;
+        Gpio::new(pin).into_alt(function);
+        Ok(channel)
+    }
+
+    /// Waits until the clock manager's busy flag equals `busy`.
+    fn wait_clock(&self, busy: bool) -> Result<(), Error> {
+        let deadline = timer::deadline(CLOCK_TIMEOUT);
+        while self.clock.CTL.has_mask(ClockControl::Busy as u32) != busy {
+            if deadline.has_passed() {
+                return Err(Error::ClockBusy);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the PWM clock at `source`'s rate divided by `divider` and returns
+    /// the resulting rate in Hz. Both channels are stopped while the clock
+    /// changes and restarted afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidDivider` if `divider` isn't in `2..=4095` and
+    /// `Error::ClockBusy` if the clock manager doesn't respond.
+    pub fn set_clock(&mut self, source: ClockSource, divider: u16) -> Result<u32, Error> {
+        if divider < 2 || divider > 0xFFF {
+            return Err(Error::InvalidDivider(divider));
+        }
+
+        let control = self.registers.CTL.read();
+        self.registers.CTL.write(0);
+
+        let stopped = self.clock.CTL.read() & ClockControl::Source as u32;
+        self.clock.CTL.write(CLOCK_PASSWORD | stopped);
+        self.wait_clock(false)?;
+
+        self.clock.DIV.write(CLOCK_PASSWORD | (divider as u32) << 12);
+        self.clock.CTL.write(CLOCK_PASSWORD | source as u32);
+        self.clock.CTL.write(CLOCK_PASSWORD | source as u32 | ClockControl::Enable as u32);
+        self.wait_clock(true)?;
+
+        self.registers.CTL.write(control);
+        self.clock_rate = source.rate() / divider as u32;
+        Ok(self.clock_rate)
+    }
+
+    /// The PWM clock rate set by `set_clock()` in Hz, or `0` if it hasn't
+    /// been set.
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Sets the number of clock cycles in one period of `channel`.
+    pub fn set_range(&mut self, channel: Channel, range: u32) {
+        match channel {
+            Channel::One => self.registers.RNG1.write(range),
+            Channel::Two => self.registers.RNG2.write(range),
+        }
+    }
+
+    /// Sets the number of high clock cycles per period of `channel`.
+    pub fn set_data(&mut self, channel: Channel, data: u32) {
+        match channel {
+            Channel::One => self.registers.DAT1.write(data),
+            Channel::Two => self.registers.DAT2.write(data),
+        }
+    }
+
+    /// Sets how `channel` generates pulses.
+    pub fn set_mode(&mut self, channel: Channel, mode: Mode) {
+        let bit = (Control::MarkSpace as u32) << channel.shift();
+        match mode {
+            Mode::Balanced => self.registers.CTL.and_mask(!bit),
+            Mode::MarkSpace => self.registers.CTL.or_mask(bit),
+        }
+    }
+
+    /// Makes `channel` take its data from the FIFO instead of its data
+    /// register. Channels sharing the FIFO take turns reading from it.
+    pub fn set_use_fifo(&mut self, channel: Channel, use_fifo: bool) {
+        let bit = (Control::UseFifo as u32) << channel.shift();
+        match use_fifo {
+            true => self.registers.CTL.or_mask(bit),
+            false => self.registers.CTL.and_mask(!bit),
+        }
+    }
+
+    /// Starts outputting pulses on `channel`.
+    pub fn enable(&mut self, channel: Channel) {
+        self.registers.CTL.or_mask((Control::Enable as u32) << channel.shift());
+    }
+
+    /// Stops outputting pulses on `channel`.
+    pub fn disable(&mut self, channel: Channel) {
+        self.registers.CTL.and_mask(!((Control::Enable as u32) << channel.shift()));
+    }
+
+    /// Discards the contents of the FIFO.
+    pub fn clear_fifo(&mut self) {
+        self.registers.CTL.or_mask(CLEAR_FIFO);
+    }
+
+    /// Returns `true` if the FIFO can't accept another word.
+    pub fn is_fifo_full(&self) -> bool {
+        self.registers.STA.has_mask(Status::Full as u32)
+    }
+
+    /// Returns `true` if the FIFO has been drained.
+    pub fn is_fifo_empty(&self) -> bool {
+        self.registers.STA.has_mask(Status::Empty as u32)
+    }
+
+    /// Writes `word` to the FIFO, blocking until there is room for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoTimeout` if the FIFO stays full for too long.
+    pub fn write_fifo(&mut self, word: u32) -> Result<(), Error> {
+        let deadline = timer::deadline(FIFO_TIMEOUT);
+        while self.is_fifo_full() {
+            if deadline.has_passed() {
+                return Err(Error::FifoTimeout);
+            }
+        }
+
+        self.registers.FIF1.write(word);
+        Ok(())
+    }
+
+    /// Blocks until the FIFO has been drained.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoTimeout` if the FIFO doesn't drain in time.
+    pub fn wait_fifo_empty(&self) -> Result<(), Error> {
+        let deadline = timer::deadline(FIFO_TIMEOUT);
+        while !self.is_fifo_empty() {
+            if deadline.has_passed() {
+                return Err(Error::FifoTimeout);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears the FIFO error, gap and bus error flags.
+    pub fn clear_errors(&mut self) {
+        self.registers.STA.write(Status::Errors as u32);
+    }
+}
+
+/// The PWM clock divider used for PCM playback: 500MHz / 2 = 250MHz.
+const PCM_DIVIDER: u16 = 2;
+
+/// The smallest range PCM playback accepts: 8 bits of resolution.
+const PCM_MIN_RANGE: u32 = 256;
+
+/// Mono PCM playback through the analog audio jack.
+///
+/// Each sample is written to the FIFO once per channel, so both sides of the
+/// jack play the same signal. Writes block while the FIFO is full, so
+/// playback runs at the sample rate for as long as samples are supplied.
+pub struct Pcm {
+    pwm: Pwm,
+    range: u32,
+    sample_rate: u32,
+}
+
+impl Pcm {
+    /// Routes GPIO pins 40 and 41 to the PWM channels and starts both
+    /// channels reading from the FIFO at `sample_rate` Hz, outputting silence.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnsupportedRate` if `sample_rate` is zero or too high
+    /// to leave 8 bits of resolution, and `Error::ClockBusy` if the PWM clock
+    /// can't be configured.
+    pub fn new(sample_rate: u32) -> Result<Pcm, Error> {
+        let mut pwm = Pwm::new();
+        let clock = ClockSource::PllD.rate() / PCM_DIVIDER as u32;
+        let range = match sample_rate {
+            0 => return Err(Error::UnsupportedRate(sample_rate)),
+            _ => clock / sample_rate
+        };
+
+        if range < PCM_MIN_RANGE {
+            return Err(Error::UnsupportedRate(sample_rate));
+        }
+
+        pwm.route_pin(40)?;
+        pwm.route_pin(41)?;
+        pwm.set_clock(ClockSource::PllD, PCM_DIVIDER)?;
+
+        pwm.registers.CTL.write(0);
+        for &channel in [Channel::One, Channel::Two].iter() {
+            pwm.set_range(channel, range);
+            pwm.set_mode(channel, Mode::Balanced);
+            pwm.set_use_fifo(channel, true);
+        }
+
+        pwm.clear_fifo();
+        pwm.clear_errors();
+        pwm.enable(Channel::One);
+        pwm.enable(Channel::Two);
+
+        Ok(Pcm { pwm, range, sample_rate: clock / range })
+    }
+
+    /// The sample rate actually generated, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of distinct output levels.
+    pub fn range(&self) -> u32 {
+        self.range
+    }
+
+    /// Queues one 16-bit sample, blocking until there is room in the FIFO.
+    /// `0x8000` is silence.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoTimeout` if the FIFO stops being read.
+    pub fn write_sample(&mut self, sample: u16) -> Result<(), Error> {
+        let level = ((sample as u64 * self.range as u64) >> 16) as u32;
+        self.pwm.write_fifo(level)?;
+        self.pwm.write_fifo(level)
+    }
+
+    /// Plays unsigned 8-bit samples. `0x80` is silence.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoTimeout` if the FIFO stops being read.
+    pub fn play_u8(&mut self, samples: &[u8]) -> Result<(), Error> {
+        for &sample in samples {
+            self.write_sample((sample as u16) << 8)?;
+        }
+
+        Ok(())
+    }
+
+    /// Plays unsigned 16-bit samples. `0x8000` is silence.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoTimeout` if the FIFO stops being read.
+    pub fn play_u16(&mut self, samples: &[u16]) -> Result<(), Error> {
+        for &sample in samples {
+            self.write_sample(sample)?;
+        }
+
+        Ok(())
+    }
+
+    /// Blocks until every queued sample has been played.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoTimeout` if the FIFO doesn't drain in time.
+    pub fn drain(&mut self) -> Result<(), Error> {
+        self.pwm.wait_fifo_empty()
+    }
+}
+
+impl Drop for Pcm {
+    /// Stops both channels once the queued samples have played, or the FIFO
+    /// has stopped draining.
+    fn drop(&mut self) {
+        let _ = self.drain();
+        self.pwm.disable(Channel::One);
+        self.pwm.disable(Channel::Two);
+        self.pwm.set_use_fifo(Channel::One, false);
+        self.pwm.set_use_fifo(Channel::Two, false);
+    }
+}
@@ -0,0 +1,84 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of bytes a `RingBuffer` can hold. Must be a power of two.
+pub const RING_SIZE: usize = 256;
+
+/// A fixed-size, lock-free, single-producer single-consumer byte queue.
+///
+/// The buffer is safe to share between exactly one producer (the only caller
+/// of `push`) and exactly one consumer (the only caller of `pop`), even when
+/// one of the two runs in interrupt context. `head` is only ever written by the
+/// producer and `tail` is only ever written by the consumer; both increase
+/// monotonically and are reduced modulo `RING_SIZE` when indexing.
+pub struct RingBuffer {
+    buffer: UnsafeCell<[u8; RING_SIZE]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+unsafe impl Sync for RingBuffer {  }
+
+impl RingBuffer {
+    /// Returns a new, empty `RingBuffer`.
+    pub const fn new() -> RingBuffer {
+        RingBuffer {
+            buffer: UnsafeCell::new([0; RING_SIZE]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the maximum number of bytes this buffer can hold.
+    pub fn capacity(&self) -> usize {
+        RING_SIZE
+    }
+
+    /// Returns the number of bytes currently in the buffer.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        head.wrapping_sub(tail)
+    }
+
+    /// Returns `true` if there are no bytes in the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be pushed into the buffer.
+    pub fn is_full(&self) -> bool {
+        self.len() == RING_SIZE
+    }
+
+    /// Appends `byte` to the buffer. Must only be called by the producer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(byte)` if the buffer is full.
+    pub fn push(&self, byte: u8) -> Result<(), u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) == RING_SIZE {
+            return Err(byte);
+        }
+
+        unsafe { (*self.buffer.get())[head % RING_SIZE] = byte; }
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Removes and returns the oldest byte in the buffer, or `None` if the
+    /// buffer is empty. Must only be called by the consumer.
+    pub fn pop(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let byte = unsafe { (*self.buffer.get())[tail % RING_SIZE] };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+}
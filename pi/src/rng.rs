@@ -0,0 +1,65 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use common::IO_BASE;
+
+/// The base address of the hardware random number generator's registers.
+const RNG_BASE: usize = IO_BASE + 0x104000;
+
+/// The number of numbers the generator discards after it is enabled, while
+/// its entropy source warms up.
+const WARMUP_COUNT: u32 = 0x40000;
+
+/// Enable bit of the `CTRL` register.
+const CTRL_ENABLE: u32 = 1;
+
+/// Interrupt disable bit of the `INT_MASK` register.
+const INT_MASK_DISABLE: u32 = 1;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTRL: Volatile<u32>,
+    STATUS: Volatile<u32>,
+    DATA: Volatile<u32>,
+    FF_THRESHOLD: Volatile<u32>,
+    INT_MASK: Volatile<u32>,
+}
+
+/// The hardware random number generator.
+pub struct Rng {
+    registers: &'static mut Registers,
+}
+
+impl Rng {
+    /// Returns a handle to the generator, enabling it with its interrupt
+    /// masked if it isn't running yet.
+    pub fn new() -> Rng {
+        let registers = unsafe { &mut *(RNG_BASE as *mut Registers) };
+        if !registers.CTRL.has_mask(CTRL_ENABLE) {
+            registers.STATUS.write(WARMUP_COUNT);
+            registers.INT_MASK.or_mask(INT_MASK_DISABLE);
+            registers.CTRL.or_mask(CTRL_ENABLE);
+        }
+
+        Rng { registers }
+    }
+
+    /// Returns a random 32-bit number, waiting for the generator to produce
+    /// one if its FIFO is empty.
+    pub fn next_u32(&mut self) -> u32 {
+        // The top byte of `STATUS` counts the words available in the FIFO.
+        while self.registers.STATUS.read() >> 24 == 0 {}
+        self.registers.DATA.read()
+    }
+
+    /// Fills `buf` with random bytes.
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(4) {
+            let word = self.next_u32();
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = (word >> (i * 8)) as u8;
+            }
+        }
+    }
+}
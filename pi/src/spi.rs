@@ -0,0 +1,191 @@
+use common::IO_BASE;
+use gpio::{Gpio, Function};
+use volatile::prelude::*;
+use volatile::Volatile;
+
+/// The base address of the `SPI0` registers.
+const SPI0_BASE: usize = IO_BASE + 0x204000;
+
+/// The GPIO pins used by SPI0: CE1, CE0, MISO, MOSI and SCLK.
+const SPI0_PINS: [u8; 5] = [7, 8, 9, 10, 11];
+
+/// The clock divider used until `set_clock_divider()` is called: ~3.9MHz
+/// with the default 250MHz core clock.
+const DEFAULT_DIVIDER: u16 = 64;
+
+/// Enum representing bit fields of the `CS` register.
+#[repr(u32)]
+enum Cs {
+    ChipSelect = 0b11,
+    Cpha = 1 << 2,
+    Cpol = 1 << 3,
+    ClearTx = 1 << 4,
+    ClearRx = 1 << 5,
+    TransferActive = 1 << 7,
+    Done = 1 << 16,
+    RxData = 1 << 17,
+    TxData = 1 << 18,
+    /// Chip select 0's polarity; chip selects 1 and 2 follow.
+    CsPol0 = 1 << 21,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CS: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    CLK: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    LTOH: Volatile<u32>,
+    DC: Volatile<u32>,
+}
+
+/// The SPI clock polarity and phase.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// CPOL = 0, CPHA = 0: idle low, sample on the leading edge.
+    Mode0,
+    /// CPOL = 0, CPHA = 1: idle low, sample on the trailing edge.
+    Mode1,
+    /// CPOL = 1, CPHA = 0: idle high, sample on the leading edge.
+    Mode2,
+    /// CPOL = 1, CPHA = 1: idle high, sample on the trailing edge.
+    Mode3,
+}
+
+/// The chip select line asserted during a transfer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChipSelect {
+    /// CE0 on GPIO 8.
+    Cs0 = 0,
+    /// CE1 on GPIO 7.
+    Cs1 = 1,
+    /// Don't assert any chip select; for devices with an external select.
+    None = 3,
+}
+
+/// The SPI0 master.
+pub struct Spi0 {
+    registers: &'static mut Registers
+}
+
+impl Spi0 {
+    /// Initializes SPI0 by switching GPIO pins 7 through 11 to alternative
+    /// function 0 and clearing the FIFOs. The bus starts in mode 0 with chip
+    /// select 0, active low, and a clock divider of 64.
+    pub fn new() -> Spi0 {
+        for &pin in SPI0_PINS.iter() {
+            Gpio::new(pin).into_alt(Function::Alt0);
+        }
+
+        let mut spi = Spi0 {
+            registers: unsafe { &mut *(SPI0_BASE as *mut Registers) },
+        };
+
+        spi.registers.CS.write(Cs::ClearTx as u32 | Cs::ClearRx as u32);
+        spi.set_clock_divider(DEFAULT_DIVIDER);
+        spi
+    }
+
+    /// Sets the SPI clock to the core clock divided by `divider`. Odd dividers
+    /// are rounded down to the next even number; `0` divides by 65536.
+    pub fn set_clock_divider(&mut self, divider: u16) {
+        self.registers.CLK.write((divider & !1) as u32);
+    }
+
+    /// Sets the clock divider closest to, but not above, `hz` for a core clock
+    /// of `core_clock` Hz. Returns the resulting SPI clock rate.
+    pub fn set_clock_speed(&mut self, core_clock: u32, hz: u32) -> u32 {
+        let divider = match hz {
+            0 => 0x1_0000,
+            _ => {
+                let divider = (core_clock as u64 + hz as u64 - 1) / hz as u64;
+                (divider + (divider & 1)).max(2).min(0x1_0000) as u32
+            }
+        };
+
+        self.set_clock_divider(divider as u16);
+        core_clock / divider
+    }
+
+    /// Sets the clock polarity and phase.
+    pub fn set_mode(&mut self, mode: Mode) {
+        let bits = match mode {
+            Mode::Mode0 => 0,
+            Mode::Mode1 => Cs::Cpha as u32,
+            Mode::Mode2 => Cs::Cpol as u32,
+            Mode::Mode3 => Cs::Cpol as u32 | Cs::Cpha as u32,
+        };
+
+        self.registers.CS.and_mask(!(Cs::Cpol as u32 | Cs::Cpha as u32));
+        self.registers.CS.or_mask(bits);
+    }
+
+    /// Selects the chip select line asserted during transfers.
+    pub fn set_chip_select(&mut self, cs: ChipSelect) {
+        self.registers.CS.and_mask(!(Cs::ChipSelect as u32));
+        self.registers.CS.or_mask(cs as u32);
+    }
+
+    /// Sets whether the chip select line `cs` is active high.
+    pub fn set_chip_select_polarity(&mut self, cs: ChipSelect, active_high: bool) {
+        if cs == ChipSelect::None {
+            return;
+        }
+
+        let bit = (Cs::CsPol0 as u32) << cs as u32;
+        match active_high {
+            true => self.registers.CS.or_mask(bit),
+            false => self.registers.CS.and_mask(!bit),
+        }
+    }
+
+    /// Runs a transfer of `len` bytes, feeding the TX FIFO with `tx(i)` for the
+    /// `i`th byte and passing each received byte to `rx(i, byte)`.
+    fn run<T, R>(&mut self, len: usize, tx: T, mut rx: R)
+        where T: Fn(usize) -> u8, R: FnMut(usize, u8)
+    {
+        self.registers.CS.or_mask(Cs::ClearTx as u32 | Cs::ClearRx as u32);
+        self.registers.CS.or_mask(Cs::TransferActive as u32);
+
+        let (mut sent, mut received) = (0, 0);
+        while received < len {
+            while sent < len && self.registers.CS.has_mask(Cs::TxData as u32) {
+                self.registers.FIFO.write(tx(sent) as u32);
+                sent += 1;
+            }
+
+            while received < len && self.registers.CS.has_mask(Cs::RxData as u32) {
+                rx(received, self.registers.FIFO.read() as u8);
+                received += 1;
+            }
+        }
+
+        while !self.registers.CS.has_mask(Cs::Done as u32) {
+            // Wait for the last byte to be shifted out.
+        }
+
+        self.registers.CS.and_mask(!(Cs::TransferActive as u32));
+    }
+
+    /// Writes `tx` while simultaneously reading the same number of bytes into
+    /// `rx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tx` and `rx` have different lengths.
+    pub fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) {
+        assert_eq!(tx.len(), rx.len(), "Spi0::transfer(): buffer lengths differ");
+        self.run(tx.len(), |i| tx[i], |i, byte| rx[i] = byte);
+    }
+
+    /// Writes every byte in `data`, discarding the bytes read back.
+    pub fn write(&mut self, data: &[u8]) {
+        self.run(data.len(), |i| data[i], |_, _| { });
+    }
+
+    /// Fills `buf` with bytes read while writing zeroes.
+    pub fn read(&mut self, buf: &mut [u8]) {
+        self.run(buf.len(), |_| 0, |i, byte| buf[i] = byte);
+    }
+}
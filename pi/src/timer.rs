@@ -1,7 +1,18 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aarch64::{self, mask_irq, restore_irq};
 use common::IO_BASE;
+use generic_timer::{self, GenericTimer};
 use volatile::prelude::*;
 use volatile::{Volatile, ReadVolatile};
 
+pub use core::time::Duration;
+#[cfg(feature = "std")]
+pub use std::time::Instant;
+
+/// Whether timer interrupts are handled, allowing `sleep` to wait in `wfi`.
+static SLEEP_INTERRUPTS: AtomicBool = AtomicBool::new(false);
+
 /// The base address for the ARM system timer registers.
 const TIMER_REG_BASE: usize = IO_BASE + 0x3000;
 
@@ -27,22 +38,20 @@ impl Timer {
         }
     }
 
-    /// Reads the system timer's counter and returns the 64-bit counter value.
-    /// The returned value is the number of elapsed microseconds.
+    /// Reads the ARM generic timer's counter and returns the number of
+    /// elapsed microseconds. The counter frequency is read once and cached.
     pub fn read(&self) -> u64 {
-        let count : u64;
-        let interval : u64;
-
-        unsafe {
-            asm!("mrs $0, cntpct_el0" : "=r"(count));
-            asm!("mrs $0, cntfrq_el0" : "=r"(interval));
-        }
-
-        count / (interval / 1000000)
+        generic_timer::ticks_to_us(generic_timer::counter())
+    }
 
-        // let mut x : u64 = u64::from(self.registers.CHI.read()) << 32;
-        // x += u64::from(self.registers.CLO.read());
-        // x
+    /// Sets up a match in timer 1 to occur `us` microseconds from now,
+    /// acknowledging any earlier match. If `Interrupt::Timer1` is enabled and
+    /// IRQs are unmasked, an interrupt is raised when the match occurs.
+    /// Channels 0 and 2 are used by the GPU.
+    pub fn tick_in(&mut self, us: u32) {
+        let now = self.registers.CLO.read();
+        self.registers.COMPARE[1].write(now.wrapping_add(us));
+        self.registers.CS.write(1 << 1);
     }
 }
 
@@ -51,19 +60,121 @@ pub fn current_time() -> u64 {
     Timer::new().read()
 }
 
+/// Sets up a match in timer 1 to occur `us` microseconds from now. See
+/// `Timer::tick_in()`.
+pub fn tick_in(us: u32) {
+    Timer::new().tick_in(us)
+}
+
+/// Converts `duration` to microseconds, saturating on overflow.
+fn as_micros(duration: Duration) -> u64 {
+    duration.as_secs()
+        .saturating_mul(1_000_000)
+        .saturating_add(duration.subsec_micros() as u64)
+}
+
+/// A point in time after which a blocking operation should give up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Deadline {
+    /// The time, in microseconds, at which the deadline passes. `None` if the
+    /// deadline never passes.
+    end: Option<u64>
+}
+
+/// Returns a deadline `timeout` from now. Deadlines too far in the future to
+/// be represented never pass.
+pub fn deadline(timeout: Duration) -> Deadline {
+    Deadline { end: current_time().checked_add(as_micros(timeout)) }
+}
+
+impl Deadline {
+    /// Returns a deadline that never passes.
+    pub fn never() -> Deadline {
+        Deadline { end: None }
+    }
+
+    /// Returns `true` if the deadline has passed.
+    pub fn has_passed(&self) -> bool {
+        match self.end {
+            Some(end) => current_time() >= end,
+            None => false
+        }
+    }
+
+    /// Returns the time left until the deadline passes, or `None` if it never
+    /// passes.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.end.map(|end| Duration::from_micros(end.saturating_sub(current_time())))
+    }
+}
+
+/// Spins until `duration` has passed.
+pub fn spin_sleep(duration: Duration) {
+    let deadline = deadline(duration);
+    while !deadline.has_passed() {
+        // Spin until the deadline.
+    }
+}
+
 /// Spins until `us` microseconds have passed.
 pub fn spin_sleep_us(us: u64) {
-    let t = Timer::new();
-    let r = t.read();
-    let target = r + us;
+    spin_sleep(Duration::from_micros(us));
+}
+
+/// Spins until `ms` milliseconds have passed.
+pub fn spin_sleep_ms(ms: u64) {
+    spin_sleep(Duration::from_millis(ms));
+}
+
+/// Allows `sleep` to wait for a timer interrupt in `wfi` instead of spinning.
+///
+/// Must only be called once `LocalInterrupt::CntPns` IRQs are routed to a
+/// handler that calls `GenericTimer::acknowledge()` and IRQs are unmasked.
+pub fn enable_sleep_interrupts() {
+    SLEEP_INTERRUPTS.store(true, Ordering::SeqCst);
+}
+
+/// Sleeps until `duration` has passed.
+///
+/// If timer interrupts have been enabled with `enable_sleep_interrupts()`,
+/// the core waits in `wfi` for a generic timer interrupt; otherwise, this
+/// function spins like `spin_sleep()`. The core may also be woken early by
+/// other interrupts, in which case it goes back to sleep.
+pub fn sleep(duration: Duration) {
+    if !SLEEP_INTERRUPTS.load(Ordering::SeqCst) {
+        return spin_sleep(duration);
+    }
+
+    let deadline = deadline(duration);
+    let mut timer = GenericTimer::new();
     loop {
-        if t.read() >= target {
-            break
+        // With IRQs masked, a timer interrupt that fires before `wfi` still
+        // wakes it; the handler runs once IRQs are restored.
+        let daif = mask_irq();
+        let remaining = match deadline.remaining() {
+            Some(remaining) => as_micros(remaining),
+            None => u64::max_value()
+        };
+
+        if remaining == 0 {
+            restore_irq(daif);
+            break;
         }
+
+        timer.arm(remaining);
+        aarch64::wfi();
+        restore_irq(daif);
     }
+
+    timer.disarm();
 }
 
-/// Spins until `ms` milliseconds have passed.
-pub fn spin_sleep_ms(ms: u64) {
-    spin_sleep_us(ms * 1000);
+/// Sleeps until `us` microseconds have passed. See `sleep()`.
+pub fn sleep_us(us: u64) {
+    sleep(Duration::from_micros(us));
+}
+
+/// Sleeps until `ms` milliseconds have passed. See `sleep()`.
+pub fn sleep_ms(ms: u64) {
+    sleep(Duration::from_millis(ms));
 }
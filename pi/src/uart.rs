@@ -1,11 +1,15 @@
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use volatile::prelude::*;
 use volatile::{Volatile, ReadVolatile, Reserved};
 
-use timer;
+use aarch64::{mask_irq, restore_irq, irq_masked};
+use timer::{self, Deadline, Duration};
 use common::IO_BASE;
 use gpio::{Gpio, Function};
+use ring::RingBuffer;
+use mailbox::{self, Clock};
 
 /// The base address for the `MU` registers.
 const MU_REG_BASE: usize = IO_BASE + 0x215040;
@@ -17,9 +21,146 @@ const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>
 #[repr(u8)]
 enum LsrStatus {
     DataReady = 1,
+    Overrun = 1 << 1,
     TxAvailable = 1 << 5,
 }
 
+/// Enum representing bit fields of the `AUX_MU_IER_REG` register.
+///
+/// The BCM2837 documentation has the RX and TX bits swapped. Bits 2 and 3 are
+/// documented as "don't care" but must be set for receive interrupts to fire.
+#[repr(u8)]
+enum IerFlag {
+    RxEnable = 1 | 0b1100,
+    TxEnable = 1 << 1,
+}
+
+/// Enum representing bit fields of the `AUX_MU_IIR_REG` register.
+#[repr(u8)]
+enum IirStatus {
+    NonePending = 1,
+}
+
+/// Bytes received by the interrupt handler, waiting to be read.
+static RX_BUFFER: RingBuffer = RingBuffer::new();
+
+/// Bytes waiting to be sent by the interrupt handler.
+static TX_BUFFER: RingBuffer = RingBuffer::new();
+
+/// Whether the mini UART is being driven by interrupts.
+static IRQ_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Number of received bytes dropped because `RX_BUFFER` was full.
+static RX_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of times the hardware RX FIFO overran before it was drained.
+static RX_OVERRUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether GPIO pins 14 and 15 have been switched to the mini UART.
+static GPIO_CONFIGURED: AtomicBool = AtomicBool::new(false);
+
+/// The largest accepted difference between a requested and an achieved baud
+/// rate, in hundredths of a percent.
+pub const MAX_BAUD_ERROR: u32 = 300;
+
+/// The number of data bits per frame. The mini UART always uses no parity and
+/// one stop bit.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataBits {
+    Seven = 0b00,
+    Eight = 0b11,
+}
+
+/// Configuration for `MiniUart::with_config()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UartConfig {
+    /// The desired baud rate.
+    pub baud: u32,
+    /// The number of data bits per frame.
+    pub data_bits: DataBits,
+}
+
+impl Default for UartConfig {
+    /// 115200 baud, 8N1.
+    fn default() -> UartConfig {
+        UartConfig { baud: 115200, data_bits: DataBits::Eight }
+    }
+}
+
+/// Error type for `MiniUart::with_config()` failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UartError {
+    /// The core clock rate couldn't be queried from the VideoCore.
+    ClockUnavailable,
+    /// The requested baud rate can't be generated from the core clock. The
+    /// closest achievable rate, if any, is included.
+    UnsupportedBaud { requested: u32, closest: Option<u32> },
+}
+
+/// A requested baud rate and the rate actually generated by the baud divider.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BaudRate {
+    /// The baud rate that was asked for.
+    pub requested: u32,
+    /// The baud rate the hardware is generating.
+    pub achieved: u32,
+}
+
+impl BaudRate {
+    /// Computes the baud divider closest to `requested` for a core clock of
+    /// `clock` Hz. The mini UART oversamples by 8, so the achieved rate is
+    /// `clock / (8 * (divisor + 1))`.
+    fn for_clock(clock: u32, requested: u32) -> Result<(u16, BaudRate), UartError> {
+        let unsupported = |closest| UartError::UnsupportedBaud { requested, closest };
+        if requested == 0 {
+            return Err(unsupported(None));
+        }
+
+        let step = 8 * requested as u64;
+        let divisor = (clock as u64 + step / 2) / step;
+        if divisor == 0 || divisor > 0x1_0000 {
+            return Err(unsupported(None));
+        }
+
+        let baud = BaudRate {
+            requested: requested,
+            achieved: (clock as u64 / (8 * divisor)) as u32,
+        };
+
+        if baud.error_hundredths().abs() as u32 > MAX_BAUD_ERROR {
+            return Err(unsupported(Some(baud.achieved)));
+        }
+
+        Ok(((divisor - 1) as u16, baud))
+    }
+
+    /// Returns the relative error of the achieved rate in hundredths of a
+    /// percent. Positive values mean the achieved rate is too fast.
+    pub fn error_hundredths(&self) -> i32 {
+        let diff = self.achieved as i64 - self.requested as i64;
+        (diff * 10000 / self.requested as i64) as i32
+    }
+}
+
+impl fmt::Display for BaudRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let error = self.error_hundredths();
+        let sign = if error < 0 { "-" } else { "+" };
+        write!(f, "{} baud ({}{}.{:02}%)", self.achieved, sign,
+               error.abs() / 100, error.abs() % 100)
+    }
+}
+
+/// Counters describing bytes lost by the mini UART.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Stats {
+    /// Received bytes dropped because the RX ring buffer was full.
+    pub rx_dropped: usize,
+    /// Hardware RX FIFO overruns: bytes lost before software could read them.
+    pub rx_overruns: usize,
+}
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -49,21 +190,59 @@ struct Registers {
 /// The Raspberry Pi's "mini UART".
 pub struct MiniUart {
     registers: &'static mut Registers,
-    timeout: Option<u32>,
+    timeout: Option<Duration>,
+    baud: BaudRate,
 }
 
 impl MiniUart {
-    /// Initializes the mini UART by enabling it as an auxiliary peripheral,
-    /// setting the data size to 8 bits, setting the BAUD rate to ~115200 (baud
-    /// divider of 270), setting GPIO pins 14 and 15 to alternative function 5
-    /// (TXD1/RDXD1), and finally enabling the UART transmitter and receiver.
+    /// Initializes the mini UART with the default configuration: 8 data bits
+    /// and ~115200 baud. See `with_config()`.
+    ///
+    /// If the core clock can't be queried, it is assumed to run at 250MHz and
+    /// the baud divider is set to 270.
     ///
     /// By default, reads will never time out. To set a read timeout, use
     /// `set_read_timeout()`.
     pub fn new() -> MiniUart {
-        // Set GPIO pins 14 and 15 to Alt 5 function.
-        Gpio::new(14).into_alt(Function::Alt5);
-        Gpio::new(15).into_alt(Function::Alt5);
+        MiniUart::with_config(UartConfig::default()).unwrap_or_else(|_| {
+            let baud = BaudRate { requested: 115200, achieved: 115313 };
+            MiniUart::init(270, DataBits::Eight, baud)
+        })
+    }
+
+    /// Initializes the mini UART by enabling it as an auxiliary peripheral,
+    /// setting the data size to `config.data_bits`, setting the baud divider
+    /// to the one closest to `config.baud` for the current core clock, setting
+    /// GPIO pins 14 and 15 to alternative function 5 (TXD1/RDXD1), and finally
+    /// enabling the UART transmitter and receiver. The GPIO pins are only
+    /// configured the first time a `MiniUart` is constructed.
+    ///
+    /// The core clock is queried from the VideoCore through the mailbox. Since
+    /// the core clock may be scaled by the firmware, `core_freq` should be
+    /// fixed in `config.txt` for the baud rate to remain stable.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UartError::ClockUnavailable` if the core clock can't be
+    /// queried. Returns `UartError::UnsupportedBaud` if the closest baud rate
+    /// the divider can represent is more than `MAX_BAUD_ERROR` away from
+    /// `config.baud`.
+    pub fn with_config(config: UartConfig) -> Result<MiniUart, UartError> {
+        let clock = mailbox::clock_rate(Clock::Core)
+            .map_err(|_| UartError::ClockUnavailable)?;
+        let (divisor, baud) = BaudRate::for_clock(clock, config.baud)?;
+        Ok(MiniUart::init(divisor, config.data_bits, baud))
+    }
+
+    /// Enables and configures the mini UART with the baud divider `divisor`.
+    fn init(divisor: u16, data_bits: DataBits, baud: BaudRate) -> MiniUart {
+        if !GPIO_CONFIGURED.load(Ordering::Relaxed) {
+            GPIO_CONFIGURED.store(true, Ordering::Relaxed);
+
+            // Set GPIO pins 14 and 15 to Alt 5 function.
+            Gpio::new(14).into_alt(Function::Alt5);
+            Gpio::new(15).into_alt(Function::Alt5);
+        }
 
         let registers = unsafe {
             // Enable the mini UART as an auxiliary device.
@@ -71,40 +250,137 @@ impl MiniUart {
             &mut *(MU_REG_BASE as *mut Registers)
         };
 
-        // Set UART to 8 bit mode.
-        registers.LCR.write(0b11);
-        // Set baud rate to 115200 (divisor of 270).
-        registers.BAUD.write(270);
+        // Disable TX and RX while reconfiguring.
+        registers.CNTL.write(0);
+        // Set the data size.
+        registers.LCR.write(data_bits as u8);
+        // Set the baud rate.
+        registers.BAUD.write(divisor);
         // Enable UART TX and RX.
         registers.CNTL.write(0b11);
 
         MiniUart {
             registers: registers,
-            timeout: None
+            timeout: None,
+            baud: baud
+        }
+    }
+
+    /// Returns the requested and achieved baud rates of this UART.
+    pub fn baud(&self) -> BaudRate {
+        self.baud
+    }
+
+    /// Set the read timeout to `timeout`.
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Switches the mini UART to interrupt-driven operation.
+    ///
+    /// Once enabled, received bytes are moved into an RX ring buffer and
+    /// written bytes are queued in a TX ring buffer; both are serviced by
+    /// `handle_irq()`. The caller is responsible for routing the `Aux`
+    /// interrupt to `handle_irq()`. Bytes written while IRQs are masked are
+    /// sent by polling, as described in `try_write()`.
+    pub fn enable_interrupts(&mut self) {
+        IRQ_ENABLED.store(true, Ordering::SeqCst);
+        self.registers.IER.write(IerFlag::RxEnable as u8);
+    }
+
+    /// Returns the number of dropped and overrun bytes since boot.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            rx_dropped: RX_DROPPED.load(Ordering::Relaxed),
+            rx_overruns: RX_OVERRUNS.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Attempts to write the byte `byte` without blocking.
+    ///
+    /// When interrupts are enabled, `byte` is queued in the TX ring buffer for
+    /// `handle_irq()` to send. If IRQs are masked on this core, though, the
+    /// handler can't run: queued bytes are moved into the TX FIFO here instead,
+    /// and `byte` is written to the FIFO once none are left. The same happens
+    /// when the ring buffer is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(byte)` if there is no room in the TX FIFO (or the TX ring
+    /// buffer, when interrupts are enabled).
+    pub fn try_write(&mut self, byte: u8) -> Result<(), u8> {
+        if IRQ_ENABLED.load(Ordering::Relaxed) {
+            // With IRQs masked, `handle_irq()` doesn't race with this for the
+            // TX ring buffer.
+            let daif = mask_irq();
+            let result = self.queue_byte(byte, irq_masked(daif));
+            restore_irq(daif);
+            return result;
+        }
+
+        if !self.registers.LSR.has_mask(LsrStatus::TxAvailable as u8) {
+            return Err(byte);
+        }
+
+        self.registers.IO.write(byte);
+        Ok(())
+    }
+
+    /// Queues `byte` in the TX ring buffer, or writes it to the TX FIFO if
+    /// `masked`, when IRQs were masked by the caller. Must be called with IRQs
+    /// masked.
+    fn queue_byte(&mut self, byte: u8, masked: bool) -> Result<(), u8> {
+        if masked || TX_BUFFER.is_full() {
+            fill_tx_fifo(self.registers);
         }
+
+        if masked {
+            // Bytes queued earlier go out first.
+            if !TX_BUFFER.is_empty()
+                || !self.registers.LSR.has_mask(LsrStatus::TxAvailable as u8) {
+                return Err(byte);
+            }
+
+            self.registers.IO.write(byte);
+            return Ok(());
+        }
+
+        TX_BUFFER.push(byte)?;
+        self.registers.IER.or_mask(IerFlag::TxEnable as u8);
+        Ok(())
     }
 
-    /// Set the read timeout to `milliseconds` milliseconds.
-    pub fn set_read_timeout(&mut self, milliseconds: u32) {
-        self.timeout = Some(milliseconds);
+    /// Attempts to read a byte without blocking. Returns `None` if no byte is
+    /// available.
+    pub fn try_read(&mut self) -> Option<u8> {
+        if IRQ_ENABLED.load(Ordering::Relaxed) {
+            return RX_BUFFER.pop();
+        }
+
+        if !self.has_byte() {
+            return None;
+        }
+
+        Some(self.registers.IO.read())
     }
 
     /// Write the byte `byte`. This method blocks until there is space available
-    /// in the output FIFO.
+    /// in the output FIFO (or TX ring buffer). See `try_write()`.
     pub fn write_byte(&mut self, byte: u8) {
-        while self.registers.LSR.read() & LsrStatus::TxAvailable as u8 == 0 {
+        while let Err(_) = self.try_write(byte) {
             // Spin while TX FIFO is full.
         }
-
-        // Add to FIFO.
-        self.registers.IO.write(byte);
     }
 
     /// Returns `true` if there is at least one byte ready to be read. If this
     /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
     /// return immediately. This method does not block.
     pub fn has_byte(&self) -> bool {
-        (self.registers.LSR.read() & LsrStatus::DataReady as u8) != 0
+        if IRQ_ENABLED.load(Ordering::Relaxed) {
+            return !RX_BUFFER.is_empty();
+        }
+
+        self.registers.LSR.has_mask(LsrStatus::DataReady as u8)
     }
 
     /// Blocks until there is a byte ready to read. If a read timeout is set,
@@ -116,16 +392,14 @@ impl MiniUart {
     /// returns `Ok(())`, a subsequent call to `read_byte` is guaranteed to
     /// return immediately.
     pub fn wait_for_byte(&self) -> Result<(), ()> {
-        let start = timer::current_time();
+        let deadline = match self.timeout {
+            Some(timeout) => timer::deadline(timeout),
+            None => Deadline::never()
+        };
 
         while !self.has_byte() {
-            match self.timeout {
-                Some(ms) => {
-                    if timer::current_time() > start + (ms as u64 * 1000 as u64) {
-                        return Err(())
-                    }
-                },
-                None => ()
+            if deadline.has_passed() {
+                return Err(())
             }
         }
 
@@ -134,11 +408,53 @@ impl MiniUart {
 
     /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
     pub fn read_byte(&mut self) -> u8 {
-        while !self.has_byte() {
-            // Spin while waiting for a byte.
+        loop {
+            if let Some(byte) = self.try_read() {
+                return byte;
+            }
+        }
+    }
+}
+
+/// Services a pending mini UART interrupt: moves received bytes from the RX
+/// FIFO into the RX ring buffer and refills the TX FIFO from the TX ring
+/// buffer. Must be called from the `Aux` IRQ handler, never concurrently with
+/// itself.
+pub fn handle_irq() {
+    let registers = unsafe { &mut *(MU_REG_BASE as *mut Registers) };
+
+    while !registers.IIR.has_mask(IirStatus::NonePending as u8) {
+        loop {
+            // The overrun flag is cleared on read, so check it on every read.
+            let status = registers.LSR.read();
+            if status & LsrStatus::Overrun as u8 != 0 {
+                RX_OVERRUNS.fetch_add(1, Ordering::Relaxed);
+            }
+
+            if status & LsrStatus::DataReady as u8 == 0 {
+                break;
+            }
+
+            if RX_BUFFER.push(registers.IO.read()).is_err() {
+                RX_DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
         }
 
-        self.registers.IO.read()
+        fill_tx_fifo(registers);
+        if TX_BUFFER.is_empty() {
+            registers.IER.and_mask(!(IerFlag::TxEnable as u8));
+        }
+    }
+}
+
+/// Moves bytes from the TX ring buffer into the TX FIFO until either is
+/// exhausted. Must only be called from `handle_irq()` or with IRQs masked.
+fn fill_tx_fifo(registers: &mut Registers) {
+    while registers.LSR.has_mask(LsrStatus::TxAvailable as u8) {
+        match TX_BUFFER.pop() {
+            Some(byte) => registers.IO.write(byte),
+            None => break
+        }
     }
 }
 
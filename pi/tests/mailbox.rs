@@ -0,0 +1,145 @@
+extern crate pi;
+
+use pi::mailbox::*;
+
+/// The code the VideoCore writes to a buffer's header on success.
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+
+/// The bit the VideoCore sets in a tag's code once it processed the tag.
+const TAG_RESPONSE: u32 = 1 << 31;
+
+/// Simulates the VideoCore answering the tag whose header starts at word
+/// `offset` with `values`, reporting a response of `len` bytes.
+fn respond(buffer: &mut PropertyBuffer, offset: usize, len: u32, values: &[u32]) {
+    let words = buffer.as_words_mut();
+    words[1] = RESPONSE_SUCCESS;
+    words[offset + 2] = TAG_RESPONSE | len;
+    words[offset + 3..][..values.len()].copy_from_slice(values);
+}
+
+#[test]
+fn empty_buffer_layout() {
+    let buffer = PropertyBuffer::new();
+    // Size in bytes, request code, end tag.
+    assert_eq!(buffer.as_words(), &[12, 0, 0]);
+}
+
+#[test]
+fn push_encodes_tags() {
+    let mut buffer = PropertyBuffer::new();
+    buffer.push(&GetClockRate(Clock::Core)).unwrap();
+    buffer.push(&SetClockRate { clock: Clock::Arm, rate: 1_200_000_000, skip_turbo: true })
+        .unwrap();
+    buffer.push(&GetBoardSerial).unwrap();
+
+    assert_eq!(buffer.as_words(), &[
+        // Header: size in bytes and request code.
+        19 * 4, 0,
+        // GetClockRate: ID, value buffer size, request code, values.
+        0x0003_0002, 8, 0, 4, 0,
+        // SetClockRate.
+        0x0003_8002, 12, 0, 3, 1_200_000_000, 1,
+        // GetBoardSerial: the value buffer is zeroed.
+        0x0001_0004, 8, 0, 0, 0,
+        // End tag.
+        0,
+    ][..]);
+}
+
+#[test]
+fn buffer_is_16_byte_aligned() {
+    let buffer = PropertyBuffer::new();
+    assert_eq!(buffer.as_words().as_ptr() as usize % 16, 0);
+
+    let buffers = [PropertyBuffer::new(), PropertyBuffer::new()];
+    assert_eq!(buffers[1].as_words().as_ptr() as usize % 16, 0);
+}
+
+#[test]
+fn decodes_responses() {
+    let mut buffer = PropertyBuffer::new();
+    let serial = buffer.push(&GetBoardSerial).unwrap();
+    let memory = buffer.push(&GetArmMemory).unwrap();
+    let mac = buffer.push(&GetMacAddress).unwrap();
+    let voltage = buffer.push(&GetVoltage(Voltage::Core)).unwrap();
+
+    respond(&mut buffer, 2, 8, &[0x9ABC_DEF0, 0x1234_5678]);
+    respond(&mut buffer, 7, 8, &[0, 0x3B00_0000]);
+    respond(&mut buffer, 12, 6, &[0x4433_2211, 0x6655]);
+    respond(&mut buffer, 17, 8, &[1, 0x8000_0000]);
+
+    assert_eq!(buffer.check(), Ok(()));
+    assert_eq!(buffer.get(serial), Ok(0x1234_5678_9ABC_DEF0));
+    assert_eq!(buffer.get(memory), Ok(MemoryRange { base: 0, size: 0x3B00_0000 }));
+    assert_eq!(buffer.get(mac), Ok([0x11, 0x22, 0x33, 0x44, 0x55, 0x66]));
+    assert_eq!(buffer.get(voltage), Ok(None));
+}
+
+#[test]
+fn decodes_command_line() {
+    let mut buffer = PropertyBuffer::new();
+    let handle = buffer.push(&GetCommandLine).unwrap();
+
+    // "quiet" and a trailing NUL the firmware counts in the length.
+    let values = [u32::from_le_bytes(*b"quie"), u32::from_le_bytes(*b"t\0\0\0")];
+    respond(&mut buffer, 2, 6, &values);
+    let line = buffer.get(handle).unwrap();
+    assert_eq!(line.as_str(), Some("quiet"));
+}
+
+#[test]
+fn check_reports_failed_requests() {
+    let mut buffer = PropertyBuffer::new();
+    assert_eq!(buffer.check(), Err(Error::InvalidResponse(0)));
+
+    buffer.as_words_mut()[1] = 0x8000_0001;
+    assert_eq!(buffer.check(), Err(Error::RequestFailed));
+
+    buffer.as_words_mut()[1] = 0x1234;
+    assert_eq!(buffer.check(), Err(Error::InvalidResponse(0x1234)));
+}
+
+#[test]
+fn unprocessed_tag_is_an_error() {
+    let mut buffer = PropertyBuffer::new();
+    let rate = buffer.push(&GetClockRate(Clock::Emmc)).unwrap();
+    let temperature = buffer.push(&GetTemperature).unwrap();
+
+    // The VideoCore answered the first tag only.
+    respond(&mut buffer, 2, 8, &[1, 250_000_000]);
+    assert_eq!(buffer.get(rate), Ok(250_000_000));
+    assert_eq!(buffer.get(temperature), Err(Error::TagNotProcessed(0x0003_0006)));
+}
+
+#[test]
+fn truncated_response_is_an_error() {
+    let mut buffer = PropertyBuffer::new();
+    let rate = buffer.push(&GetClockRate(Clock::Core)).unwrap();
+
+    // A 12-byte response doesn't fit in the tag's 8-byte value buffer.
+    respond(&mut buffer, 2, 12, &[4, 400_000_000]);
+    assert_eq!(buffer.get(rate), Err(Error::ResponseTruncated(0x0003_0002)));
+}
+
+#[test]
+fn push_fails_once_full() {
+    let mut buffer = PropertyBuffer::new();
+    let mut pushed = 0;
+    while buffer.push(&GetCommandLine).is_ok() {
+        pushed += 1;
+    }
+
+    // Each tag takes its header and `COMMAND_LINE_MAX` bytes of values.
+    let tag_words = 3 + COMMAND_LINE_MAX / 4;
+    assert_eq!(pushed, (BUFFER_WORDS - 3) / tag_words);
+
+    // The failed push leaves the buffer as it was, and small tags still fit.
+    let len = buffer.as_words().len();
+    assert_eq!(buffer.push(&GetCommandLine).err(), Some(Error::BufferFull));
+    assert_eq!(buffer.as_words().len(), len);
+    assert_eq!(buffer.as_words()[0] as usize, len * 4);
+    assert_eq!(buffer.as_words()[len - 1], 0);
+
+    assert!(buffer.push(&GetBoardModel).is_ok());
+    assert_eq!(buffer.as_words().len(), len + 4);
+}
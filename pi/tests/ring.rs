@@ -0,0 +1,97 @@
+extern crate pi;
+
+use pi::ring::{RingBuffer, RING_SIZE};
+
+#[test]
+fn starts_empty() {
+    let ring = RingBuffer::new();
+    assert_eq!(ring.capacity(), RING_SIZE);
+    assert_eq!(ring.len(), 0);
+    assert!(ring.is_empty());
+    assert!(!ring.is_full());
+    assert_eq!(ring.pop(), None);
+}
+
+#[test]
+fn pops_in_push_order() {
+    let ring = RingBuffer::new();
+    for byte in b"hello" {
+        ring.push(*byte).unwrap();
+    }
+
+    assert_eq!(ring.len(), 5);
+    let popped: Vec<u8> = (0..5).map(|_| ring.pop().unwrap()).collect();
+    assert_eq!(popped, b"hello");
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop(), None);
+}
+
+#[test]
+fn rejects_pushes_when_full() {
+    let ring = RingBuffer::new();
+    for i in 0..RING_SIZE {
+        assert_eq!(ring.push(i as u8), Ok(()));
+    }
+
+    assert!(ring.is_full());
+    assert_eq!(ring.len(), RING_SIZE);
+    assert_eq!(ring.push(0xAB), Err(0xAB));
+
+    assert_eq!(ring.pop(), Some(0));
+    assert!(!ring.is_full());
+    assert_eq!(ring.push(0xAB), Ok(()));
+    assert_eq!(ring.push(0xCD), Err(0xCD));
+
+    for i in 1..RING_SIZE {
+        assert_eq!(ring.pop(), Some(i as u8));
+    }
+
+    assert_eq!(ring.pop(), Some(0xAB));
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn wraps_around() {
+    let ring = RingBuffer::new();
+    let mut next_push = 0u32;
+    let mut next_pop = 0u32;
+
+    // Keep the buffer partly full while its indices pass the end many times.
+    for _ in 0..RING_SIZE * 5 {
+        for _ in 0..3 {
+            ring.push(next_push as u8).unwrap();
+            next_push += 1;
+        }
+
+        for _ in 0..2 {
+            assert_eq!(ring.pop(), Some(next_pop as u8));
+            next_pop += 1;
+        }
+
+        if ring.len() > RING_SIZE / 2 {
+            while let Some(byte) = ring.pop() {
+                assert_eq!(byte, next_pop as u8);
+                next_pop += 1;
+            }
+        }
+    }
+
+    assert_eq!(ring.len(), (next_push - next_pop) as usize);
+}
+
+#[test]
+fn fills_again_after_draining() {
+    let ring = RingBuffer::new();
+    for round in 0..3u8 {
+        for i in 0..RING_SIZE {
+            ring.push((i as u8).wrapping_add(round)).unwrap();
+        }
+
+        assert!(ring.is_full());
+        for i in 0..RING_SIZE {
+            assert_eq!(ring.pop(), Some((i as u8).wrapping_add(round)));
+        }
+
+        assert!(ring.is_empty());
+    }
+}
@@ -0,0 +1,18 @@
+//! Memory allocation APIs.
+//!
+//! The global allocator is provided by the binary linking against this
+//! library through `#[global_allocator]`; this module only re-exports the
+//! allocation API from `alloc` and reports allocation failures.
+
+#![stable(feature = "alloc_module", since = "1.28.0")]
+
+#[stable(feature = "alloc_module", since = "1.28.0")]
+#[doc(inline)]
+pub use alloc_crate::alloc::*;
+
+/// Called when an allocation fails. There is no way to recover, so this
+/// panics with the size of the failed request.
+#[alloc_error_handler]
+fn oom(layout: Layout) -> ! {
+    panic!("memory allocation of {} bytes failed", layout.size())
+}
@@ -554,7 +554,7 @@ impl fmt::Display for Error {
                 write!(fmt, "os error {}", code)
             }
             // Repr::Custom(ref c) => c.error.fmt(fmt),
-            Repr::Custom(kind, msg) => write!(fmt, "{}: {}", kind.as_str(), msg),
+            Repr::Custom(_, msg) => write!(fmt, "{}", msg),
             Repr::Simple(kind) => write!(fmt, "{}", kind.as_str()),
         }
     }
@@ -254,25 +254,25 @@ impl<'a> Write for &'a mut [u8] {
     fn flush(&mut self) -> io::Result<()> { Ok(()) }
 }
 
-// /// Write is implemented for `Vec<u8>` by appending to the vector.
-// /// The vector will grow as needed.
-// #[stable(feature = "rust1", since = "1.0.0")]
-// impl Write for Vec<u8> {
-//     #[inline]
-//     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-//         self.extend_from_slice(buf);
-//         Ok(buf.len())
-//     }
+/// Write is implemented for `Vec<u8>` by appending to the vector.
+/// The vector will grow as needed.
+#[stable(feature = "rust1", since = "1.0.0")]
+impl Write for Vec<u8> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
 
-//     #[inline]
-//     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-//         self.extend_from_slice(buf);
-//         Ok(())
-//     }
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
 
-//     #[inline]
-//     fn flush(&mut self) -> io::Result<()> { Ok(()) }
-// }
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
 
 #[cfg(test)]
 mod tests {
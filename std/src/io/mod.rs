@@ -311,89 +311,89 @@ mod util;
 // const DEFAULT_BUF_SIZE: usize = ::sys_common::io::DEFAULT_BUF_SIZE;
 const DEFAULT_BUF_SIZE: usize = 4096;
 
-// struct Guard<'a> { buf: &'a mut Vec<u8>, len: usize }
+struct Guard<'a> { buf: &'a mut Vec<u8>, len: usize }
 
-// impl<'a> Drop for Guard<'a> {
-//     fn drop(&mut self) {
-//         unsafe { self.buf.set_len(self.len); }
-//     }
-// }
-
-//// A few methods below (read_to_string, read_line) will append data into a
-//// `String` buffer, but we need to be pretty careful when doing this. The
-//// implementation will just call `.as_mut_vec()` and then delegate to a
-//// byte-oriented reading method, but we must ensure that when returning we never
-//// leave `buf` in a state such that it contains invalid UTF-8 in its bounds.
-////
-//// To this end, we use an RAII guard (to protect against panics) which updates
-//// the length of the string when it is dropped. This guard initially truncates
-//// the string to the prior length and only after we've validated that the
-//// new contents are valid UTF-8 do we allow it to set a longer length.
-////
-//// The unsafety in this function is twofold:
-////
-//// 1. We're looking at the raw bytes of `buf`, so we take on the burden of UTF-8
-////    checks.
-//// 2. We're passing a raw buffer to the function `f`, and it is expected that
-////    the function only *appends* bytes to the buffer. We'll get undefined
-////    behavior if existing bytes are overwritten to have non-UTF-8 data.
-//fn append_to_string<F>(buf: &mut String, f: F) -> Result<usize>
-//    where F: FnOnce(&mut Vec<u8>) -> Result<usize>
-//{
-//    unsafe {
-//        let mut g = Guard { len: buf.len(), buf: buf.as_mut_vec() };
-//        let ret = f(g.buf);
-//        if str::from_utf8(&g.buf[g.len..]).is_err() {
-//            ret.and_then(|_| {
-//                Err(Error::new(ErrorKind::InvalidData,
-//                               "stream did not contain valid UTF-8"))
-//            })
-//        } else {
-//            g.len = g.buf.len();
-//            ret
-//        }
-//    }
-//}
-
-//// This uses an adaptive system to extend the vector when it fills. We want to
-//// avoid paying to allocate and zero a huge chunk of memory if the reader only
-//// has 4 bytes while still making large reads if the reader does have a ton
-//// of data to return. Simply tacking on an extra DEFAULT_BUF_SIZE space every
-//// time is 4,500 times (!) slower than this if the reader has a very small
-//// amount of data to return.
-////
-//// Because we're extending the buffer with uninitialized data for trusted
-//// readers, we need to make sure to truncate that if any of this panics.
-//fn read_to_end<R: Read + ?Sized>(r: &mut R, buf: &mut Vec<u8>) -> Result<usize> {
-//    let start_len = buf.len();
-//    let mut g = Guard { len: buf.len(), buf: buf };
-//    let ret;
-//    loop {
-//        if g.len == g.buf.len() {
-//            unsafe {
-//                g.buf.reserve(32);
-//                let capacity = g.buf.capacity();
-//                g.buf.set_len(capacity);
-//                r.initializer().initialize(&mut g.buf[g.len..]);
-//            }
-//        }
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        unsafe { self.buf.set_len(self.len); }
+    }
+}
 
-//        match r.read(&mut g.buf[g.len..]) {
-//            Ok(0) => {
-//                ret = Ok(g.len - start_len);
-//                break;
-//            }
-//            Ok(n) => g.len += n,
-//            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
-//            Err(e) => {
-//                ret = Err(e);
-//                break;
-//            }
-//        }
-//    }
+// A few methods below (read_to_string, read_line) will append data into a
+// `String` buffer, but we need to be pretty careful when doing this. The
+// implementation will just call `.as_mut_vec()` and then delegate to a
+// byte-oriented reading method, but we must ensure that when returning we never
+// leave `buf` in a state such that it contains invalid UTF-8 in its bounds.
+//
+// To this end, we use an RAII guard (to protect against panics) which updates
+// the length of the string when it is dropped. This guard initially truncates
+// the string to the prior length and only after we've validated that the
+// new contents are valid UTF-8 do we allow it to set a longer length.
+//
+// The unsafety in this function is twofold:
+//
+// 1. We're looking at the raw bytes of `buf`, so we take on the burden of UTF-8
+//    checks.
+// 2. We're passing a raw buffer to the function `f`, and it is expected that
+//    the function only *appends* bytes to the buffer. We'll get undefined
+//    behavior if existing bytes are overwritten to have non-UTF-8 data.
+fn append_to_string<F>(buf: &mut String, f: F) -> Result<usize>
+   where F: FnOnce(&mut Vec<u8>) -> Result<usize>
+{
+   unsafe {
+       let mut g = Guard { len: buf.len(), buf: buf.as_mut_vec() };
+       let ret = f(g.buf);
+       if str::from_utf8(&g.buf[g.len..]).is_err() {
+           ret.and_then(|_| {
+               Err(Error::new(ErrorKind::InvalidData,
+                              "stream did not contain valid UTF-8"))
+           })
+       } else {
+           g.len = g.buf.len();
+           ret
+       }
+   }
+}
 
-//    ret
-//}
+// This uses an adaptive system to extend the vector when it fills. We want to
+// avoid paying to allocate and zero a huge chunk of memory if the reader only
+// has 4 bytes while still making large reads if the reader does have a ton
+// of data to return. Simply tacking on an extra DEFAULT_BUF_SIZE space every
+// time is 4,500 times (!) slower than this if the reader has a very small
+// amount of data to return.
+//
+// Because we're extending the buffer with uninitialized data for trusted
+// readers, we need to make sure to truncate that if any of this panics.
+fn read_to_end<R: Read + ?Sized>(r: &mut R, buf: &mut Vec<u8>) -> Result<usize> {
+   let start_len = buf.len();
+   let mut g = Guard { len: buf.len(), buf: buf };
+   let ret;
+   loop {
+       if g.len == g.buf.len() {
+           unsafe {
+               g.buf.reserve(32);
+               let capacity = g.buf.capacity();
+               g.buf.set_len(capacity);
+               r.initializer().initialize(&mut g.buf[g.len..]);
+           }
+       }
+
+       match r.read(&mut g.buf[g.len..]) {
+           Ok(0) => {
+               ret = Ok(g.len - start_len);
+               break;
+           }
+           Ok(n) => g.len += n,
+           Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+           Err(e) => {
+               ret = Err(e);
+               break;
+           }
+       }
+   }
+
+   ret
+}
 
 /// The `Read` trait allows for reading bytes from a source.
 ///
@@ -555,99 +555,99 @@ pub trait Read {
         Initializer::zeroing()
     }
 
-    ///// Read all bytes until EOF in this source, placing them into `buf`.
-    /////
-    ///// All bytes read from this source will be appended to the specified buffer
-    ///// `buf`. This function will continuously call [`read()`] to append more data to
-    ///// `buf` until [`read()`] returns either [`Ok(0)`] or an error of
-    ///// non-[`ErrorKind::Interrupted`] kind.
-    /////
-    ///// If successful, this function will return the total number of bytes read.
-    /////
-    ///// # Errors
-    /////
-    ///// If this function encounters an error of the kind
-    ///// [`ErrorKind::Interrupted`] then the error is ignored and the operation
-    ///// will continue.
-    /////
-    ///// If any other read error is encountered then this function immediately
-    ///// returns. Any bytes which have already been read will be appended to
-    ///// `buf`.
-    /////
-    ///// # Examples
-    /////
-    ///// [`File`]s implement `Read`:
-    /////
-    ///// [`read()`]: trait.Read.html#tymethod.read
-    ///// [`Ok(0)`]: ../../std/result/enum.Result.html#variant.Ok
-    ///// [`ErrorKind::Interrupted`]: ../../std/io/enum.ErrorKind.html#variant.Interrupted
-    ///// [`File`]: ../fs/struct.File.html
-    /////
-    ///// ```
-    ///// use std::io;
-    ///// use std::io::prelude::*;
-    ///// use std::fs::File;
-    /////
-    ///// # fn foo() -> io::Result<()> {
-    ///// let mut f = File::open("foo.txt")?;
-    ///// let mut buffer = Vec::new();
-    /////
-    ///// // read the whole file
-    ///// f.read_to_end(&mut buffer)?;
-    ///// # Ok(())
-    ///// # }
-    ///// ```
-    //#[stable(feature = "rust1", since = "1.0.0")]
-    //fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
-    //    read_to_end(self, buf)
-    //}
+    /// Read all bytes until EOF in this source, placing them into `buf`.
+    ///
+    /// All bytes read from this source will be appended to the specified buffer
+    /// `buf`. This function will continuously call [`read()`] to append more data to
+    /// `buf` until [`read()`] returns either [`Ok(0)`] or an error of
+    /// non-[`ErrorKind::Interrupted`] kind.
+    ///
+    /// If successful, this function will return the total number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters an error of the kind
+    /// [`ErrorKind::Interrupted`] then the error is ignored and the operation
+    /// will continue.
+    ///
+    /// If any other read error is encountered then this function immediately
+    /// returns. Any bytes which have already been read will be appended to
+    /// `buf`.
+    ///
+    /// # Examples
+    ///
+    /// [`File`]s implement `Read`:
+    ///
+    /// [`read()`]: trait.Read.html#tymethod.read
+    /// [`Ok(0)`]: ../../std/result/enum.Result.html#variant.Ok
+    /// [`ErrorKind::Interrupted`]: ../../std/io/enum.ErrorKind.html#variant.Interrupted
+    /// [`File`]: ../fs/struct.File.html
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::prelude::*;
+    /// use std::fs::File;
+    ///
+    /// # fn foo() -> io::Result<()> {
+    /// let mut f = File::open("foo.txt")?;
+    /// let mut buffer = Vec::new();
+    ///
+    /// // read the whole file
+    /// f.read_to_end(&mut buffer)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        read_to_end(self, buf)
+    }
 
-    ///// Read all bytes until EOF in this source, appending them to `buf`.
-    /////
-    ///// If successful, this function returns the number of bytes which were read
-    ///// and appended to `buf`.
-    /////
-    ///// # Errors
-    /////
-    ///// If the data in this stream is *not* valid UTF-8 then an error is
-    ///// returned and `buf` is unchanged.
-    /////
-    ///// See [`read_to_end`][readtoend] for other error semantics.
-    /////
-    ///// [readtoend]: #method.read_to_end
-    /////
-    ///// # Examples
-    /////
-    ///// [`File`][file]s implement `Read`:
-    /////
-    ///// [file]: ../fs/struct.File.html
-    /////
-    ///// ```
-    ///// use std::io;
-    ///// use std::io::prelude::*;
-    ///// use std::fs::File;
-    /////
-    ///// # fn foo() -> io::Result<()> {
-    ///// let mut f = File::open("foo.txt")?;
-    ///// let mut buffer = String::new();
-    /////
-    ///// f.read_to_string(&mut buffer)?;
-    ///// # Ok(())
-    ///// # }
-    ///// ```
-    //#[stable(feature = "rust1", since = "1.0.0")]
-    //fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
-    //    // Note that we do *not* call `.read_to_end()` here. We are passing
-    //    // `&mut Vec<u8>` (the raw contents of `buf`) into the `read_to_end`
-    //    // method to fill it up. An arbitrary implementation could overwrite the
-    //    // entire contents of the vector, not just append to it (which is what
-    //    // we are expecting).
-    //    //
-    //    // To prevent extraneously checking the UTF-8-ness of the entire buffer
-    //    // we pass it to our hardcoded `read_to_end` implementation which we
-    //    // know is guaranteed to only read data into the end of the buffer.
-    //    append_to_string(buf, |b| read_to_end(self, b))
-    //}
+    /// Read all bytes until EOF in this source, appending them to `buf`.
+    ///
+    /// If successful, this function returns the number of bytes which were read
+    /// and appended to `buf`.
+    ///
+    /// # Errors
+    ///
+    /// If the data in this stream is *not* valid UTF-8 then an error is
+    /// returned and `buf` is unchanged.
+    ///
+    /// See [`read_to_end`][readtoend] for other error semantics.
+    ///
+    /// [readtoend]: #method.read_to_end
+    ///
+    /// # Examples
+    ///
+    /// [`File`][file]s implement `Read`:
+    ///
+    /// [file]: ../fs/struct.File.html
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::prelude::*;
+    /// use std::fs::File;
+    ///
+    /// # fn foo() -> io::Result<()> {
+    /// let mut f = File::open("foo.txt")?;
+    /// let mut buffer = String::new();
+    ///
+    /// f.read_to_string(&mut buffer)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
+        // Note that we do *not* call `.read_to_end()` here. We are passing
+        // `&mut Vec<u8>` (the raw contents of `buf`) into the `read_to_end`
+        // method to fill it up. An arbitrary implementation could overwrite the
+        // entire contents of the vector, not just append to it (which is what
+        // we are expecting).
+        //
+        // To prevent extraneously checking the UTF-8-ness of the entire buffer
+        // we pass it to our hardcoded `read_to_end` implementation which we
+        // know is guaranteed to only read data into the end of the buffer.
+        append_to_string(buf, |b| read_to_end(self, b))
+    }
 
     /// Read the exact number of bytes required to fill `buf`.
     ///
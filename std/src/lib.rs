@@ -5,7 +5,7 @@
 
 // std is implemented with unstable features, many of which are internal
 // compiler details that will never be stable
-// #![feature(alloc)]
+#![feature(alloc)]
 #![feature(alloc_error_handler)]
 #![feature(allocator_api)]
 #![feature(allocator_internals)]
@@ -100,9 +100,9 @@ pub use core::{assert_eq, assert_ne, debug_assert, debug_assert_eq, debug_assert
 #[stable(feature = "rust1", since = "1.0.0")]
 pub use core::{unreachable, unimplemented, panic, write, writeln, try};
 
-// #[macro_use]
-// #[macro_reexport(vec, format)]
-// extern crate alloc;
+#[allow(unused_imports)]
+#[macro_use]
+extern crate alloc as alloc_crate;
 // extern crate alloc_system;
 // extern crate std_unicode;
 // #[doc(masked)]
@@ -187,12 +187,12 @@ pub use core::u16;
 pub use core::u32;
 #[stable(feature = "rust1", since = "1.0.0")]
 pub use core::u64;
-// #[stable(feature = "rust1", since = "1.0.0")]
-// pub use alloc::boxed;
-// #[stable(feature = "rust1", since = "1.0.0")]
-// pub use alloc::rc;
-// #[stable(feature = "rust1", since = "1.0.0")]
-// pub use alloc::borrow;
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::boxed;
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::rc;
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::borrow;
 // #[stable(feature = "rust1", since = "1.0.0")]
 // pub use alloc::fmt;
 #[stable(feature = "rust1", since = "1.0.0")]
@@ -203,15 +203,19 @@ pub use core::pin;
 pub use core::slice;
 #[stable(feature = "rust1", since = "1.0.0")]
 pub use core::str;
-// #[stable(feature = "rust1", since = "1.0.0")]
-// pub use alloc::string;
-// #[stable(feature = "rust1", since = "1.0.0")]
-// pub use alloc::vec;
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::string;
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::vec;
 #[stable(feature = "rust1", since = "1.0.0")]
 pub use core::char;
 #[unstable(feature = "i128", issue = "35118")]
 pub use core::u128;
 
+// Re-export macros defined in liballoc
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::format;
+
 // TODO: This is an addition. This should should actually come from `alloc`.
 // pub mod str;
 // pub mod slice;
@@ -235,8 +239,8 @@ pub mod io;
 // pub mod path;
 // pub mod process;
 pub mod sync;
-// pub mod time;
-// pub mod heap;
+pub mod time;
+pub mod alloc;
 
 // // Platform-abstraction modules
 // #[macro_use]
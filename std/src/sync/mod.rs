@@ -17,8 +17,8 @@
 
 #![stable(feature = "rust1", since = "1.0.0")]
 
-// #[stable(feature = "rust1", since = "1.0.0")]
-// pub use alloc::arc::{Arc, Weak};
+#[stable(feature = "rust1", since = "1.0.0")]
+pub use alloc_crate::sync::{Arc, Weak};
 #[stable(feature = "rust1", since = "1.0.0")]
 pub use core::sync::atomic;
 
@@ -0,0 +1,182 @@
+//! Temporal quantification.
+//!
+//! `Instant` is backed by the ARM generic timer's system counter, which runs at
+//! a fixed frequency from the moment the board is powered on. The counter is
+//! read through the functions passed to `set_source()`, which the kernel calls
+//! with the generic timer driver's before using `Instant`.
+
+#![stable(feature = "time", since = "1.3.0")]
+
+use fmt;
+use ops::{Add, Sub, AddAssign, SubAssign};
+
+#[stable(feature = "time", since = "1.3.0")]
+pub use core::time::Duration;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// The functions returning the system counter's value and its frequency in
+/// Hz, as passed to `set_source()`.
+static mut SOURCE: Option<(fn() -> u64, fn() -> u64)> = None;
+
+/// Makes `Instant` read the system counter's value from `counter` and its
+/// frequency in Hz from `frequency`.
+///
+/// # Safety
+///
+/// Must be called before `Instant` is used, while no other thread is running.
+#[unstable(feature = "time_source", issue = "0")]
+pub unsafe fn set_source(counter: fn() -> u64, frequency: fn() -> u64) {
+    SOURCE = Some((counter, frequency));
+}
+
+/// Returns the source passed to `set_source()`.
+///
+/// # Panics
+///
+/// Panics if `set_source()` hasn't been called.
+fn source() -> (fn() -> u64, fn() -> u64) {
+    unsafe { SOURCE.expect("std::time: set_source() hasn't been called") }
+}
+
+/// Returns the frequency of the system counter in Hz.
+fn frequency() -> u64 {
+    (source().1)()
+}
+
+/// Returns the current value of the system counter.
+fn counter() -> u64 {
+    (source().0)()
+}
+
+/// Converts a number of counter ticks into a `Duration`.
+fn ticks_to_duration(ticks: u64) -> Duration {
+    let frequency = frequency();
+    let nanos = (ticks % frequency) * NANOS_PER_SEC / frequency;
+    Duration::new(ticks / frequency, nanos as u32)
+}
+
+/// Converts a `Duration` into a number of counter ticks, or `None` on
+/// overflow.
+fn duration_to_ticks(duration: Duration) -> Option<u64> {
+    let frequency = frequency();
+    let nanos = duration.subsec_nanos() as u64 * frequency / NANOS_PER_SEC;
+    duration.as_secs().checked_mul(frequency)?.checked_add(nanos)
+}
+
+/// A measurement of the monotonically nondecreasing system counter. Opaque
+/// and useful only with `Duration`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[stable(feature = "time2", since = "1.8.0")]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Returns an instant corresponding to "now".
+    #[stable(feature = "time2", since = "1.8.0")]
+    pub fn now() -> Instant {
+        Instant(counter())
+    }
+
+    /// Returns the amount of time elapsed from another instant to this one.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `earlier` is later than `self`.
+    #[stable(feature = "time2", since = "1.8.0")]
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier)
+            .expect("supplied instant is later than self")
+    }
+
+    /// Returns the amount of time elapsed from another instant to this one,
+    /// or `None` if that instant is later than this one.
+    #[stable(feature = "checked_duration_since", since = "1.39.0")]
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(ticks_to_duration)
+    }
+
+    /// Returns the amount of time elapsed from another instant to this one,
+    /// or zero duration if that instant is later than this one.
+    #[stable(feature = "checked_duration_since", since = "1.39.0")]
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or(Duration::new(0, 0))
+    }
+
+    /// Returns the amount of time elapsed since this instant was created.
+    #[stable(feature = "time2", since = "1.8.0")]
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    /// Returns `Some(t)` where `t` is the time `self + duration` if `t` can be
+    /// represented, `None` otherwise.
+    #[stable(feature = "time_checked_add", since = "1.34.0")]
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        duration_to_ticks(duration)
+            .and_then(|ticks| self.0.checked_add(ticks))
+            .map(Instant)
+    }
+
+    /// Returns `Some(t)` where `t` is the time `self - duration` if `t` can be
+    /// represented, `None` otherwise.
+    #[stable(feature = "time_checked_add", since = "1.34.0")]
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        duration_to_ticks(duration)
+            .and_then(|ticks| self.0.checked_sub(ticks))
+            .map(Instant)
+    }
+}
+
+#[stable(feature = "time2", since = "1.8.0")]
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    /// # Panics
+    ///
+    /// This function may panic if the resulting point in time cannot be
+    /// represented. See `Instant::checked_add` for a version without panic.
+    fn add(self, other: Duration) -> Instant {
+        self.checked_add(other)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+#[stable(feature = "time_augmented_assignment", since = "1.9.0")]
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+#[stable(feature = "time2", since = "1.8.0")]
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, other: Duration) -> Instant {
+        self.checked_sub(other)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+#[stable(feature = "time_augmented_assignment", since = "1.9.0")]
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+#[stable(feature = "time2", since = "1.8.0")]
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, other: Instant) -> Duration {
+        self.duration_since(other)
+    }
+}
+
+#[stable(feature = "time2", since = "1.8.0")]
+impl fmt::Debug for Instant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Instant({:?})", ticks_to_duration(self.0))
+    }
+}
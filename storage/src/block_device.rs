@@ -0,0 +1,137 @@
+use std::cmp;
+use std::io::{self, Read, Write, Seek, SeekFrom};
+
+/// Trait implemented by devices that can be read/written in sector
+/// granularities.
+pub trait BlockDevice: Send {
+    /// Sector size in bytes. Must be a multiple of 512 >= 512. Defaults to 512.
+    fn sector_size(&self) -> u64 {
+        512
+    }
+
+    /// The number of sectors on the device.
+    fn sector_count(&self) -> u64;
+
+    /// Read sector number `n` into `buf`.
+    ///
+    /// `self.sector_size()` or `buf.len()` bytes, whichever is less, are read
+    /// into `buf`. The number of bytes read is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `n` is past the end of the
+    /// device, or the device's error if reading fails.
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Append sector number `n` into `vec`.
+    ///
+    /// `self.sector_size()` bytes are appended to `vec`. The number of bytes
+    /// read is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `read_sector()`.
+    fn read_all_sector(&mut self, n: u64, vec: &mut Vec<u8>) -> io::Result<usize> {
+        let sector_size = self.sector_size() as usize;
+        let start = vec.len();
+        vec.resize(start + sector_size, 0);
+
+        let read = self.read_sector(n, &mut vec[start..])?;
+        vec.truncate(start + read);
+        Ok(read)
+    }
+
+    /// Overwrites sector `n` with the contents of `buf`.
+    ///
+    /// `self.sector_size()` or `buf.len()` bytes, whichever is less, are
+    /// written to the sector. The number of bytes written is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `n` is past the end of the
+    /// device, or the device's error if writing fails.
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize>;
+
+    /// Writes any buffered changes through to the underlying storage.
+    ///
+    /// Devices that write directly have nothing to do, which is the default.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: BlockDevice> BlockDevice for &mut T {
+    fn sector_size(&self) -> u64 {
+        (**self).sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        (**self).sector_count()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_sector(n, buf)
+    }
+
+    fn read_all_sector(&mut self, n: u64, vec: &mut Vec<u8>) -> io::Result<usize> {
+        (**self).read_all_sector(n, vec)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        (**self).write_sector(n, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Returns an error if sector `n` is past the end of `device`.
+pub(crate) fn check_sector<T: BlockDevice + ?Sized>(device: &T, n: u64) -> io::Result<()> {
+    match n < device.sector_count() {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::InvalidInput, "sector out of range"))
+    }
+}
+
+/// Implements `BlockDevice` for a `Read + Write + Seek` type holding a disk
+/// image, given an expression for the image's length in bytes.
+macro_rules! impl_for_read_write_seek {
+    (<$($lt:lifetime),*> $T:ty, |$this:ident| $len:expr) => {
+        impl<$($lt),*> BlockDevice for $T {
+            fn sector_count(&self) -> u64 {
+                let $this = self;
+                $len / self.sector_size()
+            }
+
+            fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+                check_sector(self, n)?;
+                let sector_size = self.sector_size();
+                let to_read = cmp::min(sector_size as usize, buf.len());
+                self.seek(SeekFrom::Start(n * sector_size))?;
+                self.read_exact(&mut buf[..to_read])?;
+                Ok(to_read)
+            }
+
+            fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+                check_sector(self, n)?;
+                let sector_size = self.sector_size();
+                let to_write = cmp::min(sector_size as usize, buf.len());
+                self.seek(SeekFrom::Start(n * sector_size))?;
+                self.write_all(&buf[..to_write])?;
+                Ok(to_write)
+            }
+        }
+    };
+    ($T:ty, |$this:ident| $len:expr) => {
+        impl_for_read_write_seek!(<> $T, |$this| $len);
+    };
+}
+
+impl_for_read_write_seek!(<'a> io::Cursor<&'a mut [u8]>, |cursor| cursor.get_ref().len() as u64);
+impl_for_read_write_seek!(io::Cursor<Vec<u8>>, |cursor| cursor.get_ref().len() as u64);
+impl_for_read_write_seek!(io::Cursor<Box<[u8]>>, |cursor| cursor.get_ref().len() as u64);
+
+// Disk image files only exist on the host.
+#[cfg(not(target_os = "none"))]
+impl_for_read_write_seek!(::std::fs::File, |file| file.metadata().map(|m| m.len()).unwrap_or(0));
@@ -0,0 +1,209 @@
+use std::cmp;
+use std::io;
+
+use block_device::{check_sector, BlockDevice};
+
+/// Counters describing how well a `CachedDevice` is doing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Sector reads and writes served by a cached sector.
+    pub hits: u64,
+    /// Sector reads and writes of a sector that wasn't cached.
+    pub misses: u64,
+    /// Cached sectors dropped to make room for another sector.
+    pub evictions: u64,
+    /// Dirty sectors written back to the device.
+    pub writebacks: u64,
+}
+
+/// A cached sector.
+#[derive(Debug)]
+struct Slot {
+    sector: u64,
+    data: Vec<u8>,
+    /// Set when `data` has been written and not yet written back.
+    dirty: bool,
+    /// The value of the access clock when the sector was last used.
+    used: u64,
+}
+
+/// A write-back cache of up to a fixed number of sectors of a block device.
+///
+/// Reads and writes go to the cached copy of a sector, loading it from the
+/// device on a miss unless the whole sector is being overwritten. Written
+/// sectors are only written back when they're evicted to make room for
+/// another sector, when `BlockDevice::flush()` is called, or when the cache is dropped.
+/// The least recently used sector is evicted first.
+///
+/// Slots are found by a linear scan, which is cheap next to a device access
+/// for the few hundred sectors a cache holds.
+#[derive(Debug)]
+pub struct CachedDevice<T: BlockDevice> {
+    device: T,
+    capacity: usize,
+    slots: Vec<Slot>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl<T: BlockDevice> CachedDevice<T> {
+    /// Wraps `device` in a cache holding up to `capacity` sectors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is `0`.
+    pub fn new(device: T, capacity: usize) -> CachedDevice<T> {
+        assert!(capacity > 0, "CachedDevice::new(): capacity must be non-zero");
+        CachedDevice {
+            device,
+            capacity,
+            slots: Vec::with_capacity(capacity),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The maximum number of sectors the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of sectors currently cached.
+    pub fn cached(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The number of cached sectors that haven't been written back.
+    pub fn dirty(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.dirty).count()
+    }
+
+    /// The cache's counters since it was created or last reset.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets the cache's counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Returns the underlying device.
+    ///
+    /// The device doesn't reflect writes that haven't been flushed.
+    pub fn get_ref(&self) -> &T {
+        &self.device
+    }
+
+    /// Writes slot `i` back to the device and marks it clean.
+    fn write_back(&mut self, i: usize) -> io::Result<()> {
+        let slot = &mut self.slots[i];
+        self.device.write_sector(slot.sector, &slot.data)?;
+        slot.dirty = false;
+        self.stats.writebacks += 1;
+        Ok(())
+    }
+
+    /// Returns the index of the slot caching sector `n`, making room for and
+    /// installing it on a miss. The sector is read from the device on a miss
+    /// only if `load` is set; otherwise the slot's contents are unspecified.
+    fn slot(&mut self, n: u64, load: bool) -> io::Result<usize> {
+        self.clock += 1;
+        if let Some(i) = self.slots.iter().position(|slot| slot.sector == n) {
+            self.stats.hits += 1;
+            self.slots[i].used = self.clock;
+            return Ok(i);
+        }
+
+        self.stats.misses += 1;
+        let mut data = match self.slots.len() < self.capacity {
+            true => vec![0; self.device.sector_size() as usize],
+            false => {
+                let victim = self.evict()?;
+                self.slots.swap_remove(victim).data
+            }
+        };
+
+        if load {
+            self.device.read_sector(n, &mut data)?;
+        }
+
+        self.slots.push(Slot { sector: n, data, dirty: false, used: self.clock });
+        Ok(self.slots.len() - 1)
+    }
+
+    /// Writes back the least recently used slot if it's dirty and returns
+    /// its index, ready to be reused.
+    fn evict(&mut self) -> io::Result<usize> {
+        let victim = (0..self.slots.len())
+            .min_by_key(|&i| self.slots[i].used)
+            .expect("evicting from an empty cache");
+
+        if self.slots[victim].dirty {
+            self.write_back(victim)?;
+        }
+
+        self.stats.evictions += 1;
+        Ok(victim)
+    }
+}
+
+impl<T: BlockDevice> BlockDevice for CachedDevice<T> {
+    fn sector_size(&self) -> u64 {
+        self.device.sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.device.sector_count()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        check_sector(self, n)?;
+        let i = self.slot(n, true)?;
+        let data = &self.slots[i].data;
+        let len = cmp::min(data.len(), buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        check_sector(self, n)?;
+        let whole = buf.len() as u64 >= self.sector_size();
+        let i = self.slot(n, !whole)?;
+        let slot = &mut self.slots[i];
+        let len = cmp::min(slot.data.len(), buf.len());
+        slot.data[..len].copy_from_slice(&buf[..len]);
+        slot.dirty = true;
+        Ok(len)
+    }
+
+    /// Writes every dirty sector back to the device in sector order, then
+    /// flushes the device.
+    ///
+    /// If a write fails, the sectors that weren't written back remain dirty
+    /// so the flush can be retried.
+    fn flush(&mut self) -> io::Result<()> {
+        let mut dirty: Vec<usize> = (0..self.slots.len())
+            .filter(|&i| self.slots[i].dirty)
+            .collect();
+
+        {
+            let slots = &self.slots;
+            dirty.sort_by_key(|&i| slots[i].sector);
+        }
+
+        for i in dirty {
+            self.write_back(i)?;
+        }
+
+        self.device.flush()
+    }
+}
+
+impl<T: BlockDevice> Drop for CachedDevice<T> {
+    /// Writes dirty sectors back on a best-effort basis. Call `flush()` first
+    /// to find out whether that succeeded.
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,18 @@
+//! Block storage shared by the kernel and host-side tools and tests.
+//!
+//! `BlockDevice` abstracts over anything that can be read and written a sector
+//! at a time: the SD card on the Pi, and in-memory buffers or disk image files
+//! on the host. `MasterBootRecord` parses an MBR partition table from such a
+//! device and opens its partitions as `Partition` block devices.
+//! `CachedDevice` keeps recently used sectors of a device in memory and
+//! writes changes back lazily.
+
+mod block_device;
+mod cache;
+mod partition;
+pub mod mbr;
+
+pub use block_device::BlockDevice;
+pub use cache::{CachedDevice, CacheStats};
+pub use partition::Partition;
+pub use mbr::{MasterBootRecord, PartitionEntry};
@@ -0,0 +1,198 @@
+use std::io;
+
+use block_device::BlockDevice;
+use partition::Partition;
+
+/// The size of the master boot record in bytes.
+pub const MBR_SIZE: usize = 512;
+
+/// The number of primary partitions in the partition table.
+pub const NUM_PARTITIONS: usize = 4;
+
+/// The offset of the partition table in the MBR.
+const TABLE_OFFSET: usize = 446;
+
+/// The size of a partition table entry in bytes.
+const ENTRY_SIZE: usize = 16;
+
+/// The offset of the optional disk identifier in the MBR.
+const DISK_ID_OFFSET: usize = 440;
+
+/// The last two bytes of a valid MBR.
+const SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// Partition types for FAT32 with CHS and with LBA addressing.
+const FAT32_KINDS: [u8; 2] = [0x0B, 0x0C];
+
+/// A used entry of the partition table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PartitionEntry {
+    /// The entry's index in the table, `0` to `3`.
+    pub index: usize,
+    /// Whether the partition is marked as active.
+    pub bootable: bool,
+    /// The partition type, such as `0x0C` for FAT32 (LBA).
+    pub kind: u8,
+    /// The partition's first sector.
+    pub start: u64,
+    /// The number of sectors in the partition.
+    pub sectors: u64,
+}
+
+impl PartitionEntry {
+    /// The sector just past the end of the partition.
+    pub fn end(&self) -> u64 {
+        self.start + self.sectors
+    }
+
+    /// Returns `true` if the partition type is one of the FAT32 types.
+    pub fn is_fat32(&self) -> bool {
+        FAT32_KINDS.contains(&self.kind)
+    }
+}
+
+/// Error type for MBR parsing failures.
+#[derive(Debug)]
+pub enum Error {
+    /// There was an I/O error while reading the MBR.
+    Io(io::Error),
+    /// The device's sectors are too small to hold an MBR.
+    SectorTooSmall(u64),
+    /// The MBR magic signature was invalid.
+    BadSignature,
+    /// Partition `.0` (0-indexed) contains an invalid or unknown boot
+    /// indicator.
+    UnknownBootIndicator(usize),
+    /// Partition `.0` has a type but no sectors.
+    EmptyPartition(usize),
+    /// Partition `.0` extends past the end of the device.
+    OutOfBounds(usize),
+    /// Partitions `.0` and `.1` overlap.
+    Overlap(usize, usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+/// A parsed and validated master boot record.
+#[derive(Debug, Clone)]
+pub struct MasterBootRecord {
+    disk_id: u32,
+    entries: [Option<PartitionEntry>; NUM_PARTITIONS],
+}
+
+/// Reads a little-endian `u32` from `bytes` at `offset`.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+impl MasterBootRecord {
+    /// Reads and returns the master boot record (MBR) from `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Io(err)` if the I/O error `err` occured while reading the MBR
+    /// and `SectorTooSmall` if the device's sectors are smaller than an MBR.
+    /// Otherwise returns the errors of `parse()`.
+    pub fn from<T: BlockDevice>(mut device: T) -> Result<MasterBootRecord, Error> {
+        let sector_size = device.sector_size();
+        if sector_size < MBR_SIZE as u64 {
+            return Err(Error::SectorTooSmall(sector_size));
+        }
+
+        let mut sector = vec![0; sector_size as usize];
+        let read = device.read_sector(0, &mut sector)?;
+        if read < MBR_SIZE {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "short MBR read")));
+        }
+
+        MasterBootRecord::parse(&sector[..MBR_SIZE], device.sector_count())
+    }
+
+    /// Parses the 512-byte MBR in `bytes` for a device of `sector_count`
+    /// sectors.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BadSignature` if the MBR signature is invalid,
+    /// `UnknownBootIndicator(n)` if partition `n` has an invalid boot
+    /// indicator, `EmptyPartition(n)` if partition `n` has a type but no
+    /// sectors, `OutOfBounds(n)` if partition `n` doesn't fit on the device,
+    /// and `Overlap(a, b)` if partitions `a` and `b` share sectors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is shorter than `MBR_SIZE`.
+    pub fn parse(bytes: &[u8], sector_count: u64) -> Result<MasterBootRecord, Error> {
+        if bytes[MBR_SIZE - 2..MBR_SIZE] != SIGNATURE {
+            return Err(Error::BadSignature);
+        }
+
+        let mut entries = [None; NUM_PARTITIONS];
+        for (index, entry) in entries.iter_mut().enumerate() {
+            let raw = &bytes[TABLE_OFFSET + index * ENTRY_SIZE..][..ENTRY_SIZE];
+            let bootable = match raw[0] {
+                0x00 => false,
+                0x80 => true,
+                _ => return Err(Error::UnknownBootIndicator(index))
+            };
+
+            let kind = raw[4];
+            if kind == 0 {
+                continue;
+            }
+
+            let (start, sectors) = (read_u32(raw, 8) as u64, read_u32(raw, 12) as u64);
+            if sectors == 0 {
+                return Err(Error::EmptyPartition(index));
+            }
+
+            if start == 0 || start + sectors > sector_count {
+                return Err(Error::OutOfBounds(index));
+            }
+
+            *entry = Some(PartitionEntry { index, bootable, kind, start, sectors });
+        }
+
+        for (i, a) in entries.iter().enumerate() {
+            for b in entries[i + 1..].iter() {
+                if let (Some(a), Some(b)) = (a, b) {
+                    if a.start < b.end() && b.start < a.end() {
+                        return Err(Error::Overlap(a.index, b.index));
+                    }
+                }
+            }
+        }
+
+        Ok(MasterBootRecord { disk_id: read_u32(bytes, DISK_ID_OFFSET), entries })
+    }
+
+    /// The optional disk identifier, `0` if unset.
+    pub fn disk_id(&self) -> u32 {
+        self.disk_id
+    }
+
+    /// Returns the used entries of the partition table in table order.
+    pub fn partitions<'a>(&'a self) -> impl Iterator<Item = &'a PartitionEntry> + 'a {
+        self.entries.iter().filter_map(|entry| entry.as_ref())
+    }
+
+    /// Returns partition table entry `index` if it is used.
+    pub fn partition(&self, index: usize) -> Option<&PartitionEntry> {
+        self.entries.get(index).and_then(|entry| entry.as_ref())
+    }
+
+    /// Returns the first FAT32 partition, if any.
+    pub fn first_fat32(&self) -> Option<&PartitionEntry> {
+        self.partitions().find(|entry| entry.is_fat32())
+    }
+
+    /// Opens partition `index` of `device`, which this MBR was read from.
+    /// Returns `None` if the entry is unused.
+    pub fn open<T: BlockDevice>(&self, device: T, index: usize) -> Option<Partition<T>> {
+        self.partition(index).map(|entry| Partition::new(device, entry))
+    }
+}
@@ -0,0 +1,57 @@
+use std::io;
+
+use block_device::{check_sector, BlockDevice};
+use mbr::PartitionEntry;
+
+/// A partition of a block device, itself usable as a block device.
+///
+/// Sector `0` of a `Partition` is the partition's first sector on the
+/// underlying device. Sectors past the end of the partition can't be read or
+/// written.
+#[derive(Debug)]
+pub struct Partition<T: BlockDevice> {
+    device: T,
+    start: u64,
+    sectors: u64,
+}
+
+impl<T: BlockDevice> Partition<T> {
+    /// Returns the partition of `device` described by `entry`.
+    pub fn new(device: T, entry: &PartitionEntry) -> Partition<T> {
+        Partition { device, start: entry.start, sectors: entry.sectors }
+    }
+
+    /// The partition's first sector on the underlying device.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the underlying device.
+    pub fn into_inner(self) -> T {
+        self.device
+    }
+}
+
+impl<T: BlockDevice> BlockDevice for Partition<T> {
+    fn sector_size(&self) -> u64 {
+        self.device.sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        check_sector(self, n)?;
+        self.device.read_sector(self.start + n, buf)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        check_sector(self, n)?;
+        self.device.write_sector(self.start + n, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.device.flush()
+    }
+}
@@ -0,0 +1,255 @@
+extern crate storage;
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use storage::{BlockDevice, CachedDevice, CacheStats};
+
+const SECTOR_SIZE: usize = 512;
+
+/// An in-memory device that counts and logs its accesses, and can be made to
+/// fail writes.
+struct Counting {
+    data: Vec<u8>,
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    fail_writes: Arc<AtomicBool>,
+}
+
+impl Counting {
+    fn new(sectors: usize) -> Counting {
+        let data = (0..sectors * SECTOR_SIZE).map(|i| (i / SECTOR_SIZE) as u8 ^ i as u8).collect();
+        Counting { data, reads: Vec::new(), writes: Vec::new(), fail_writes: Arc::new(AtomicBool::new(false)) }
+    }
+
+    fn sector(&self, n: u64) -> &[u8] {
+        let start = n as usize * SECTOR_SIZE;
+        &self.data[start..start + SECTOR_SIZE]
+    }
+}
+
+impl BlockDevice for Counting {
+    fn sector_count(&self) -> u64 {
+        (self.data.len() / SECTOR_SIZE) as u64
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads.push(n);
+        let len = buf.len().min(SECTOR_SIZE);
+        buf[..len].copy_from_slice(&self.sector(n)[..len]);
+        Ok(len)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        if self.fail_writes.load(Ordering::SeqCst) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "write failed"));
+        }
+
+        self.writes.push(n);
+        let len = buf.len().min(SECTOR_SIZE);
+        let start = n as usize * SECTOR_SIZE;
+        self.data[start..start + len].copy_from_slice(&buf[..len]);
+        Ok(len)
+    }
+}
+
+fn read(cache: &mut CachedDevice<Counting>, n: u64) -> Vec<u8> {
+    let mut buf = vec![0; SECTOR_SIZE];
+    assert_eq!(cache.read_sector(n, &mut buf).unwrap(), SECTOR_SIZE);
+    buf
+}
+
+#[test]
+fn reads_are_served_from_the_cache() {
+    let mut cache = CachedDevice::new(Counting::new(8), 4);
+    let expected = cache.get_ref().sector(3).to_vec();
+
+    assert_eq!(read(&mut cache, 3), expected);
+    assert_eq!(read(&mut cache, 3), expected);
+
+    let mut short = [0; 16];
+    assert_eq!(cache.read_sector(3, &mut short).unwrap(), 16);
+    assert_eq!(&short[..], &expected[..16]);
+
+    assert_eq!(cache.get_ref().reads, vec![3]);
+    assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 1, evictions: 0, writebacks: 0 });
+}
+
+#[test]
+fn writes_are_deferred_until_flushed() {
+    let mut cache = CachedDevice::new(Counting::new(8), 4);
+    let original = cache.get_ref().sector(2).to_vec();
+
+    cache.write_sector(2, &[0xAA; SECTOR_SIZE]).unwrap();
+    cache.write_sector(2, &[0xBB; SECTOR_SIZE]).unwrap();
+    assert_eq!(read(&mut cache, 2), vec![0xBB; SECTOR_SIZE]);
+    assert_eq!(cache.get_ref().sector(2), &original[..]);
+    assert!(cache.get_ref().writes.is_empty());
+    assert_eq!(cache.dirty(), 1);
+
+    cache.flush().unwrap();
+    assert_eq!(cache.get_ref().sector(2), &[0xBB; SECTOR_SIZE][..]);
+    assert_eq!(cache.get_ref().writes, vec![2]);
+    assert_eq!(cache.dirty(), 0);
+
+    // Flushing a clean cache writes nothing.
+    cache.flush().unwrap();
+    assert_eq!(cache.get_ref().writes, vec![2]);
+    assert_eq!(cache.stats().writebacks, 1);
+}
+
+#[test]
+fn only_partial_writes_load_the_sector() {
+    let mut cache = CachedDevice::new(Counting::new(8), 4);
+    let original = cache.get_ref().sector(5).to_vec();
+
+    cache.write_sector(4, &[1; SECTOR_SIZE]).unwrap();
+    assert!(cache.get_ref().reads.is_empty());
+
+    cache.write_sector(5, &[2; 100]).unwrap();
+    assert_eq!(cache.get_ref().reads, vec![5]);
+
+    cache.flush().unwrap();
+    assert_eq!(cache.get_ref().sector(4), &[1; SECTOR_SIZE][..]);
+    assert_eq!(&cache.get_ref().sector(5)[..100], &[2; 100][..]);
+    assert_eq!(&cache.get_ref().sector(5)[100..], &original[100..]);
+}
+
+#[test]
+fn evicts_the_least_recently_used_sector() {
+    let mut cache = CachedDevice::new(Counting::new(8), 2);
+    read(&mut cache, 0);
+    read(&mut cache, 1);
+    read(&mut cache, 0);
+
+    // Sector 1 is the least recently used, so reading 2 evicts it.
+    read(&mut cache, 2);
+    read(&mut cache, 0);
+    assert_eq!(cache.get_ref().reads, vec![0, 1, 2]);
+
+    read(&mut cache, 1);
+    assert_eq!(cache.get_ref().reads, vec![0, 1, 2, 1]);
+    assert_eq!(cache.cached(), 2);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 4, 2));
+
+    cache.reset_stats();
+    assert_eq!(cache.stats(), CacheStats::default());
+}
+
+#[test]
+fn evicting_a_dirty_sector_writes_it_back() {
+    let mut cache = CachedDevice::new(Counting::new(8), 1);
+    cache.write_sector(6, &[7; SECTOR_SIZE]).unwrap();
+    assert!(cache.get_ref().writes.is_empty());
+
+    read(&mut cache, 1);
+    assert_eq!(cache.get_ref().writes, vec![6]);
+    assert_eq!(cache.get_ref().sector(6), &[7; SECTOR_SIZE][..]);
+    assert_eq!(cache.dirty(), 0);
+
+    // Reading the evicted sector back loads what was written.
+    assert_eq!(read(&mut cache, 6), vec![7; SECTOR_SIZE]);
+}
+
+#[test]
+fn flush_writes_in_sector_order() {
+    let mut cache = CachedDevice::new(Counting::new(8), 8);
+    for &n in &[5, 2, 7, 0] {
+        cache.write_sector(n, &[n as u8; SECTOR_SIZE]).unwrap();
+    }
+    read(&mut cache, 3);
+
+    cache.flush().unwrap();
+    assert_eq!(cache.get_ref().writes, vec![0, 2, 5, 7]);
+}
+
+#[test]
+fn dropping_the_cache_flushes_it() {
+    let mut device = Counting::new(8);
+    {
+        let mut cache = CachedDevice::new(&mut device, 4);
+        cache.write_sector(3, &[9; SECTOR_SIZE]).unwrap();
+    }
+
+    assert_eq!(device.writes, vec![3]);
+    assert_eq!(device.sector(3), &[9; SECTOR_SIZE][..]);
+}
+
+#[test]
+fn failed_write_backs_stay_dirty() {
+    let device = Counting::new(8);
+    let fail_writes = device.fail_writes.clone();
+    let original = device.sector(1).to_vec();
+    let mut cache = CachedDevice::new(device, 1);
+    cache.write_sector(1, &[3; SECTOR_SIZE]).unwrap();
+
+    // Neither a failed flush nor a failed eviction may lose the write.
+    fail_writes.store(true, Ordering::SeqCst);
+    assert!(cache.flush().is_err());
+    assert!(cache.read_sector(2, &mut [0; SECTOR_SIZE]).is_err());
+    assert_eq!(cache.dirty(), 1);
+    assert_eq!(read(&mut cache, 1), vec![3; SECTOR_SIZE]);
+    assert_eq!(cache.get_ref().sector(1), &original[..]);
+
+    fail_writes.store(false, Ordering::SeqCst);
+    cache.flush().unwrap();
+    assert_eq!(cache.get_ref().sector(1), &[3; SECTOR_SIZE][..]);
+    assert_eq!(cache.dirty(), 0);
+}
+
+#[test]
+fn out_of_range_sectors_are_rejected() {
+    let mut cache = CachedDevice::new(Counting::new(8), 4);
+    let err = cache.read_sector(8, &mut [0; SECTOR_SIZE]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = cache.write_sector(8, &[0; SECTOR_SIZE]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    assert!(cache.get_ref().reads.is_empty());
+    assert_eq!(cache.cached(), 0);
+    assert_eq!(cache.stats(), CacheStats::default());
+}
+
+#[test]
+fn stays_coherent_with_the_device() {
+    const SECTORS: usize = 32;
+
+    for &capacity in &[1, 3, 8, 64] {
+        let mut model = Counting::new(SECTORS).data;
+        let mut cache = CachedDevice::new(Counting::new(SECTORS), capacity);
+
+        // A linear congruential generator keeps the workload reproducible.
+        let mut state = capacity as u64;
+        let mut next = move |bound: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        for step in 0..5000 {
+            let n = next(SECTORS as u64);
+            let start = n as usize * SECTOR_SIZE;
+            match next(4) {
+                0 => {
+                    let len = next(SECTOR_SIZE as u64) as usize + 1;
+                    let fill = vec![step as u8; len];
+                    assert_eq!(cache.write_sector(n, &fill).unwrap(), len);
+                    model[start..start + len].copy_from_slice(&fill);
+                }
+                1 if next(50) == 0 => cache.flush().unwrap(),
+                _ => assert_eq!(read(&mut cache, n), &model[start..start + SECTOR_SIZE]),
+            }
+        }
+
+        let stats = cache.stats();
+        assert!(cache.get_ref().reads.len() as u64 <= stats.misses);
+        assert_eq!(cache.get_ref().writes.len() as u64, stats.writebacks);
+        assert!(cache.cached() <= capacity);
+
+        cache.flush().unwrap();
+        assert_eq!(cache.dirty(), 0);
+        assert!(cache.get_ref().data == model, "device differs with capacity {}", capacity);
+    }
+}
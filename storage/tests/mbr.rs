@@ -0,0 +1,156 @@
+extern crate storage;
+
+use std::io::Cursor;
+
+use storage::{BlockDevice, MasterBootRecord, PartitionEntry};
+use storage::mbr::{Error, MBR_SIZE};
+
+const SECTOR_SIZE: usize = 512;
+
+/// The number of sectors of the devices the tables are parsed for.
+const DEVICE_SECTORS: u64 = 1000;
+
+/// Returns an MBR with a valid signature and an empty partition table.
+fn mbr() -> Vec<u8> {
+    let mut bytes = vec![0; MBR_SIZE];
+    bytes[510] = 0x55;
+    bytes[511] = 0xAA;
+    bytes
+}
+
+/// Fills in partition table entry `index` of `mbr`.
+fn set_entry(mbr: &mut [u8], index: usize, boot: u8, kind: u8, start: u32, sectors: u32) {
+    let entry = &mut mbr[446 + index * 16..][..16];
+    entry[0] = boot;
+    entry[4] = kind;
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+}
+
+fn parse(mbr: &[u8]) -> Result<MasterBootRecord, Error> {
+    MasterBootRecord::parse(mbr, DEVICE_SECTORS)
+}
+
+#[test]
+fn parses_valid_table() {
+    let mut bytes = mbr();
+    bytes[440..444].copy_from_slice(&0xCAFE_F00Du32.to_le_bytes());
+    set_entry(&mut bytes, 0, 0x00, 0x83, 1, 99);
+    set_entry(&mut bytes, 2, 0x80, 0x0C, 100, 900);
+
+    let mbr = parse(&bytes).expect("valid MBR");
+    assert_eq!(mbr.disk_id(), 0xCAFE_F00D);
+
+    let linux = PartitionEntry { index: 0, bootable: false, kind: 0x83, start: 1, sectors: 99 };
+    let fat = PartitionEntry { index: 2, bootable: true, kind: 0x0C, start: 100, sectors: 900 };
+    assert_eq!(mbr.partitions().cloned().collect::<Vec<_>>(), vec![linux, fat]);
+    assert_eq!(mbr.partition(0), Some(&linux));
+    assert_eq!(mbr.partition(1), None);
+    assert_eq!(mbr.partition(4), None);
+    assert_eq!(mbr.first_fat32(), Some(&fat));
+    assert_eq!(fat.end(), 1000);
+    assert!(!linux.is_fat32());
+}
+
+#[test]
+fn parses_empty_table() {
+    let mbr = parse(&mbr()).expect("valid MBR");
+    assert_eq!(mbr.disk_id(), 0);
+    assert_eq!(mbr.partitions().count(), 0);
+    assert_eq!(mbr.first_fat32(), None);
+}
+
+#[test]
+fn reads_and_opens_partitions_from_device() {
+    let mut image = vec![0; DEVICE_SECTORS as usize * SECTOR_SIZE];
+    let mut bytes = mbr();
+    set_entry(&mut bytes, 1, 0x00, 0x0B, 10, 5);
+    image[..MBR_SIZE].copy_from_slice(&bytes);
+    image[12 * SECTOR_SIZE] = 0x42;
+
+    let mut device = Cursor::new(image);
+    let mbr = MasterBootRecord::from(&mut device).expect("valid MBR");
+    assert_eq!(mbr.first_fat32().map(|entry| entry.index), Some(1));
+    assert!(mbr.open(&mut device, 0).is_none());
+
+    let mut partition = mbr.open(&mut device, 1).expect("partition 1");
+    assert_eq!(partition.sector_count(), 5);
+    let mut sector = [0; SECTOR_SIZE];
+    assert_eq!(partition.read_sector(2, &mut sector).unwrap(), SECTOR_SIZE);
+    assert_eq!(sector[0], 0x42);
+    assert!(partition.read_sector(5, &mut sector).is_err());
+}
+
+#[test]
+fn rejects_bad_signature() {
+    let mut bytes = mbr();
+    bytes[511] = 0xAB;
+    match parse(&bytes) {
+        Err(Error::BadSignature) => {}
+        result => panic!("expected BadSignature, got {:?}", result)
+    }
+
+    match MasterBootRecord::from(Cursor::new(vec![0; 4 * SECTOR_SIZE])) {
+        Err(Error::BadSignature) => {}
+        result => panic!("expected BadSignature, got {:?}", result)
+    }
+}
+
+#[test]
+fn rejects_unknown_boot_indicator() {
+    let mut bytes = mbr();
+    set_entry(&mut bytes, 0, 0x80, 0x0C, 1, 10);
+    // Unused entries must have a valid indicator too.
+    set_entry(&mut bytes, 2, 0x01, 0x00, 0, 0);
+    match parse(&bytes) {
+        Err(Error::UnknownBootIndicator(2)) => {}
+        result => panic!("expected UnknownBootIndicator(2), got {:?}", result)
+    }
+}
+
+#[test]
+fn rejects_empty_partition() {
+    let mut bytes = mbr();
+    set_entry(&mut bytes, 1, 0x00, 0x0C, 50, 0);
+    match parse(&bytes) {
+        Err(Error::EmptyPartition(1)) => {}
+        result => panic!("expected EmptyPartition(1), got {:?}", result)
+    }
+}
+
+#[test]
+fn rejects_out_of_bounds_partitions() {
+    let mut bytes = mbr();
+    set_entry(&mut bytes, 3, 0x00, 0x0C, 900, 101);
+    match parse(&bytes) {
+        Err(Error::OutOfBounds(3)) => {}
+        result => panic!("expected OutOfBounds(3), got {:?}", result)
+    }
+
+    // Ending exactly at the end of the device is fine.
+    set_entry(&mut bytes, 3, 0x00, 0x0C, 900, 100);
+    assert!(parse(&bytes).is_ok());
+
+    // Sector 0 holds the MBR itself.
+    set_entry(&mut bytes, 0, 0x00, 0x0C, 0, 10);
+    match parse(&bytes) {
+        Err(Error::OutOfBounds(0)) => {}
+        result => panic!("expected OutOfBounds(0), got {:?}", result)
+    }
+}
+
+#[test]
+fn rejects_overlapping_partitions() {
+    let mut bytes = mbr();
+    set_entry(&mut bytes, 0, 0x00, 0x0C, 1, 100);
+    set_entry(&mut bytes, 1, 0x00, 0x83, 101, 100);
+    set_entry(&mut bytes, 3, 0x00, 0x83, 100, 1);
+    match parse(&bytes) {
+        Err(Error::Overlap(0, 3)) => {}
+        result => panic!("expected Overlap(0, 3), got {:?}", result)
+    }
+
+    // Adjacent partitions don't overlap.
+    set_entry(&mut bytes, 3, 0x00, 0x83, 201, 1);
+    assert_eq!(parse(&bytes).expect("valid MBR").partitions().count(), 3);
+}
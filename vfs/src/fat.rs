@@ -0,0 +1,114 @@
+//! `vfs` traits for the `fat32` crate's types.
+
+use std::io;
+
+use fat32::{self, Shared, VFat};
+use storage::BlockDevice;
+
+use super::{Dir, Entry, File, FileSystem, FileType, Metadata, Timestamp, Usage};
+
+/// Converts FAT metadata to `vfs` metadata.
+fn metadata(metadata: &fat32::Metadata, kind: FileType, size: u64) -> Metadata {
+    let modified = metadata.modified;
+    Metadata {
+        kind,
+        size,
+        read_only: metadata.attributes.read_only(),
+        hidden: metadata.attributes.hidden(),
+        modified: match modified.is_set() {
+            true => Some(Timestamp {
+                year: modified.year() as u16,
+                month: modified.month(),
+                day: modified.day(),
+                hour: modified.hour(),
+                minute: modified.minute(),
+                second: modified.second(),
+            }),
+            false => None
+        }
+    }
+}
+
+impl<T: BlockDevice + 'static> File for fat32::File<T> {
+    fn size(&self) -> u64 {
+        fat32::File::size(self)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        fat32::File::set_len(self, len)
+    }
+}
+
+impl<T: BlockDevice + 'static> Dir for fat32::Dir<T> {
+    fn entries(&self) -> io::Result<Vec<Box<dyn Entry>>> {
+        Ok(fat32::Dir::entries(self)?
+            .filter(|entry| entry.name() != "." && entry.name() != "..")
+            .map(|entry| Box::new(entry) as Box<dyn Entry>)
+            .collect())
+    }
+}
+
+impl<T: BlockDevice + 'static> Entry for fat32::Entry<T> {
+    fn name(&self) -> &str {
+        fat32::Entry::name(self)
+    }
+
+    fn metadata(&self) -> Metadata {
+        match *self {
+            fat32::Entry::File(ref file) => metadata(file.metadata(), FileType::File, file.size()),
+            fat32::Entry::Dir(ref dir) => metadata(dir.metadata(), FileType::Dir, 0),
+        }
+    }
+
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn File>> {
+        match fat32::Entry::into_file(*self) {
+            Some(file) => Ok(Box::new(file)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"))
+        }
+    }
+
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn Dir>> {
+        match fat32::Entry::into_dir(*self) {
+            Some(dir) => Ok(Box::new(dir)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+        }
+    }
+}
+
+impl<T: BlockDevice + 'static> FileSystem for Shared<VFat<T>> {
+    fn kind(&self) -> &str {
+        "fat32"
+    }
+
+    fn open(&self, path: &[&str]) -> io::Result<Box<dyn Entry>> {
+        Ok(Box::new(Shared::open(self, &path.join("/"))?))
+    }
+
+    fn create(&self, path: &[&str]) -> io::Result<Box<dyn File>> {
+        Ok(Box::new(Shared::create(self, &path.join("/"))?))
+    }
+
+    fn create_dir(&self, path: &[&str]) -> io::Result<()> {
+        Shared::create_dir(self, &path.join("/")).map(|_| ())
+    }
+
+    fn remove(&self, path: &[&str]) -> io::Result<()> {
+        Shared::remove(self, &path.join("/"))
+    }
+
+    fn rename(&self, from: &[&str], to: &[&str]) -> io::Result<()> {
+        Shared::rename(self, &from.join("/"), &to.join("/"))
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.lock().device_mut().flush()
+    }
+
+    fn usage(&self) -> io::Result<Usage> {
+        let mut vfat = self.lock();
+        let bytes_per_cluster = vfat.bytes_per_cluster() as u64;
+        let total = vfat.cluster_count() as u64 * bytes_per_cluster;
+        let free = vfat.count_free_clusters()? as u64 * bytes_per_cluster;
+        Ok(Usage { total, used: total - free })
+    }
+}
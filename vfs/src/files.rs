@@ -0,0 +1,576 @@
+//! The file system commands.
+//!
+//! The commands run against any `Vfs`, resolve relative paths against a
+//! `Session`'s working directory, and write their output to any `fmt::Write`,
+//! so they behave the same on the console as against an in-memory file system
+//! and a `String`.
+
+use std::cmp;
+use std::fmt::{self, Write};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::str;
+
+use super::{canonicalize, join, split, File, FileType, Metadata, Vfs};
+
+/// The size of the chunks files are read in.
+const CHUNK_SIZE: usize = 512;
+
+/// The number of lines `head` and `tail` print by default.
+const DEFAULT_LINES: usize = 10;
+
+/// Error type for file command failures.
+#[derive(Debug)]
+pub enum Error {
+    /// The arguments were invalid. Holds the command's usage.
+    Usage(&'static str),
+    /// An operation on the named path failed.
+    Io(String, io::Error),
+    /// Writing the command's output failed.
+    Output,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Error {
+        Error::Output
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(usage) => write!(f, "usage: {}", usage),
+            Error::Io(ref path, ref error) => write!(f, "{}: {}", path, error),
+            Error::Output => write!(f, "failed to write output"),
+        }
+    }
+}
+
+/// Attaches the path an operation was on to its error.
+trait At<T> {
+    fn at(self, path: &str) -> Result<T, Error>;
+}
+
+impl<T> At<T> for io::Result<T> {
+    fn at(self, path: &str) -> Result<T, Error> {
+        self.map_err(|error| Error::Io(path.to_string(), error))
+    }
+}
+
+fn not_a_directory(path: &str) -> Error {
+    Error::Io(path.to_string(), io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+}
+
+fn is_a_directory(path: &str) -> Error {
+    Error::Io(path.to_string(), io::Error::new(io::ErrorKind::InvalidInput, "is a directory"))
+}
+
+/// Writes bytes to a `fmt::Write` as UTF-8 text, replacing invalid sequences
+/// with U+FFFD. Sequences may be split across calls to `write()`.
+struct Lossy<'w, W: Write + 'w> {
+    out: &'w mut W,
+    /// The start of a sequence that may be completed by the next write.
+    partial: Vec<u8>,
+}
+
+impl<'w, W: Write> Lossy<'w, W> {
+    fn new(out: &'w mut W) -> Lossy<'w, W> {
+        Lossy { out, partial: Vec::new() }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.partial.extend_from_slice(bytes);
+        let mut start = 0;
+        loop {
+            match str::from_utf8(&self.partial[start..]) {
+                Ok(text) => {
+                    self.out.write_str(text)?;
+                    start = self.partial.len();
+                    break;
+                }
+                Err(error) => {
+                    let valid = start + error.valid_up_to();
+                    let text = str::from_utf8(&self.partial[start..valid]).expect("valid prefix");
+                    self.out.write_str(text)?;
+                    match error.error_len() {
+                        Some(len) => {
+                            self.out.write_char('\u{FFFD}')?;
+                            start = valid + len;
+                        }
+                        None => {
+                            start = valid;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.partial.drain(..start);
+        Ok(())
+    }
+
+    /// Replaces a sequence left incomplete at the end of the input.
+    fn finish(self) -> fmt::Result {
+        match self.partial.is_empty() {
+            true => Ok(()),
+            false => self.out.write_char('\u{FFFD}')
+        }
+    }
+}
+
+/// Divides `a` by `b`, rounding up.
+fn div_ceil(a: u64, b: u64) -> u64 {
+    match a % b {
+        0 => a / b,
+        _ => a / b + 1
+    }
+}
+
+/// Writes one line of `ls` output for the entry `name`.
+fn write_entry<W: Write>(out: &mut W, name: &str, metadata: &Metadata, long: bool) -> fmt::Result {
+    if long {
+        out.write_char(match metadata.kind {
+            FileType::File => '-',
+            FileType::Dir => 'd',
+            FileType::CharDevice => 'c',
+        })?;
+
+        let flags = [(true, 'r'), (!metadata.read_only, 'w'), (metadata.hidden, 'h')];
+        for &(set, c) in flags.iter() {
+            out.write_char(if set { c } else { '-' })?;
+        }
+
+        write!(out, " {:>10} ", metadata.size)?;
+        match metadata.modified {
+            Some(time) => write!(out, "{} ", time)?,
+            None => write!(out, "{:19} ", "")?
+        }
+    }
+
+    let suffix = if metadata.is_dir() { "/" } else { "" };
+    writeln!(out, "{}{}", name, suffix)
+}
+
+/// Parses the arguments of `head` and `tail`: `[-n lines] <path>`.
+fn parse_lines<'a>(args: &[&'a str], usage: &'static str) -> Result<(usize, &'a str), Error> {
+    match args.len() {
+        1 => Ok((DEFAULT_LINES, args[0])),
+        3 if args[0] == "-n" => match args[1].parse() {
+            Ok(lines) => Ok((lines, args[2])),
+            Err(_) => Err(Error::Usage(usage))
+        },
+        _ => Err(Error::Usage(usage))
+    }
+}
+
+/// A shell's view of the file system: the `Vfs` it works on and its working
+/// directory.
+pub struct Session<'a> {
+    vfs: &'a Vfs,
+    cwd: String,
+}
+
+impl<'a> Session<'a> {
+    /// Returns a session on `vfs` whose working directory is the root.
+    pub fn new(vfs: &'a Vfs) -> Session<'a> {
+        Session { vfs, cwd: "/".to_string() }
+    }
+
+    /// The canonical path of the working directory.
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    /// Writes `data` to the file at `path`, creating or truncating it as
+    /// `Vfs::create()` does.
+    pub fn write_file(&self, path: &str, data: &[u8]) -> Result<(), Error> {
+        let mut file = self.vfs.create(&self.resolve(path)?).at(path)?;
+        file.write_all(data).at(path)
+    }
+
+    /// Reads the whole file at `path`.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.open_file(path)?.read_to_end(&mut data).at(path)?;
+        Ok(data)
+    }
+
+    /// Runs the file command `args[0]` with the arguments `args[1..]`,
+    /// writing its output to `out`. Returns `None` if `args[0]` isn't a file
+    /// command.
+    pub fn run<W: Write>(&mut self, args: &[&str], out: &mut W) -> Option<Result<(), Error>> {
+        let (name, args) = (args[0], &args[1..]);
+        Some(match name {
+            "ls" => self.ls(args, out),
+            "cd" => self.cd(args),
+            "pwd" => self.pwd(args, out),
+            "cat" => self.cat(args, out),
+            "head" => self.head(args, out),
+            "tail" => self.tail(args, out),
+            "mkdir" => self.mkdir(args),
+            "rmdir" => self.rmdir(args),
+            "rm" => self.rm(args),
+            "cp" => self.cp(args),
+            "mv" => self.mv(args),
+            "df" => self.df(args, out),
+            _ => return None
+        })
+    }
+
+    /// Resolves `path` against the working directory.
+    fn resolve(&self, path: &str) -> Result<String, Error> {
+        canonicalize(&self.cwd, path).at(path)
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata, Error> {
+        self.vfs.metadata(&self.resolve(path)?).at(path)
+    }
+
+    fn open_file(&self, path: &str) -> Result<Box<dyn File>, Error> {
+        self.vfs.open_file(&self.resolve(path)?).at(path)
+    }
+
+    /// `ls [-a] [-l] [path...]`: lists directories, or the working directory
+    /// if no path is given. `-a` includes hidden entries, `.` and `..`; `-l`
+    /// adds attributes, sizes and modification times.
+    fn ls<W: Write>(&self, args: &[&str], out: &mut W) -> Result<(), Error> {
+        const USAGE: &str = "ls [-a] [-l] [path...]";
+
+        let (mut all, mut long) = (false, false);
+        let mut paths = Vec::new();
+        for &arg in args {
+            if !arg.starts_with('-') || arg.len() == 1 {
+                paths.push(arg);
+                continue;
+            }
+
+            for flag in arg[1..].chars() {
+                match flag {
+                    'a' => all = true,
+                    'l' => long = true,
+                    _ => return Err(Error::Usage(USAGE))
+                }
+            }
+        }
+
+        if paths.is_empty() {
+            paths.push(".");
+        }
+
+        for (i, &path) in paths.iter().enumerate() {
+            let canonical = self.resolve(path)?;
+            let metadata = self.vfs.metadata(&canonical).at(path)?;
+            if !metadata.is_dir() {
+                write_entry(out, path, &metadata, long)?;
+                continue;
+            }
+
+            if paths.len() > 1 {
+                if i > 0 {
+                    out.write_char('\n')?;
+                }
+                writeln!(out, "{}:", path)?;
+            }
+
+            let mut entries: Vec<(String, Metadata)> = self.vfs.read_dir(&canonical).at(path)?
+                .iter()
+                .map(|entry| (entry.name().to_string(), entry.metadata()))
+                .filter(|(name, metadata)| all || !(metadata.hidden || name.starts_with('.')))
+                .collect();
+            entries.sort_by_key(|entry| entry.0.to_lowercase());
+
+            if all {
+                let parent = self.vfs.metadata(split(&canonical).0).at(path)?;
+                entries.insert(0, ("..".to_string(), parent));
+                entries.insert(0, (".".to_string(), metadata));
+            }
+
+            for (name, metadata) in entries.iter() {
+                write_entry(out, name, metadata, long)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `cd [path]`: changes the working directory, to the root if no path is
+    /// given.
+    fn cd(&mut self, args: &[&str]) -> Result<(), Error> {
+        let path = match args.len() {
+            0 => "/",
+            1 => args[0],
+            _ => return Err(Error::Usage("cd [path]"))
+        };
+
+        let canonical = self.resolve(path)?;
+        if !self.vfs.metadata(&canonical).at(path)?.is_dir() {
+            return Err(not_a_directory(path));
+        }
+
+        self.cwd = canonical;
+        Ok(())
+    }
+
+    /// `pwd`: prints the working directory.
+    fn pwd<W: Write>(&self, args: &[&str], out: &mut W) -> Result<(), Error> {
+        if !args.is_empty() {
+            return Err(Error::Usage("pwd"));
+        }
+
+        writeln!(out, "{}", self.cwd)?;
+        Ok(())
+    }
+
+    /// `cat <path...>`: prints the contents of files.
+    fn cat<W: Write>(&self, args: &[&str], out: &mut W) -> Result<(), Error> {
+        if args.is_empty() {
+            return Err(Error::Usage("cat <path...>"));
+        }
+
+        for &path in args {
+            let mut file = self.open_file(path)?;
+            let mut text = Lossy::new(out);
+            let mut buf = [0; CHUNK_SIZE];
+            loop {
+                match file.read(&mut buf).at(path)? {
+                    0 => break,
+                    n => text.write(&buf[..n])?
+                }
+            }
+            text.finish()?;
+        }
+
+        Ok(())
+    }
+
+    /// `head [-n lines] <path>`: prints the first lines of a file, ten
+    /// unless `-n` is given.
+    fn head<W: Write>(&self, args: &[&str], out: &mut W) -> Result<(), Error> {
+        let (lines, path) = parse_lines(args, "head [-n lines] <path>")?;
+        let mut file = self.open_file(path)?;
+        let mut text = Lossy::new(out);
+        let mut buf = [0; CHUNK_SIZE];
+        let mut remaining = lines;
+        while remaining > 0 {
+            let n = file.read(&mut buf).at(path)?;
+            if n == 0 {
+                break;
+            }
+
+            let mut end = n;
+            for (i, &byte) in buf[..n].iter().enumerate() {
+                if byte == b'\n' {
+                    remaining -= 1;
+                    if remaining == 0 {
+                        end = i + 1;
+                        break;
+                    }
+                }
+            }
+
+            text.write(&buf[..end])?;
+        }
+
+        text.finish()?;
+        Ok(())
+    }
+
+    /// `tail [-n lines] <path>`: prints the last lines of a file, ten unless
+    /// `-n` is given.
+    fn tail<W: Write>(&self, args: &[&str], out: &mut W) -> Result<(), Error> {
+        let (lines, path) = parse_lines(args, "tail [-n lines] <path>")?;
+        let mut file = self.open_file(path)?;
+        if lines == 0 {
+            return Ok(());
+        }
+
+        // Read backwards from the end until `lines` line breaks, not counting
+        // one ending the file, have been seen.
+        let size = file.size();
+        let mut buf = [0; CHUNK_SIZE];
+        let (mut position, mut start, mut seen) = (size, 0, 0);
+        'search: while position > 0 {
+            let len = cmp::min(position, CHUNK_SIZE as u64) as usize;
+            position -= len as u64;
+            file.seek(SeekFrom::Start(position)).at(path)?;
+            file.read_exact(&mut buf[..len]).at(path)?;
+
+            for i in (0..len).rev() {
+                let offset = position + i as u64;
+                if buf[i] == b'\n' && offset + 1 != size {
+                    seen += 1;
+                    if seen == lines {
+                        start = offset + 1;
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        file.seek(SeekFrom::Start(start)).at(path)?;
+        let mut text = Lossy::new(out);
+        loop {
+            match file.read(&mut buf).at(path)? {
+                0 => break,
+                n => text.write(&buf[..n])?
+            }
+        }
+
+        text.finish()?;
+        Ok(())
+    }
+
+    /// `mkdir <path...>`: creates directories. Their parents must exist.
+    fn mkdir(&self, args: &[&str]) -> Result<(), Error> {
+        if args.is_empty() {
+            return Err(Error::Usage("mkdir <path...>"));
+        }
+
+        for &path in args {
+            self.vfs.create_dir(&self.resolve(path)?).at(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// `rmdir <path...>`: removes empty directories.
+    fn rmdir(&self, args: &[&str]) -> Result<(), Error> {
+        if args.is_empty() {
+            return Err(Error::Usage("rmdir <path...>"));
+        }
+
+        for &path in args {
+            if !self.metadata(path)?.is_dir() {
+                return Err(not_a_directory(path));
+            }
+
+            self.vfs.remove(&self.resolve(path)?).at(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// `rm <path...>`: removes files.
+    fn rm(&self, args: &[&str]) -> Result<(), Error> {
+        if args.is_empty() {
+            return Err(Error::Usage("rm <path...>"));
+        }
+
+        for &path in args {
+            if self.metadata(path)?.is_dir() {
+                return Err(is_a_directory(path));
+            }
+
+            self.vfs.remove(&self.resolve(path)?).at(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the source and destination of `cp` and `mv`. A destination
+    /// that is an existing directory stands for the source's name in it.
+    fn target(&self, from: &str, to: &str) -> Result<(String, String), Error> {
+        let source = self.resolve(from)?;
+        let mut destination = self.resolve(to)?;
+        if let Ok(metadata) = self.vfs.metadata(&destination) {
+            if metadata.is_dir() {
+                destination = join(&destination, split(&source).1);
+            }
+        }
+
+        Ok((source, destination))
+    }
+
+    /// Copies the file at the canonical path `from` to the canonical path
+    /// `to`, naming them `from_arg` and `to_arg` in errors.
+    fn copy(&self, from: &str, from_arg: &str, to: &str, to_arg: &str) -> Result<(), Error> {
+        let mut source = self.vfs.open_file(from).at(from_arg)?;
+        let mut destination = self.vfs.create(to).at(to_arg)?;
+        let mut buf = [0; CHUNK_SIZE];
+        loop {
+            match source.read(&mut buf).at(from_arg)? {
+                0 => return Ok(()),
+                n => destination.write_all(&buf[..n]).at(to_arg)?
+            }
+        }
+    }
+
+    /// `cp <source> <destination>`: copies a file, into `destination` if
+    /// it's a directory.
+    fn cp(&self, args: &[&str]) -> Result<(), Error> {
+        if args.len() != 2 {
+            return Err(Error::Usage("cp <source> <destination>"));
+        }
+
+        let (from, to) = self.target(args[0], args[1])?;
+        if self.metadata(args[0])?.is_dir() {
+            return Err(is_a_directory(args[0]));
+        } else if from == to {
+            let error = io::Error::new(io::ErrorKind::InvalidInput, "source and destination are the same file");
+            return Err(Error::Io(args[1].to_string(), error));
+        }
+
+        self.copy(&from, args[0], &to, args[1])
+    }
+
+    /// `mv <source> <destination>`: moves a file or directory, into
+    /// `destination` if it's a directory. Files are moved between file
+    /// systems by copying them.
+    fn mv(&self, args: &[&str]) -> Result<(), Error> {
+        if args.len() != 2 {
+            return Err(Error::Usage("mv <source> <destination>"));
+        }
+
+        let (from, to) = self.target(args[0], args[1])?;
+        let same_fs = self.vfs.mount_point(&from).at(args[0])? == self.vfs.mount_point(&to).at(args[1])?;
+        if same_fs {
+            return self.vfs.rename(&from, &to).at(args[0]);
+        }
+
+        if self.metadata(args[0])?.is_dir() {
+            let error = io::Error::new(io::ErrorKind::InvalidInput, "can't move a directory across file systems");
+            return Err(Error::Io(args[0].to_string(), error));
+        } else if self.vfs.metadata(&to).is_ok() {
+            let error = io::Error::new(io::ErrorKind::AlreadyExists, "file exists");
+            return Err(Error::Io(args[1].to_string(), error));
+        }
+
+        self.copy(&from, args[0], &to, args[1])?;
+        self.vfs.remove(&from).at(args[0])
+    }
+
+    /// `df`: prints the size and usage of every mounted file system in KiB.
+    fn df<W: Write>(&self, args: &[&str], out: &mut W) -> Result<(), Error> {
+        if !args.is_empty() {
+            return Err(Error::Usage("df"));
+        }
+
+        let kib = |bytes: u64| div_ceil(bytes, 1024);
+        let mut mounts = self.vfs.mounts();
+        mounts.sort_by(|a, b| a.0.cmp(&b.0));
+
+        writeln!(out, "{:<10} {:>10} {:>10} {:>10} {:>4} Mounted on", "Type", "Size", "Used", "Avail", "Use%")?;
+        let mut result = Ok(());
+        for (path, fs) in mounts.iter() {
+            let usage = match fs.usage() {
+                Ok(usage) => usage,
+                Err(e) => {
+                    if result.is_ok() {
+                        result = Err(Error::Io(path.clone(), e));
+                    }
+                    continue;
+                }
+            };
+
+            write!(out, "{:<10} {:>9}K {:>9}K {:>9}K ",
+                   fs.kind(), kib(usage.total), kib(usage.used), kib(usage.available()))?;
+            match usage.total {
+                0 => write!(out, "{:>4} ", "-")?,
+                total => write!(out, "{:>3}% ", div_ceil(usage.used * 100, total))?
+            }
+            writeln!(out, "{}", path)?;
+        }
+
+        result
+    }
+}
@@ -0,0 +1,543 @@
+//! The virtual file system: a single namespace over every mounted file system.
+//!
+//! Concrete file systems implement the object-safe `FileSystem`, `Entry`,
+//! `Dir` and `File` traits and are attached to the namespace at any path with
+//! `Vfs::mount()`. Every other `Vfs` operation takes an absolute path, finds
+//! the file system mounted at the longest prefix of it, and passes on the rest
+//! of the path as components relative to that file system's root. Relative
+//! paths are resolved against a working directory with `canonicalize()`
+//! first. Errors are `io::Error`s whose kind describes the failure.
+//!
+//! The crate also has the file systems and commands that don't depend on the
+//! hardware: the `ramfs` scratch file system, the `vfs` traits for the
+//! `fat32` crate's types, and the shell's file commands in `files`. They run
+//! the same in the kernel and in host tests.
+
+extern crate storage;
+extern crate fat32;
+extern crate lock;
+
+mod fat;
+mod mount;
+mod path;
+pub mod files;
+pub mod ramfs;
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use lock::Mutex;
+
+pub use self::mount::{Mount, MountTable};
+pub use self::path::{canonicalize, components, join, split, starts_with};
+
+/// The kind of object an entry refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    /// A device that reads and writes a stream of bytes, such as a serial
+    /// port. It has no size and can't be seeked.
+    CharDevice,
+}
+
+/// A calendar date and time with one-second resolution and no time zone.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Timestamp {
+    /// Converts seconds since the Unix epoch to a UTC date and time.
+    pub fn from_unix(secs: u64) -> Timestamp {
+        let (days, secs) = (secs / 86_400, secs % 86_400);
+
+        // Count from 0000-03-01 so that leap days end each year, in 400 year
+        // eras of 146,097 days.
+        let days = days + 719_468;
+        let (era, day_of_era) = (days / 146_097, days % 146_097);
+        let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+        let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+        Timestamp {
+            year: year as u16,
+            month: month as u8,
+            day: (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u8,
+            hour: (secs / 3_600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+               self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+/// The type, size and attributes of a file or directory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: FileType,
+    /// The size in bytes. Directories have a size of `0`.
+    pub size: u64,
+    pub read_only: bool,
+    pub hidden: bool,
+    /// The time of the last modification, if the file system records it.
+    pub modified: Option<Timestamp>,
+}
+
+impl Metadata {
+    /// Returns the metadata of a writable, visible `size`-byte file with no
+    /// modification time.
+    pub fn file(size: u64) -> Metadata {
+        Metadata { kind: FileType::File, size, read_only: false, hidden: false, modified: None }
+    }
+
+    /// Returns the metadata of a writable, visible directory with no
+    /// modification time.
+    pub fn dir() -> Metadata {
+        Metadata { kind: FileType::Dir, ..Metadata::file(0) }
+    }
+
+    /// Returns `true` if this is the metadata of a directory.
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileType::Dir
+    }
+}
+
+/// How much space a file system has for its contents, in bytes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub total: u64,
+    pub used: u64,
+}
+
+impl Usage {
+    /// The space left for new contents.
+    pub fn available(&self) -> u64 {
+        self.total.saturating_sub(self.used)
+    }
+}
+
+/// An open file.
+pub trait File: io::Read + io::Write + io::Seek + Send {
+    /// The size of the file in bytes.
+    fn size(&self) -> u64;
+
+    /// Truncates or zero-extends the file to `len` bytes.
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    /// Performs the device-specific operation `request` with the argument
+    /// `arg` and returns its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` unless the file is a device
+    /// that supports `request`.
+    fn control(&mut self, _request: u32, _arg: u64) -> io::Result<u64> {
+        Err(unsupported_control())
+    }
+}
+
+/// An open directory.
+pub trait Dir: Send {
+    /// Returns the directory's entries, excluding `.` and `..`.
+    ///
+    /// File systems mounted below the directory aren't included; use
+    /// `Vfs::read_dir()` to list them as well.
+    fn entries(&self) -> io::Result<Vec<Box<dyn Entry>>>;
+}
+
+/// A named file or directory found in a directory.
+pub trait Entry: Send {
+    /// The entry's name. A file system's root directory has an empty name.
+    fn name(&self) -> &str;
+
+    /// The entry's metadata.
+    fn metadata(&self) -> Metadata;
+
+    /// Opens the entry as a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the entry is a directory.
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn File>>;
+
+    /// Opens the entry as a directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the entry isn't a directory.
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn Dir>>;
+}
+
+/// A file system that can be mounted in the `Vfs`.
+///
+/// Paths are passed as the components of a canonical path relative to the
+/// file system's root, so they never contain empty, `.` or `..` components.
+/// The operations that modify the file system fail with `PermissionDenied`
+/// unless they're implemented.
+pub trait FileSystem: Send + Sync {
+    /// A short name for the type of file system, such as `fat32`.
+    fn kind(&self) -> &str;
+
+    /// Opens the file or directory at `path`. An empty `path` opens the root.
+    fn open(&self, path: &[&str]) -> io::Result<Box<dyn Entry>>;
+
+    /// Opens the file at `path` for writing, truncating it if it exists and
+    /// creating it if it doesn't.
+    fn create(&self, _path: &[&str]) -> io::Result<Box<dyn File>> {
+        Err(read_only())
+    }
+
+    /// Creates an empty directory at `path`.
+    fn create_dir(&self, _path: &[&str]) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    /// Removes the file or empty directory at `path`.
+    fn remove(&self, _path: &[&str]) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    /// Moves the file or directory at `from` to `to`, which must not exist.
+    fn rename(&self, _from: &[&str], _to: &[&str]) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    /// Writes buffered changes through to the underlying storage.
+    fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns how much space the file system has and uses. File systems
+    /// without storage of their own have no space.
+    fn usage(&self) -> io::Result<Usage> {
+        Ok(Usage::default())
+    }
+}
+
+/// Returns the error for modifying a read-only file system.
+pub fn read_only() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "read-only file system")
+}
+
+/// Returns the error for a control request a file doesn't support.
+pub fn unsupported_control() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "inappropriate control request for device")
+}
+
+/// The mount point of a file system that doesn't exist in the file system
+/// it's mounted on, or a directory leading to one.
+struct MountPoint {
+    name: String,
+}
+
+impl Entry for MountPoint {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata { read_only: true, ..Metadata::dir() }
+    }
+
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn File>> {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"))
+    }
+
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn Dir>> {
+        Ok(self)
+    }
+}
+
+impl Dir for MountPoint {
+    fn entries(&self) -> io::Result<Vec<Box<dyn Entry>>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A path resolved to the file system it's on.
+struct Resolved {
+    /// The canonical absolute path.
+    path: String,
+    /// Where the file system is mounted.
+    mount: String,
+    fs: Arc<dyn FileSystem>,
+}
+
+impl Resolved {
+    /// The path's components relative to the file system's root.
+    fn parts(&self) -> Vec<&str> {
+        components(&self.path[self.mount.len()..])
+    }
+
+    /// Returns `true` if the path is where the file system is mounted.
+    fn is_mount_point(&self) -> bool {
+        self.path == self.mount
+    }
+}
+
+/// The mount table and the operations on the namespace it defines.
+pub struct Vfs(Mutex<Option<MountTable>>);
+
+impl Vfs {
+    /// Returns an uninitialized `Vfs`.
+    ///
+    /// The `Vfs` must be initialized by calling `initialize()` before it's
+    /// used. Failure to do so will result in panics.
+    pub const fn uninitialized() -> Vfs {
+        Vfs(Mutex::new(None))
+    }
+
+    /// Returns an initialized `Vfs` with nothing mounted.
+    pub fn new() -> Vfs {
+        let vfs = Vfs::uninitialized();
+        vfs.initialize();
+        vfs
+    }
+
+    /// Initializes the `Vfs` with an empty mount table.
+    pub fn initialize(&self) {
+        *self.0.lock() = Some(MountTable::new());
+    }
+
+    /// Calls `f` with the mount table, holding the lock for the duration.
+    fn with<R, F: FnOnce(&mut MountTable) -> R>(&self, f: F) -> R {
+        f(self.0.lock().as_mut().expect("vfs uninitialized"))
+    }
+
+    /// Attaches `fs` to the namespace at the absolute path `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `path` isn't absolute and
+    /// `AlreadyExists` if a file system is already mounted there.
+    pub fn mount(&self, path: &str, fs: Arc<dyn FileSystem>) -> io::Result<()> {
+        let path = absolute(path)?;
+        self.with(|table| table.mount(path, fs))
+    }
+
+    /// Detaches the file system mounted at `path` and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if nothing is mounted at `path`.
+    pub fn unmount(&self, path: &str) -> io::Result<Arc<dyn FileSystem>> {
+        let path = absolute(path)?;
+        self.with(|table| table.unmount(&path))
+    }
+
+    /// Returns the mount points and their file systems, longest path first.
+    pub fn mounts(&self) -> Vec<(String, Arc<dyn FileSystem>)> {
+        self.with(|table| {
+            table.mounts().iter().map(|m| (m.path().to_string(), m.fs().clone())).collect()
+        })
+    }
+
+    /// Returns where the file system `path` is on is mounted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `path` isn't absolute and
+    /// `NotFound` if no file system is mounted at or above it.
+    pub fn mount_point(&self, path: &str) -> io::Result<String> {
+        Ok(self.resolve(path)?.mount)
+    }
+
+    /// Canonicalizes the absolute path `path` and finds its file system. The
+    /// mount table's lock isn't held once this returns.
+    fn resolve(&self, path: &str) -> io::Result<Resolved> {
+        let path = absolute(path)?;
+        let found = self.with(|table| {
+            table.resolve(&path).map(|m| (m.path().to_string(), m.fs().clone()))
+        });
+
+        match found {
+            Some((mount, fs)) => Ok(Resolved { path, mount, fs }),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no file system mounted"))
+        }
+    }
+
+    /// Opens the file or directory at `path`.
+    ///
+    /// Directories leading to a mount point are opened as empty read-only
+    /// directories if they don't exist in the file system they're on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `path` isn't absolute,
+    /// `NotFound` if it doesn't exist, and the file system's errors.
+    pub fn open(&self, path: &str) -> io::Result<Box<dyn Entry>> {
+        let resolved = self.resolve(path)?;
+        match resolved.fs.open(&resolved.parts()) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                let leads_to_mount = self.with(|table| !table.children(&resolved.path).is_empty());
+                match leads_to_mount {
+                    true => Ok(Box::new(MountPoint { name: split(&resolved.path).1.to_string() })),
+                    false => Err(io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+                }
+            }
+            result => result
+        }
+    }
+
+    /// Returns the metadata of the file or directory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()`.
+    pub fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        Ok(self.open(path)?.metadata())
+    }
+
+    /// Opens the file at `path` for reading and writing.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()`, and `InvalidInput` if `path` is a
+    /// directory.
+    pub fn open_file(&self, path: &str) -> io::Result<Box<dyn File>> {
+        self.open(path)?.into_file()
+    }
+
+    /// Returns the entries of the directory at `path`, including the mount
+    /// points directly below it, excluding `.` and `..`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()`, and `InvalidInput` if `path` isn't a
+    /// directory.
+    pub fn read_dir(&self, path: &str) -> io::Result<Vec<Box<dyn Entry>>> {
+        let path = absolute(path)?;
+        let mut entries = self.open(&path)?.into_dir()?.entries()?;
+        for name in self.with(|table| table.children(&path)) {
+            if !entries.iter().any(|entry| entry.name() == name) {
+                entries.push(Box::new(MountPoint { name }));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Opens the file at `path` for writing, truncating it if it exists and
+    /// creating it if it doesn't.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `open()` for the parent directory,
+    /// `InvalidInput` if `path` is a directory, and the file system's errors.
+    pub fn create(&self, path: &str) -> io::Result<Box<dyn File>> {
+        let resolved = self.resolve(path)?;
+        if resolved.is_mount_point() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"));
+        }
+
+        resolved.fs.create(&resolved.parts())
+    }
+
+    /// Creates an empty directory at `path`. Its parent must exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `AlreadyExists` if `path` exists and the
+    /// file system's errors.
+    pub fn create_dir(&self, path: &str) -> io::Result<()> {
+        let resolved = self.resolve(path)?;
+        if resolved.is_mount_point() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+        }
+
+        resolved.fs.create_dir(&resolved.parts())
+    }
+
+    /// Removes the file or empty directory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `PermissionDenied` if `path` is a mount
+    /// point and the file system's errors.
+    pub fn remove(&self, path: &str) -> io::Result<()> {
+        let resolved = self.resolve(path)?;
+        if resolved.is_mount_point() {
+            return Err(mount_point_busy());
+        }
+
+        resolved.fs.remove(&resolved.parts())
+    }
+
+    /// Moves the file or directory at `from` to `to`, which must not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `PermissionDenied` if either path is a mount
+    /// point, `InvalidInput` if a directory would be moved into itself,
+    /// `Other` if the paths are on different file systems, and the file
+    /// system's errors.
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let from = self.resolve(from)?;
+        let to = self.resolve(to)?;
+        if from.is_mount_point() || to.is_mount_point() {
+            return Err(mount_point_busy());
+        } else if to.path != from.path && starts_with(&to.path, &from.path) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't move a directory into itself"));
+        } else if from.mount != to.mount {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't rename across file systems"));
+        }
+
+        from.fs.rename(&from.parts(), &to.parts())
+    }
+
+    /// Writes buffered changes of every mounted file system through to
+    /// storage.
+    ///
+    /// # Errors
+    ///
+    /// Every file system is synced even if one fails. The first error is
+    /// returned.
+    pub fn sync(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for (_, fs) in self.mounts() {
+            let synced = fs.sync();
+            if result.is_ok() {
+                result = synced;
+            }
+        }
+
+        result
+    }
+}
+
+impl Default for Vfs {
+    fn default() -> Vfs {
+        Vfs::new()
+    }
+}
+
+/// Canonicalizes `path`, which must be absolute.
+fn absolute(path: &str) -> io::Result<String> {
+    match path.starts_with('/') {
+        true => canonicalize("/", path),
+        false => Err(io::Error::new(io::ErrorKind::InvalidInput, "relative path"))
+    }
+}
+
+/// Returns the error for modifying a mount point.
+fn mount_point_busy() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "mount point busy")
+}
+
+/// The kernel's file system namespace.
+pub static VFS: Vfs = Vfs::uninitialized();
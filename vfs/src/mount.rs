@@ -0,0 +1,106 @@
+use std::io;
+use std::sync::Arc;
+
+use super::FileSystem;
+use super::path::{self, starts_with};
+
+/// A file system attached to the namespace.
+pub struct Mount {
+    path: String,
+    fs: Arc<dyn FileSystem>,
+}
+
+impl Mount {
+    /// The canonical path the file system is mounted at.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The mounted file system.
+    pub fn fs(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+}
+
+/// The file systems attached to the namespace, by canonical path.
+///
+/// Mounts are kept longest path first, so the first mount containing a path
+/// is the one the path resolves to.
+pub struct MountTable {
+    mounts: Vec<Mount>,
+}
+
+impl MountTable {
+    /// Returns an empty mount table.
+    pub fn new() -> MountTable {
+        MountTable { mounts: Vec::new() }
+    }
+
+    /// Mounts `fs` at the canonical path `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `AlreadyExists` if a file system is already
+    /// mounted at `path`.
+    pub fn mount(&mut self, path: String, fs: Arc<dyn FileSystem>) -> io::Result<()> {
+        if self.mounts.iter().any(|m| m.path == path) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already mounted"));
+        }
+
+        let i = self.mounts.iter().position(|m| m.path.len() < path.len()).unwrap_or(self.mounts.len());
+        self.mounts.insert(i, Mount { path, fs });
+        Ok(())
+    }
+
+    /// Unmounts the file system mounted at the canonical path `path` and
+    /// returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if nothing is mounted at `path`.
+    pub fn unmount(&mut self, path: &str) -> io::Result<Arc<dyn FileSystem>> {
+        match self.mounts.iter().position(|m| m.path == path) {
+            Some(i) => Ok(self.mounts.remove(i).fs),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "not mounted"))
+        }
+    }
+
+    /// The mounts, longest path first.
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// Returns the mount the canonical path `path` is on, if any.
+    pub fn resolve(&self, path: &str) -> Option<&Mount> {
+        self.mounts.iter().find(|m| starts_with(path, &m.path))
+    }
+
+    /// Returns the names of the components directly below the canonical path
+    /// `dir` that lead to mount points.
+    pub fn children(&self, dir: &str) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for mount in self.mounts.iter() {
+            if mount.path == dir || !starts_with(&mount.path, dir) {
+                continue;
+            }
+
+            let rest = match dir {
+                "/" => &mount.path[1..],
+                _ => &mount.path[dir.len() + 1..]
+            };
+
+            let name = path::components(rest)[0];
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+
+        names
+    }
+}
+
+impl Default for MountTable {
+    fn default() -> MountTable {
+        MountTable::new()
+    }
+}
@@ -0,0 +1,82 @@
+use std::io;
+
+/// Resolves `path` to a canonical absolute path. Relative paths are resolved
+/// against `cwd`, which must itself be canonical.
+///
+/// Empty components and `.` are dropped, and `..` removes the preceding
+/// component; `..` at the root stays at the root. The result has a single
+/// leading slash, no trailing slash, and no repeated slashes, so `/` is the
+/// only canonical path ending in a slash.
+///
+/// # Errors
+///
+/// Returns an error of kind `InvalidInput` if `path` is empty or `cwd` isn't
+/// absolute.
+pub fn canonicalize(cwd: &str, path: &str) -> io::Result<String> {
+    if path.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty path"));
+    }
+
+    let mut parts = Vec::new();
+    if !path.starts_with('/') {
+        if !cwd.starts_with('/') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "relative working directory"));
+        }
+
+        parts.extend(components(cwd));
+    }
+
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => { parts.pop(); }
+            part => parts.push(part)
+        }
+    }
+
+    let mut canonical = String::with_capacity(path.len() + 1);
+    for part in parts {
+        canonical.push('/');
+        canonical.push_str(part);
+    }
+
+    if canonical.is_empty() {
+        canonical.push('/');
+    }
+
+    Ok(canonical)
+}
+
+/// Returns the components of `path`, ignoring empty components. `/` has no
+/// components.
+pub fn components(path: &str) -> Vec<&str> {
+    path.split('/').filter(|part| !part.is_empty()).collect()
+}
+
+/// Splits the canonical path `path` into its parent directory and its last
+/// component. The root splits into itself and an empty name.
+pub fn split(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("/", path)
+    }
+}
+
+/// Returns the canonical path of `name` in the directory with canonical path
+/// `dir`.
+pub fn join(dir: &str, name: &str) -> String {
+    let mut path = String::with_capacity(dir.len() + name.len() + 1);
+    path.push_str(dir);
+    if !dir.ends_with('/') {
+        path.push('/');
+    }
+    path.push_str(name);
+    path
+}
+
+/// Returns `true` if the canonical path `path` is `ancestor` or lies below it.
+pub fn starts_with(path: &str, ancestor: &str) -> bool {
+    ancestor == "/" || path == ancestor
+        || path.starts_with(ancestor) && path.as_bytes()[ancestor.len()] == b'/'
+}
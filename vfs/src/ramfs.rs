@@ -0,0 +1,423 @@
+//! A writable file system kept on the heap, for scratch space.
+//!
+//! Files and directories are reference counted nodes, so a file that's
+//! removed while it's open stays readable and writable through the open
+//! handle, and its memory is returned to the heap when the last handle is
+//! closed. A file system has a fixed capacity shared by its file contents
+//! and a `NODE_SIZE` charge for each file and directory.
+
+use std::cmp;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+use lock::Mutex;
+use super::{FileSystem, Metadata, Usage};
+
+/// The space charged for each file and directory on top of its contents, so
+/// that empty files can't use up the heap.
+pub const NODE_SIZE: u64 = 64;
+
+/// The length of the longest file name, in bytes.
+pub const MAX_NAME_LEN: usize = 255;
+
+fn no_space() -> io::Error {
+    io::Error::new(io::ErrorKind::WriteZero, "no space left on file system")
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+}
+
+fn exists() -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, "file exists")
+}
+
+/// The space of a file system, shared by its nodes.
+#[derive(Debug)]
+struct Space {
+    capacity: u64,
+    used: u64,
+}
+
+impl Space {
+    /// Takes `bytes` of space for new contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Other` error if there isn't enough space left.
+    fn reserve(&mut self, bytes: u64) -> io::Result<()> {
+        if self.capacity - self.used < bytes {
+            return Err(no_space());
+        }
+
+        self.used += bytes;
+        Ok(())
+    }
+
+    /// Gives back `bytes` of space.
+    fn release(&mut self, bytes: u64) {
+        self.used -= cmp::min(bytes, self.used);
+    }
+}
+
+/// The contents of a file.
+struct FileData {
+    data: Vec<u8>,
+    space: Arc<Mutex<Space>>,
+}
+
+impl FileData {
+    /// Truncates or zero-extends the file to `len` bytes, reserving or
+    /// releasing the difference.
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let old = self.data.len() as u64;
+        if len > old {
+            self.space.lock().reserve(len - old)?;
+            self.data.resize(len as usize, 0);
+        } else if len < old {
+            self.data.truncate(len as usize);
+            self.data.shrink_to_fit();
+            self.space.lock().release(old - len);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for FileData {
+    fn drop(&mut self) {
+        self.space.lock().release(NODE_SIZE + self.data.len() as u64);
+    }
+}
+
+/// The entries of a directory.
+struct DirData {
+    entries: Vec<(String, Node)>,
+    space: Arc<Mutex<Space>>,
+}
+
+impl DirData {
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|(entry, _)| entry == name)
+    }
+}
+
+impl Drop for DirData {
+    fn drop(&mut self) {
+        self.space.lock().release(NODE_SIZE);
+    }
+}
+
+/// A file or directory.
+#[derive(Clone)]
+enum Node {
+    File(Arc<Mutex<FileData>>),
+    Dir(Arc<Mutex<DirData>>),
+}
+
+impl Node {
+    fn new_file(space: &Arc<Mutex<Space>>) -> io::Result<Node> {
+        space.lock().reserve(NODE_SIZE)?;
+        Ok(Node::File(Arc::new(Mutex::new(FileData { data: Vec::new(), space: space.clone() }))))
+    }
+
+    fn new_dir(space: &Arc<Mutex<Space>>) -> io::Result<Node> {
+        space.lock().reserve(NODE_SIZE)?;
+        Ok(Node::Dir(Arc::new(Mutex::new(DirData { entries: Vec::new(), space: space.clone() }))))
+    }
+
+    fn as_dir(&self) -> io::Result<&Arc<Mutex<DirData>>> {
+        match *self {
+            Node::Dir(ref dir) => Ok(dir),
+            Node::File(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        match *self {
+            Node::File(ref file) => Metadata::file(file.lock().data.len() as u64),
+            Node::Dir(_) => Metadata::dir(),
+        }
+    }
+}
+
+/// A heap-backed file system with a fixed capacity.
+pub struct RamFs {
+    root: Arc<Mutex<DirData>>,
+    space: Arc<Mutex<Space>>,
+}
+
+impl RamFs {
+    /// Returns an empty file system that can hold `capacity` bytes, counting
+    /// `NODE_SIZE` for each file and directory, including the root.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` can't hold the root directory.
+    pub fn new(capacity: u64) -> RamFs {
+        let space = Arc::new(Mutex::new(Space { capacity, used: 0 }));
+        let root = match Node::new_dir(&space).expect("capacity too small for the root directory") {
+            Node::Dir(root) => root,
+            Node::File(_) => unreachable!()
+        };
+
+        RamFs { root, space }
+    }
+
+    /// Returns the node at `path`.
+    fn node(&self, path: &[&str]) -> io::Result<Node> {
+        let mut node = Node::Dir(self.root.clone());
+        for &name in path {
+            let next = {
+                let dir = node.as_dir()?.lock();
+                match dir.find(name) {
+                    Some(i) => dir.entries[i].1.clone(),
+                    None => return Err(not_found())
+                }
+            };
+            node = next;
+        }
+
+        Ok(node)
+    }
+
+    /// Returns the directory containing `path` and the name of `path` in it.
+    fn parent<'p>(&self, path: &[&'p str]) -> io::Result<(Arc<Mutex<DirData>>, &'p str)> {
+        let (name, parent) = match path.split_last() {
+            Some((&name, parent)) => (name, parent),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't modify the root directory"))
+        };
+
+        if name.len() > MAX_NAME_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "file name too long"));
+        }
+
+        let dir = self.node(parent)?.as_dir()?.clone();
+        Ok((dir, name))
+    }
+
+    /// Adds a node made by `new` to the directory containing `path`.
+    fn insert(&self, path: &[&str], new: fn(&Arc<Mutex<Space>>) -> io::Result<Node>) -> io::Result<Node> {
+        let (dir, name) = self.parent(path)?;
+        let mut dir = dir.lock();
+        if dir.find(name).is_some() {
+            return Err(exists());
+        }
+
+        let node = new(&self.space)?;
+        dir.entries.push((name.to_string(), node.clone()));
+        Ok(node)
+    }
+}
+
+impl FileSystem for RamFs {
+    fn kind(&self) -> &str {
+        "ramfs"
+    }
+
+    fn open(&self, path: &[&str]) -> io::Result<Box<dyn super::Entry>> {
+        let node = self.node(path)?;
+        let name = path.last().map_or("", |name| *name).to_string();
+        Ok(Box::new(Entry { name, node }))
+    }
+
+    fn create(&self, path: &[&str]) -> io::Result<Box<dyn super::File>> {
+        let file = match self.node(path) {
+            Ok(Node::File(file)) => {
+                file.lock().set_len(0)?;
+                file
+            }
+            Ok(Node::Dir(_)) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory")),
+            Err(_) => match self.insert(path, Node::new_file)? {
+                Node::File(file) => file,
+                Node::Dir(_) => unreachable!()
+            }
+        };
+
+        Ok(Box::new(File { data: file, position: 0 }))
+    }
+
+    fn create_dir(&self, path: &[&str]) -> io::Result<()> {
+        self.insert(path, Node::new_dir).map(|_| ())
+    }
+
+    fn remove(&self, path: &[&str]) -> io::Result<()> {
+        let (dir, name) = self.parent(path)?;
+        let removed = {
+            let mut dir = dir.lock();
+            let i = dir.find(name).ok_or_else(not_found)?;
+            if let Node::Dir(ref child) = dir.entries[i].1 {
+                if !child.lock().entries.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "directory not empty"));
+                }
+            }
+
+            dir.entries.remove(i)
+        };
+
+        // The node's memory is released here, with no locks held, unless
+        // it's still open.
+        drop(removed);
+        Ok(())
+    }
+
+    fn rename(&self, from: &[&str], to: &[&str]) -> io::Result<()> {
+        if to.len() > from.len() && to.starts_with(from) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "can't move a directory into itself"));
+        }
+
+        let (from_dir, from_name) = self.parent(from)?;
+        let (to_dir, to_name) = self.parent(to)?;
+        if Arc::ptr_eq(&from_dir, &to_dir) {
+            let mut dir = from_dir.lock();
+            let i = dir.find(from_name).ok_or_else(not_found)?;
+            if from_name == to_name {
+                return Ok(());
+            } else if dir.find(to_name).is_some() {
+                return Err(exists());
+            }
+
+            dir.entries[i].0 = to_name.to_string();
+            return Ok(());
+        }
+
+        // Lock the two directories in address order so that concurrent
+        // renames between them can't deadlock.
+        let from_first = (&*from_dir as *const Mutex<DirData>) < (&*to_dir as *const Mutex<DirData>);
+        let (mut source, mut target) = match from_first {
+            true => {
+                let source = from_dir.lock();
+                (source, to_dir.lock())
+            }
+            false => {
+                let target = to_dir.lock();
+                (from_dir.lock(), target)
+            }
+        };
+
+        let i = source.find(from_name).ok_or_else(not_found)?;
+        if target.find(to_name).is_some() {
+            return Err(exists());
+        }
+
+        let (_, node) = source.entries.remove(i);
+        target.entries.push((to_name.to_string(), node));
+        Ok(())
+    }
+
+    fn usage(&self) -> io::Result<Usage> {
+        let space = self.space.lock();
+        Ok(Usage { total: space.capacity, used: space.used })
+    }
+}
+
+/// A named file or directory.
+struct Entry {
+    name: String,
+    node: Node,
+}
+
+impl super::Entry for Entry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.node.metadata()
+    }
+
+    fn into_file(self: Box<Self>) -> io::Result<Box<dyn super::File>> {
+        match self.node {
+            Node::File(data) => Ok(Box::new(File { data, position: 0 })),
+            Node::Dir(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "is a directory"))
+        }
+    }
+
+    fn into_dir(self: Box<Self>) -> io::Result<Box<dyn super::Dir>> {
+        let dir = self.node.as_dir()?.clone();
+        Ok(Box::new(Dir(dir)))
+    }
+}
+
+/// An open directory.
+struct Dir(Arc<Mutex<DirData>>);
+
+impl super::Dir for Dir {
+    fn entries(&self) -> io::Result<Vec<Box<dyn super::Entry>>> {
+        let entries = self.0.lock().entries.clone();
+        Ok(entries.into_iter()
+            .map(|(name, node)| Box::new(Entry { name, node }) as Box<dyn super::Entry>)
+            .collect())
+    }
+}
+
+/// An open file.
+struct File {
+    data: Arc<Mutex<FileData>>,
+    position: u64,
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let file = self.data.lock();
+        let len = file.data.len() as u64;
+        let start = cmp::min(self.position, len) as usize;
+        let n = cmp::min(buf.len(), file.data.len() - start);
+        buf[..n].copy_from_slice(&file.data[start..start + n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut file = self.data.lock();
+        let end = self.position.checked_add(buf.len() as u64)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "file too large"))?;
+        if end > file.data.len() as u64 {
+            file.set_len(end)?;
+        }
+
+        let start = self.position as usize;
+        file.data[start..start + buf.len()].copy_from_slice(buf);
+        self.position = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(offset) => (self.data.lock().data.len() as u64, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        let position = match offset >= 0 {
+            true => base.checked_add(offset as u64),
+            false => base.checked_sub(offset.wrapping_neg() as u64),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))
+        }
+    }
+}
+
+impl super::File for File {
+    fn size(&self) -> u64 {
+        self.data.lock().data.len() as u64
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.data.lock().set_len(len)
+    }
+}
@@ -0,0 +1,284 @@
+extern crate vfs;
+
+use std::io;
+use std::sync::Arc;
+
+use vfs::Vfs;
+use vfs::files::{Error, Session};
+use vfs::ramfs::RamFs;
+
+const CAPACITY: u64 = 1024 * 1024;
+
+/// Returns a `Vfs` with a `RamFs` at the root and another one at `/mnt`.
+fn vfs() -> Vfs {
+    let vfs = Vfs::new();
+    vfs.mount("/", Arc::new(RamFs::new(CAPACITY))).unwrap();
+    vfs.mount("/mnt", Arc::new(RamFs::new(CAPACITY))).unwrap();
+    vfs
+}
+
+/// Runs the file command `line`, split at spaces, and returns its output.
+fn run(session: &mut Session, line: &str) -> Result<String, Error> {
+    let args: Vec<&str> = line.split(' ').collect();
+    let mut out = String::new();
+    match session.run(&args, &mut out) {
+        Some(result) => result.map(|_| out),
+        None => panic!("`{}` isn't a file command", args[0])
+    }
+}
+
+/// Runs `line`, which must succeed, and returns its output.
+fn ok(session: &mut Session, line: &str) -> String {
+    match run(session, line) {
+        Ok(out) => out,
+        Err(e) => panic!("`{}` failed: {}", line, e)
+    }
+}
+
+/// Runs `line`, which must fail, and returns its error message.
+fn fails(session: &mut Session, line: &str) -> String {
+    match run(session, line) {
+        Ok(out) => panic!("`{}` succeeded with {:?}", line, out),
+        Err(e) => e.to_string()
+    }
+}
+
+/// Asserts that `line` fails with an error of `kind`, naming `path`.
+fn assert_io_error(session: &mut Session, line: &str, path: &str, kind: io::ErrorKind) {
+    match run(session, line) {
+        Err(Error::Io(ref p, ref e)) if p == path && e.kind() == kind => {}
+        result => panic!("`{}`: expected a {:?} error for {}, got {:?}", line, kind, path, result)
+    }
+}
+
+#[test]
+fn ignores_other_commands() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    assert!(session.run(&["echo", "hi"], &mut String::new()).is_none());
+}
+
+#[test]
+fn ls_lists_sorted_entries_and_mount_points() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("b.txt", b"bee").unwrap();
+    session.write_file("A.txt", b"a").unwrap();
+    session.write_file(".hidden", b"").unwrap();
+    ok(&mut session, "mkdir docs");
+
+    assert_eq!(ok(&mut session, "ls"), "A.txt\nb.txt\ndocs/\nmnt/\n");
+    assert_eq!(ok(&mut session, "ls -a"), "./\n../\n.hidden\nA.txt\nb.txt\ndocs/\nmnt/\n");
+    assert_eq!(ok(&mut session, "ls b.txt"), "b.txt\n");
+    assert_eq!(ok(&mut session, "ls docs /mnt"), "docs:\n\n/mnt:\n");
+    assert_eq!(fails(&mut session, "ls -x"), "usage: ls [-a] [-l] [path...]");
+    assert_io_error(&mut session, "ls missing", "missing", io::ErrorKind::NotFound);
+}
+
+#[test]
+fn ls_long_shows_kind_attributes_and_size() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("docs.txt", b"hello").unwrap();
+    ok(&mut session, "mkdir docs");
+
+    // `RamFs` doesn't record modification times, so that column is blank.
+    // `mnt` doesn't exist in the root file system, so it's listed as a
+    // read-only mount point.
+    let blank = " ".repeat(19);
+    assert_eq!(ok(&mut session, "ls -l"), format!(
+        "drw-          0 {0} docs/\n\
+         -rw-          5 {0} docs.txt\n\
+         dr--          0 {0} mnt/\n", blank));
+    assert_eq!(ok(&mut session, "ls -la docs"), format!(
+        "drw-          0 {0} ./\n\
+         drw-          0 {0} ../\n", blank));
+    assert_eq!(ok(&mut session, "ls -a -l docs"), ok(&mut session, "ls -la docs"));
+}
+
+#[test]
+fn cd_and_pwd_resolve_dot_dot() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    ok(&mut session, "mkdir docs");
+    ok(&mut session, "mkdir docs/sub");
+    session.write_file("docs/file", b"").unwrap();
+
+    assert_eq!(ok(&mut session, "pwd"), "/\n");
+    ok(&mut session, "cd docs/sub");
+    assert_eq!(ok(&mut session, "pwd"), "/docs/sub\n");
+    assert_eq!(session.cwd(), "/docs/sub");
+
+    ok(&mut session, "cd ..");
+    assert_eq!(ok(&mut session, "pwd"), "/docs\n");
+    ok(&mut session, "cd ../../..");
+    assert_eq!(ok(&mut session, "pwd"), "/\n");
+
+    // Relative paths resolve against the new working directory.
+    ok(&mut session, "cd docs/./sub/../sub");
+    assert_eq!(ok(&mut session, "ls .."), "file\nsub/\n");
+    ok(&mut session, "cd ../../mnt");
+    assert_eq!(ok(&mut session, "pwd"), "/mnt\n");
+    ok(&mut session, "cd");
+    assert_eq!(ok(&mut session, "pwd"), "/\n");
+
+    assert_eq!(fails(&mut session, "cd docs/file"), "docs/file: not a directory");
+    assert_io_error(&mut session, "cd missing", "missing", io::ErrorKind::NotFound);
+    assert_eq!(session.cwd(), "/");
+    assert_eq!(fails(&mut session, "cd a b"), "usage: cd [path]");
+    assert_eq!(fails(&mut session, "pwd x"), "usage: pwd");
+}
+
+#[test]
+fn cat_prints_files_and_replaces_invalid_utf8() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("a", b"one\n").unwrap();
+    session.write_file("b", b"t\xFFwo\n\xE2\x82").unwrap();
+
+    assert_eq!(ok(&mut session, "cat a b"), "one\nt\u{FFFD}wo\n\u{FFFD}");
+    assert_eq!(fails(&mut session, "cat"), "usage: cat <path...>");
+    assert_io_error(&mut session, "cat /", "/", io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn head_and_tail_print_lines() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    let text: String = (1..13).map(|i| format!("{}\n", i)).collect();
+    session.write_file("lines", text.as_bytes()).unwrap();
+    session.write_file("partial", b"a\nb\nc").unwrap();
+
+    assert_eq!(ok(&mut session, "head lines"), "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+    assert_eq!(ok(&mut session, "head -n 2 lines"), "1\n2\n");
+    assert_eq!(ok(&mut session, "head -n 0 lines"), "");
+    assert_eq!(ok(&mut session, "head -n 20 partial"), "a\nb\nc");
+
+    assert_eq!(ok(&mut session, "tail lines"), "3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n");
+    assert_eq!(ok(&mut session, "tail -n 2 lines"), "11\n12\n");
+    assert_eq!(ok(&mut session, "tail -n 0 lines"), "");
+    assert_eq!(ok(&mut session, "tail -n 1 partial"), "c");
+    assert_eq!(ok(&mut session, "tail -n 20 partial"), "a\nb\nc");
+
+    assert_eq!(fails(&mut session, "head -n x lines"), "usage: head [-n lines] <path>");
+    assert_eq!(fails(&mut session, "tail -c 1 lines"), "usage: tail [-n lines] <path>");
+    assert_eq!(fails(&mut session, "tail"), "usage: tail [-n lines] <path>");
+    assert_io_error(&mut session, "head missing", "missing", io::ErrorKind::NotFound);
+}
+
+#[test]
+fn tail_reads_lines_spanning_chunks() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    let long = "x".repeat(1000);
+    let text = format!("first\n{}\nlast\n", long);
+    session.write_file("long", text.as_bytes()).unwrap();
+
+    assert_eq!(ok(&mut session, "tail -n 2 long"), format!("{}\nlast\n", long));
+    assert_eq!(ok(&mut session, "head -n 2 long"), format!("first\n{}\n", long));
+}
+
+#[test]
+fn cp_copies_into_directories_and_across_mounts() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("a.txt", b"contents").unwrap();
+    ok(&mut session, "mkdir docs");
+
+    ok(&mut session, "cp a.txt docs");
+    assert_eq!(session.read_file("docs/a.txt").unwrap(), b"contents");
+    ok(&mut session, "cp a.txt /mnt/b.txt");
+    assert_eq!(session.read_file("/mnt/b.txt").unwrap(), b"contents");
+    ok(&mut session, "cp docs/a.txt /mnt");
+    assert_eq!(ok(&mut session, "ls /mnt"), "a.txt\nb.txt\n");
+    assert_eq!(session.read_file("a.txt").unwrap(), b"contents");
+
+    // An existing file is overwritten.
+    session.write_file("short", b"new").unwrap();
+    ok(&mut session, "cp short a.txt");
+    assert_eq!(session.read_file("a.txt").unwrap(), b"new");
+
+    assert_eq!(fails(&mut session, "cp docs x"), "docs: is a directory");
+    assert_eq!(fails(&mut session, "cp a.txt ."), ".: source and destination are the same file");
+    assert_io_error(&mut session, "cp missing x", "missing", io::ErrorKind::NotFound);
+    assert_eq!(fails(&mut session, "cp a.txt"), "usage: cp <source> <destination>");
+}
+
+#[test]
+fn mv_moves_into_directories_and_across_mounts() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("a.txt", b"contents").unwrap();
+    ok(&mut session, "mkdir docs");
+    ok(&mut session, "mkdir dir");
+
+    ok(&mut session, "mv a.txt docs");
+    assert_eq!(ok(&mut session, "ls docs"), "a.txt\n");
+    assert_io_error(&mut session, "cat a.txt", "a.txt", io::ErrorKind::NotFound);
+
+    ok(&mut session, "mv dir docs/renamed");
+    assert_eq!(ok(&mut session, "ls docs"), "a.txt\nrenamed/\n");
+
+    // Files are copied to the other file system and removed from this one.
+    ok(&mut session, "mv docs/a.txt /mnt");
+    assert_eq!(ok(&mut session, "ls docs"), "renamed/\n");
+    assert_eq!(session.read_file("/mnt/a.txt").unwrap(), b"contents");
+    ok(&mut session, "cd /mnt");
+    ok(&mut session, "mv a.txt ../b.txt");
+    assert_eq!(ok(&mut session, "ls"), "");
+    assert_eq!(session.read_file("/b.txt").unwrap(), b"contents");
+
+    assert_eq!(fails(&mut session, "mv /docs/renamed /mnt"),
+               "/docs/renamed: can't move a directory across file systems");
+    session.write_file("/mnt/b.txt", b"").unwrap();
+    assert_io_error(&mut session, "mv /b.txt /mnt", "/mnt", io::ErrorKind::AlreadyExists);
+    assert_io_error(&mut session, "mv /docs /docs/renamed", "/docs", io::ErrorKind::InvalidInput);
+    assert_io_error(&mut session, "mv /mnt /mnt/x", "/mnt", io::ErrorKind::PermissionDenied);
+    assert_eq!(fails(&mut session, "mv /b.txt"), "usage: mv <source> <destination>");
+}
+
+#[test]
+fn rm_and_rmdir_check_the_kind_of_entry() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("file", b"").unwrap();
+    ok(&mut session, "mkdir dir");
+    ok(&mut session, "mkdir full");
+    session.write_file("full/inner", b"").unwrap();
+
+    assert_eq!(fails(&mut session, "rm dir"), "dir: is a directory");
+    assert_eq!(fails(&mut session, "rmdir file"), "file: not a directory");
+    assert_eq!(fails(&mut session, "rmdir full"), "full: directory not empty");
+    assert_io_error(&mut session, "rm missing", "missing", io::ErrorKind::NotFound);
+    assert_io_error(&mut session, "rmdir /mnt", "/mnt", io::ErrorKind::PermissionDenied);
+    assert_eq!(ok(&mut session, "ls"), "dir/\nfile\nfull/\nmnt/\n");
+
+    ok(&mut session, "rm file full/inner");
+    ok(&mut session, "rmdir dir full");
+    assert_eq!(ok(&mut session, "ls"), "mnt/\n");
+    assert_eq!(fails(&mut session, "rm"), "usage: rm <path...>");
+    assert_eq!(fails(&mut session, "rmdir"), "usage: rmdir <path...>");
+}
+
+#[test]
+fn mkdir_requires_parent_and_new_name() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    ok(&mut session, "mkdir a a/b");
+    assert_eq!(ok(&mut session, "ls a"), "b/\n");
+    assert_io_error(&mut session, "mkdir a", "a", io::ErrorKind::AlreadyExists);
+    assert_io_error(&mut session, "mkdir x/y", "x/y", io::ErrorKind::NotFound);
+    assert_io_error(&mut session, "mkdir /mnt", "/mnt", io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn df_reports_every_mount() {
+    let vfs = vfs();
+    let mut session = Session::new(&vfs);
+    session.write_file("/mnt/data", &[0; 2048]).unwrap();
+
+    assert_eq!(ok(&mut session, "df"),
+        "Type             Size       Used      Avail Use% Mounted on\n\
+         ramfs           1024K         1K      1024K   1% /\n\
+         ramfs           1024K         3K      1022K   1% /mnt\n");
+}